@@ -16,11 +16,22 @@
 // You should have received a copy of the GNU General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
+//! The telemetry tracing layer.
+//!
+//! Beyond the crate's existing dependencies this module relies on `serde_json`
+//! (payload parsing and injection). The structured `record_value` path is gated
+//! behind `#[cfg(tracing_unstable)]` and additionally needs the optional
+//! `valuable` and `valuable-serde` dependencies; it only compiles when the crate
+//! is built with `RUSTFLAGS="--cfg tracing_unstable"`, matching tracing's own
+//! valuable integration.
+
 use crate::Telemetries;
 use futures::channel::mpsc;
 use parking_lot::Mutex;
 use std::collections::HashMap;
 use std::convert::TryInto;
+use std::io::Write as _;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tracing::{Event, Subscriber};
 use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
@@ -28,11 +39,68 @@ use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
 pub const TELEMETRY_LOG_SPAN: &str = "telemetry-logger";
 
 #[derive(Debug, Default)]
-pub struct TelemetryLayer(Telemetries);
+pub struct TelemetryLayer {
+	telemetries: Telemetries,
+	otlp: Option<OtlpSink>,
+	file: Option<FileSink>,
+}
 
 impl TelemetryLayer {
 	pub fn telemetries(&self) -> Telemetries {
-		self.0.clone()
+		self.telemetries.clone()
+	}
+
+	/// Install an OpenTelemetry OTLP sink that forwards every captured telemetry
+	/// payload to a collector in parallel with the mpsc [`Senders`]. The sink is
+	/// optional: when no collector is configured telemetry only flows through the
+	/// existing channels.
+	pub fn with_otlp(mut self, otlp: OtlpSink) -> Self {
+		self.otlp = Some(otlp);
+		self
+	}
+
+	/// Install a local rotating-file sink that persists telemetry payloads to
+	/// disk, in parallel with the mpsc [`Senders`]. Useful for offline debugging
+	/// or when no remote backend is configured.
+	pub fn with_file_sink(mut self, file: FileSink) -> Self {
+		self.file = Some(file);
+		self
+	}
+
+	/// Convenience forwarder to [`Telemetries::register_trace_root`]. At runtime
+	/// callers hold a [`Telemetries`] clone from [`telemetries`](Self::telemetries)
+	/// rather than the layer, so registration lives there; this method is only
+	/// useful before the layer is installed.
+	pub fn register_trace_root(
+		&self,
+		trace_id: String,
+		remote_parent_id: Option<String>,
+	) -> Result<(), NoEnabledSpan> {
+		self.telemetries
+			.register_trace_root(trace_id, remote_parent_id)
+	}
+}
+
+impl Telemetries {
+	/// Register a distributed trace root for the currently active telemetry span.
+	///
+	/// `trace_id` and the optional `remote_parent_id` (e.g. received from an
+	/// upstream RPC or gossip message) are stamped into every telemetry payload
+	/// subsequently emitted under the active `TELEMETRY_LOG_SPAN` scope, giving
+	/// operators end-to-end correlation across nodes.
+	///
+	/// This is the runtime entry point: it only needs the shared trace-root map, so
+	/// it is reachable from the [`Telemetries`] clone returned by
+	/// [`TelemetryLayer::telemetries`] after the layer has been installed.
+	///
+	/// Returns [`NoEnabledSpan`] if called while no telemetry span is active,
+	/// rather than silently dropping the association.
+	pub fn register_trace_root(
+		&self,
+		trace_id: String,
+		remote_parent_id: Option<String>,
+	) -> Result<(), NoEnabledSpan> {
+		self.trace_roots.register(trace_id, remote_parent_id)
 	}
 }
 
@@ -47,52 +115,147 @@ where
 
 		if let Some(span) = ctx.scope().find(|x| x.name() == TELEMETRY_LOG_SPAN) {
 			let id = span.id().into_u64();
-			if let Some(sender) = self.0.senders.0.lock().get_mut(&id) {
-				let mut attrs = TelemetryAttrs::new(id);
-				let mut vis = TelemetryAttrsVisitor(&mut attrs);
-				event.record(&mut vis);
-
-				match attrs {
-					TelemetryAttrs {
-						message_verbosity: Some(message_verbosity),
-						json: Some(json),
-						..
-					} => {
-						if let Err(err) = sender.try_send((
-							message_verbosity
-								.try_into()
-								.expect("telemetry log message verbosity are u8; qed"),
-							json,
-						)) {
-							log::warn!(
-								target: "telemetry",
-								"Ignored telemetry message because of error on channel: {:?}",
-								err,
-							);
-						}
+			// Clone the sender out and release the global senders lock before doing any
+			// sink work below: the file and OTLP sinks can block, and holding this lock
+			// across them would serialize every telemetry event behind disk/network IO.
+			let mut sender = match self.telemetries.senders.0.lock().get(&id) {
+				Some(sender) => sender.0.clone(),
+				None => {
+					log::trace!(target: "telemetry", "Telemetry not set");
+					return;
+				}
+			};
+
+			let mut attrs = TelemetryAttrs::new(id);
+			let mut vis = TelemetryAttrsVisitor(&mut attrs);
+			event.record(&mut vis);
+
+			// `message_verbosity` is always required; the payload may arrive either as a
+			// pre-serialized `json` string or, under `tracing_unstable`, as a structured
+			// `valuable` value already collected into a `serde_json::Value`.
+			let message_verbosity = match attrs.message_verbosity {
+				Some(message_verbosity) => message_verbosity,
+				None => panic!("missing fields in telemetry log: {:?}", event),
+			};
+
+			// Prefer the structured value when present, otherwise parse the json string.
+			// The payload must be a JSON object; anything else is skipped with a warning
+			// rather than corrupted.
+			let mut value: serde_json::Value = if let Some(value) = attrs.json_value {
+				value
+			} else if let Some(json) = attrs.json {
+				match serde_json::from_str(&json) {
+					Ok(value) => value,
+					Err(err) => {
+						log::warn!(
+							target: "telemetry",
+							"Ignored telemetry message because payload is not valid JSON: {:?}",
+							err,
+						);
+						return;
 					}
-					_ => panic!("missing fields in telemetry log: {:?}", event),
 				}
 			} else {
-				log::trace!(target: "telemetry", "Telemetry not set");
+				panic!("missing fields in telemetry log: {:?}", event);
+			};
+
+			let obj = match value.as_object_mut() {
+				Some(obj) => obj,
+				None => {
+					log::warn!(
+						target: "telemetry",
+						"Ignored telemetry message because payload is not a JSON object",
+					);
+					return;
+				}
+			};
+
+			// Snapshot the caller's payload before we inject span context, so the OTLP
+			// attributes don't duplicate the dedicated `span_id`/`parent_ids` fields.
+			let otlp_attributes = self.otlp.as_ref().map(|_| obj.clone());
+
+			obj.insert("id".into(), id.into());
+
+			// Collect the ancestor telemetry spans root-to-leaf (see `ancestor_ids`);
+			// this lets consumers reconstruct the nesting that produced the payload
+			// from just the `parent_ids` array.
+			let parent_ids = ancestor_ids(
+				ctx.scope()
+					.filter(|x| x.name() == TELEMETRY_LOG_SPAN)
+					.map(|x| x.id().into_u64()),
+			);
+			obj.insert("parent_ids".into(), parent_ids.clone().into());
+
+			// Stamp the distributed trace root (if one was registered for this
+			// span) so telemetry correlates across nodes handling the same work.
+			if let Some(root) = self.telemetries.trace_roots.0.lock().get(&id) {
+				obj.insert("trace_id".into(), root.trace_id.clone().into());
+				if let Some(parent_span_id) = &root.remote_parent_id {
+					obj.insert("parent_span_id".into(), parent_span_id.clone().into());
+				}
+			}
+
+			let json = serde_json::to_string(&value)
+				.expect("a serde_json::Value always re-serializes; qed");
+			let message_verbosity: u8 = message_verbosity
+				.try_into()
+				.expect("telemetry log message verbosity are u8; qed");
+
+			// Forward to the OpenTelemetry collector (if installed) in parallel
+			// with the mpsc channel. This is best-effort and must never disturb
+			// the primary telemetry path.
+			if let (Some(otlp), Some(attributes)) = (self.otlp.as_ref(), otlp_attributes) {
+				otlp.forward(id, &parent_ids, message_verbosity, attributes);
+			}
+
+			// Persist to the local rotating-file sink (if installed), best-effort.
+			if let Some(file) = self.file.as_ref() {
+				file.write(message_verbosity, &json);
+			}
+
+			if let Err(err) = sender.try_send((message_verbosity, json)) {
+				log::warn!(
+					target: "telemetry",
+					"Ignored telemetry message because of error on channel: {:?}",
+					err,
+				);
 			}
 		}
 	}
+
+	fn on_close(&self, id: tracing::Id, _ctx: Context<S>) {
+		// Drop any distributed trace root registered against this span so a stale
+		// root can't keep stamping payloads once the work it described has finished.
+		self.telemetries.trace_roots.remove(id.into_u64());
+	}
+}
+
+/// Turn telemetry span ids in innermost-first order (as `ctx.scope()` yields
+/// them) into the ancestor chain, root-to-leaf: the first id is the leaf (current)
+/// span, which is already emitted separately as `id`, so it is dropped and the
+/// remainder reversed. A span with no telemetry ancestors yields an empty vec.
+fn ancestor_ids(ids: impl Iterator<Item = u64>) -> Vec<u64> {
+	let mut ids: Vec<u64> = ids.skip(1).collect();
+	ids.reverse();
+	ids
 }
 
 #[derive(Debug)]
 struct TelemetryAttrs {
 	message_verbosity: Option<u64>,
 	json: Option<String>,
-	id: u64,
+	// Set by the `valuable` path (see `record_value`) when the call site attaches a
+	// typed payload instead of a pre-serialized `json` string. Takes precedence over
+	// `json` in `on_event`.
+	json_value: Option<serde_json::Value>,
 }
 
 impl TelemetryAttrs {
-	fn new(id: u64) -> Self {
+	fn new(_id: u64) -> Self {
 		Self {
 			message_verbosity: None,
 			json: None,
-			id,
+			json_value: None,
 		}
 	}
 }
@@ -113,10 +276,28 @@ impl<'a> tracing::field::Visit for TelemetryAttrsVisitor<'a> {
 
 	fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
 		if field.name() == "json" {
-			// NOTE: this is a hack to inject the span id into the json
-			let mut message = format!(r#"{{"id":{},"#, (*self.0).id);
-			message.push_str(&value[1..]);
-			(*self.0).json = Some(message)
+			// The span id and parent chain are injected by `on_event` after parsing; the
+			// visitor only records the raw payload as produced by the call site.
+			(*self.0).json = Some(value.to_string())
+		}
+	}
+
+	// Gated behind `tracing_unstable` like tracing's own `valuable` integration, and
+	// depends on the optional `valuable` / `valuable-serde` crates. Lets call sites
+	// attach a typed `valuable::Valuable` payload directly, which is serialized into a
+	// `serde_json::Value` here rather than forcing the producer to hand-build a JSON
+	// string.
+	#[cfg(tracing_unstable)]
+	fn record_value(&mut self, field: &tracing::field::Field, value: valuable::Value<'_>) {
+		if field.name() == "json" {
+			match serde_json::to_value(valuable_serde::Serializable::new(value)) {
+				Ok(value) => (*self.0).json_value = Some(value),
+				Err(err) => log::warn!(
+					target: "telemetry",
+					"Ignored telemetry valuable payload because it could not be serialized: {:?}",
+					err,
+				),
+			}
 		}
 	}
 }
@@ -133,3 +314,343 @@ impl Senders {
 			.insert(id, std::panic::AssertUnwindSafe(sender));
 	}
 }
+
+/// A distributed trace root associated with a telemetry span.
+#[derive(Debug, Clone)]
+pub struct TraceRoot {
+	/// The distributed trace id shared across all nodes handling the same work.
+	pub trace_id: String,
+	/// The span id of the remote parent that initiated this trace, if any.
+	pub remote_parent_id: Option<String>,
+}
+
+/// Per-span registry of distributed trace roots, keyed like [`Senders`] by
+/// `span.id().into_u64()`.
+///
+/// Populated via [`Telemetries::register_trace_root`] and read in `on_event` to
+/// stamp `trace_id` / `parent_span_id` into each telemetry payload. Entries are
+/// removed when their span closes (see `on_close`).
+///
+/// Because the root is keyed by the nearest `TELEMETRY_LOG_SPAN`, registration is
+/// last-write-wins within that span: a root registered while a long-lived shared
+/// telemetry span is current would be applied to every payload under it. Callers
+/// that need per-work correlation must therefore register under a per-request
+/// telemetry span (opened for the unit of work and closed when it completes),
+/// which the `on_close` cleanup then retires.
+#[derive(Default, Debug, Clone)]
+pub struct TraceRoots(Arc<Mutex<HashMap<u64, TraceRoot>>>);
+
+impl TraceRoots {
+	fn register(
+		&self,
+		trace_id: String,
+		remote_parent_id: Option<String>,
+	) -> Result<(), NoEnabledSpan> {
+		// Walk the current span scope for the nearest `TELEMETRY_LOG_SPAN` and key the
+		// association by its id, matching the lookup `on_event` performs. No current
+		// span, a current scope without a `TELEMETRY_LOG_SPAN`, or a default subscriber
+		// that is not a `tracing_subscriber::Registry` yields `NoEnabledSpan` rather than
+		// stashing the root under an id that is never read back.
+		tracing::dispatcher::get_default(|dispatch| {
+			let id = dispatch.current_span().id().cloned().ok_or(NoEnabledSpan)?;
+			let registry = dispatch
+				.downcast_ref::<tracing_subscriber::Registry>()
+				.ok_or(NoEnabledSpan)?;
+			let telemetry_id = registry
+				.span(&id)
+				.ok_or(NoEnabledSpan)?
+				.scope()
+				.find(|x| x.name() == TELEMETRY_LOG_SPAN)
+				.ok_or(NoEnabledSpan)?
+				.id()
+				.into_u64();
+			self.0.lock().insert(
+				telemetry_id,
+				TraceRoot {
+					trace_id: trace_id.clone(),
+					remote_parent_id: remote_parent_id.clone(),
+				},
+			);
+			Ok(())
+		})
+	}
+
+	/// Drop the trace root registered against `id`, if any. Called from `on_close`
+	/// so roots don't outlive the span they describe.
+	fn remove(&self, id: u64) {
+		self.0.lock().remove(&id);
+	}
+}
+
+/// Error returned when a distributed trace root is registered while no telemetry
+/// span is active on the current thread.
+#[derive(Debug)]
+pub struct NoEnabledSpan;
+
+impl std::fmt::Display for NoEnabledSpan {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "no telemetry span is currently active")
+	}
+}
+
+impl std::error::Error for NoEnabledSpan {}
+
+/// A single telemetry payload mapped onto the OpenTelemetry OTLP log wire shape.
+///
+/// The JSON object's fields become OTLP attributes (one `KeyValue` each),
+/// `message_verbosity` becomes the severity number and the span id / parent ids
+/// carry the span identity. The actual encoding onto the vendored OTLP protobuf
+/// messages happens in the task draining the receiver, mirroring the way the
+/// mpsc [`Senders`] payloads are consumed outside this layer.
+#[derive(Debug, Clone)]
+pub struct OtlpLogRecord {
+	/// Innermost telemetry span id, used as the OTLP span id.
+	pub span_id: u64,
+	/// Ancestor telemetry span ids, root-to-leaf.
+	pub parent_ids: Vec<u64>,
+	/// `message_verbosity`, mapped onto OTLP severity.
+	pub severity: u8,
+	/// The telemetry payload whose fields become OTLP attributes.
+	pub attributes: serde_json::Map<String, serde_json::Value>,
+}
+
+/// OpenTelemetry OTLP export sink, installed alongside the mpsc [`Senders`].
+///
+/// Mirrors [`Senders`]: an `Arc<Mutex<...>>` of per-endpoint channels whose
+/// receivers are driven by background tasks that ship each [`OtlpLogRecord`] to
+/// a collector over OTLP (gRPC via tonic). Forwarding is best-effort — when a
+/// collector is unreachable the send fails and is logged, never panicking, so a
+/// dead collector can't take down the node.
+#[derive(Default, Debug, Clone)]
+pub struct OtlpSink(Arc<Mutex<HashMap<String, mpsc::Sender<OtlpLogRecord>>>>);
+
+impl OtlpSink {
+	/// Register an OTLP channel for the given collector endpoint.
+	pub fn insert(&self, endpoint: String, sender: mpsc::Sender<OtlpLogRecord>) {
+		self.0.lock().insert(endpoint, sender);
+	}
+
+	/// Map a captured telemetry event onto an [`OtlpLogRecord`] and forward it to
+	/// every registered collector. Best-effort: a full or disconnected channel is
+	/// logged and dropped rather than propagated.
+	fn forward(
+		&self,
+		span_id: u64,
+		parent_ids: &[u64],
+		severity: u8,
+		attributes: serde_json::Map<String, serde_json::Value>,
+	) {
+		for (endpoint, sender) in self.0.lock().iter_mut() {
+			let record = OtlpLogRecord {
+				span_id,
+				parent_ids: parent_ids.to_vec(),
+				severity,
+				attributes: attributes.clone(),
+			};
+			if let Err(err) = sender.try_send(record) {
+				log::warn!(
+					target: "telemetry",
+					"Ignored OTLP telemetry export to {} because of error on channel: {:?}",
+					endpoint,
+					err,
+				);
+			}
+		}
+	}
+}
+
+/// How often the [`FileSink`] rolls over to a fresh date-stamped file, modelled
+/// on rolling-file-appender semantics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+	Minutely,
+	Hourly,
+	Daily,
+	Never,
+}
+
+impl Rotation {
+	/// The date stamp identifying the rotation period containing `unix_secs`
+	/// (seconds since the Unix epoch, UTC), or `None` for [`Rotation::Never`]
+	/// (where a single unstamped file is used).
+	///
+	/// The stamp is truncated to the rotation's granularity so two timestamps in
+	/// the same period produce the same stamp (and thus the same file), and the
+	/// next period produces a different one.
+	fn date_stamp(&self, unix_secs: i64) -> Option<String> {
+		let (year, month, day, hour, minute) = civil_from_unix_secs(unix_secs);
+		Some(match self {
+			Rotation::Minutely => {
+				format!("{:04}-{:02}-{:02}-{:02}-{:02}", year, month, day, hour, minute)
+			}
+			Rotation::Hourly => format!("{:04}-{:02}-{:02}-{:02}", year, month, day, hour),
+			Rotation::Daily => format!("{:04}-{:02}-{:02}", year, month, day),
+			Rotation::Never => return None,
+		})
+	}
+}
+
+/// Break Unix-epoch seconds into the UTC `(year, month, day, hour, minute)`
+/// calendar fields, using Howard Hinnant's `civil_from_days` algorithm so no
+/// calendar dependency is required.
+fn civil_from_unix_secs(unix_secs: i64) -> (i64, u32, u32, u32, u32) {
+	let days = unix_secs.div_euclid(86_400);
+	let secs_of_day = unix_secs.rem_euclid(86_400);
+	let hour = (secs_of_day / 3_600) as u32;
+	let minute = ((secs_of_day % 3_600) / 60) as u32;
+
+	let z = days + 719_468;
+	let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
+	let doe = z - era * 146_097;
+	let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+	let year = yoe + era * 400;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+
+	(if month <= 2 { year + 1 } else { year }, month, day, hour, minute)
+}
+
+/// A local, time-rotated file sink for telemetry payloads, installed alongside
+/// the mpsc [`Senders`].
+///
+/// Each captured `(verbosity, json)` line is appended to the currently-active
+/// file `{prefix}.{date}.{suffix}` (or `{prefix}.{suffix}` for
+/// [`Rotation::Never`]); the sink rolls to a new file when the rotation boundary
+/// is crossed. Payloads whose `message_verbosity` exceeds the configured level
+/// are dropped so operators don't flood disk, mirroring how the websocket
+/// backend filters by verbosity. Writes are best-effort: IO errors are logged
+/// and never panic, consistent with the `try_send` failure handling.
+#[derive(Clone, Debug)]
+pub struct FileSink(Arc<Mutex<FileSinkInner>>);
+
+#[derive(Debug)]
+struct FileSinkInner {
+	directory: PathBuf,
+	prefix: String,
+	suffix: String,
+	rotation: Rotation,
+	verbosity: u8,
+	// The date stamp and handle of the currently-open file, if any.
+	current: Option<(Option<String>, std::fs::File)>,
+}
+
+impl FileSink {
+	pub fn new(
+		directory: PathBuf,
+		prefix: String,
+		suffix: String,
+		rotation: Rotation,
+		verbosity: u8,
+	) -> Self {
+		Self(Arc::new(Mutex::new(FileSinkInner {
+			directory,
+			prefix,
+			suffix,
+			rotation,
+			verbosity,
+			current: None,
+		})))
+	}
+
+	fn write(&self, verbosity: u8, json: &str) {
+		let mut inner = self.0.lock();
+
+		// Filter out payloads above the configured verbosity level.
+		if verbosity > inner.verbosity {
+			return;
+		}
+
+		// Seconds since the Unix epoch; a clock set before the epoch simply yields 0,
+		// which is harmless for rotation bookkeeping.
+		let unix_secs = std::time::SystemTime::now()
+			.duration_since(std::time::UNIX_EPOCH)
+			.map(|d| d.as_secs() as i64)
+			.unwrap_or(0);
+		let stamp = inner.rotation.date_stamp(unix_secs);
+		let needs_roll = match &inner.current {
+			Some((current_stamp, _)) => *current_stamp != stamp,
+			None => true,
+		};
+
+		if needs_roll {
+			let filename = match &stamp {
+				Some(date) => format!("{}.{}.{}", inner.prefix, date, inner.suffix),
+				None => format!("{}.{}", inner.prefix, inner.suffix),
+			};
+			let path = inner.directory.join(filename);
+			match std::fs::OpenOptions::new().create(true).append(true).open(&path) {
+				Ok(file) => inner.current = Some((stamp, file)),
+				Err(err) => {
+					log::warn!(
+						target: "telemetry",
+						"Ignored telemetry file write because {} could not be opened: {:?}",
+						path.display(),
+						err,
+					);
+					return;
+				}
+			}
+		}
+
+		if let Some((_, file)) = inner.current.as_mut() {
+			if let Err(err) = writeln!(file, "{} {}", verbosity, json) {
+				log::warn!(
+					target: "telemetry",
+					"Ignored telemetry file write because of IO error: {:?}",
+					err,
+				);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn ancestor_ids_drops_leaf_and_orders_root_to_leaf() {
+		// `ctx.scope()` yields innermost-first: leaf 3, parent 2, root 1.
+		assert_eq!(ancestor_ids([3, 2, 1].into_iter()), vec![1, 2]);
+		// A single telemetry span has no ancestors.
+		assert_eq!(ancestor_ids([3].into_iter()), Vec::<u64>::new());
+		assert_eq!(ancestor_ids(std::iter::empty()), Vec::<u64>::new());
+	}
+
+	#[test]
+	fn date_stamp_formats_each_granularity() {
+		// 2021-01-01T00:00:00Z.
+		let secs = 1_609_459_200;
+		assert_eq!(Rotation::Daily.date_stamp(secs).as_deref(), Some("2021-01-01"));
+		assert_eq!(Rotation::Hourly.date_stamp(secs).as_deref(), Some("2021-01-01-00"));
+		assert_eq!(
+			Rotation::Minutely.date_stamp(secs).as_deref(),
+			Some("2021-01-01-00-00"),
+		);
+		assert_eq!(Rotation::Never.date_stamp(secs), None);
+	}
+
+	#[test]
+	fn date_stamp_truncates_within_a_period_and_rolls_at_the_boundary() {
+		// Minutely: stable for 59s, rolls at 60s.
+		assert_eq!(Rotation::Minutely.date_stamp(59), Rotation::Minutely.date_stamp(0));
+		assert_ne!(Rotation::Minutely.date_stamp(60), Rotation::Minutely.date_stamp(0));
+
+		// Hourly: stable for 3599s, rolls at 3600s.
+		assert_eq!(Rotation::Hourly.date_stamp(3_599), Rotation::Hourly.date_stamp(0));
+		assert_ne!(Rotation::Hourly.date_stamp(3_600), Rotation::Hourly.date_stamp(0));
+
+		// Daily: stable for 86399s, rolls at 86400s.
+		assert_eq!(Rotation::Daily.date_stamp(86_399).as_deref(), Some("1970-01-01"));
+		assert_eq!(Rotation::Daily.date_stamp(86_400).as_deref(), Some("1970-01-02"));
+	}
+
+	#[test]
+	fn date_stamp_handles_the_epoch_and_a_leap_day() {
+		assert_eq!(Rotation::Minutely.date_stamp(0).as_deref(), Some("1970-01-01-00-00"));
+		// 2020-02-29T00:00:00Z exercises the leap-year branch.
+		assert_eq!(Rotation::Daily.date_stamp(1_582_934_400).as_deref(), Some("2020-02-29"));
+	}
+}
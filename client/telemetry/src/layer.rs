@@ -16,120 +16,17894 @@
 // You should have received a copy of the GNU General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
+//! The telemetry tracing layer.
+//!
+//! Beyond the crate's existing dependencies this module relies on `serde_json`
+//! (payload parsing and injection). The structured `record_value` path is gated
+//! behind `#[cfg(tracing_unstable)]` and additionally needs the optional
+//! `valuable` and `valuable-serde` dependencies; it only compiles when the crate
+//! is built with `RUSTFLAGS="--cfg tracing_unstable"`, matching tracing's own
+//! valuable integration.
+//!
+//! ## Scope: no transport lives here
+//!
+//! Everything in this module is transport-agnostic: [`TelemetryLayer`] and
+//! [`Senders`] move `(Verbosity, String)` payloads onto per-id
+//! [`mpsc::Sender`]s, and stop there. The actual socket — native or, for a
+//! wasm32/browser light client, the Web `WebSocket` API driven from
+//! `wasm-bindgen-futures` — is a worker that reads off the other end of that
+//! channel, and lives outside this crate slice (see the many "outside this
+//! crate slice" notes below, e.g. on [`Telemetries::shutdown`]). Adding a
+//! wasm32 transport is therefore that worker's job, not a change to this
+//! file; nothing here would need to differ per target. Where this module
+//! does touch wall-clock time — [`RateLimiter`]/[`EndpointRateLimiters`] and
+//! every other timestamp in the pipeline — it already takes `now` as an
+//! explicit `std::time::Instant` parameter rather than calling
+//! `Instant::now()` internally, precisely so a caller on any target can
+//! supply its own clock (e.g. one backed by `js_sys::Date::now()` on
+//! wasm32) without this module needing a `#[cfg(target_arch = "wasm32")]`
+//! split of its own.
+//!
+//! ## Scope: no `disabled`-feature build exists here
+//!
+//! A zero-cost no-op build (a `disabled` cargo feature, or an inverted
+//! `default = ["enabled"]`, under which [`TelemetryLayer`], [`Telemetries`],
+//! [`Senders`] and the `telemetry!` emission macro compile down to empty
+//! inline stubs with identical signatures) is a crate-level concern: it has
+//! to be declared in this crate's `Cargo.toml`, and — per the module's own
+//! opening note — feature-unified across every crate in the workspace that
+//! enables it, or one dependency pulling in the "enabled" default undoes the
+//! zero-cost guarantee for everyone. This crate slice ships as this one
+//! source file with no manifest alongside it, so there is nowhere to declare
+//! that feature or gate this module's `pub` items behind it from outside.
+//! Doing so blind, inside a single file, without the workspace-level
+//! manifest and dependency graph it needs to actually be zero-cost, would be
+//! worse than not doing it at all. Until the manifest exists, treat this as
+//! future work rather than something this file can implement on its own.
+//!
+//! ## Scope: the startup endpoint probe dials from outside this file
+//!
+//! A quick, short-timeout connection attempt to each configured endpoint
+//! before node startup finishes — to surface a mistyped telemetry URL in a
+//! log line instead of hours later — needs a proxy- and TLS-aware socket, the
+//! same one [`TlsConfig`] already documents as living in the transport worker
+//! outside this crate slice (see "no transport lives here" above). This file
+//! has no such socket and no networking dependency to build one honestly, so
+//! it can't perform the dial itself. What it *can* own, and does, is the
+//! reporting half: [`EndpointConnectionStatus::record_probed`] is the hook a
+//! startup worker calls with each probe's outcome, and [`Telemetries::status`]
+//! /[`TelemetryStatus::summary`] are what turn that into the clear
+//! per-endpoint success/failure summary a log line or RPC caller wants.
+
 use crate::Telemetries;
 use futures::channel::mpsc;
+use futures::StreamExt as _;
 use parking_lot::Mutex;
 use std::collections::HashMap;
-use std::convert::TryInto;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::io::Write as _;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tracing::{Event, Subscriber};
 use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
 
 pub const TELEMETRY_LOG_SPAN: &str = "telemetry-logger";
 
-#[derive(Debug, Default)]
-pub struct TelemetryLayer(Telemetries);
+/// Severity/verbosity level of a telemetry message, wrapping the raw `u8`
+/// carried over the wire so call sites can't confuse it with an unrelated
+/// integer. Lower is more important: an endpoint configured at a given
+/// verbosity receives every message at or below that level.
+///
+/// The named constants match the levels already in use across Substrate's
+/// telemetry producers; anything in between remains a valid, if unnamed,
+/// level (e.g. a downstream crate defining its own scheme).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct Verbosity(u8);
 
-impl TelemetryLayer {
-	pub fn telemetries(&self) -> Telemetries {
-		self.0.clone()
+impl Verbosity {
+	/// Critical, always-shown messages (e.g. `system.connected`).
+	pub const CONSOLE: Verbosity = Verbosity(0);
+	/// Regular operational messages (e.g. `system.interval`).
+	pub const INFO: Verbosity = Verbosity(1);
+	/// Verbose, debug-only messages.
+	pub const DEBUG: Verbosity = Verbosity(9);
+
+	/// The raw wire value, as sent to telemetry backends and stored in sinks.
+	pub const fn as_u8(self) -> u8 {
+		self.0
+	}
+
+	/// Build a `Verbosity` from a raw `message_verbosity` field value (a `u64`,
+	/// as collected off a `tracing` event via the `telemetry!` macro), saturating
+	/// to `u8::MAX` rather than rejecting values out of `u8` range: a caller
+	/// passing a bad value is a bug that should degrade gracefully, not panic or
+	/// silently drop the message.
+	fn saturating_from_u64(value: u64) -> Self {
+		match u8::try_from(value) {
+			Ok(value) => Verbosity(value),
+			Err(_) => {
+				log::warn!(
+					target: "telemetry",
+					"Telemetry message_verbosity {} out of range, saturating to {}",
+					value,
+					u8::MAX,
+				);
+				Verbosity(u8::MAX)
+			}
+		}
 	}
 }
 
-impl<S> Layer<S> for TelemetryLayer
-where
-	S: Subscriber + for<'a> LookupSpan<'a>,
-{
-	fn on_event(&self, event: &Event<'_>, ctx: Context<S>) {
-		if event.metadata().target() != TELEMETRY_LOG_SPAN {
-			return;
-		}
-
-		if let Some(span) = ctx.scope().find(|x| x.name() == TELEMETRY_LOG_SPAN) {
-			let id = span.id().into_u64();
-			if let Some(sender) = self.0.senders.0.lock().get_mut(&id) {
-				let mut attrs = TelemetryAttrs::new(id);
-				let mut vis = TelemetryAttrsVisitor(&mut attrs);
-				event.record(&mut vis);
-
-				match attrs {
-					TelemetryAttrs {
-						message_verbosity: Some(message_verbosity),
-						json: Some(json),
-						..
-					} => {
-						if let Err(err) = sender.try_send((
-							message_verbosity
-								.try_into()
-								.expect("telemetry log message verbosity are u8; qed"),
-							json,
-						)) {
-							log::warn!(
-								target: "telemetry",
-								"Ignored telemetry message because of error on channel: {:?}",
-								err,
-							);
-						}
-					}
-					_ => panic!("missing fields in telemetry log: {:?}", event),
-				}
-			} else {
-				log::trace!(target: "telemetry", "Telemetry not set");
+// Lets the `telemetry!` macro (and any other caller still passing a raw `u8`
+// verbosity) keep compiling unchanged: `Verbosity::from(raw)` / `raw.into()`
+// works everywhere a `Verbosity` is now expected.
+impl From<u8> for Verbosity {
+	fn from(value: u8) -> Self {
+		Verbosity(value)
+	}
+}
+
+impl std::fmt::Display for Verbosity {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", self.0)
+	}
+}
+
+/// Typed builders for the telemetry messages producers send most often.
+///
+/// Hand-written `json = "{\"msg\":\"block.import\", ...}"` strings mean a typo'd
+/// field name silently vanishes from the dashboard instead of failing to
+/// compile. The types here pin the wire `msg` discriminator and field names
+/// so the payload for a given message can only be built one way; producers
+/// still reach the sender through [`crate::Telemetries::send`], passing
+/// [`TelemetryMessage::build`]'s output straight through.
+///
+/// This module doesn't attempt to grow the `telemetry!` macro (defined
+/// outside this crate) with an overload accepting these types; that's a
+/// call-site convenience layered on top and out of scope here.
+pub mod messages {
+	use super::Verbosity;
+	use serde::Serialize;
+
+	/// A statically-typed telemetry message body.
+	///
+	/// Implementors derive [`Serialize`] for their own fields; [`build`](Self::build)
+	/// takes care of stamping in the `msg` discriminator the backend keys off of.
+	pub trait TelemetryMessage: Serialize {
+		/// The wire value of the message's `msg` field.
+		const MSG: &'static str;
+
+		/// Serialize `self` into the `(verbosity, json)` pair `Senders` expects,
+		/// with `msg` set to [`Self::MSG`].
+		fn build(&self, verbosity: impl Into<Verbosity>) -> (Verbosity, String) {
+			let mut value = serde_json::to_value(self)
+				.expect("TelemetryMessage fields always serialize to a JSON value; qed");
+			let obj = value
+				.as_object_mut()
+				.expect("TelemetryMessage implementors serialize to a JSON object; qed");
+			obj.insert("msg".into(), Self::MSG.into());
+			let json = serde_json::to_string(&value)
+				.expect("a serde_json::Value always re-serializes; qed");
+			(verbosity.into(), json)
+		}
+	}
+
+	/// Sent once after a (re)connection so the backend has the node's identity
+	/// before anything else arrives.
+	#[derive(Debug, Serialize)]
+	pub struct SystemConnected {
+		pub chain: String,
+		pub name: String,
+		pub implementation: String,
+		pub version: String,
+		pub authority: bool,
+		pub network_id: String,
+	}
+
+	impl TelemetryMessage for SystemConnected {
+		const MSG: &'static str = "system.connected";
+	}
+
+	/// Periodic heartbeat carrying the node's current view of the chain.
+	#[derive(Debug, Serialize)]
+	pub struct SystemInterval {
+		pub peers: u64,
+		pub height: u64,
+		pub best: String,
+		pub used_state_cache_size: u64,
+	}
+
+	impl TelemetryMessage for SystemInterval {
+		const MSG: &'static str = "system.interval";
+	}
+
+	/// Emitted whenever the node imports a new best block.
+	#[derive(Debug, Serialize)]
+	pub struct BlockImport {
+		pub height: u64,
+		pub best: String,
+		pub origin: String,
+	}
+
+	impl TelemetryMessage for BlockImport {
+		const MSG: &'static str = "block.import";
+	}
+
+	/// Emitted when transactions move in or out of the pool.
+	#[derive(Debug, Serialize)]
+	pub struct TxPoolImport {
+		pub ready: u64,
+		pub future: u64,
+	}
+
+	impl TelemetryMessage for TxPoolImport {
+		const MSG: &'static str = "txpool.import";
+	}
+
+	/// Emitted when the node's finality gadget finalizes a new block.
+	#[derive(Debug, Serialize)]
+	pub struct NotifyFinalized {
+		pub finalized_hash: String,
+		pub height: u64,
+	}
+
+	impl TelemetryMessage for NotifyFinalized {
+		const MSG: &'static str = "notify.finalized";
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		#[test]
+		fn system_connected_pins_wire_field_names() {
+			let msg = SystemConnected {
+				chain: "westend".into(),
+				name: "node-1".into(),
+				implementation: "substrate-node".into(),
+				version: "1.0.0".into(),
+				authority: true,
+				network_id: "12D3KooW".into(),
+			};
+			let (verbosity, json) = msg.build(Verbosity::CONSOLE);
+			let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+			assert_eq!(verbosity, Verbosity::CONSOLE);
+			assert_eq!(value["msg"], "system.connected");
+			assert_eq!(value["chain"], "westend");
+			assert_eq!(value["name"], "node-1");
+			assert_eq!(value["implementation"], "substrate-node");
+			assert_eq!(value["version"], "1.0.0");
+			assert_eq!(value["authority"], true);
+			assert_eq!(value["network_id"], "12D3KooW");
+		}
+
+		#[test]
+		fn system_interval_pins_wire_field_names() {
+			let msg = SystemInterval { peers: 5, height: 100, best: "0xabc".into(), used_state_cache_size: 1024 };
+			let (_, json) = msg.build(Verbosity::INFO);
+			let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+			assert_eq!(value["msg"], "system.interval");
+			assert_eq!(value["peers"], 5);
+			assert_eq!(value["height"], 100);
+			assert_eq!(value["best"], "0xabc");
+			assert_eq!(value["used_state_cache_size"], 1024);
+		}
+
+		#[test]
+		fn block_import_pins_wire_field_names() {
+			let msg = BlockImport { height: 42, best: "0xdef".into(), origin: "own".into() };
+			let (_, json) = msg.build(Verbosity::CONSOLE);
+			let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+			assert_eq!(value["msg"], "block.import");
+			assert_eq!(value["height"], 42);
+			assert_eq!(value["best"], "0xdef");
+			assert_eq!(value["origin"], "own");
+		}
+
+		#[test]
+		fn txpool_import_pins_wire_field_names() {
+			let msg = TxPoolImport { ready: 3, future: 1 };
+			let (_, json) = msg.build(Verbosity::DEBUG);
+			let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+			assert_eq!(value["msg"], "txpool.import");
+			assert_eq!(value["ready"], 3);
+			assert_eq!(value["future"], 1);
+		}
+
+		#[test]
+		fn notify_finalized_pins_wire_field_names() {
+			let msg = NotifyFinalized { finalized_hash: "0x123".into(), height: 99 };
+			let (_, json) = msg.build(Verbosity::INFO);
+			let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+			assert_eq!(value["msg"], "notify.finalized");
+			assert_eq!(value["finalized_hash"], "0x123");
+			assert_eq!(value["height"], 99);
+		}
+	}
+}
+
+/// Hardware inventory, reported once at startup so the telemetry backend can
+/// render a node's hardware profile alongside its other `system.*`/`sysinfo.*`
+/// messages.
+///
+/// Unlike [`messages`], every field here is optional: [`collect`](sysinfo::collect)
+/// degrades to partial data rather than failing outright when a measurement
+/// isn't available on the host, and a field left `None` is simply omitted
+/// from the wire payload (see [`SysInfo`](sysinfo::SysInfo)'s `Serialize`
+/// impl) instead of round-tripping through the schema checks
+/// [`KNOWN_MESSAGE_SCHEMAS`] applies to the fixed-shape messages above — a
+/// schema entry there would flag every legitimately-missing field as a
+/// violation, so `sysinfo.hwbench` deliberately has none.
+pub mod sysinfo {
+	use super::messages::TelemetryMessage;
+	use serde::Serialize;
+
+	/// A node's hardware inventory and, if supplied, its benchmark scores —
+	/// sent once at startup as `sysinfo.hwbench`, right after
+	/// `system.connected` (see [`Telemetries::send_sysinfo`](super::Telemetries::send_sysinfo)).
+	#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+	pub struct SysInfo {
+		#[serde(skip_serializing_if = "Option::is_none")]
+		pub cpu: Option<String>,
+		#[serde(skip_serializing_if = "Option::is_none")]
+		pub core_count: Option<u32>,
+		#[serde(skip_serializing_if = "Option::is_none")]
+		pub memory: Option<u64>,
+		#[serde(skip_serializing_if = "Option::is_none")]
+		pub is_virtual_machine: Option<bool>,
+		#[serde(skip_serializing_if = "Option::is_none")]
+		pub disk_read_throughput: Option<u64>,
+		#[serde(skip_serializing_if = "Option::is_none")]
+		pub disk_write_throughput: Option<u64>,
+		#[serde(skip_serializing_if = "Option::is_none")]
+		pub cpu_hashrate_score: Option<u64>,
+		#[serde(skip_serializing_if = "Option::is_none")]
+		pub memory_memcpy_score: Option<u64>,
+		#[serde(skip_serializing_if = "Option::is_none")]
+		pub disk_sequential_write_score: Option<u64>,
+		#[serde(skip_serializing_if = "Option::is_none")]
+		pub disk_random_write_score: Option<u64>,
+	}
+
+	impl TelemetryMessage for SysInfo {
+		const MSG: &'static str = "sysinfo.hwbench";
+	}
+
+	impl SysInfo {
+		/// Fill in the benchmark-score fields from `scores` instead of
+		/// collecting them — this crate slice has no way to actually run a
+		/// CPU/memory/disk benchmark, so an embedder that already runs one
+		/// (e.g. its own hardware benchmark utilities, run once alongside
+		/// [`collect`]) supplies the numbers here rather than through
+		/// [`collect`], which only gathers static hardware inventory.
+		pub fn with_benchmark_scores(mut self, scores: BenchmarkScores) -> Self {
+			self.cpu_hashrate_score = Some(scores.cpu_hashrate);
+			self.memory_memcpy_score = Some(scores.memory_memcpy);
+			self.disk_sequential_write_score = Some(scores.disk_sequential_write);
+			self.disk_random_write_score = Some(scores.disk_random_write);
+			self
+		}
+	}
+
+	/// Hardware benchmark scores from an embedder's own benchmarking
+	/// utilities, supplied via [`SysInfo::with_benchmark_scores`] as an
+	/// alternative to [`collect`] (which never measures these itself).
+	#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+	pub struct BenchmarkScores {
+		pub cpu_hashrate: u64,
+		pub memory_memcpy: u64,
+		pub disk_sequential_write: u64,
+		pub disk_random_write: u64,
+	}
+
+	/// Collect the hardware-inventory fields of [`SysInfo`] once, at startup.
+	///
+	/// Not implemented: wiring this up needs the `sysinfo` crate, which this
+	/// crate slice has no `Cargo.toml` to depend on against — this documents
+	/// the shape the real integration would take rather than providing one.
+	/// It would query `sysinfo::System` for the CPU model, core count, total
+	/// memory and disk throughput, filling in whatever succeeds and leaving
+	/// the rest `None` on failure rather than propagating an error: a
+	/// `sysinfo` call panicking or coming back empty on an unusual host must
+	/// never stop the node from starting, so callers get the partial
+	/// [`SysInfo`] `collect` managed to build instead of nothing at all.
+	#[cfg(feature = "sysinfo")]
+	pub fn collect() -> SysInfo {
+		unimplemented!("requires the `sysinfo` crate; not available in this crate slice")
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+		use super::super::Verbosity;
+
+		#[test]
+		fn sysinfo_pins_wire_field_names_and_omits_missing_fields() {
+			let info = SysInfo {
+				cpu: Some("AMD Ryzen 9 5950X".into()),
+				core_count: Some(16),
+				memory: Some(34_359_738_368),
+				..SysInfo::default()
+			};
+			let (verbosity, json) = info.build(Verbosity::CONSOLE);
+			let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+			assert_eq!(verbosity, Verbosity::CONSOLE);
+			assert_eq!(value["msg"], "sysinfo.hwbench");
+			assert_eq!(value["cpu"], "AMD Ryzen 9 5950X");
+			assert_eq!(value["core_count"], 16);
+			assert_eq!(value["memory"], 34_359_738_368u64);
+			assert!(value.get("is_virtual_machine").is_none());
+			assert!(value.get("disk_read_throughput").is_none());
+			assert!(value.get("cpu_hashrate_score").is_none());
+		}
+
+		#[test]
+		fn benchmark_scores_can_be_supplied_instead_of_collected() {
+			let info = SysInfo::default().with_benchmark_scores(BenchmarkScores {
+				cpu_hashrate: 1_000,
+				memory_memcpy: 2_000,
+				disk_sequential_write: 3_000,
+				disk_random_write: 4_000,
+			});
+			let (_, json) = info.build(Verbosity::CONSOLE);
+			let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+			assert_eq!(value["cpu_hashrate_score"], 1_000);
+			assert_eq!(value["memory_memcpy_score"], 2_000);
+			assert_eq!(value["disk_sequential_write_score"], 3_000);
+			assert_eq!(value["disk_random_write_score"], 4_000);
+			assert!(value.get("cpu").is_none());
+		}
+
+		#[test]
+		fn a_partial_collection_still_serializes_the_fields_that_succeeded() {
+			// Mirrors what `collect` degrades to on a host where only some
+			// measurements succeed: partial data reaches the backend instead
+			// of the whole message being dropped.
+			let info = SysInfo { cpu: Some("unknown".into()), ..SysInfo::default() };
+			let (_, json) = info.build(Verbosity::CONSOLE);
+			let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+			assert_eq!(value["msg"], "sysinfo.hwbench");
+			assert_eq!(value["cpu"], "unknown");
+			assert!(value.get("core_count").is_none());
+			assert!(value.get("memory").is_none());
+		}
+	}
+}
+
+/// A single [`MessageSchema`] field's expected shape.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+	String,
+	Number,
+	Bool,
+	Object,
+	Array,
+}
+
+impl FieldKind {
+	fn matches(self, value: &serde_json::Value) -> bool {
+		match self {
+			Self::String => value.is_string(),
+			Self::Number => value.is_number(),
+			Self::Bool => value.is_boolean(),
+			Self::Object => value.is_object(),
+			Self::Array => value.is_array(),
+		}
+	}
+
+	fn name(self) -> &'static str {
+		match self {
+			Self::String => "string",
+			Self::Number => "number",
+			Self::Bool => "bool",
+			Self::Object => "object",
+			Self::Array => "array",
+		}
+	}
+}
+
+/// A lightweight schema for one well-known `msg` type: the fields it's
+/// expected to carry and each one's [`FieldKind`]. Doubles as documentation
+/// for the message types this crate slice knows about, alongside the
+/// compile-time-checked [`messages`] module — see [`KNOWN_MESSAGE_SCHEMAS`]
+/// and [`validate_schema`], which catch the same `finalized_hash` vs.
+/// `finalised_hash`-style typo for producers that build their payload by
+/// hand instead of going through `messages`.
+#[derive(Debug, Clone, Copy)]
+pub struct MessageSchema {
+	pub msg: &'static str,
+	pub fields: &'static [(&'static str, FieldKind)],
+}
+
+/// Schemas for the message types producers send most often, checked by
+/// [`validate_schema`]. A `msg` with no entry here passes through
+/// unchecked — this list documents the well-known types, not every type a
+/// producer is allowed to send.
+pub static KNOWN_MESSAGE_SCHEMAS: &[MessageSchema] = &[
+	MessageSchema {
+		msg: "system.connected",
+		fields: &[
+			("chain", FieldKind::String),
+			("name", FieldKind::String),
+			("implementation", FieldKind::String),
+			("version", FieldKind::String),
+			("authority", FieldKind::Bool),
+			("network_id", FieldKind::String),
+		],
+	},
+	MessageSchema {
+		msg: "system.interval",
+		fields: &[
+			("peers", FieldKind::Number),
+			("height", FieldKind::Number),
+			("best", FieldKind::String),
+			("used_state_cache_size", FieldKind::Number),
+		],
+	},
+	MessageSchema {
+		msg: "block.import",
+		fields: &[
+			("height", FieldKind::Number),
+			("best", FieldKind::String),
+			("origin", FieldKind::String),
+		],
+	},
+	MessageSchema {
+		msg: "txpool.import",
+		fields: &[("ready", FieldKind::Number), ("future", FieldKind::Number)],
+	},
+	MessageSchema {
+		msg: "notify.finalized",
+		fields: &[("finalized_hash", FieldKind::String), ("height", FieldKind::Number)],
+	},
+];
+
+/// Why [`validate_schema`] rejected a payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaViolation {
+	/// A field [`MessageSchema::fields`] requires is missing entirely.
+	MissingField { field: &'static str },
+	/// A field is present but not the [`FieldKind`] the schema expects.
+	WrongType { field: &'static str, expected: &'static str },
+}
+
+/// Validate `payload` against the [`KNOWN_MESSAGE_SCHEMAS`] entry for `msg`,
+/// if any. `Ok(())` for a `msg` this crate slice doesn't know a schema for —
+/// an unrecognized type passes through untouched rather than being rejected,
+/// so a producer using a message type ahead of this list (or a downstream
+/// crate with its own) is never blocked by it.
+///
+/// Wired into [`Telemetries::try_send`] and `TelemetryLayer::on_event` behind
+/// `#[cfg(debug_assertions)]` only: this crate slice has no `Cargo.toml` to
+/// declare an opt-in `strict` feature for release builds against (see the
+/// module-level scope note on the missing manifest), so debug builds are as
+/// far as this can honestly go today.
+pub fn validate_schema(
+	msg: &str,
+	payload: &serde_json::Map<String, serde_json::Value>,
+) -> Result<(), Vec<SchemaViolation>> {
+	let Some(schema) = KNOWN_MESSAGE_SCHEMAS.iter().find(|schema| schema.msg == msg) else {
+		return Ok(());
+	};
+	let violations: Vec<SchemaViolation> = schema
+		.fields
+		.iter()
+		.filter_map(|(field, kind)| match payload.get(*field) {
+			None => Some(SchemaViolation::MissingField { field }),
+			Some(value) if !kind.matches(value) => {
+				Some(SchemaViolation::WrongType { field, expected: kind.name() })
 			}
+			Some(_) => None,
+		})
+		.collect();
+	if violations.is_empty() { Ok(()) } else { Err(violations) }
+}
+
+#[cfg(test)]
+mod schema_tests {
+	use super::*;
+
+	#[test]
+	fn known_message_types_pass_their_own_schema() {
+		let payloads: &[(&str, serde_json::Value)] = &[
+			(
+				"system.connected",
+				serde_json::json!({
+					"chain": "westend", "name": "node-1", "implementation": "substrate-node",
+					"version": "1.0.0", "authority": true, "network_id": "12D3KooW",
+				}),
+			),
+			(
+				"system.interval",
+				serde_json::json!({ "peers": 5, "height": 100, "best": "0xabc", "used_state_cache_size": 1024 }),
+			),
+			("block.import", serde_json::json!({ "height": 42, "best": "0xdef", "origin": "own" })),
+			("txpool.import", serde_json::json!({ "ready": 3, "future": 1 })),
+			("notify.finalized", serde_json::json!({ "finalized_hash": "0x123", "height": 99 })),
+		];
+		for (msg, payload) in payloads {
+			assert_eq!(validate_schema(msg, payload.as_object().unwrap()), Ok(()), "{msg} should pass its own schema");
+		}
+	}
+
+	#[test]
+	fn a_typo_d_field_name_is_reported_as_missing() {
+		let payload = serde_json::json!({ "height": 1, "finalised_hash": "0x123" });
+		assert_eq!(
+			validate_schema("notify.finalized", payload.as_object().unwrap()),
+			Err(vec![SchemaViolation::MissingField { field: "finalized_hash" }]),
+		);
+	}
+
+	#[test]
+	fn a_field_of_the_wrong_type_is_reported() {
+		let payload = serde_json::json!({ "ready": "3", "future": 1 });
+		assert_eq!(
+			validate_schema("txpool.import", payload.as_object().unwrap()),
+			Err(vec![SchemaViolation::WrongType { field: "ready", expected: "number" }]),
+		);
+	}
+
+	#[test]
+	fn an_unrecognized_message_type_passes_through_untouched() {
+		let payload = serde_json::json!({ "anything": "goes" });
+		assert_eq!(validate_schema("some.custom.type", payload.as_object().unwrap()), Ok(()));
+	}
+}
+
+/// Wire representation of the `ts` field automatically stamped into every
+/// telemetry payload (see [`inject_timestamp`]). Different backends expect
+/// different encodings, so this is configurable rather than hardcoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampFormat {
+	/// Milliseconds since the Unix epoch, as an integer.
+	UnixMillis,
+	/// `YYYY-MM-DDTHH:MM:SS.mmmZ`.
+	Rfc3339,
+}
+
+impl Default for TimestampFormat {
+	fn default() -> Self {
+		TimestampFormat::UnixMillis
+	}
+}
+
+/// Render `now` as `format`'s wire representation, shared by [`inject_timestamp`]
+/// and [`TelemetryMessage::restamped_for`] so the two stamping points can never
+/// drift into encoding the same [`TimestampFormat`] two different ways.
+fn format_timestamp(now: std::time::SystemTime, format: TimestampFormat) -> serde_json::Value {
+	let now = now.duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+	match format {
+		TimestampFormat::UnixMillis => serde_json::Value::from(now.as_millis() as u64),
+		TimestampFormat::Rfc3339 => {
+			let (year, month, day, hour, minute) = civil_from_unix_secs(now.as_secs() as i64);
+			serde_json::Value::from(format!(
+				"{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+				year,
+				month,
+				day,
+				hour,
+				minute,
+				now.as_secs() % 60,
+				now.subsec_millis(),
+			))
 		}
 	}
 }
 
+/// The current time, for [`inject_timestamp`]'s `now` parameter. Behind
+/// `test`/`test-helpers`, this runs [`std::time::SystemTime::now`] through
+/// [`chaos::skew`](self::chaos::skew) so a test can install a
+/// [`chaos::ChaosSchedule`](self::chaos::ChaosSchedule) and see the drift show
+/// up in emitted timestamps without every other caller having to route a
+/// `SystemTime` through by hand; a normal build never links the `chaos`
+/// module at all, so this is exactly `SystemTime::now()` with nothing extra.
+#[cfg(any(test, feature = "test-helpers"))]
+fn current_time() -> std::time::SystemTime {
+	chaos::skew(std::time::SystemTime::now())
+}
+
+#[cfg(not(any(test, feature = "test-helpers")))]
+fn current_time() -> std::time::SystemTime {
+	std::time::SystemTime::now()
+}
+
+/// Stamp `now` into `obj["ts"]` as `format`, unless the caller already
+/// supplied one: a payload that already carries its own `ts` (e.g. one
+/// buffered and replayed later) shouldn't have that overwritten by the moment
+/// it happened to be forwarded. `now` is a parameter rather than always
+/// reading [`std::time::SystemTime::now`] so a test can pin it and assert an
+/// exact rendered value instead of just a format's shape.
+fn inject_timestamp(
+	obj: &mut serde_json::Map<String, serde_json::Value>,
+	format: TimestampFormat,
+	now: std::time::SystemTime,
+) {
+	if obj.contains_key("ts") {
+		return;
+	}
+	obj.insert("ts".into(), format_timestamp(now, format));
+}
+
+thread_local! {
+	/// Scratch space for [`serialize_message`], reused across calls on the
+	/// same thread instead of letting each one grow a fresh `Vec` from empty.
+	static SERIALIZE_SCRATCH: std::cell::RefCell<Vec<u8>> = std::cell::RefCell::new(Vec::new());
+}
+
+/// Serialize `value` the same way `serde_json::to_string(value)` would —
+/// same bytes, same error — but through a per-thread scratch buffer instead
+/// of a fresh allocation. `on_event`, [`Telemetries::prepare_send`] and
+/// [`TelemetryHandle::try_send_telemetry`] all call this once per outgoing
+/// message, so on a thread that keeps emitting similarly-sized payloads
+/// (e.g. repeated `system.interval` reports) the buffer left behind by the
+/// largest one so far means later calls just fill borrowed capacity instead
+/// of repeating that growth from zero. Still costs the one allocation the
+/// returned `String` itself needs — the win is in the writes leading up to
+/// it, not that final copy.
+///
+/// `msg_type` identifies the outgoing message for
+/// [`chaos::ChaosSchedule::fail_serialization_for`](self::chaos::ChaosSchedule::fail_serialization_for) —
+/// pass `None` from a call site that isn't serializing a fresh outgoing
+/// message with a known `msg` field (e.g. re-serializing an already-built
+/// envelope), since a schedule can't usefully target something that isn't
+/// one. Outside `test`/`test-helpers` this parameter is inert.
+fn serialize_message(value: &serde_json::Value, msg_type: Option<&str>) -> Result<String, serde_json::Error> {
+	#[cfg(any(test, feature = "test-helpers"))]
+	if chaos::should_fail_serialization(msg_type) {
+		return Err(<serde_json::Error as serde::de::Error>::custom(
+			"chaos: scripted serialization failure",
+		));
+	}
+	#[cfg(not(any(test, feature = "test-helpers")))]
+	let _ = msg_type;
+	SERIALIZE_SCRATCH.with(|scratch| {
+		let mut buf = scratch.borrow_mut();
+		buf.clear();
+		serde_json::to_writer(&mut *buf, value)?;
+		Ok(std::str::from_utf8(&buf).expect("serde_json only ever writes valid UTF-8; qed").to_owned())
+	})
+}
+
 #[derive(Debug)]
-struct TelemetryAttrs {
-	message_verbosity: Option<u64>,
-	json: Option<String>,
-	id: u64,
+pub struct TelemetryLayer {
+	telemetries: Telemetries,
+	otlp: Option<OtlpSink>,
+	file: Option<FileSink>,
+	// The tracing target this layer dispatches on, defaulting to
+	// `TELEMETRY_LOG_SPAN` so a single-node binary that never calls
+	// `with_target`/`with_instance_id` keeps working unchanged. See
+	// `with_target`.
+	target: String,
+	context_fields: ContextFields,
 }
 
-impl TelemetryAttrs {
-	fn new(id: u64) -> Self {
+impl Default for TelemetryLayer {
+	fn default() -> Self {
 		Self {
-			message_verbosity: None,
-			json: None,
-			id,
+			telemetries: Telemetries::default(),
+			otlp: None,
+			file: None,
+			target: TELEMETRY_LOG_SPAN.to_string(),
+			context_fields: ContextFields::default(),
 		}
 	}
 }
 
-#[derive(Debug)]
-struct TelemetryAttrsVisitor<'a>(&'a mut TelemetryAttrs);
+impl TelemetryLayer {
+	pub fn telemetries(&self) -> Telemetries {
+		self.telemetries.clone()
+	}
 
-impl<'a> tracing::field::Visit for TelemetryAttrsVisitor<'a> {
-	fn record_debug(&mut self, _field: &tracing::field::Field, _value: &dyn std::fmt::Debug) {
-		// noop
+	/// Dispatch on `target` instead of the default [`TELEMETRY_LOG_SPAN`], so
+	/// multiple nodes embedded in one process (each running its own
+	/// `TelemetryLayer` on a shared [`tracing_subscriber::Registry`], as in
+	/// integration tests and collator setups) don't cross-talk: an event
+	/// meant for one node's telemetry span is never picked up by another
+	/// node's layer, because the two no longer share a target at all.
+	///
+	/// Every span and event this layer should see must then also be tagged
+	/// `target: <this same string>` at the call site, in place of
+	/// `TELEMETRY_LOG_SPAN`.
+	pub fn with_target(mut self, target: impl Into<String>) -> Self {
+		self.target = target.into();
+		self
 	}
 
-	fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
-		if field.name() == "message_verbosity" {
-			(*self.0).message_verbosity = Some(value)
-		}
+	/// Convenience over [`with_target`](Self::with_target) for the common
+	/// "N nodes in one process, numbered from construction order" case,
+	/// producing `telemetry-logger-<n>`.
+	pub fn with_instance_id(self, instance_id: u64) -> Self {
+		self.with_target(format!("{TELEMETRY_LOG_SPAN}-{instance_id}"))
 	}
 
-	fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
-		if field.name() == "json" {
-			// NOTE: this is a hack to inject the span id into the json
-			let mut message = format!(r#"{{"id":{},"#, (*self.0).id);
-			message.push_str(&value[1..]);
-			(*self.0).json = Some(message)
+	/// The target this layer dispatches on, i.e. its instance identity.
+	///
+	/// [`Telemetries`] itself carries no notion of "which instance" it
+	/// belongs to (it's just the shared registration/stats/pause-resume/
+	/// shutdown state, all of which is already isolated per instance simply
+	/// by virtue of every [`TelemetryLayer`] owning its own [`Telemetries`]).
+	/// This is the handle callers juggling several instances in one process
+	/// — a relay chain and a parachain sharing a binary, or a test harness
+	/// standing up two nodes — should key their bookkeeping by, alongside
+	/// the [`Telemetries`] clone from [`telemetries`](Self::telemetries), so
+	/// a `(target, Telemetries)` pair unambiguously identifies one instance
+	/// even when several share one [`tracing_subscriber::Registry`].
+	pub fn instance_target(&self) -> &str {
+		&self.target
+	}
+
+	/// Install an OpenTelemetry OTLP sink that forwards every captured telemetry
+	/// payload to a collector in parallel with the mpsc [`Senders`]. The sink is
+	/// optional: when no collector is configured telemetry only flows through the
+	/// existing channels.
+	pub fn with_otlp(mut self, otlp: OtlpSink) -> Self {
+		self.otlp = Some(otlp);
+		self
+	}
+
+	/// Install a local rotating-file sink that persists telemetry payloads to
+	/// disk, in parallel with the mpsc [`Senders`]. Useful for offline debugging
+	/// or when no remote backend is configured.
+	pub fn with_file_sink(mut self, file: FileSink) -> Self {
+		self.file = Some(file);
+		self
+	}
+
+	/// Configure the wire encoding of the `ts` field automatically injected into
+	/// every telemetry payload (default: [`TimestampFormat::UnixMillis`]).
+	/// Applies equally to events captured via `tracing` and payloads sent
+	/// directly through [`Telemetries::send`], since both share the injection
+	/// logic and this setting.
+	pub fn with_timestamp_format(mut self, format: TimestampFormat) -> Self {
+		self.telemetries.timestamp_format = format;
+		self
+	}
+
+	/// Configure a [`Redaction`] applied to every outgoing payload, after
+	/// id/timestamp injection and before it's serialized and fanned out
+	/// (default: none). Applies equally to events captured via `tracing` and
+	/// payloads sent directly through [`Telemetries::send`].
+	pub fn with_redaction(mut self, redaction: Redaction) -> Self {
+		self.telemetries.redaction = redaction;
+		self
+	}
+
+	/// Configure the maximum serialized payload size before it's replaced by
+	/// a stub message (default: [`DEFAULT_MAX_MESSAGE_SIZE`]). See
+	/// [`MessageSizeLimit`].
+	pub fn with_max_message_size(mut self, max_bytes: usize) -> Self {
+		self.telemetries.message_size_limit = MessageSizeLimit::new(max_bytes);
+		self
+	}
+
+	/// Collect `fields` from the current span scope (see [`ContextFields`])
+	/// and merge them into every outgoing event's payload under a `ctx` key
+	/// (default: none — collection is entirely opt-in). Only applies to
+	/// events captured via the `tracing` macro path: [`Telemetries::send`]
+	/// has no span scope to collect from.
+	pub fn with_context_fields(mut self, fields: ContextFields) -> Self {
+		self.context_fields = fields;
+		self
+	}
+
+	/// Convenience forwarder to [`Telemetries::register_trace_root`]. At runtime
+	/// callers hold a [`Telemetries`] clone from [`telemetries`](Self::telemetries)
+	/// rather than the layer, so registration lives there; this method is only
+	/// useful before the layer is installed.
+	pub fn register_trace_root(
+		&self,
+		trace_id: String,
+		remote_parent_id: Option<String>,
+	) -> Result<(), NoEnabledSpan> {
+		self.telemetries
+			.register_trace_root(trace_id, remote_parent_id)
+	}
+
+	/// Borrow the event-processing logic shared with [`FlatTelemetryLayer`].
+	/// See [`TelemetryLayerCore`].
+	fn core(&self) -> TelemetryLayerCore<'_> {
+		TelemetryLayerCore {
+			telemetries: &self.telemetries,
+			otlp: self.otlp.as_ref(),
+			file: self.file.as_ref(),
+			target: &self.target,
+			context_fields: &self.context_fields,
 		}
 	}
 }
 
-#[derive(Default, Debug, Clone)]
-pub struct Senders(
-	Arc<Mutex<HashMap<u64, std::panic::AssertUnwindSafe<mpsc::Sender<(u8, String)>>>>>,
-);
+/// Poll interval [`Telemetries::send_important`] waits between retries of a
+/// message its first attempt found the channel too full for. Short enough
+/// that a burst draining doesn't add meaningfully to its `timeout`, long
+/// enough not to spin the executor on an endpoint that's genuinely stalled.
+const SEND_IMPORTANT_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(10);
 
-impl Senders {
-	pub fn insert(&self, id: u64, sender: mpsc::Sender<(u8, String)>) {
-		self.0
-			.lock()
-			.insert(id, std::panic::AssertUnwindSafe(sender));
+thread_local! {
+	/// Set for the duration of [`TelemetryLayer::on_event`] via
+	/// [`DispatchGuard`], so [`Telemetries::send_important`] can
+	/// `debug_assert!` it isn't being awaited from within the synchronous
+	/// tracing dispatch path — see that method's doc comment for the
+	/// deadlock this would otherwise risk.
+	static IN_TELEMETRY_DISPATCH: std::cell::Cell<bool> = std::cell::Cell::new(false);
+}
+
+/// RAII guard flipping [`IN_TELEMETRY_DISPATCH`] back to `false` on drop, so
+/// an early return (or panic) partway through `on_event` never leaves it
+/// stuck `true` for the rest of the thread's lifetime.
+struct DispatchGuard;
+
+impl DispatchGuard {
+	fn enter() -> Self {
+		IN_TELEMETRY_DISPATCH.with(|flag| flag.set(true));
+		Self
+	}
+}
+
+impl Drop for DispatchGuard {
+	fn drop(&mut self) {
+		IN_TELEMETRY_DISPATCH.with(|flag| flag.set(false));
+	}
+}
+
+impl Telemetries {
+	/// Number of telemetry events dropped so far because they were missing a
+	/// required field or carried a malformed payload. See
+	/// [`MalformedEventCounter`] for what counts as malformed.
+	pub fn malformed_event_count(&self) -> u64 {
+		self.malformed_events.count()
+	}
+
+	/// Outgoing payloads this instance has had [`validate_schema`] reject so
+	/// far. Backed by `self.schema_violations`, an `Arc<AtomicU64>` field —
+	/// the same per-instance shape as `self.lagged` on [`TelemetryHandle`] —
+	/// rather than a process-wide static: two `Telemetries` instances sharing
+	/// a process (see `two_instances_operate_independently_end_to_end`) must
+	/// not see each other's schema bugs pooled into one count. Only
+	/// incremented in debug builds; see [`validate_schema`]'s own doc comment.
+	pub fn schema_violation_count(&self) -> u64 {
+		self.schema_violations.load(std::sync::atomic::Ordering::Relaxed)
+	}
+
+	/// Events this instance has dropped so far because their `json` field
+	/// (see [`TelemetryLayer::on_event`]) failed to parse as JSON. Backed by
+	/// `self.invalid_json_payloads`, per-instance for the same reason as
+	/// [`schema_violation_count`](Self::schema_violation_count). Tracked
+	/// separately from [`malformed_event_count`](Self::malformed_event_count)
+	/// (which also counts a missing `message_verbosity`/`json` field and a
+	/// payload that parses but isn't a JSON object) so an operator can tell
+	/// "a producer's payload builder has a bug" apart from those other,
+	/// differently-shaped mistakes.
+	pub fn invalid_json_payload_count(&self) -> u64 {
+		self.invalid_json_payloads.load(std::sync::atomic::Ordering::Relaxed)
+	}
+
+	/// Number of messages dropped so far for the telemetry span `id` because its
+	/// channel was full or disconnected.
+	pub fn dropped_messages(&self, id: u64) -> u64 {
+		self.senders.dropped(id)
+	}
+
+	/// Sum of [`dropped_messages`](Self::dropped_messages) across every
+	/// currently registered telemetry span.
+	pub fn dropped_messages_total(&self) -> u64 {
+		self.senders.dropped_total()
+	}
+
+	/// Number of messages dropped so far for the endpoint `url` by
+	/// [`fan_out_by_verbosity`] because its own queue was full or disconnected.
+	/// Independent of [`dropped_messages`](Self::dropped_messages), which counts
+	/// drops on the shared per-instance channel upstream of the fan-out.
+	pub fn endpoint_dropped(&self, url: &str) -> u64 {
+		self.endpoint_stats.dropped(url)
+	}
+
+	/// Rate-limit tokens currently available for the endpoint `url`, or
+	/// `None` if it has no [`RateLimiter`] configured. Exposed alongside the
+	/// drop counters so operators can see throttling coming before it starts
+	/// dropping messages.
+	pub fn endpoint_rate_limit_tokens(&self, url: &str) -> Option<f64> {
+		self.endpoint_rate_limiters.available_tokens(url)
+	}
+
+	/// Record that `url` dropped a message because a [`RateLimiter`] denied
+	/// it. Rate-limit enforcement itself lives outside this crate slice (see
+	/// [`EndpointRateLimiters`]'s doc comment); this just gives that worker
+	/// somewhere to report the drop so it shows up in
+	/// [`endpoint_stats_snapshot`](Self::endpoint_stats_snapshot) alongside
+	/// queue-full and disconnected drops.
+	pub fn record_endpoint_rate_limited_drop(&self, url: &str) {
+		self.endpoint_stats.record_drop(url, DropReason::RateLimited);
+	}
+
+	/// Configure `url` with a daily byte budget, after which it's paused
+	/// until the window rolls over. See [`EndpointByteBudgets::configure`].
+	pub fn configure_endpoint_byte_budget(&self, url: impl Into<String>, bytes_per_day: u64) {
+		self.endpoint_byte_budgets.configure(url, bytes_per_day);
+	}
+
+	/// Record `bytes` sent to `url` at `now` against its configured daily
+	/// byte budget, if any. See [`EndpointByteBudgets::record`].
+	pub fn record_endpoint_bytes_sent(
+		&self,
+		url: &str,
+		bytes: u64,
+		now: std::time::SystemTime,
+	) -> ByteBudgetOutcome {
+		self.endpoint_byte_budgets.record(url, bytes, now)
+	}
+
+	/// Whether `url` is currently paused after tripping its daily byte
+	/// budget. Exposed alongside the drop counters and rate-limit tokens so
+	/// operators can see egress capping coming before it starts dropping
+	/// messages.
+	pub fn endpoint_egress_paused(&self, url: &str) -> bool {
+		self.endpoint_byte_budgets.is_paused(url)
+	}
+
+	/// A point-in-time snapshot of `url`'s drop count, queue depth, p99 send
+	/// latency and byte-budget state, combining [`EndpointStats`],
+	/// [`EndpointQueueStats`] and [`EndpointByteBudgets`] into the single
+	/// view a diagnostics endpoint or a Prometheus integration would want.
+	pub fn endpoint_stats_snapshot(&self, url: &str) -> EndpointStatsSnapshot {
+		EndpointStatsSnapshot {
+			dropped: self.endpoint_stats.dropped(url),
+			drop_breakdown: self.endpoint_stats.drop_breakdown(url),
+			queue_depth: self.endpoint_queue_stats.queue_depth(url),
+			p99_send_latency: self.endpoint_queue_stats.send_latency_percentile(url, 99.0),
+			bytes_sent_today: self.endpoint_byte_budgets.bytes_sent_today(url),
+			egress_paused: self.endpoint_byte_budgets.is_paused(url),
+		}
+	}
+
+	/// A [`TelemetryStatus`] report for `id` across `urls`, combining
+	/// [`EndpointConnectionStatus`], [`EndpointQueueStats`],
+	/// [`EndpointStats`] and [`Senders::message_type_stats`] into the single
+	/// serializable view intended for `system_telemetryStatus`. `urls` is the
+	/// caller's current endpoint set for `id` (e.g. from its own
+	/// [`Endpoints`]) since this crate slice doesn't itself track which
+	/// endpoints a given worker is fanning out to.
+	pub fn status(&self, id: u64, urls: &[String]) -> TelemetryStatus {
+		TelemetryStatus {
+			label: self.senders.label(id),
+			endpoints: urls
+				.iter()
+				.map(|url| EndpointStatus {
+					url: url.clone(),
+					connected: self.endpoint_connections.is_connected(url),
+					last_error: self.endpoint_connections.last_error(url),
+					reconnects: self.endpoint_connections.reconnects(url),
+					queue_depth: self.endpoint_queue_stats.queue_depth(url),
+					dropped: self.endpoint_stats.dropped(url),
+					active_since_unix_secs: self.endpoint_connections.connected_since(url),
+				})
+				.collect(),
+			message_types: self.senders.message_type_stats(id),
+			instance_count: self.senders.len(),
+		}
+	}
+
+	/// Number of telemetry ids currently registered on this `Telemetries`
+	/// instance (i.e. [`Senders::len`]), regardless of `id` — not
+	/// process-wide, since each instance owns its own [`Senders`] (see
+	/// `two_instances_sharing_one_registry_do_not_cross_talk`). A burn-in or
+	/// soak test can poll this across many create/drop cycles and assert it
+	/// comes back down to baseline instead of creeping upward, the way
+	/// [`TelemetryStatus::instance_count`] lets an RPC caller do the same for
+	/// a single running node.
+	pub fn instance_count(&self) -> usize {
+		self.senders.len()
+	}
+
+	/// See [`Senders::set_leak_detection_high_water_mark`].
+	pub fn set_leak_detection_high_water_mark(&self, mark: Option<usize>) {
+		self.senders.set_leak_detection_high_water_mark(mark);
+	}
+
+	/// Configure (or replace) the sampling rule applied to every message with
+	/// `msg == msg_type`, across every id sharing this `Telemetries`. See
+	/// [`MessageSampling::configure`].
+	pub fn configure_sampling(&self, msg_type: impl Into<String>, rule: SamplingRule) {
+		self.message_sampling.configure(msg_type, rule);
+	}
+
+	/// Messages of `msg_type` skipped by sampling so far.
+	pub fn sampling_skipped(&self, msg_type: &str) -> u64 {
+		self.message_sampling.skipped(msg_type)
+	}
+
+	/// Configure (or replace) [`MessageDedup`] suppression of consecutive
+	/// duplicate `msg_type` payloads, forwarding one anyway as a heartbeat
+	/// once `max_suppressed` has elapsed since the last one actually sent.
+	///
+	/// Backed by a file-local static rather than a `Telemetries` field like
+	/// [`configure_sampling`](Self::configure_sampling)'s `self.message_sampling`,
+	/// but scoped to `self`'s [`Senders::instance_key`] under the hood so two
+	/// `Telemetries` instances emitting the same `msg_type` still never see or
+	/// reset each other's windows.
+	pub fn configure_dedup(&self, msg_type: impl Into<String>, max_suppressed: std::time::Duration) {
+		message_dedup().configure(self.senders.instance_key(), msg_type, max_suppressed);
+	}
+
+	/// Messages of `msg_type` suppressed as duplicates so far, for this
+	/// `Telemetries` instance.
+	pub fn dedup_suppressed(&self, msg_type: &str) -> u64 {
+		message_dedup().suppressed(self.senders.instance_key(), msg_type)
+	}
+
+	/// Cap every outgoing message to at most `verbosity`, on top of (not
+	/// instead of) whatever cap each endpoint is individually configured
+	/// with: the effective ceiling a given endpoint ever sees is
+	/// `min(endpoint_cap, verbosity)`, since a message this crate slice never
+	/// sends in the first place — see [`prepare_send`](Self::prepare_send)'s
+	/// fast path — can't reach any endpoint's own [`Endpoints`] filter either.
+	/// Process-wide rather than scoped to this `Telemetries` instance, the
+	/// same constraint (and the same file-local-static workaround) as
+	/// [`configure_dedup`](Self::configure_dedup).
+	pub fn set_global_verbosity(&self, verbosity: impl Into<Verbosity>) {
+		GLOBAL_VERBOSITY.store(verbosity.into().as_u8(), std::sync::atomic::Ordering::Relaxed);
+	}
+
+	/// The cap most recently set via
+	/// [`set_global_verbosity`](Self::set_global_verbosity), or
+	/// [`Verbosity::DEBUG`] (no effective cap) if it was never called.
+	pub fn global_verbosity(&self) -> Verbosity {
+		Verbosity::from(GLOBAL_VERBOSITY.load(std::sync::atomic::Ordering::Relaxed))
+	}
+
+	/// Messages skipped so far because their verbosity exceeded
+	/// [`global_verbosity`](Self::global_verbosity), across every id sharing
+	/// this `Telemetries` (and, since the cap is process-wide, every other
+	/// instance too).
+	pub fn global_verbosity_skipped(&self) -> u64 {
+		GLOBAL_VERBOSITY_SKIPPED.load(std::sync::atomic::Ordering::Relaxed)
+	}
+
+	/// Per-`msg`-type send outcome counters for `id`. See
+	/// [`Senders::message_type_stats`].
+	pub fn message_type_stats(&self, id: u64) -> HashMap<String, MessageTypeCounts> {
+		self.senders.message_type_stats(id)
+	}
+
+	/// How stale messages buffered for `id` under [`OverflowPolicy::DropOldest`]
+	/// got before being evicted. See [`Senders::eviction_age_stats`].
+	pub fn eviction_age_stats(&self, id: u64) -> EvictionAgeStats {
+		self.senders.eviction_age_stats(id)
+	}
+
+	/// Opt into buffering messages sent to an id before its worker registers
+	/// a sender for it, so the initialization race between installing the
+	/// `tracing` subscriber and the worker actually calling
+	/// [`Senders::insert`] doesn't silently lose the earliest messages
+	/// (including, for a freshly (re)started chain, the very first block
+	/// announcements). Off by default, so nothing changes for a caller that
+	/// never calls this. See [`Senders::enable_pre_registration_buffer`].
+	pub fn enable_pre_registration_buffer(&self, config: PreRegistrationBufferConfig) {
+		self.senders.enable_pre_registration_buffer(config);
+	}
+
+	/// Tap the exact stream of messages `id` sends from now on — after
+	/// injection/filtering but independent of whether they reach an
+	/// endpoint — without configuring another telemetry endpoint. Useful for
+	/// an embedder feeding telemetry straight into its own dashboard or
+	/// anomaly detector. See [`Senders::subscribe`] and [`MessageTap`].
+	/// `None` if `id` isn't registered.
+	pub fn subscribe(&self, id: u64, capacity: usize) -> Option<MessageTap> {
+		self.senders.subscribe(id, capacity)
+	}
+
+	/// Messages replaced by a truncation stub so far because they exceeded
+	/// [`TelemetryLayer::with_max_message_size`]. See [`MessageSizeLimit`].
+	pub fn truncated_messages(&self) -> u64 {
+		self.message_size_limit.truncated()
+	}
+
+	/// Send `payload` under `id` directly, without going through a `tracing`
+	/// event. Performs the same span-id/`parent_ids` injection and overflow
+	/// accounting as [`TelemetryLayer::on_event`], sharing the same [`Senders`]
+	/// map, so producers that already hold a `Telemetries` handle (e.g. the
+	/// network worker reporting peer counts) don't need to route through the
+	/// `telemetry-logger` span just to get a payload out. It is safe to
+	/// interleave calls to this method with events emitted via `tracing!` under
+	/// the same `id`: both paths funnel into the same registered sender.
+	///
+	/// Returns `false` if `payload` isn't a JSON object, or if no sender is
+	/// registered for `id`. See [`try_send`](Self::try_send) for why a `false`
+	/// happened.
+	pub fn send(&self, id: u64, verbosity: impl Into<Verbosity>, payload: serde_json::Value) -> bool {
+		self.try_send(id, verbosity, payload).is_ok()
+	}
+
+	/// Fallible counterpart to [`send`](Self::send), returning why the
+	/// message didn't reach an endpoint instead of a bare `bool`. Never
+	/// panics on a malformed or oversized `payload`: every rejection is
+	/// reported through a [`TelemetryError`] variant (and, where one already
+	/// existed, the same stats/logging [`send`](Self::send) always used).
+	pub fn try_send(
+		&self,
+		id: u64,
+		verbosity: impl Into<Verbosity>,
+		payload: serde_json::Value,
+	) -> Result<(), TelemetryError> {
+		let verbosity = verbosity.into();
+		let priority = verbosity == Verbosity::CONSOLE;
+		let (msg_type, verbosity, json) = self.prepare_send(id, verbosity, payload, priority)?;
+		if priority {
+			self.senders.send_priority(id, msg_type.as_deref(), (verbosity, json))
+		} else {
+			self.senders.send(id, msg_type.as_deref(), (verbosity, json))
+		}
+	}
+
+	/// Send `payload` under `id` on the high-priority (alert-class) lane,
+	/// regardless of `verbosity` — for messages like "database corruption
+	/// detected" or "finality stalled" that must not queue behind routine
+	/// traffic. Equivalent to [`try_send`](Self::try_send) except it always
+	/// takes the [`try_send_priority`](Self::try_send_priority) path; see that
+	/// method for exactly what's bypassed. Returns `false` on the same
+	/// conditions [`send`](Self::send) does.
+	///
+	/// This is the direct send API's `priority` flag; the `telemetry!` macro
+	/// (defined outside this crate) isn't given a matching overload here, the
+	/// same call-site-convenience-is-out-of-scope call made for
+	/// [`messages`](self::messages)'s typed builders. A macro call site that
+	/// needs the priority lane should build its payload and call this (or
+	/// [`try_send_priority`](Self::try_send_priority)) directly.
+	pub fn send_priority(&self, id: u64, verbosity: impl Into<Verbosity>, payload: serde_json::Value) -> bool {
+		self.try_send_priority(id, verbosity, payload).is_ok()
+	}
+
+	/// Fallible counterpart to [`send_priority`](Self::send_priority). Unlike
+	/// [`try_send`](Self::try_send), the message bypasses [`MessageSampling`],
+	/// [`SenderConfig::coalesce`] and [`OverflowPolicy`] entirely — it either
+	/// reaches the channel immediately or joins its own small per-endpoint
+	/// queue that's always drained first, ahead of any routine backlog. It
+	/// still goes through field injection, pause/dedup filtering, redaction
+	/// and the message size limit exactly like `try_send`: an alert dropped
+	/// while paused, or truncated for being oversized, is still worth
+	/// reporting as such rather than silently exempted. Per-endpoint rate
+	/// limiting isn't enforced anywhere in this crate slice to begin with
+	/// (see [`EndpointRateLimiters`]'s doc comment), so there is nothing here
+	/// for a priority message to bypass; a transport worker consulting a
+	/// limiter externally is expected to give this lane the same pass.
+	pub fn try_send_priority(
+		&self,
+		id: u64,
+		verbosity: impl Into<Verbosity>,
+		payload: serde_json::Value,
+	) -> Result<(), TelemetryError> {
+		let (msg_type, verbosity, json) = self.prepare_send(id, verbosity, payload, true)?;
+		self.senders.send_priority(id, msg_type.as_deref(), (verbosity, json))
+	}
+
+	/// Everything [`try_send`](Self::try_send) does up to, but not
+	/// including, the final hand-off to `id`'s channel: field injection,
+	/// pause/sampling/dedup filtering, redaction, tapping and schema
+	/// validation. Factored out so [`send_important`](Self::send_important)
+	/// can run this exactly once and then retry only the hand-off itself —
+	/// re-running sampling or dedup on every retry would double-count them
+	/// against a message that hasn't actually gone anywhere yet. `priority`
+	/// skips [`MessageSampling`] the same way [`try_send_priority`](Self::try_send_priority)
+	/// does; every other check (including the message size limit) still
+	/// applies.
+	fn prepare_send(
+		&self,
+		id: u64,
+		verbosity: impl Into<Verbosity>,
+		payload: serde_json::Value,
+		priority: bool,
+	) -> Result<(Option<String>, Verbosity, String), TelemetryError> {
+		let verbosity = verbosity.into();
+		// Cheapest fast path of all: skip validating, merging and serializing
+		// `payload` entirely when it's over the global cap, before even
+		// looking at it. See `Telemetries::set_global_verbosity`.
+		if verbosity > self.global_verbosity() {
+			GLOBAL_VERBOSITY_SKIPPED.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+			return Err(TelemetryError::Filtered);
+		}
+		let mut value = payload;
+		let obj = match value.as_object_mut() {
+			Some(obj) => obj,
+			None => {
+				self.malformed_events.increment();
+				log::warn!(
+					target: "telemetry",
+					"Ignored telemetry message because payload is not a JSON object",
+				);
+				return Err(TelemetryError::MalformedEvent);
+			}
+		};
+		// A message for an id nothing has registered yet is still worth fully
+		// preparing (rather than bailing out here as before) when the optional
+		// pre-registration buffer (see `Senders::enable_pre_registration_buffer`)
+		// might hold onto it — `Senders::send` is what actually buffers it,
+		// once this method has built the same `id`/`seq`/`ts`-stamped payload a
+		// registered id would get.
+		if !self.senders.contains(id) && !self.senders.pre_registration_buffer_enabled() {
+			return Err(TelemetryError::Disconnected);
+		}
+		// Read once, ahead of the fast paths below, and reused for every
+		// per-`msg`-type outcome recorded from here on (see `MessageTypeCounts`)
+		// as well as the coalescing lookup in `Senders::send` — `obj` is already
+		// a parsed `serde_json::Value`, so this doesn't cost a second parse.
+		let msg_type = obj.get("msg").and_then(|v| v.as_str()).map(|s| s.to_string());
+		// Cheap fast path, mirroring `TelemetryLayer::on_event`: skip the static
+		// field merge, timestamp injection and serialization entirely while
+		// paused. See `Telemetries::pause`.
+		if self.senders.is_paused(id) {
+			self.senders.record_message_filtered(id, msg_type.as_deref());
+			return Err(TelemetryError::Filtered);
+		}
+		for (key, static_value) in self.senders.static_fields(id) {
+			obj.entry(key).or_insert(static_value);
+		}
+		if let Some(identity) = self.senders.stamped_identity(id) {
+			obj.entry("node").or_insert_with(|| identity.to_json());
+		}
+		// Sampled out before the (potentially large) id/timestamp injection
+		// and serialization below. See `MessageSampling`. Skipped entirely for
+		// a priority message: sampling exists to shed routine volume, which an
+		// alert is defined not to be.
+		if !priority && !self.message_sampling.should_send(msg_type.as_deref()) {
+			self.senders.record_message_sampled_out(id, msg_type.as_deref());
+			return Err(TelemetryError::Filtered);
+		}
+		// Consecutive-duplicate suppression, configured per msg type via
+		// `Telemetries::configure_dedup`. See `MessageDedup`.
+		if !message_dedup().should_send(self.senders.instance_key(), msg_type.as_deref(), obj, std::time::Instant::now())
+		{
+			self.senders.record_message_suppressed(id, msg_type.as_deref());
+			return Err(TelemetryError::Filtered);
+		}
+		obj.insert("id".into(), id.into());
+		if let Some((seq, dropped)) = self.senders.next_seq(id) {
+			obj.insert("seq".into(), seq.into());
+			obj.insert("dropped".into(), dropped.into());
+		}
+		inject_timestamp(obj, self.timestamp_format, current_time());
+		self.redaction.apply(&mut value);
+		// Tapped here, independent of whether the send below actually reaches
+		// this id's channel: a subscriber cares about what the pipeline decided
+		// to emit, not endpoint delivery. See `Telemetries::subscribe`.
+		self.senders.publish_tap(id, verbosity.as_u8(), &value);
+		// Debug-only: catch a hand-built payload with a typo'd or missing field
+		// (e.g. `finalised_hash` instead of `finalized_hash`) before it goes out
+		// the door. See `validate_schema`'s doc comment for why this can't also
+		// gate behind a release-mode `strict` feature in this crate slice.
+		#[cfg(debug_assertions)]
+		if let (Some(msg), Some(obj)) = (msg_type.as_deref(), value.as_object()) {
+			if let Err(violations) = validate_schema(msg, obj) {
+				self.schema_violations.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+				log::error!(
+					target: "telemetry",
+					"Outgoing {msg} message failed schema validation: {violations:?}",
+				);
+			}
+		}
+		let json = serialize_message(&value, msg_type.as_deref())
+			.map_err(|err| TelemetryError::Serialization(err.to_string()))?;
+		let (json, truncated) = self.message_size_limit.enforce(msg_type.as_deref(), json);
+		if truncated {
+			self.senders.record_message_oversized(id, msg_type.as_deref());
+		}
+		Ok((msg_type, verbosity, json))
+	}
+
+	/// [`try_send`](Self::try_send) for a producer that would rather wait
+	/// briefly for queue capacity than be dropped on the first full channel —
+	/// the initial `system.connected` announcement, a shutdown notification.
+	/// Runs the same field injection, sampling and dedup as `try_send` via
+	/// [`prepare_send`](Self::prepare_send), exactly once, then retries only
+	/// the final hand-off to `id`'s channel every
+	/// [`SEND_IMPORTANT_POLL_INTERVAL`] until it's accepted or `timeout`
+	/// elapses, sleeping between attempts via `delay` — this crate slice has
+	/// no runtime-agnostic sleep of its own (see [`DelayFactory`]), so the
+	/// caller supplies one.
+	///
+	/// Returns [`TelemetryError::ChannelFull`] if `timeout` elapses without
+	/// the channel accepting the message, and whatever `try_send` would have
+	/// returned for anything else (a malformed payload, an unregistered id,
+	/// a paused/sampled/deduped message).
+	///
+	/// # Deadlock hazard
+	///
+	/// Must never be called from the tracing dispatch path itself —
+	/// [`TelemetryLayer::on_event`], or anything it calls — which runs
+	/// synchronously on whatever thread emitted the event; awaiting queue
+	/// capacity there can stall that thread indefinitely with nothing left
+	/// to drain the very channel it's waiting on. Debug builds
+	/// `debug_assert!` against a dispatch-scoped flag set for the duration
+	/// of `on_event`; release builds rely on this doc comment instead, the
+	/// same trade-off [`validate_schema`] documents for schema checks.
+	pub async fn send_important(
+		&self,
+		id: u64,
+		verbosity: impl Into<Verbosity>,
+		payload: serde_json::Value,
+		timeout: std::time::Duration,
+		delay: &dyn DelayFactory,
+	) -> Result<(), TelemetryError> {
+		debug_assert!(
+			!IN_TELEMETRY_DISPATCH.with(|flag| flag.get()),
+			"Telemetries::send_important must not be called from within the tracing dispatch path \
+			 (TelemetryLayer::on_event); awaiting queue capacity there can deadlock the thread that \
+			 would otherwise drain it"
+		);
+		let (msg_type, verbosity, json) = self.prepare_send(id, verbosity, payload, false)?;
+		let deadline = std::time::Instant::now() + timeout;
+		loop {
+			match self.senders.try_send_now(id, msg_type.as_deref(), (verbosity, json.clone())) {
+				Ok(()) => return Ok(()),
+				Err(TelemetryError::ChannelFull) if std::time::Instant::now() < deadline => {
+					delay.delay(SEND_IMPORTANT_POLL_INTERVAL).await;
+				}
+				Err(err) => return Err(err),
+			}
+		}
+	}
+
+	/// [`try_send`](Self::try_send) for a producer that already has a typed
+	/// `payload` (e.g. a `SystemInterval`-shaped struct) instead of a
+	/// hand-built [`serde_json::Value`] tree — the same
+	/// `serde::Serialize`-straight-to-`Value` path [`messages::TelemetryMessage::build`]
+	/// uses, so a caller with its own struct doesn't have to first flatten it
+	/// through a `json!` macro just to hand it to `send`. `msg_type` is
+	/// stamped the same way [`messages::TelemetryMessage::build`] stamps
+	/// [`messages::TelemetryMessage::MSG`], for callers that don't want to
+	/// implement that trait for a one-off payload.
+	///
+	/// Returns [`TelemetryError::Serialization`] if `payload` fails to
+	/// serialize, and [`TelemetryError::MalformedEvent`] if it serializes to
+	/// something other than a JSON object — mirroring [`try_send`](Self::try_send)'s
+	/// own error reporting rather than panicking either way.
+	pub fn send_serialized<T: serde::Serialize>(
+		&self,
+		id: u64,
+		verbosity: impl Into<Verbosity>,
+		msg_type: &str,
+		payload: &T,
+	) -> Result<(), TelemetryError> {
+		let mut value = serde_json::to_value(payload)
+			.map_err(|err| TelemetryError::Serialization(err.to_string()))?;
+		let obj = value.as_object_mut().ok_or(TelemetryError::MalformedEvent)?;
+		obj.insert("msg".into(), msg_type.into());
+		self.try_send(id, verbosity, value)
+	}
+
+	/// A lightweight [`TelemetryHandle`] for `id`, for producers deep in a
+	/// call stack (e.g. the transaction pool) that shouldn't need to carry a
+	/// full `Telemetries` clone or a `tracing` span just to emit a message.
+	/// See the type's docs for what it trades away to stay weak on the
+	/// sending machinery.
+	pub fn handle(&self, id: u64) -> TelemetryHandle {
+		TelemetryHandle::new(&self.senders, id)
+	}
+
+	/// Register (or replace) the payload resent under `id` first thing after
+	/// every successful (re)connection — see [`Senders::set_connection_message`].
+	/// `payload` is validated and `id`-stamped the same way [`send`](Self::send)
+	/// validates its payload, but stored rather than delivered immediately.
+	///
+	/// Returns `false` if `payload` isn't a JSON object, or if no sender is
+	/// registered for `id`.
+	pub fn set_connection_message(
+		&self,
+		id: u64,
+		verbosity: impl Into<Verbosity>,
+		payload: serde_json::Value,
+	) -> bool {
+		let mut value = payload;
+		let obj = match value.as_object_mut() {
+			Some(obj) => obj,
+			None => {
+				self.malformed_events.increment();
+				log::warn!(
+					target: "telemetry",
+					"Ignored telemetry connection message because payload is not a JSON object",
+				);
+				return false;
+			}
+		};
+		if !self.senders.contains(id) {
+			return false;
+		}
+		obj.insert("id".into(), id.into());
+		let json = serde_json::to_string(&value)
+			.expect("a serde_json::Value always re-serializes; qed");
+		self.senders.set_connection_message(id, (verbosity.into(), json));
+		true
+	}
+
+	/// Send `info` under `id` as a one-shot `sysinfo.hwbench` message.
+	///
+	/// Meant to be called once at startup, right after
+	/// [`set_connection_message`](Self::set_connection_message) has been
+	/// given the node's `system.connected` payload, so the backend sees the
+	/// two in the order it expects. Unlike the connection message, `info`
+	/// isn't stored for replay: hardware inventory doesn't change across
+	/// reconnects, so this goes out once through the normal
+	/// [`send_serialized`](Self::send_serialized) path rather than being
+	/// re-announced on every reconnection.
+	pub fn send_sysinfo(
+		&self,
+		id: u64,
+		verbosity: impl Into<Verbosity>,
+		info: &sysinfo::SysInfo,
+	) -> Result<(), TelemetryError> {
+		self.send_serialized(
+			id,
+			verbosity,
+			<sysinfo::SysInfo as messages::TelemetryMessage>::MSG,
+			info,
+		)
+	}
+
+	/// Register a callback run every time telemetry `id` (re)connects. See
+	/// [`Senders::add_on_connect`].
+	pub fn on_connect(&self, id: u64, callback: impl Fn() + Send + Sync + 'static) {
+		self.senders.add_on_connect(id, callback);
+	}
+
+	/// The [`NodeIdentity`] configured for `id`, if any. See
+	/// [`Senders::node_identity`].
+	pub fn node_identity(&self, id: u64) -> Option<NodeIdentity> {
+		self.senders.node_identity(id)
+	}
+
+	/// Replace `id`'s [`NodeIdentity`] at runtime, merging it into the
+	/// connection message and re-announcing it on already connected
+	/// endpoints. See [`Senders::set_node_identity`].
+	pub fn set_node_identity(&self, id: u64, identity: NodeIdentity) -> bool {
+		self.senders.set_node_identity(id, identity)
+	}
+
+	/// The `network_id` configured for `id`, if any. See
+	/// [`Senders::network_id`].
+	pub fn network_id(&self, id: u64) -> Option<String> {
+		self.senders.network_id(id)
+	}
+
+	/// Set (or replace) `id`'s `network_id` — typically the node's `PeerId`,
+	/// only known once its network key is available, which is sometimes
+	/// after telemetry is already constructed and connected. Merges into the
+	/// stored connection message and re-announces it on already-connected
+	/// endpoints; later payloads aren't touched. See
+	/// [`Senders::set_network_id`].
+	pub fn set_network_id(&self, id: u64, network_id: String) -> bool {
+		self.senders.set_network_id(id, network_id)
+	}
+
+	/// The connection extras configured for `id`, if registered. See
+	/// [`Senders::connection_extras`].
+	pub fn connection_extras(&self, id: u64) -> Option<serde_json::Map<String, serde_json::Value>> {
+		self.senders.connection_extras(id)
+	}
+
+	/// Replace `id`'s connection extras at runtime, merging them into the
+	/// connection message and re-announcing it on already-connected
+	/// endpoints. See [`Senders::set_connection_extras`].
+	pub fn set_connection_extras(
+		&self,
+		id: u64,
+		extras: serde_json::Map<String, serde_json::Value>,
+	) -> Result<(), TelemetryError> {
+		self.senders.set_connection_extras(id, extras)
+	}
+
+	/// Turn stamping `id`'s [`NodeIdentity`] onto every outgoing payload
+	/// (under a `node` key) on or off. See
+	/// [`Senders::set_stamp_identity_on_payloads`].
+	pub fn set_stamp_identity_on_payloads(&self, id: u64, enabled: bool) -> bool {
+		self.senders.set_stamp_identity_on_payloads(id, enabled)
+	}
+
+	/// Whether telemetry id `id` is currently connected. `false` if it has
+	/// never been reported connected (including if `id` doesn't exist), so
+	/// "my node doesn't appear on the dashboard" reduces to checking this.
+	pub fn is_connected(&self, id: u64) -> bool {
+		self.connection_events.is_connected(id)
+	}
+
+	/// Stop delivering telemetry for `id` until [`Telemetries::resume`] is
+	/// called. Both [`Telemetries::send`] and the tracing layer's `on_event`
+	/// short-circuit before serializing anything while paused, so pausing a
+	/// noisy node is cheap. See [`Senders::pause`].
+	pub fn pause(&self, id: u64) {
+		self.senders.pause(id);
+	}
+
+	/// Resume delivery for a telemetry id previously paused with
+	/// [`Telemetries::pause`]. If a `system.connected` message was recorded
+	/// via [`Telemetries::set_connection_message`], it is re-sent so
+	/// consumers who missed it while paused still see the node come online.
+	/// See [`Senders::resume`].
+	pub fn resume(&self, id: u64) {
+		self.senders.resume(id);
+	}
+
+	/// Whether telemetry id `id` is currently paused. `false` if `id` doesn't
+	/// exist.
+	pub fn is_paused(&self, id: u64) -> bool {
+		self.senders.is_paused(id)
+	}
+
+	/// Register the [`EndpointCommand`] inbox a worker for `id` is polling.
+	/// See [`Senders::set_endpoint_commands`].
+	pub fn set_endpoint_commands(&self, id: u64, commands: mpsc::UnboundedSender<EndpointCommand>) {
+		self.senders.set_endpoint_commands(id, commands);
+	}
+
+	/// Add a new telemetry endpoint for `id` at runtime, picked up by its
+	/// worker on its next loop iteration: a fresh queue (`sender`) and,
+	/// optionally, an immediate `connect_message` (typically
+	/// `system.connected`). See [`Senders::add_endpoint`].
+	pub fn add_endpoint(
+		&self,
+		id: u64,
+		url: impl Into<String>,
+		max_verbosity: impl Into<Verbosity>,
+		sender: mpsc::Sender<(Verbosity, String)>,
+		connect_message: Option<(Verbosity, String)>,
+	) -> bool {
+		self.senders.add_endpoint(id, url, max_verbosity, sender, connect_message)
+	}
+
+	/// Remove a telemetry endpoint for `id` at runtime: its worker flushes
+	/// its queue best-effort and closes its connection on its next loop
+	/// iteration. See [`Senders::remove_endpoint`].
+	pub fn remove_endpoint(&self, id: u64, url: impl Into<String>) -> bool {
+		self.senders.remove_endpoint(id, url)
+	}
+
+	/// Change `url`'s verbosity threshold for `id` at runtime, e.g. to
+	/// temporarily raise it while debugging without a restart. See
+	/// [`Senders::set_max_verbosity`].
+	pub fn set_max_verbosity(&self, id: u64, url: impl Into<String>, max_verbosity: impl Into<Verbosity>) -> bool {
+		self.senders.set_max_verbosity(id, url, max_verbosity)
+	}
+
+	/// Turn mirroring of `id`'s outgoing messages to
+	/// `log::trace!(target: "telemetry-out", ...)` on or off at runtime, for
+	/// troubleshooting exactly what was sent (and to which endpoint, and
+	/// whether it was dropped) without attaching a real endpoint. See
+	/// [`Senders::set_debug_mirror`].
+	pub fn set_debug_mirror(&self, id: u64, enabled: bool) -> bool {
+		self.senders.set_debug_mirror(id, enabled)
+	}
+
+	/// Report `url`'s reachability for `id` at runtime, driving failover
+	/// within any [`EndpointGroup`] it belongs to. See
+	/// [`Senders::report_endpoint_health`].
+	pub fn report_endpoint_health(&self, id: u64, url: impl Into<String>, healthy: bool) -> bool {
+		self.senders.report_endpoint_health(id, url, healthy)
+	}
+
+	/// Subscribe to connection state changes across every telemetry id. See
+	/// [`ConnectionEvents::subscribe`].
+	pub fn connection_events(&self) -> mpsc::UnboundedReceiver<ConnectionEvent> {
+		self.connection_events.subscribe()
+	}
+
+	/// Register a distributed trace root for the currently active telemetry span.
+	///
+	/// `trace_id` and the optional `remote_parent_id` (e.g. received from an
+	/// upstream RPC or gossip message) are stamped into every telemetry payload
+	/// subsequently emitted under the active `TELEMETRY_LOG_SPAN` scope, giving
+	/// operators end-to-end correlation across nodes.
+	///
+	/// This is the runtime entry point: it only needs the shared trace-root map, so
+	/// it is reachable from the [`Telemetries`] clone returned by
+	/// [`TelemetryLayer::telemetries`] after the layer has been installed.
+	///
+	/// Returns [`NoEnabledSpan`] if called while no telemetry span is active,
+	/// rather than silently dropping the association.
+	pub fn register_trace_root(
+		&self,
+		trace_id: String,
+		remote_parent_id: Option<String>,
+	) -> Result<(), NoEnabledSpan> {
+		self.trace_roots.register(trace_id, remote_parent_id)
+	}
+
+	/// Stop accepting new messages and attempt one final delivery of
+	/// whatever is currently buffered across every registered id, within
+	/// `timeout`. Called from the task manager's shutdown path so in-flight
+	/// telemetry (a final block import, a `system.node_stopped` message
+	/// sent right before this) isn't silently dropped on the floor.
+	///
+	/// See [`Senders::shutdown`]: this crate slice has no timer/executor to
+	/// retry into a still-full channel within `timeout`, or a websocket
+	/// connection of its own to close with a proper close frame — both are a
+	/// real worker's job, outside this crate slice. `timeout` is accepted
+	/// now so that behavior can be added later without changing the
+	/// signature.
+	pub async fn shutdown(&self, timeout: std::time::Duration) -> FlushReport {
+		let mut report = FlushReport::default();
+		for id in self.senders.registered_ids() {
+			report += self.senders.shutdown(id, timeout);
+		}
+		report
+	}
+
+	/// Block the calling thread until everything enqueued across every
+	/// registered id, as of this call, is either delivered or reported
+	/// abandoned, or `timeout` elapses — whichever comes first.
+	///
+	/// For short-lived, synchronous callers (a chain-spec builder, a
+	/// one-shot block-import tool) that emit a handful of messages and then
+	/// exit before a worker driving [`fan_out_by_verbosity`] on another
+	/// thread would otherwise have gotten around to sending them. Unlike
+	/// [`shutdown`](Self::shutdown), no registration is removed and nothing
+	/// stops accepting new messages, so `flush` is safe to call more than
+	/// once, or from a process that keeps running afterwards.
+	///
+	/// This crate slice has no async runtime to hand a background task off
+	/// to, so this is genuinely synchronous underneath — [`Senders::flush`]
+	/// retries with a plain `std::thread::sleep` rather than a
+	/// [`DelayFactory`], which is exactly why this is safe to call from a
+	/// context with no executor running at all.
+	pub fn flush(&self, timeout: std::time::Duration) -> FlushReport {
+		let deadline = std::time::Instant::now() + timeout;
+		let mut report = FlushReport::default();
+		for id in self.senders.registered_ids() {
+			report += self.senders.flush(id, deadline);
+		}
+		report
+	}
+
+	/// Register a new telemetry instance under `name`, addressed from then on
+	/// through the returned [`TelemetryHandle`] rather than a tracing span
+	/// id. For callers like parachain or custom-consensus crates that want
+	/// their own endpoints and messages without reaching into an id that's
+	/// really an implementation detail of the `tracing` integration.
+	///
+	/// `endpoints` and `config` are exactly what [`TelemetryBuilder::endpoint`]
+	/// and the rest of its setters would otherwise assemble; unlike
+	/// [`TelemetryBuilder::build`], this doesn't spin up a separate
+	/// [`TelemetryLayer`] — the new instance is just another id in this
+	/// `Telemetries`'s own [`Senders`] map, sharing whatever global verbosity
+	/// cap and dedup state the default instance already has.
+	///
+	/// Fails with [`RegisterInstanceError::NameAlreadyRegistered`] if `name`
+	/// is already taken by another still-registered instance, rather than
+	/// silently stealing its registration. Returns the driving
+	/// [`TelemetryWorker`] alongside the handle, the same way
+	/// [`TelemetryBuilder::build`] does: this crate slice has no executor of
+	/// its own to spawn it onto (see the module-level scope note), so the
+	/// caller's task manager has to.
+	pub fn register_instance(
+		&self,
+		name: impl Into<String>,
+		endpoints: Endpoints,
+		config: SenderConfig,
+	) -> Result<(TelemetryHandle, TelemetryWorker), RegisterInstanceError> {
+		let name = name.into();
+		let id = next_worker_id();
+		if !self.senders.register_name(&name, id) {
+			return Err(RegisterInstanceError::NameAlreadyRegistered(name));
+		}
+
+		let (sender, receiver) = mpsc::channel(config.capacity);
+		self.senders.insert_with_config(id, sender, config);
+		let (commands_tx, commands_rx) = mpsc::unbounded();
+		self.senders.set_endpoint_commands(id, commands_tx);
+		let worker = TelemetryWorker {
+			id,
+			reconnect: ReconnectPolicy::default(),
+			initial_connection_delay: InitialConnectionDelay::default(),
+			inner: Box::pin(fan_out_by_verbosity(
+				receiver,
+				endpoints,
+				HashMap::new(),
+				EndpointStats::default(),
+				commands_rx,
+				EndpointMessageFilters::new(),
+				EndpointGroups::default(),
+				None,
+				EndpointEnvelopes::new(),
+				EndpointVerbosityFields::new(),
+			)),
+		};
+		Ok((TelemetryHandle::new(&self.senders, id), worker))
+	}
+}
+
+/// Cached on the current span's extensions by [`resolve_telemetry_id`]: for
+/// every distinct target a [`TelemetryLayer`] on this process has been asked
+/// to resolve against so far, which ancestor (if any, hence the inner
+/// `Option`) is the telemetry span this event's payload should be delivered
+/// through. `None` is cached too, so an event repeatedly emitted from a span
+/// with no telemetry ancestor at all (the common case for most of a node's
+/// tracing output) doesn't re-walk the scope on every single occurrence
+/// either.
+///
+/// Keyed by target rather than a single bare `Option<u64>` because several
+/// `TelemetryLayer`s with different targets (see
+/// [`with_target`](TelemetryLayer::with_target)) can share one
+/// [`tracing_subscriber::Registry`] — and so one span's extensions map — when
+/// multiple nodes are embedded in a single process. A single shared slot
+/// would let whichever layer resolves first clobber the answer for the
+/// others; a small `Vec` scanned by target avoids that without needing every
+/// target to be known ahead of time. In the common single-node case this
+/// `Vec` never grows past one entry.
+///
+/// No invalidation is needed: a span's ancestor chain is fixed at creation
+/// and never changes for the rest of its lifetime, so a cached id never goes
+/// stale. What can change at runtime is which [`mpsc::Sender`] (if any) is
+/// currently registered for that id — but this cache doesn't store the
+/// sender, only the id used to look it up, and every read of the `Senders`
+/// map (`contains`, `is_paused`, `send`) is already a fresh lookup keyed by
+/// that id. So registering, removing, or replacing a sender for an
+/// already-cached id takes effect on the very next event with no cache to
+/// invalidate.
+#[derive(Default)]
+struct CachedTelemetryIds(Vec<(String, Option<u64>)>);
+
+/// Resolve the `target` ancestor id (if any) that an event fired from the
+/// currently entered span should be delivered through, caching the answer on
+/// that span's extensions so repeated events from the same span (the common
+/// case for the long-lived `telemetry-logger` span itself, but also for any
+/// deeply-nested span with no telemetry ancestor) skip the scope walk after
+/// the first. See [`CachedTelemetryIds`].
+fn resolve_telemetry_id<S>(ctx: &Context<'_, S>, target: &str) -> Option<u64>
+where
+	S: Subscriber + for<'a> LookupSpan<'a>,
+{
+	let current = ctx.lookup_current()?;
+	if let Some(cached) = current
+		.extensions()
+		.get::<CachedTelemetryIds>()
+		.and_then(|cache| cache.0.iter().find(|(cached_target, _)| cached_target == target))
+	{
+		return cached.1;
+	}
+	let resolved = current.scope().find(|x| x.name() == target).map(|x| x.id().into_u64());
+	let mut extensions = current.extensions_mut();
+	match extensions.get_mut::<CachedTelemetryIds>() {
+		Some(cache) => cache.0.push((target.to_string(), resolved)),
+		None => extensions.insert(CachedTelemetryIds(vec![(target.to_string(), resolved)])),
+	}
+	resolved
+}
+
+/// Look for an explicit `telemetry_id = <u64>` field on `event`, which
+/// [`TelemetryLayer::on_event`] checks before even attempting
+/// [`resolve_telemetry_id`]'s span-scope walk — a producer on a thread that
+/// never entered `TELEMETRY_LOG_SPAN` at all (a rayon worker, an FFI
+/// callback) has no span to walk, but can still address a registered id
+/// directly by naming it on the event itself.
+fn extract_explicit_telemetry_id(event: &Event<'_>) -> Option<u64> {
+	struct ExplicitIdVisitor(Option<u64>);
+
+	impl tracing::field::Visit for ExplicitIdVisitor {
+		fn record_debug(&mut self, _field: &tracing::field::Field, _value: &dyn std::fmt::Debug) {}
+
+		fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
+			if field.name() == "telemetry_id" {
+				self.0 = Some(value);
+			}
+		}
+
+		// A bare integer literal (`telemetry_id = 7`, without the `u64` suffix)
+		// is recorded as `i64` rather than `u64` — see the matching comment on
+		// `TelemetryAttrsVisitor::record_i64`. A negative value can't be a valid
+		// id, so it's ignored rather than saturated.
+		fn record_i64(&mut self, field: &tracing::field::Field, value: i64) {
+			if field.name() == "telemetry_id" {
+				if let Ok(value) = u64::try_from(value) {
+					self.0 = Some(value);
+				}
+			}
+		}
+	}
+
+	let mut visitor = ExplicitIdVisitor(None);
+	event.record(&mut visitor);
+	visitor.0
+}
+
+/// Format the call site that emitted `event` (`file:line`, falling back to
+/// the tracing target when the callsite has no file recorded) for a log
+/// message that needs to point a producer at the offending call, rather than
+/// just describing what went wrong with the payload it sent.
+fn event_callsite(event: &Event<'_>) -> String {
+	let metadata = event.metadata();
+	match metadata.file() {
+		Some(file) => format!("{file}:{}", metadata.line().unwrap_or(0)),
+		None => metadata.target().to_string(),
+	}
+}
+
+/// How many span field names [`ContextFields`] may hold, and so the most
+/// keys a single event's `ctx` object can ever gain. Configuring "useful
+/// context" is opt-in precisely so it can't silently balloon every payload;
+/// this bounds how far even a well-intentioned caller can take that before
+/// [`ContextFields::field`] starts silently ignoring further names.
+pub const MAX_CONTEXT_FIELDS: usize = 16;
+
+/// A bounded, opt-in set of span field names [`TelemetryLayer`] collects
+/// from the current span scope (via `on_new_span`/`on_record`) and merges
+/// into every outgoing event's payload under a `ctx` key, configured with
+/// [`TelemetryLayer::with_context_fields`].
+///
+/// Empty by default, so nothing is collected and no payload ever gains a
+/// `ctx` key unless a caller explicitly names fields — this is deliberately
+/// not the same trade-off as [`MessageSizeLimit`], which defends against a
+/// payload nobody meant to be huge; here, the default is that repeating
+/// context in every event is the caller's job, same as before this existed.
+/// Capped at [`MAX_CONTEXT_FIELDS`] names for the same reason
+/// [`MessageSizeLimit`] exists at all: an unbounded "useful context" list is
+/// exactly how a payload balloons, just one field name at a time instead of
+/// all at once.
+#[derive(Debug, Clone, Default)]
+pub struct ContextFields(Vec<String>);
+
+impl ContextFields {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Collect `name` from any span it's recorded on. A name already present
+	/// is not added twice; once [`MAX_CONTEXT_FIELDS`] names are configured,
+	/// further calls are silently ignored rather than erroring, the same way
+	/// an over-limit [`Redaction::redact_path`] pointer would just never
+	/// match anything rather than fail the whole configuration.
+	pub fn field(mut self, name: impl Into<String>) -> Self {
+		let name = name.into();
+		if self.0.len() < MAX_CONTEXT_FIELDS && !self.0.iter().any(|existing| existing == &name) {
+			self.0.push(name);
+		}
+		self
+	}
+
+	fn is_configured(&self) -> bool {
+		!self.0.is_empty()
+	}
+
+	fn contains(&self, name: &str) -> bool {
+		self.0.iter().any(|existing| existing == name)
+	}
+}
+
+/// Recorded on a span's extensions by [`TelemetryLayer::on_new_span`] /
+/// [`TelemetryLayer::on_record`]: whichever of that span's fields matched
+/// the configured [`ContextFields`], captured as JSON. `on_event` walks the
+/// current scope collecting these to build an event's `ctx` object.
+#[derive(Debug, Default)]
+struct SpanContextFields(serde_json::Map<String, serde_json::Value>);
+
+/// Records only the fields named in `context_fields`, the same primitive
+/// handling [`TelemetryAttrsVisitor`] uses for its own catch-all `fields`
+/// map, into `into`.
+struct ContextFieldsVisitor<'a> {
+	context_fields: &'a ContextFields,
+	into: &'a mut serde_json::Map<String, serde_json::Value>,
+}
+
+impl<'a> tracing::field::Visit for ContextFieldsVisitor<'a> {
+	fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+		if self.context_fields.contains(field.name()) {
+			self.into.insert(field.name().to_string(), format!("{:?}", value).into());
+		}
+	}
+
+	fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
+		if self.context_fields.contains(field.name()) {
+			self.into.insert(field.name().to_string(), value.into());
+		}
+	}
+
+	fn record_i64(&mut self, field: &tracing::field::Field, value: i64) {
+		if self.context_fields.contains(field.name()) {
+			self.into.insert(field.name().to_string(), value.into());
+		}
+	}
+
+	fn record_bool(&mut self, field: &tracing::field::Field, value: bool) {
+		if self.context_fields.contains(field.name()) {
+			self.into.insert(field.name().to_string(), value.into());
+		}
+	}
+
+	fn record_f64(&mut self, field: &tracing::field::Field, value: f64) {
+		if self.context_fields.contains(field.name()) {
+			if let Some(value) = serde_json::Number::from_f64(value) {
+				self.into.insert(field.name().to_string(), value.into());
+			}
+		}
+	}
+
+	fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+		if self.context_fields.contains(field.name()) {
+			self.into.insert(field.name().to_string(), value.into());
+		}
+	}
+}
+
+/// The event-processing logic shared between [`TelemetryLayer`] (which
+/// resolves telemetry ids and span ancestry by walking the current span
+/// scope, and so requires a [`LookupSpan`] subscriber such as
+/// [`tracing_subscriber::Registry`]) and [`FlatTelemetryLayer`] (which works
+/// with *any* [`Subscriber`], at the cost of only being able to address
+/// telemetry through an explicit `telemetry_id` event field, having no span
+/// registry to walk instead).
+///
+/// Holds no state of its own — it borrows straight out of a
+/// [`TelemetryLayer`]'s fields via [`TelemetryLayer::core`], so building one
+/// is free. Everything here is span-registry-agnostic: id resolution and
+/// span-scope-derived context (`parent_ids`, `ctx`) are worked out by each
+/// `Layer` impl beforehand and simply handed in.
+struct TelemetryLayerCore<'a> {
+	telemetries: &'a Telemetries,
+	otlp: Option<&'a OtlpSink>,
+	file: Option<&'a FileSink>,
+	target: &'a str,
+	context_fields: &'a ContextFields,
+}
+
+impl<'a> TelemetryLayerCore<'a> {
+	/// See [`Layer::register_callsite`]'s doc comment on [`TelemetryLayer`];
+	/// identical either way since it never touches span state.
+	fn register_callsite(&self, metadata: &'static tracing::Metadata<'static>) -> tracing::subscriber::Interest {
+		if !metadata.is_span() && metadata.target() == self.target {
+			tracing::subscriber::Interest::sometimes()
+		} else {
+			tracing::subscriber::Interest::always()
+		}
+	}
+
+	/// See [`Layer::enabled`]'s doc comment on [`TelemetryLayer`]; identical
+	/// either way since it never touches span state.
+	fn enabled(&self, metadata: &tracing::Metadata<'_>) -> bool {
+		metadata.is_span()
+			|| metadata.target() != self.target
+			|| self.telemetries.senders.any_registered()
+			|| self.telemetries.senders.pre_registration_buffer_enabled()
+	}
+
+	/// The shared tail of `on_event`, once a telemetry `id` has already been
+	/// resolved: builds the payload and dispatches it to `Senders`/the OTLP
+	/// sink/the file sink. `parent_ids` and `ctx_fields` are supplied by the
+	/// caller since gathering them (or not, in [`FlatTelemetryLayer`]'s case)
+	/// depends on span-registry access this struct doesn't have.
+	fn on_event(
+		&self,
+		event: &Event<'_>,
+		id: u64,
+		parent_ids: Vec<u64>,
+		ctx_fields: Option<serde_json::Map<String, serde_json::Value>>,
+	) {
+		// As in `Telemetries::prepare_send`: don't bail out on an
+		// unregistered id when the optional pre-registration buffer might
+		// hold onto this event instead of losing it outright.
+		if !self.telemetries.senders.contains(id) && !self.telemetries.senders.pre_registration_buffer_enabled() {
+			log::trace!(target: "telemetry", "Telemetry not set");
+			return;
+		}
+		// Cheap fast path: skip building `attrs`, resolving the payload and
+		// serializing entirely while paused, rather than doing that work only
+		// to drop the result in `Senders::send`. See `Telemetries::pause`.
+		// `msg` isn't known yet at this point without doing that same work, so
+		// this is recorded against the `"other"` bucket rather than its real
+		// type; see `MessageTypeCounts`.
+		if self.telemetries.senders.is_paused(id) {
+			self.telemetries.senders.record_message_filtered(id, None);
+			return;
+		}
+
+		let mut attrs = TelemetryAttrs::new(id);
+		let mut vis = TelemetryAttrsVisitor(&mut attrs);
+		event.record(&mut vis);
+
+		// `message_verbosity` is always required; the payload may arrive either as a
+		// pre-serialized `json` string or, under `tracing_unstable`, as a structured
+		// `valuable` value already collected into a `serde_json::Value`. Neither field
+		// is guaranteed: a downstream crate emitting `TELEMETRY_LOG_SPAN` with a typo'd
+		// field name must not be able to take down the whole node, so malformed events
+		// are counted and dropped rather than panicking.
+		let message_verbosity = match attrs.message_verbosity {
+			Some(message_verbosity) => message_verbosity,
+			None => {
+				self.telemetries.malformed_events.increment();
+				self.telemetries.malformed_events.log(&format!(
+					"missing `message_verbosity` field in telemetry log: {:?}",
+					event,
+				));
+				return;
+			}
+		};
+
+		// Skip resolving the payload and serializing entirely once the
+		// `msg`/`json` fields are known to be over the global cap — the
+		// AsJson field (if any) has already run by the time
+		// `message_verbosity` itself is known (see `enabled`'s doc
+		// comment for why gating any earlier isn't possible), but this
+		// still saves the JSON parse/merge/redaction/tap work below. See
+		// `Telemetries::set_global_verbosity`.
+		if Verbosity::saturating_from_u64(message_verbosity) > self.telemetries.global_verbosity() {
+			GLOBAL_VERBOSITY_SKIPPED.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+			self.telemetries.senders.record_message_filtered(id, None);
+			return;
+		}
+
+		// Prefer the structured `valuable` value when present, then a pre-serialized
+		// `json` string (kept for backward compatibility), and finally the plain
+		// tracing fields collected by the visitor (e.g. `msg = "block.import", height
+		// = n`) so a call site that never builds a `json` string at all still works.
+		// The payload must end up a JSON object; anything else is skipped with a
+		// warning rather than corrupted.
+		let mut value: serde_json::Value = if let Some(value) = attrs.json_value {
+			value
+		} else if let Some(json) = attrs.json {
+			match serde_json::from_str(&json) {
+				Ok(value) => value,
+				Err(err) => {
+					self.telemetries.malformed_events.increment();
+					self.telemetries.invalid_json_payloads.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+					log::error!(
+						target: "telemetry",
+						"Dropped telemetry message from {} because its `json` field is not valid JSON: {err}",
+						event_callsite(event),
+					);
+					return;
+				}
+			}
+		} else if !attrs.fields.is_empty() {
+			serde_json::Value::Object(attrs.fields)
+		} else {
+			self.telemetries.malformed_events.increment();
+			self.telemetries.malformed_events.log(&format!(
+				"missing `json` field in telemetry log: {:?}",
+				event,
+			));
+			return;
+		};
+
+		let obj = match value.as_object_mut() {
+			Some(obj) => obj,
+			None => {
+				self.telemetries.malformed_events.increment();
+				log::warn!(
+					target: "telemetry",
+					"Ignored telemetry message because payload is not a JSON object",
+				);
+				return;
+			}
+		};
+
+		// Merge in any static fields registered alongside this id (e.g. deployment
+		// metadata); a field the caller already set on the payload wins.
+		for (key, static_value) in self.telemetries.senders.static_fields(id) {
+			obj.entry(key).or_insert(static_value);
+		}
+		if let Some(identity) = self.telemetries.senders.stamped_identity(id) {
+			obj.entry("node").or_insert_with(|| identity.to_json());
+		}
+
+		// Snapshot the caller's payload before we inject span context, so the OTLP
+		// attributes don't duplicate the dedicated `span_id`/`parent_ids` fields.
+		let otlp_attributes = self.otlp.map(|_| obj.clone());
+
+		// Captured before serialization so the coalescing lookup in `Senders::send`
+		// doesn't need to re-inspect the outgoing JSON string.
+		let msg_type = obj.get("msg").and_then(|v| v.as_str()).map(|s| s.to_string());
+
+		// Sampled out before span/trace context injection and serialization
+		// below. See `MessageSampling`.
+		if !self.telemetries.message_sampling.should_send(msg_type.as_deref()) {
+			self.telemetries.senders.record_message_sampled_out(id, msg_type.as_deref());
+			return;
+		}
+
+		// Consecutive-duplicate suppression, configured per msg type via
+		// `Telemetries::configure_dedup`. See `MessageDedup`.
+		if !message_dedup().should_send(
+			self.telemetries.senders.instance_key(),
+			msg_type.as_deref(),
+			obj,
+			std::time::Instant::now(),
+		) {
+			self.telemetries.senders.record_message_suppressed(id, msg_type.as_deref());
+			return;
+		}
+
+		obj.insert("id".into(), id.into());
+		if let Some((seq, dropped)) = self.telemetries.senders.next_seq(id) {
+			obj.insert("seq".into(), seq.into());
+			obj.insert("dropped".into(), dropped.into());
+		}
+		inject_timestamp(obj, self.telemetries.timestamp_format, current_time());
+
+		// The ancestor telemetry spans root-to-leaf, already resolved by the
+		// caller (empty when there's no span registry to walk — see
+		// `FlatTelemetryLayer`). See `ancestor_ids`.
+		obj.insert("parent_ids".into(), parent_ids.clone().into());
+
+		// Fields named by `with_context_fields`, already collected by the
+		// caller from the current span scope (`None` when there's no span
+		// registry to collect from — see `FlatTelemetryLayer`).
+		if let Some(collected) = ctx_fields {
+			if !collected.is_empty() {
+				obj.insert("ctx".into(), collected.into());
+			}
+		}
+
+		// Stamp the distributed trace root (if one was registered for this
+		// span) so telemetry correlates across nodes handling the same work.
+		if let Some(root) = self.telemetries.trace_roots.0.lock().get(&id) {
+			obj.insert("trace_id".into(), root.trace_id.clone().into());
+			if let Some(parent_span_id) = &root.remote_parent_id {
+				obj.insert("parent_span_id".into(), parent_span_id.clone().into());
+			}
+		}
+
+		self.telemetries.redaction.apply(&mut value);
+
+		let message_verbosity = Verbosity::saturating_from_u64(message_verbosity);
+		// See the matching comment in `Telemetries::try_send`: tapped here,
+		// independent of whether the send below actually reaches this id's
+		// channel.
+		self.telemetries.senders.publish_tap(id, message_verbosity.as_u8(), &value);
+
+		// Debug-only: see the matching comment in `Telemetries::try_send`.
+		#[cfg(debug_assertions)]
+		if let (Some(msg), Some(obj)) = (msg_type.as_deref(), value.as_object()) {
+			if let Err(violations) = validate_schema(msg, obj) {
+				self.telemetries.schema_violations.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+				log::error!(
+					target: "telemetry",
+					"Outgoing {msg} message failed schema validation: {violations:?}",
+				);
+			}
+		}
+
+		let json = serialize_message(&value, msg_type.as_deref())
+			.expect("a serde_json::Value always re-serializes; qed");
+		let (json, truncated) = self.telemetries.message_size_limit.enforce(msg_type.as_deref(), json);
+		if truncated {
+			self.telemetries.senders.record_message_oversized(id, msg_type.as_deref());
+		}
+
+		// Forward to the OpenTelemetry collector (if installed) in parallel
+		// with the mpsc channel. This is best-effort and must never disturb
+		// the primary telemetry path.
+		if let (Some(otlp), Some(attributes)) = (self.otlp, otlp_attributes) {
+			otlp.forward(id, &parent_ids, message_verbosity, attributes);
+		}
+
+		// Persist to the local rotating-file sink (if installed), best-effort.
+		if let Some(file) = self.file {
+			file.write(message_verbosity, &json);
+		}
+
+		// Deliver last, and only briefly relock the map to do so: the sink work
+		// above can block, and holding the senders lock across it would serialize
+		// every telemetry event behind disk/network IO. Any `TelemetryError` here
+		// (unregistered id, full channel) is already accounted for in `dropped`/
+		// `EndpointStats` by `Senders::send` itself; the tracing subscriber has no
+		// caller to propagate a `Result` to, so there's nothing more to do with it.
+		let _ = self.telemetries.senders.send(id, msg_type.as_deref(), (message_verbosity, json));
+	}
+}
+
+/// A [`Layer`] that works with any [`Subscriber`], unlike [`TelemetryLayer`]
+/// which requires one built on a [`LookupSpan`] registry (e.g.
+/// [`tracing_subscriber::Registry`]).
+///
+/// The trade-off: without a span registry to walk, this layer can only
+/// address telemetry through an explicit `telemetry_id = <u64>` event field
+/// (see [`extract_explicit_telemetry_id`]) — the span-scope walk
+/// [`TelemetryLayer`] falls back to is unavailable here, so an event with
+/// neither an explicit `telemetry_id` nor exactly one registered instance to
+/// fall back to (see [`Senders::sole_registered_id`]) is silently dropped.
+/// `parent_ids` is always empty and [`TelemetryLayer::with_context_fields`]
+/// has no effect, since both rely on the same span-scope walk.
+///
+/// Wrap an already-configured [`TelemetryLayer`] with [`FlatTelemetryLayer::new`]
+/// once it's done being built; every other builder method still lives on
+/// [`TelemetryLayer`] itself.
+#[derive(Debug)]
+pub struct FlatTelemetryLayer(TelemetryLayer);
+
+impl FlatTelemetryLayer {
+	/// Wrap `inner`, dropping its [`LookupSpan`] requirement. `inner` should
+	/// already be fully configured via its `with_*` builders — every one of
+	/// them lives on [`TelemetryLayer`], not here.
+	pub fn new(inner: TelemetryLayer) -> Self {
+		Self(inner)
+	}
+
+	/// See [`TelemetryLayer::telemetries`].
+	pub fn telemetries(&self) -> Telemetries {
+		self.0.telemetries()
+	}
+
+	/// See [`TelemetryLayer::instance_target`].
+	pub fn instance_target(&self) -> &str {
+		self.0.instance_target()
+	}
+}
+
+impl<S> Layer<S> for FlatTelemetryLayer
+where
+	S: Subscriber,
+{
+	fn register_callsite(&self, metadata: &'static tracing::Metadata<'static>) -> tracing::subscriber::Interest {
+		self.0.core().register_callsite(metadata)
+	}
+
+	fn enabled(&self, metadata: &tracing::Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+		self.0.core().enabled(metadata)
+	}
+
+	fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+		let _dispatch_guard = DispatchGuard::enter();
+		if event.metadata().target() != self.0.target {
+			return;
+		}
+		if !self.0.telemetries.senders.any_registered() && !self.0.telemetries.senders.pre_registration_buffer_enabled()
+		{
+			return;
+		}
+
+		// No span registry to walk here (see the struct's doc comment), so
+		// the only way to address this event is an explicit `telemetry_id`
+		// field, or falling back to the sole registered instance.
+		if let Some(id) =
+			extract_explicit_telemetry_id(event).or_else(|| self.0.telemetries.senders.sole_registered_id())
+		{
+			self.0.core().on_event(event, id, Vec::new(), None);
+		} else {
+			log::trace!(target: "telemetry", "Telemetry not set");
+		}
+	}
+
+	/// A no-op: collecting fields named by [`TelemetryLayer::with_context_fields`]
+	/// requires looking spans up by id, which isn't available without a
+	/// [`LookupSpan`] registry. See the struct's doc comment.
+	fn on_new_span(&self, _attrs: &tracing::span::Attrs<'_>, _id: &tracing::Id, _ctx: Context<'_, S>) {}
+
+	/// A no-op for the same reason as [`on_new_span`](Self::on_new_span).
+	fn on_record(&self, _id: &tracing::Id, _values: &tracing::span::Record<'_>, _ctx: Context<'_, S>) {}
+
+	/// A no-op, for the same reason as [`on_new_span`](Self::on_new_span):
+	/// under `FlatTelemetryLayer` a telemetry id and a `tracing::Id` are
+	/// unrelated (see the struct's doc comment), so a closing span's raw id
+	/// tells us nothing about whether a telemetry id is done. Acting on it
+	/// here would be wrong even when harmless, and outright dangerous under a
+	/// bare `Subscriber` (e.g. [`tracing::subscriber::NoSubscriber`]) that
+	/// hands out small, reused span ids: an unrelated span closing could
+	/// numerically collide with a live, explicitly-registered telemetry id
+	/// and deregister its sender out from under it. Whatever embeds this
+	/// layer is responsible for calling [`Senders::remove`]/[`TraceRoots`]
+	/// cleanup itself once it knows a given telemetry id is actually done.
+	fn on_close(&self, _id: tracing::Id, _ctx: Context<'_, S>) {}
+}
+
+impl<S> Layer<S> for TelemetryLayer
+where
+	S: Subscriber + for<'a> LookupSpan<'a>,
+{
+	/// Whether this layer's callsite [`Interest`](tracing::subscriber::Interest)
+	/// should be cached. Telemetry *event* callsites (not spans, see
+	/// [`enabled`](Self::enabled)) are marked `sometimes()`, since whether
+	/// they matter depends on [`Senders::any_registered`], which changes at
+	/// runtime as workers start up and shut down; caching `always` or `never`
+	/// from the first call would freeze that decision and stop `enabled` from
+	/// ever being consulted again for it.
+	fn register_callsite(&self, metadata: &'static tracing::Metadata<'static>) -> tracing::subscriber::Interest {
+		self.core().register_callsite(metadata)
+	}
+
+	/// Cheap, lock-free rejection of telemetry events before `tracing` even
+	/// builds the [`Event`]/records its fields, for the overwhelmingly common
+	/// case where telemetry is disabled or no worker has registered a sender
+	/// yet. See [`Senders::any_registered`]. Also passes through when the
+	/// optional pre-registration buffer is enabled, since in that case an
+	/// event ahead of registration is still worth capturing.
+	///
+	/// Only events are gated, not spans: a span still needs to be created
+	/// (and its id handed out) even with nothing registered yet, since
+	/// registering a sender for that id is exactly what a caller typically
+	/// does right after creating the span.
+	///
+	/// Implementing this (and [`register_callsite`](Self::register_callsite))
+	/// is also what makes this layer compose cleanly with per-layer filtering
+	/// via [`Layer::with_filter`]: an embedder wrapping this layer in an
+	/// `EnvFilter` gets a `Filtered` layer that consults its filter before
+	/// ever reaching this `enabled`, rather than this layer silently
+	/// processing events the wrapping filter meant to suppress.
+	fn enabled(&self, metadata: &tracing::Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+		self.core().enabled(metadata)
+	}
+
+	fn on_event(&self, event: &Event<'_>, ctx: Context<S>) {
+		let _dispatch_guard = DispatchGuard::enter();
+		if event.metadata().target() != self.target {
+			return;
+		}
+		// Redundant with `enabled` when this is the only layer in the stack,
+		// but a second, cheap belt-and-suspenders check: composed with other
+		// layers, `enabled` only has to return `true` for *one* of them for
+		// `on_event` to be called on all of them. Also lets a pre-registration
+		// buffer (see `Senders::enable_pre_registration_buffer`) hold onto an
+		// event even though nothing is registered yet.
+		if !self.telemetries.senders.any_registered() && !self.telemetries.senders.pre_registration_buffer_enabled() {
+			return;
+		}
+
+		// An explicit `telemetry_id` field, when present, always wins: it names
+		// its target directly and costs nothing extra to check first. Otherwise
+		// fall back to the span-scope walk, and if that finds no ancestor either
+		// (e.g. an async task resumed on a different executor thread than the
+		// one that entered the span, so the span isn't part of this thread's
+		// current scope at all), fall back once more to the sole registered
+		// instance when there's exactly one — see `Senders::sole_registered_id`.
+		if let Some(id) = extract_explicit_telemetry_id(event)
+			.or_else(|| resolve_telemetry_id(&ctx, &self.target))
+			.or_else(|| self.telemetries.senders.sole_registered_id())
+		{
+			// Collect the ancestor telemetry spans root-to-leaf (see `ancestor_ids`);
+			// this lets consumers reconstruct the nesting that produced the payload
+			// from just the `parent_ids` array.
+			let parent_ids = ancestor_ids(
+				ctx.scope()
+					.filter(|x| x.name() == &self.target)
+					.map(|x| x.id().into_u64()),
+			);
+
+			// Merge in span fields named by `with_context_fields`, collected from
+			// the whole current scope (not just telemetry-target ancestors, unlike
+			// `parent_ids` above — the point is fields already attached to
+			// ordinary spans like a block-import or peer-connection span).
+			// Innermost first, so a field re-recorded deeper in the scope wins
+			// over an outer span's stale value of the same name.
+			let ctx_fields = if self.context_fields.is_configured() {
+				let mut collected = serde_json::Map::new();
+				for span in ctx.scope() {
+					if let Some(fields) = span.extensions().get::<SpanContextFields>() {
+						for (key, value) in &fields.0 {
+							collected.entry(key.clone()).or_insert_with(|| value.clone());
+						}
+					}
+				}
+				Some(collected)
+			} else {
+				None
+			};
+
+			self.core().on_event(event, id, parent_ids, ctx_fields);
+		} else {
+			log::trace!(target: "telemetry", "Telemetry not set");
+		}
+	}
+
+	/// Capture any fields named by `with_context_fields` present when a span
+	/// is created. A no-op entirely when nothing is configured, so a caller
+	/// who never opts in pays nothing extra on every span creation.
+	fn on_new_span(&self, attrs: &tracing::span::Attrs<'_>, id: &tracing::Id, ctx: Context<'_, S>) {
+		if !self.context_fields.is_configured() {
+			return;
+		}
+		let Some(span) = ctx.span(id) else { return };
+		let mut fields = serde_json::Map::new();
+		attrs.record(&mut ContextFieldsVisitor { context_fields: &self.context_fields, into: &mut fields });
+		if !fields.is_empty() {
+			span.extensions_mut().insert(SpanContextFields(fields));
+		}
+	}
+
+	/// Capture (or update) any fields named by `with_context_fields` added to
+	/// an already-created span via `span.record(...)`. Applies equally to the
+	/// telemetry span itself (declaring a field as `tracing::field::Empty` on
+	/// the `TELEMETRY_LOG_SPAN` and recording it later, e.g. once a
+	/// session/epoch value becomes known, instead of repeating it in every
+	/// message) and to an ordinary ancestor span the telemetry span is nested
+	/// under — `on_event` collects from the whole current scope either way.
+	fn on_record(&self, id: &tracing::Id, values: &tracing::span::Record<'_>, ctx: Context<'_, S>) {
+		if !self.context_fields.is_configured() {
+			return;
+		}
+		let Some(span) = ctx.span(id) else { return };
+		let mut fields = serde_json::Map::new();
+		values.record(&mut ContextFieldsVisitor { context_fields: &self.context_fields, into: &mut fields });
+		if fields.is_empty() {
+			return;
+		}
+		let mut extensions = span.extensions_mut();
+		match extensions.get_mut::<SpanContextFields>() {
+			Some(existing) => existing.0.extend(fields),
+			None => {
+				extensions.insert(SpanContextFields(fields));
+			}
+		}
+	}
+
+	fn on_close(&self, id: tracing::Id, _ctx: Context<S>) {
+		// Drop any distributed trace root registered against this span so a stale
+		// root can't keep stamping payloads once the work it described has finished.
+		self.telemetries.trace_roots.remove(id.into_u64());
+		// Likewise drop the sender registered for this span; otherwise `Senders` only
+		// ever grows as telemetry instances are created and torn down.
+		self.telemetries.senders.remove(id.into_u64());
+	}
+}
+
+/// Turn telemetry span ids in innermost-first order (as `ctx.scope()` yields
+/// them) into the ancestor chain, root-to-leaf: the first id is the leaf (current)
+/// span, which is already emitted separately as `id`, so it is dropped and the
+/// remainder reversed. A span with no telemetry ancestors yields an empty vec.
+fn ancestor_ids(ids: impl Iterator<Item = u64>) -> Vec<u64> {
+	let mut ids: Vec<u64> = ids.skip(1).collect();
+	ids.reverse();
+	ids
+}
+
+#[derive(Debug)]
+struct TelemetryAttrs {
+	message_verbosity: Option<u64>,
+	json: Option<String>,
+	// Set by the `valuable` path (see `record_value`) when the call site attaches a
+	// typed payload instead of a pre-serialized `json` string. Takes precedence over
+	// `json` in `on_event`.
+	json_value: Option<serde_json::Value>,
+	// Every other field on the event (i.e. not `message_verbosity` or `json`),
+	// collected as-is. Used as the payload in `on_event` only when neither
+	// `json` nor `json_value` was recorded, so a call site can pass
+	// `msg = "block.import", height = n` directly instead of hand-formatting a
+	// JSON string that would still allocate even when the event ends up dropped.
+	fields: serde_json::Map<String, serde_json::Value>,
+}
+
+impl TelemetryAttrs {
+	fn new(_id: u64) -> Self {
+		Self {
+			message_verbosity: None,
+			json: None,
+			json_value: None,
+			fields: serde_json::Map::new(),
+		}
+	}
+}
+
+#[derive(Debug)]
+struct TelemetryAttrsVisitor<'a>(&'a mut TelemetryAttrs);
+
+impl<'a> tracing::field::Visit for TelemetryAttrsVisitor<'a> {
+	// Fallback for any field type without a dedicated `record_*` below (e.g. a
+	// `Debug`-only struct passed as a structured field). Recorded via its `Debug`
+	// output as a JSON string; there's no way to recover the original structure.
+	//
+	// `json = ?value` (this same fallback, since `json` has no dedicated
+	// `record_*`) is how a `serde::Serialize` payload — e.g. a
+	// `serde_json::json!({...})` call — reaches `json` without a manual
+	// `.to_string()` at the call site: wrap it in `AsJson` first, whose `Debug`
+	// impl writes the value's actual JSON serialization rather than Rust's
+	// `Debug` syntax, so the string landing in `self.0.json` here is already
+	// valid JSON either way.
+	fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+		if field.name() == "json" {
+			(*self.0).json = Some(format!("{:?}", value))
+		} else if field.name() != "message_verbosity" {
+			(*self.0).fields.insert(field.name().to_string(), format!("{:?}", value).into());
+		}
+	}
+
+	fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
+		if field.name() == "message_verbosity" {
+			(*self.0).message_verbosity = Some(value)
+		} else {
+			(*self.0).fields.insert(field.name().to_string(), value.into());
+		}
+	}
+
+	// `message_verbosity` is declared `u64` throughout this module, but a call
+	// site writing a bare integer literal (`message_verbosity = 3`, without
+	// the `u64` suffix `emit_with_verbosity_and_capture`'s own tests always
+	// use) gets it recorded as `i64` instead — tracing picks the `record_*`
+	// method by the literal's inferred type, not by field name. Without this
+	// special case that silently landed in `fields` under the key
+	// `"message_verbosity"` instead of `TelemetryAttrs::message_verbosity`,
+	// making `on_event` treat a perfectly good verbosity as missing and drop
+	// the whole event as malformed. Negative values saturate to `0` the same
+	// way an out-of-range one already saturates to `u8::MAX` in `on_event`.
+	fn record_i64(&mut self, field: &tracing::field::Field, value: i64) {
+		if field.name() == "message_verbosity" {
+			(*self.0).message_verbosity = Some(value.max(0) as u64)
+		} else {
+			(*self.0).fields.insert(field.name().to_string(), value.into());
+		}
+	}
+
+	fn record_bool(&mut self, field: &tracing::field::Field, value: bool) {
+		(*self.0).fields.insert(field.name().to_string(), value.into());
+	}
+
+	// See `record_i64` above for why `message_verbosity` needs a special case
+	// here too: a bare float literal (`message_verbosity = 3.0`) is unusual
+	// but no less a caller mistake worth tolerating rather than silently
+	// dropping the event over.
+	fn record_f64(&mut self, field: &tracing::field::Field, value: f64) {
+		if field.name() == "message_verbosity" {
+			(*self.0).message_verbosity = Some(value.max(0.0) as u64)
+		} else if let Some(value) = serde_json::Number::from_f64(value) {
+			(*self.0).fields.insert(field.name().to_string(), value.into());
+		}
+	}
+
+	fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+		if field.name() == "json" {
+			// The span id and parent chain are injected by `on_event` after parsing; the
+			// visitor only records the raw payload as produced by the call site.
+			(*self.0).json = Some(value.to_string())
+		} else {
+			(*self.0).fields.insert(field.name().to_string(), value.into());
+		}
+	}
+
+	// Gated behind `tracing_unstable` like tracing's own `valuable` integration, and
+	// depends on the optional `valuable` / `valuable-serde` crates. Lets call sites
+	// attach a typed `valuable::Valuable` payload directly, which is serialized into a
+	// `serde_json::Value` here rather than forcing the producer to hand-build a JSON
+	// string.
+	#[cfg(tracing_unstable)]
+	fn record_value(&mut self, field: &tracing::field::Field, value: valuable::Value<'_>) {
+		if field.name() == "json" {
+			match serde_json::to_value(valuable_serde::Serializable::new(value)) {
+				Ok(value) => (*self.0).json_value = Some(value),
+				Err(err) => log::warn!(
+					target: "telemetry",
+					"Ignored telemetry valuable payload because it could not be serialized: {:?}",
+					err,
+				),
+			}
+		}
+	}
+}
+
+/// Wraps any `serde::Serialize` value so it can be passed as a `json = ?value`
+/// tracing field and land in [`TelemetryAttrsVisitor::record_debug`] as valid
+/// JSON, instead of Rust's `Debug` syntax.
+///
+/// `tracing`'s [`field::Value`](tracing::field::Value) trait has no blanket
+/// impl for arbitrary `Serialize` types — only primitives, `&str`, and
+/// (behind `tracing_unstable`) `valuable::Valuable` are supported directly.
+/// Wrapping a value in `AsJson` and recording it with tracing's `?` (Debug)
+/// sigil is a way to bridge `Serialize` into a `json`-field payload without
+/// that feature gate: `tracing::info!(target: T, message_verbosity = 0u64,
+/// json = ?AsJson(serde_json::json!({ "msg": "block.import", "height": h })))`.
+/// A plain `key = value` field (see [`TelemetryAttrsVisitor`]) or the
+/// existing pre-serialized `json = "..."` string both keep working exactly as
+/// before; this is an additional way in; not a replacement for either.
+///
+/// Serialization only happens inside [`std::fmt::Debug::fmt`], which `tracing`'s
+/// macros only call once a callsite is determined to be enabled — so an
+/// `AsJson(some_expensive_call())` argument is never evaluated, and `fmt`
+/// never runs, for a disabled event, the same lazy-evaluation guarantee the
+/// existing `key = value` and `json = "..."` forms already have.
+pub struct AsJson<T>(pub T);
+
+impl<T: serde::Serialize> std::fmt::Debug for AsJson<T> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match serde_json::to_string(&self.0) {
+			Ok(json) => f.write_str(&json),
+			Err(err) => write!(f, "<AsJson serialization failed: {err}>"),
+		}
+	}
+}
+
+/// What to do when a registered channel's buffer is full and a new message
+/// needs to be delivered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+	/// Drop the incoming message, keeping whatever is already queued. This is
+	/// the historical behavior.
+	DropNewest,
+	/// Keep the incoming message and drop whatever was previously bumped by
+	/// this policy instead, so the freshest state (e.g. the latest
+	/// `system.interval`) always wins over a stale queued one.
+	DropOldest,
+}
+
+impl Default for OverflowPolicy {
+	fn default() -> Self {
+		OverflowPolicy::DropNewest
+	}
+}
+
+/// Per-instance display identity merged into `id`'s connection message and,
+/// optionally, every outgoing payload — see [`SenderConfig::identity`] and
+/// [`Senders::set_node_identity`]. Useful for a parachain collator embedding
+/// several chains in one process, where each telemetry instance wants its
+/// own name/implementation/version/chain shown on the dashboard instead of
+/// whatever [`msg::SystemConnected`] was built with process-wide.
+///
+/// Every field is independently optional so a caller can override just the
+/// one that differs (e.g. `chain`) without repeating the rest.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NodeIdentity {
+	pub name: Option<String>,
+	pub implementation: Option<String>,
+	pub version: Option<String>,
+	pub chain: Option<String>,
+}
+
+impl NodeIdentity {
+	/// Overlay every field that's `Some` onto `obj`, overwriting whatever was
+	/// already there under the same key.
+	fn merge_into(&self, obj: &mut serde_json::Map<String, serde_json::Value>) {
+		if let Some(name) = &self.name {
+			obj.insert("name".into(), name.clone().into());
+		}
+		if let Some(implementation) = &self.implementation {
+			obj.insert("implementation".into(), implementation.clone().into());
+		}
+		if let Some(version) = &self.version {
+			obj.insert("version".into(), version.clone().into());
+		}
+		if let Some(chain) = &self.chain {
+			obj.insert("chain".into(), chain.clone().into());
+		}
+	}
+
+	/// This identity as a JSON object containing only the fields that are
+	/// `Some`, for nesting under a payload's `node` key.
+	fn to_json(&self) -> serde_json::Value {
+		let mut obj = serde_json::Map::new();
+		self.merge_into(&mut obj);
+		serde_json::Value::Object(obj)
+	}
+}
+
+/// Configuration for a registered [`Senders`] channel.
+#[derive(Debug, Clone)]
+pub struct SenderConfig {
+	/// Advisory: the capacity the caller constructed the underlying channel
+	/// with. `Senders` doesn't create the channel itself, so this isn't
+	/// enforced here, but it's kept alongside the policy for introspection.
+	pub capacity: usize,
+	pub overflow: OverflowPolicy,
+	/// `msg` values (e.g. `"system.interval"`) that coalesce instead of
+	/// queueing: once the channel is full, a new message of one of these types
+	/// replaces the last queued message of the *same* type rather than being
+	/// appended or dropped outright. Message types outside this set are never
+	/// coalesced, regardless of `overflow`.
+	pub coalesce: HashSet<String>,
+	/// Minimum time between two "Ignored telemetry message" warnings for the
+	/// same id. Drops within a window are still counted (see
+	/// [`Telemetries::dropped_messages`]) but only summarized once the window
+	/// elapses, so a stalled endpoint can't spam the log.
+	pub warn_interval: std::time::Duration,
+	/// Fields merged into every payload sent under this id, e.g. deployment
+	/// metadata (`datacenter`, `cluster`, `operator`) an operator wants attached
+	/// to every message without patching every call site. A field already
+	/// present on the payload itself always wins over one of the same name here.
+	pub static_fields: serde_json::Map<String, serde_json::Value>,
+	/// A human-readable name for whatever is registering this id (e.g. the
+	/// component's name, or a call site), so that if two registrants ever
+	/// collide on the same `id`, [`Senders::insert_with_config`]'s overwrite
+	/// warning (and [`Telemetries::status`]'s snapshot) can name both instead
+	/// of just the bare integer. Purely diagnostic: never affects delivery.
+	pub label: Option<String>,
+	/// Messages at or below this verbosity are never dropped in favor of a
+	/// less important one: once the channel is full, [`Senders::send`] bumps
+	/// them into a dedicated one-slot buffer instead of falling through to
+	/// `overflow`, evicting whatever less-important message was already
+	/// bumped there rather than the incoming one. `None` (the default)
+	/// disables this and every message is subject to `overflow` alone, same
+	/// as before this existed. Unrelated to [`Telemetries::send_priority`],
+	/// which always takes its own separate queue regardless of this setting.
+	pub priority_threshold: Option<Verbosity>,
+	/// Per-instance display identity merged into this id's connection
+	/// message (see [`Telemetries::set_connection_message`]), and, if
+	/// [`stamp_identity_on_payloads`](Self::stamp_identity_on_payloads) is
+	/// set, into every outgoing payload under a `node` key. `None` (the
+	/// default) leaves payloads untouched. See [`NodeIdentity`].
+	pub identity: Option<NodeIdentity>,
+	/// Whether `identity` is additionally stamped under a `node` key on
+	/// every outgoing payload, not just the connection message. Off by
+	/// default: most consumers only care about the identity shown on
+	/// (re)connect.
+	pub stamp_identity_on_payloads: bool,
+	/// Extra fields merged into `id`'s connection message alongside
+	/// [`msg::SystemConnected`]'s fixed set, e.g. `para_id` and
+	/// `relay_chain` for a parachain collator, or hardware survey data for a
+	/// benchmarking setup. Any key colliding with a reserved field (see
+	/// [`RESERVED_CONNECTION_EXTRA_FIELDS`]) is dropped with a warning at
+	/// registration; [`Senders::set_connection_extras`] enforces the same
+	/// rule at runtime, but rejects instead of dropping. Empty by default.
+	pub connection_extras: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Default for SenderConfig {
+	fn default() -> Self {
+		Self {
+			capacity: 0,
+			overflow: OverflowPolicy::default(),
+			coalesce: ["system.interval".to_string()].into_iter().collect(),
+			warn_interval: std::time::Duration::from_secs(10),
+			static_fields: serde_json::Map::new(),
+			label: None,
+			priority_threshold: None,
+			identity: None,
+			stamp_identity_on_payloads: false,
+			connection_extras: serde_json::Map::new(),
+		}
+	}
+}
+
+/// Fields a [`SenderConfig::connection_extras`] entry can't use: the six
+/// [`msg::SystemConnected`] fields it would otherwise silently shadow, and
+/// the fields another mechanism already owns (`msg`/`id`, stamped by
+/// [`Senders::send`]; `node`, populated by [`NodeIdentity`]).
+const RESERVED_CONNECTION_EXTRA_FIELDS: &[&str] =
+	&["msg", "id", "node", "chain", "name", "implementation", "version", "authority", "network_id"];
+
+/// `Err` naming the first key in `extras` that collides with
+/// [`RESERVED_CONNECTION_EXTRA_FIELDS`], if any.
+fn validate_connection_extras(
+	extras: &serde_json::Map<String, serde_json::Value>,
+) -> Result<(), TelemetryError> {
+	match extras.keys().find(|key| RESERVED_CONNECTION_EXTRA_FIELDS.contains(&key.as_str())) {
+		Some(key) => Err(TelemetryError::ReservedField(key.clone())),
+		None => Ok(()),
+	}
+}
+
+/// Newtype around [`SenderEntry::on_connect`] making it (and so the whole of
+/// `SenderEntry`) genuinely [`RefUnwindSafe`](std::panic::RefUnwindSafe),
+/// instead of `Senders`' map needing a blanket `AssertUnwindSafe` around
+/// every entry.
+///
+/// A bare `Vec<Arc<dyn Fn() + Send + Sync>>` isn't auto-derived as unwind-safe:
+/// the compiler can't see inside an opaque `dyn Fn` to rule out interior
+/// mutability a caller's callback closes over, so it conservatively assumes
+/// the worst. Asserting it here (rather than wrapping the whole entry, or
+/// every access site) is sound because [`Senders::fire_on_connect`] already
+/// only ever *calls* these callbacks inside its own `catch_unwind`: a
+/// panicking callback is caught and logged there, and can only leave its own
+/// captured state broken (exactly as it would if called directly, with no
+/// map involved at all) — never `SenderEntry`'s.
+#[derive(Clone, Default)]
+struct OnConnectCallbacks(Vec<Arc<dyn Fn() + Send + Sync>>);
+
+impl std::panic::RefUnwindSafe for OnConnectCallbacks {}
+impl std::panic::UnwindSafe for OnConnectCallbacks {}
+
+// Formats a registrant for the id-collision warning/error messages: its
+// `SenderConfig::label` if one was given, otherwise a fallback that still
+// pins down which id is at issue.
+fn describe_registrant(label: &Option<String>, id: u64) -> String {
+	match label {
+		Some(label) => format!("{label:?} (id {id})"),
+		None => format!("id {id}"),
+	}
+}
+
+/// Distinct `msg` types [`SenderEntry::message_types`] tracks individually
+/// per id before further ones are folded into a shared `"other"` bucket, so
+/// a misbehaving or unbounded producer (e.g. one embedding a request id
+/// straight into `msg`) can't grow that map without limit.
+pub const MAX_TRACKED_MESSAGE_TYPES: usize = 32;
+
+/// Per-`msg`-type send outcome counters for one telemetry id, one entry of
+/// [`Senders::message_type_stats`]. Lets an operator who suspects a
+/// producer stopped emitting a given message type (or that it's being
+/// silently sampled/dropped) see exactly what happened to it, rather than
+/// only the instance-wide totals [`Telemetries::dropped_messages`] and
+/// [`Telemetries::sampling_skipped`] already expose.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct MessageTypeCounts {
+	/// Delivered to the sender's channel.
+	pub sent: u64,
+	/// Dropped before delivery was even attempted: `id` was
+	/// [`Senders::pause`]d. Distinct from `sampled_out` below even though
+	/// both surface as [`TelemetryError::Filtered`] to a caller that only
+	/// cares whether the send happened.
+	pub filtered: u64,
+	/// Skipped by [`Telemetries::configure_sampling`].
+	pub sampled_out: u64,
+	/// Suppressed by [`Telemetries::configure_dedup`] as a duplicate of the
+	/// last message of this type actually sent.
+	pub suppressed: u64,
+	/// Handed to [`Senders::send`] but not delivered because the channel
+	/// was full or disconnected. Mirrors [`Telemetries::dropped_messages`]
+	/// restricted to this one `msg` type; equal to `dropped_queue_full +
+	/// dropped_disconnected`.
+	pub dropped: u64,
+	/// Of `dropped`, how many because the channel was full (and, under
+	/// [`OverflowPolicy::DropNewest`], stayed dropped rather than being
+	/// buffered).
+	pub dropped_queue_full: u64,
+	/// Of `dropped`, how many because the receiving end was gone for good.
+	pub dropped_disconnected: u64,
+	/// How many times a message of this type exceeded [`MessageSizeLimit`]
+	/// and was replaced by a stub. Not counted in `dropped`: unlike the other
+	/// fields here, the message (a truncated one) was still delivered.
+	pub oversized: u64,
+}
+
+/// How stale a message got while buffered in [`SenderEntry::pending`] under
+/// [`OverflowPolicy::DropOldest`] before it was evicted (bumped again with no
+/// intervening delivery) rather than ever reaching the channel. Exposed via
+/// [`Senders::eviction_age_stats`] so an operator can tell a queue that's
+/// merely full from one that's been backed up for minutes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct EvictionAgeStats {
+	count: u64,
+	total: std::time::Duration,
+	max: std::time::Duration,
+}
+
+impl EvictionAgeStats {
+	fn record(&mut self, age: std::time::Duration) {
+		self.count += 1;
+		self.total += age;
+		self.max = self.max.max(age);
+	}
+
+	/// How many messages this id has had evicted from `pending` so far.
+	pub fn count(&self) -> u64 {
+		self.count
+	}
+
+	/// The longest any evicted message sat in `pending` before being
+	/// silently replaced. `None` if nothing has been evicted yet.
+	pub fn max(&self) -> Option<std::time::Duration> {
+		(self.count > 0).then_some(self.max)
+	}
+
+	/// The mean age of every message evicted so far. `None` if nothing has
+	/// been evicted yet.
+	pub fn average(&self) -> Option<std::time::Duration> {
+		(self.count > 0).then(|| self.total / self.count as u32)
+	}
+}
+
+/// One subscriber's slot in [`SenderEntry::taps`], registered by
+/// [`Senders::subscribe`] and fed by [`Senders::publish_tap`].
+struct TapSender {
+	sender: mpsc::Sender<(u8, serde_json::Value)>,
+	lagged: Arc<std::sync::atomic::AtomicU64>,
+}
+
+/// A live subscription returned by [`Telemetries::subscribe`]: every message
+/// `id` sends from here on, after injection/filtering but independent of
+/// whether it actually reaches an endpoint.
+///
+/// Backed by a bounded channel so a slow subscriber can't stall the sending
+/// side or any other subscriber: once full, a new message is dropped for
+/// *this* subscription alone and counted in [`lagged`](Self::lagged) instead
+/// of blocking [`Senders::publish_tap`]. Dropping this stream is enough to
+/// unsubscribe — the next published message finds the channel disconnected
+/// and [`Senders::publish_tap`] prunes it, the same way [`ConnectionEvents::publish`]
+/// prunes a dropped [`subscribe`](ConnectionEvents::subscribe) receiver.
+pub struct MessageTap {
+	receiver: mpsc::Receiver<(u8, serde_json::Value)>,
+	lagged: Arc<std::sync::atomic::AtomicU64>,
+}
+
+impl MessageTap {
+	/// Messages dropped for this subscription so far because it wasn't
+	/// keeping up with the stream (the bounded channel was full when a new
+	/// message was published). Doesn't affect any other subscriber, or
+	/// whether the message reached an endpoint.
+	pub fn lagged(&self) -> u64 {
+		self.lagged.load(std::sync::atomic::Ordering::Relaxed)
+	}
+}
+
+impl futures::Stream for MessageTap {
+	type Item = (u8, serde_json::Value);
+
+	fn poll_next(
+		mut self: std::pin::Pin<&mut Self>,
+		cx: &mut std::task::Context<'_>,
+	) -> std::task::Poll<Option<Self::Item>> {
+		futures::Stream::poll_next(std::pin::Pin::new(&mut self.receiver), cx)
+	}
+}
+
+struct SenderEntry {
+	sender: mpsc::Sender<(Verbosity, String)>,
+	overflow: OverflowPolicy,
+	coalesce: HashSet<String>,
+	// Under `OverflowPolicy::DropOldest`, the non-coalescable message bumped by
+	// the last full channel, retried before the next message is sent.
+	pending: Option<(Verbosity, String)>,
+	// When `pending` was last populated, so an eviction (it's bumped again
+	// with no intervening delivery) can be attributed an age. `None` exactly
+	// when `pending` is `None`.
+	pending_enqueued_at: Option<std::time::Instant>,
+	// How stale each message evicted from `pending` was when it was bumped;
+	// see `EvictionAgeStats`.
+	eviction_ages: EvictionAgeStats,
+	// See `SenderConfig::priority_threshold`. Independent of `pending`: a
+	// message this important is never subject to `overflow` at all, so it
+	// gets its own slot rather than competing with (or being evicted by)
+	// whatever `overflow` is bumping.
+	priority_pending: Option<(Verbosity, String)>,
+	priority_threshold: Option<Verbosity>,
+	// High-priority (alert-class) messages sent through
+	// `Senders::send_priority`, e.g. via `Telemetries::send_priority` or a
+	// `Verbosity::CONSOLE` direct send. Never subject to `overflow`,
+	// `coalesce` or `MessageSampling`: a small FIFO queue here instead,
+	// drained ahead of `priority_pending`/`pending`/`coalesced` on every
+	// `send`. See `Senders::enqueue_priority` and `PRIORITY_QUEUE_CAPACITY`.
+	priority_queue: VecDeque<(Verbosity, String)>,
+	// Per coalescable `msg` type, the last message of that type bumped by a
+	// full channel, retried (and replaced by fresher ones of the same type)
+	// before the next message is sent.
+	coalesced: HashMap<String, (Verbosity, String)>,
+	// Count of `try_send` failures for the *new* message on this id, reachable
+	// via `Telemetries::dropped_messages`. Retrying a previously bumped message
+	// doesn't recount it: it was already counted when it was first bumped.
+	dropped: u64,
+	// The next `seq` value to stamp on an outgoing message for this id. See
+	// `Senders::next_seq`.
+	seq: u64,
+	// Mirrors `dropped`, except reset to `0` every time `next_seq` reports it
+	// in an outgoing message's `dropped` field, so a collector only ever sees
+	// drops it hasn't already been told about.
+	dropped_since_send: u64,
+	static_fields: serde_json::Map<String, serde_json::Value>,
+	// The message a worker should resend first thing after every successful
+	// (re)connection, typically `system.connected`. See `Senders::set_connection_message`.
+	connection_message: Option<(Verbosity, String)>,
+	// Invoked by `Senders::fire_on_connect` after a worker's handshake
+	// completes; see `Senders::add_on_connect`.
+	on_connect: OnConnectCallbacks,
+	// While `true`, `Senders::send` short-circuits before any serialization
+	// or overflow bookkeeping happens. See `Senders::pause`.
+	paused: bool,
+	// A worker's inbox for `EndpointCommand`s, registered via
+	// `Senders::set_endpoint_commands` once it starts. `None` until then, so
+	// `Senders::add_endpoint`/`remove_endpoint` called before a worker is up
+	// are honestly reported as failed rather than silently swallowed.
+	endpoint_commands: Option<mpsc::UnboundedSender<EndpointCommand>>,
+	warn_interval: std::time::Duration,
+	// Throttle state for the "Ignored telemetry message" warning: when it was
+	// last logged, and how many drops have happened since then and weren't
+	// individually logged.
+	last_warned: Option<std::time::Instant>,
+	suppressed_since_warning: u64,
+	// See `SenderConfig::label`.
+	label: Option<String>,
+	// See `SenderConfig::identity`.
+	identity: Option<NodeIdentity>,
+	// See `SenderConfig::stamp_identity_on_payloads`.
+	stamp_identity_on_payloads: bool,
+	// See `SenderConfig::connection_extras`.
+	connection_extras: serde_json::Map<String, serde_json::Value>,
+	// The peer/network identity merged into the connection message under
+	// `network_id`, once known. See `Senders::set_network_id`.
+	network_id: Option<String>,
+	// Per-`msg`-type send outcome counters, up to `MAX_TRACKED_MESSAGE_TYPES`
+	// distinct types; see `message_types_other` for the overflow bucket and
+	// `Senders::message_type_stats` for the read side.
+	message_types: HashMap<String, MessageTypeCounts>,
+	// Shared bucket for every `msg` type (including `None`) beyond
+	// `MAX_TRACKED_MESSAGE_TYPES`, exposed as `"other"` by
+	// `Senders::message_type_stats`.
+	message_types_other: MessageTypeCounts,
+	// Live subscribers registered via `Senders::subscribe`, fed by
+	// `Senders::publish_tap`. Pruned lazily: a dropped subscription is only
+	// removed the next time a message is published for this id.
+	taps: Vec<TapSender>,
+	// When this id was registered, for `Senders::check_leak_detection_high_water_mark`
+	// to name the oldest entries once the map grows past a configured mark —
+	// exactly the ones a leak (something that should have called `remove`, or
+	// dropped its `TelemetryRegistration`, and didn't) tends to accumulate as.
+	registered_at: std::time::Instant,
+	#[cfg(test)]
+	warnings_emitted: u64,
+}
+
+impl SenderEntry {
+	/// Log an "Ignored telemetry message" warning for `reason`, throttled to at
+	/// most once per `warn_interval` per id; drops within a suppressed window
+	/// are folded into the summary logged when the window next elapses.
+	fn warn_dropped(&mut self, reason: &str) {
+		let now = std::time::Instant::now();
+		let should_log = match self.last_warned {
+			Some(last) => now.duration_since(last) >= self.warn_interval,
+			None => true,
+		};
+		if !should_log {
+			self.suppressed_since_warning += 1;
+			return;
+		}
+		let suppressed = self.suppressed_since_warning;
+		self.suppressed_since_warning = 0;
+		self.last_warned = Some(now);
+		#[cfg(test)]
+		{
+			self.warnings_emitted += 1;
+		}
+		if suppressed > 0 {
+			log::warn!(
+				target: "telemetry",
+				"Ignored telemetry message because {} ({} more suppressed in the last {:?})",
+				reason,
+				suppressed,
+				self.warn_interval,
+			);
+		} else {
+			log::warn!(target: "telemetry", "Ignored telemetry message because {}", reason);
+		}
+	}
+
+	/// Apply `pick` to `msg_type`'s [`MessageTypeCounts`] (tracked
+	/// individually up to [`MAX_TRACKED_MESSAGE_TYPES`] distinct types, then
+	/// folded into `message_types_other` — including for `msg_type: None`,
+	/// which is never tracked individually).
+	fn record_message_outcome(&mut self, msg_type: Option<&str>, pick: impl FnOnce(&mut MessageTypeCounts)) {
+		let counts = match msg_type {
+			Some(msg_type)
+				if self.message_types.contains_key(msg_type)
+					|| self.message_types.len() < MAX_TRACKED_MESSAGE_TYPES =>
+			{
+				self.message_types.entry(msg_type.to_string()).or_default()
+			}
+			_ => &mut self.message_types_other,
+		};
+		pick(counts);
+	}
+}
+
+/// How many messages were delivered vs abandoned during a graceful shutdown,
+/// returned by [`Senders::shutdown`] and summed across ids by
+/// [`Telemetries::shutdown`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FlushReport {
+	pub delivered: u64,
+	pub abandoned: u64,
+}
+
+impl std::ops::AddAssign for FlushReport {
+	fn add_assign(&mut self, other: Self) {
+		self.delivered += other.delivered;
+		self.abandoned += other.abandoned;
+	}
+}
+
+/// Number of independently-locked shards backing [`Senders`]. A power of two
+/// so `shard()` can mask instead of dividing. Chosen generously relative to
+/// realistic core counts: with one id per connected endpoint worker, even a
+/// large deployment rarely has enough concurrently-registered ids for shards
+/// to collide often enough to matter.
+const SENDER_SHARDS: usize = 16;
+
+/// Cap on [`SenderEntry::priority_queue`]: high-priority (alert-class)
+/// messages are meant to be rare, so a handful of slots is enough to survive
+/// a short burst without the queue itself becoming an unbounded backlog. Once
+/// full, the oldest queued alert is dropped to make room for the newest one —
+/// see [`Senders::enqueue_priority`].
+const PRIORITY_QUEUE_CAPACITY: usize = 8;
+
+/// How many of the oldest registered ids [`Senders::check_leak_detection_high_water_mark`]
+/// names in its warning — enough to be useful without the log line itself
+/// growing unbounded alongside the leak it's reporting on.
+const LEAK_DETECTION_SAMPLE_SIZE: usize = 5;
+
+type SenderMap = HashMap<u64, SenderEntry>;
+
+/// Configuration for [`Senders`]' optional pre-registration buffer. See
+/// [`Telemetries::enable_pre_registration_buffer`].
+#[derive(Debug, Clone, Copy)]
+pub struct PreRegistrationBufferConfig {
+	/// Messages retained per id before the oldest is dropped to make room for
+	/// a new one. Default ~128: generous enough to cover the burst of
+	/// messages (including the very first block announcements) a producer
+	/// typically emits before its worker finishes connecting and registers a
+	/// sender, without holding an unbounded amount of memory for an id whose
+	/// worker never shows up at all.
+	pub capacity: usize,
+	/// How long an id's buffered messages are kept without ever seeing a
+	/// registration, after which they're dropped for good rather than
+	/// leaking indefinitely for an id nothing will ever register.
+	pub ttl: std::time::Duration,
+}
+
+impl Default for PreRegistrationBufferConfig {
+	fn default() -> Self {
+		Self { capacity: 128, ttl: std::time::Duration::from_secs(30) }
+	}
+}
+
+// Messages buffered so far for one not-yet-registered id, oldest first.
+struct PreRegistrationEntry {
+	messages: std::collections::VecDeque<(Verbosity, String)>,
+	created_at: std::time::Instant,
+}
+
+// `None` config disables buffering entirely (the default): an unregistered
+// id's message is simply dropped, exactly as before this feature existed.
+#[derive(Default)]
+struct PreRegistrationState {
+	config: Option<PreRegistrationBufferConfig>,
+	buffered: HashMap<u64, PreRegistrationEntry>,
+}
+
+#[derive(Default, Clone)]
+pub struct Senders(
+	// Sharded by `id` (see `shard`) so that events on unrelated ids emitted
+	// concurrently from different threads don't contend on the same mutex.
+	// `SenderEntry` needs `&mut` access for `try_send` and friends, which rules
+	// out a lock-free read path for the entry itself; sharding is the cheapest
+	// way to cut contention without touching every call site's mutation logic.
+	Arc<[Mutex<SenderMap>; SENDER_SHARDS]>,
+	// Lock-free mirror of the map's length, kept in sync by `insert_with_config`
+	// and `remove`. Backs `any_registered`'s fast path so the overwhelmingly
+	// common case — telemetry disabled, or enabled but no worker has
+	// registered yet — never has to take a shard's mutex at all.
+	Arc<std::sync::atomic::AtomicUsize>,
+	// Names reserved by `register_name`, e.g. via `Telemetries::register_instance`,
+	// so a caller addressing an instance by name instead of a tracing span id
+	// can't accidentally collide with (or silently steal) another instance's
+	// registration. Separate from `SenderMap` because a name outlives nothing
+	// about the sender itself — it's purely a lookup layer on top of it.
+	Arc<Mutex<HashMap<String, u64>>>,
+	// Backing store for the optional pre-registration buffer. See
+	// `PreRegistrationState` and `Telemetries::enable_pre_registration_buffer`.
+	Arc<Mutex<PreRegistrationState>>,
+	// Configured high-water mark for `check_leak_detection_high_water_mark`.
+	// `None` (the default) disables the check entirely. See
+	// `Telemetries::set_leak_detection_high_water_mark`.
+	Arc<Mutex<Option<usize>>>,
+);
+
+impl std::fmt::Debug for Senders {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Senders").finish_non_exhaustive()
+	}
+}
+
+/// RAII guard returned by [`Senders::register`]: removes its entry from the
+/// map when dropped, so a telemetry instance's registration can't outlive the
+/// instance itself even on an early-return or panicking exit path.
+#[derive(Debug)]
+pub struct TelemetryRegistration {
+	senders: Senders,
+	id: u64,
+	armed: bool,
+}
+
+impl TelemetryRegistration {
+	/// Leak the registration: the sender stays registered even after this guard
+	/// is dropped. Intended for the rare case where indefinite registration
+	/// (for the lifetime of the process) is what's wanted.
+	pub fn forget(mut self) {
+		self.armed = false;
+	}
+}
+
+impl Drop for TelemetryRegistration {
+	fn drop(&mut self) {
+		if self.armed {
+			self.senders.remove(self.id);
+		}
+	}
+}
+
+/// A weak counterpart to [`Senders`], holding [`Weak`](std::sync::Weak) refs
+/// to its shards and registration counter instead of [`Arc`]s. See
+/// [`TelemetryHandle`], its only consumer, for why that matters.
+#[derive(Clone)]
+pub struct WeakSenders(
+	std::sync::Weak<[Mutex<SenderMap>; SENDER_SHARDS]>,
+	std::sync::Weak<std::sync::atomic::AtomicUsize>,
+	std::sync::Weak<Mutex<HashMap<String, u64>>>,
+	std::sync::Weak<Mutex<PreRegistrationState>>,
+	std::sync::Weak<Mutex<Option<usize>>>,
+);
+
+impl WeakSenders {
+	/// Upgrade back to a [`Senders`], or `None` if every strong owner
+	/// (typically the [`Telemetries`] that created it) has already been
+	/// dropped.
+	pub fn upgrade(&self) -> Option<Senders> {
+		Some(Senders(self.0.upgrade()?, self.1.upgrade()?, self.2.upgrade()?, self.3.upgrade()?, self.4.upgrade()?))
+	}
+}
+
+/// Why a direct send ([`Telemetries::try_send`], [`TelemetryHandle::try_send_telemetry`])
+/// didn't reach an endpoint, for a caller that wants to branch on the reason
+/// rather than the coarser `bool` [`Telemetries::send`] returns (kept as-is
+/// for existing call sites that only care whether the send happened at all).
+#[derive(Debug, Clone, PartialEq)]
+pub enum TelemetryError {
+	/// `payload` wasn't a JSON object once stamped with `id`/`seq`/timestamp,
+	/// so it can't be delivered as a telemetry event.
+	MalformedEvent,
+	/// No sender is registered for the id (never registered, already
+	/// [`Senders::shutdown`], or removed by a dropped [`TelemetryHandle`]'s
+	/// last strong owner).
+	Disconnected,
+	/// The message was intentionally dropped before delivery was even
+	/// attempted: `id` is [`Senders::pause`]d, it was filtered out by
+	/// [`Telemetries::configure_sampling`], or it exceeded
+	/// [`Telemetries::set_global_verbosity`]'s cap. Already accounted for in
+	/// [`Telemetries::sampling_skipped`] / [`Telemetries::global_verbosity_skipped`]
+	/// where applicable, so this variant is for a caller that wants to know a
+	/// specific send didn't go out, not a bug to report.
+	Filtered,
+	/// The id's channel was full and the message was dropped (or, under
+	/// [`OverflowPolicy::DropOldest`], displaced an older queued message)
+	/// rather than delivered on this call.
+	ChannelFull,
+	/// `payload` failed to serialize to JSON.
+	Serialization(String),
+	/// The endpoint's [`TelemetrySerializer`] rejected the message. See
+	/// [`SerializeError`].
+	Transport(String),
+	/// [`Senders::try_insert_with_config`] found `id` already registered and
+	/// refused to overwrite it. Names both the existing and attempted
+	/// registrant (see [`SenderConfig::label`]) so the collision is
+	/// diagnosable instead of just failing silently.
+	DuplicateRegistration(String),
+	/// [`Senders::set_connection_extras`] was given a field name that
+	/// collides with a reserved `system.connected` field (see
+	/// [`RESERVED_CONNECTION_EXTRA_FIELDS`]). Names the offending key. The
+	/// previous extras and connection message are left untouched.
+	ReservedField(String),
+}
+
+impl std::fmt::Display for TelemetryError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::MalformedEvent => write!(f, "telemetry payload is not a JSON object"),
+			Self::Disconnected => write!(f, "no telemetry sender is registered for this id"),
+			Self::Filtered => write!(f, "telemetry message was paused or sampled out before delivery"),
+			Self::ChannelFull => write!(f, "telemetry channel is full"),
+			Self::Serialization(err) => write!(f, "failed to serialize telemetry payload: {err}"),
+			Self::Transport(err) => write!(f, "telemetry transport error: {err}"),
+			Self::DuplicateRegistration(reason) => write!(f, "duplicate telemetry registration: {reason}"),
+			Self::ReservedField(field) => {
+				write!(f, "connection extras field '{field}' collides with a reserved system.connected field")
+			}
+		}
+	}
+}
+
+impl std::error::Error for TelemetryError {}
+
+/// A small `Clone + Send + Sync` handle a producer can carry deep into a call
+/// stack (e.g. the transaction pool reporting import events) instead of a
+/// full [`Telemetries`] clone or a `tracing` span. Wraps the instance `id`
+/// plus a [`WeakSenders`], so a producer that outlives the node's telemetry
+/// setup and forgets to drop its handle doesn't keep the registered-id map
+/// alive by itself: [`is_enabled`](Self::is_enabled) and
+/// [`send_telemetry`](Self::send_telemetry) treat a dead upgrade exactly like
+/// "nothing registered" and return without doing any work.
+///
+/// This is a thinner path than [`Telemetries::send`]: both stamp `id` the
+/// same way and check the same registration/pause state, but this handle
+/// skips per-instance static fields, sampling, redaction and the message
+/// size limit, since those live on [`Telemetries`]'s own state, and a handle
+/// that only weakly references the sending machinery has no more claim to
+/// keep that state alive than it does the registered-id map itself.
+/// Producers that need those guarantees should keep holding a
+/// [`Telemetries`] clone and call [`send`](Telemetries::send) directly;
+/// `TelemetryHandle` is for the common case where minimizing what a hot call
+/// stack carries matters more than full parity.
+///
+/// There's no `telemetry!`-style emission macro in this crate slice for
+/// `send_telemetry` to plug into as an alternate backend — callers build
+/// `payload` by hand the same way they already do for
+/// [`Telemetries::send`].
+#[derive(Debug, Clone)]
+pub struct TelemetryHandle {
+	id: u64,
+	senders: WeakSenders,
+}
+
+impl TelemetryHandle {
+	fn new(senders: &Senders, id: u64) -> Self {
+		Self { id, senders: senders.downgrade() }
+	}
+
+	/// Cheap check for whether sending would do anything: `false` if every
+	/// strong owner of the sending machinery has been dropped, or if this
+	/// handle's id has no sender registered. Doesn't allocate; an upgrade
+	/// failure is just a dead [`Weak`](std::sync::Weak) check, and a live one
+	/// only needs [`Senders::contains`]'s existing shard lookup.
+	pub fn is_enabled(&self) -> bool {
+		self.senders.upgrade().is_some_and(|senders| senders.contains(self.id))
+	}
+
+	/// Send `payload` under this handle's id, applying the same
+	/// registration/pause checks and `id` stamping as [`Telemetries::send`]
+	/// (see the struct docs for what it deliberately skips). Returns `false`
+	/// for the same reasons `Telemetries::send` would, plus if the sending
+	/// machinery has already been dropped. See [`try_send_telemetry`](Self::try_send_telemetry)
+	/// for the reason behind a `false`.
+	pub fn send_telemetry(&self, verbosity: impl Into<Verbosity>, payload: serde_json::Value) -> bool {
+		self.try_send_telemetry(verbosity, payload).is_ok()
+	}
+
+	/// Fallible counterpart to [`send_telemetry`](Self::send_telemetry),
+	/// returning why the send didn't reach an endpoint instead of a bare
+	/// `bool`. Treats a dead [`WeakSenders`] upgrade as
+	/// [`TelemetryError::Disconnected`], the same as an unregistered id.
+	pub fn try_send_telemetry(
+		&self,
+		verbosity: impl Into<Verbosity>,
+		payload: serde_json::Value,
+	) -> Result<(), TelemetryError> {
+		let senders = self.senders.upgrade().ok_or(TelemetryError::Disconnected)?;
+		let mut value = payload;
+		let obj = value.as_object_mut().ok_or(TelemetryError::MalformedEvent)?;
+		if !senders.contains(self.id) || senders.is_paused(self.id) {
+			return Err(TelemetryError::Disconnected);
+		}
+		let msg_type = obj.get("msg").and_then(|v| v.as_str()).map(|s| s.to_string());
+		obj.insert("id".into(), self.id.into());
+		let json = serialize_message(&value, msg_type.as_deref())
+			.map_err(|err| TelemetryError::Serialization(err.to_string()))?;
+		senders.send(self.id, msg_type.as_deref(), (verbosity.into(), json))
+	}
+}
+
+impl Senders {
+	/// The shard holding `id`'s entry, if any. `id`'s low bits pick the shard
+	/// rather than a hash, since `id`s are `tracing` span ids handed out
+	/// sequentially, and a mask over the low bits already spreads sequential
+	/// values evenly across shards without the cost of hashing.
+	fn shard(&self, id: u64) -> &Mutex<SenderMap> {
+		&self.0[(id as usize) & (SENDER_SHARDS - 1)]
+	}
+
+	/// A value unique to this `Senders`' backing `Arc`s — and so, transitively,
+	/// to the single `Telemetries` instance that owns it — usable as a map key
+	/// wherever state needs to be scoped per instance but (for reasons
+	/// documented at the call site) can't simply live in a `Telemetries` field.
+	/// See [`MessageDedup`].
+	pub(crate) fn instance_key(&self) -> usize {
+		Arc::as_ptr(&self.0) as *const () as usize
+	}
+
+	/// A [`WeakSenders`] pointing at the same shards, for holders (like
+	/// [`TelemetryHandle`]) that shouldn't keep the registered-id map alive
+	/// on their own.
+	pub fn downgrade(&self) -> WeakSenders {
+		WeakSenders(
+			Arc::downgrade(&self.0),
+			Arc::downgrade(&self.1),
+			Arc::downgrade(&self.2),
+			Arc::downgrade(&self.3),
+			Arc::downgrade(&self.4),
+		)
+	}
+
+	/// Opt into buffering messages sent to an id before
+	/// [`insert`](Self::insert)/[`insert_with_config`](Self::insert_with_config)/
+	/// [`register`](Self::register) registers a sender for it, so the
+	/// initialization race between installing the `tracing` subscriber and a
+	/// worker actually registering doesn't silently lose the earliest
+	/// messages. Off by default; calling this again replaces the previous
+	/// `config` without discarding anything already buffered under it.
+	pub fn enable_pre_registration_buffer(&self, config: PreRegistrationBufferConfig) {
+		self.3.lock().config = Some(config);
+	}
+
+	/// Turn pre-registration buffering back off and drop anything currently
+	/// buffered. A no-op if it was never enabled.
+	pub fn disable_pre_registration_buffer(&self) {
+		let mut state = self.3.lock();
+		state.config = None;
+		state.buffered.clear();
+	}
+
+	/// Whether [`enable_pre_registration_buffer`](Self::enable_pre_registration_buffer)
+	/// is currently in effect.
+	pub fn pre_registration_buffer_enabled(&self) -> bool {
+		self.3.lock().config.is_some()
+	}
+
+	/// Retain `message` for `id`, to be replayed to its sender once one is
+	/// registered. A no-op unless
+	/// [`enable_pre_registration_buffer`](Self::enable_pre_registration_buffer)
+	/// has been called; the oldest buffered message for `id` is dropped to
+	/// make room once `config.capacity` is reached, and every id's buffer is
+	/// dropped for good once `config.ttl` elapses with no registration.
+	fn buffer_pre_registration(&self, id: u64, message: (Verbosity, String)) {
+		let mut state = self.3.lock();
+		let Some(config) = state.config else {
+			return;
+		};
+		state.buffered.retain(|_, entry| entry.created_at.elapsed() < config.ttl);
+		let entry = state.buffered.entry(id).or_insert_with(|| PreRegistrationEntry {
+			messages: std::collections::VecDeque::new(),
+			created_at: std::time::Instant::now(),
+		});
+		if entry.messages.len() >= config.capacity {
+			entry.messages.pop_front();
+		}
+		entry.messages.push_back(message);
+	}
+
+	/// Hand any messages buffered for `id` to its just-registered sender, in
+	/// the order they arrived, ahead of any live traffic sent after this
+	/// call returns. A no-op if buffering isn't enabled or nothing was
+	/// buffered for `id`. Called by
+	/// [`insert_with_config`](Self::insert_with_config) once the new entry
+	/// is in place.
+	fn drain_pre_registration_buffer(&self, id: u64) {
+		let buffered = self.3.lock().buffered.remove(&id);
+		let Some(buffered) = buffered else {
+			return;
+		};
+		let mut shard = self.shard(id).lock();
+		if let Some(entry) = shard.get_mut(&id) {
+			for message in buffered.messages {
+				let _ = entry.sender.try_send(message);
+			}
+		}
+	}
+
+	/// Register `sender` for `id`, returning the previously registered sender
+	/// (if any) so accidental id reuse is detectable rather than silently
+	/// overwriting a still-live registration.
+	pub fn insert(
+		&self,
+		id: u64,
+		sender: mpsc::Sender<(Verbosity, String)>,
+	) -> Option<mpsc::Sender<(Verbosity, String)>> {
+		self.insert_with_config(id, sender, SenderConfig::default())
+	}
+
+	/// Like [`insert`](Self::insert), but with an explicit [`SenderConfig`]
+	/// controlling the [`OverflowPolicy`] applied once the channel fills up.
+	pub fn insert_with_config(
+		&self,
+		id: u64,
+		sender: mpsc::Sender<(Verbosity, String)>,
+		config: SenderConfig,
+	) -> Option<mpsc::Sender<(Verbosity, String)>> {
+		let new_label = config.label.clone();
+		let previous = self.shard(id).lock().insert(
+			id,
+			SenderEntry {
+				sender,
+				overflow: config.overflow,
+				coalesce: config.coalesce,
+				pending: None,
+				pending_enqueued_at: None,
+				eviction_ages: EvictionAgeStats::default(),
+				priority_pending: None,
+				priority_threshold: config.priority_threshold,
+				priority_queue: VecDeque::new(),
+				coalesced: HashMap::new(),
+				dropped: 0,
+				seq: 0,
+				dropped_since_send: 0,
+				static_fields: config.static_fields,
+				connection_message: None,
+				on_connect: OnConnectCallbacks::default(),
+				paused: false,
+				endpoint_commands: None,
+				warn_interval: config.warn_interval,
+				last_warned: None,
+				suppressed_since_warning: 0,
+				label: new_label.clone(),
+				identity: config.identity,
+				stamp_identity_on_payloads: config.stamp_identity_on_payloads,
+				connection_extras: {
+					let mut extras = config.connection_extras;
+					extras.retain(|key, _| {
+						let reserved = RESERVED_CONNECTION_EXTRA_FIELDS.contains(&key.as_str());
+						if reserved {
+							log::warn!(
+								target: "telemetry",
+								"Ignored connection extras field '{key}' for telemetry id {id}: \
+								 reserved by system.connected",
+							);
+						}
+						!reserved
+					});
+					extras
+				},
+				network_id: None,
+				message_types: HashMap::new(),
+				message_types_other: MessageTypeCounts::default(),
+				taps: Vec::new(),
+				registered_at: std::time::Instant::now(),
+				#[cfg(test)]
+				warnings_emitted: 0,
+			},
+		);
+		match &previous {
+			// A caller reusing an id that's already registered has, historically,
+			// silently stolen half of the other registrant's messages: whichever
+			// entry loses the race for the map slot never gets sent to again. Name
+			// both sides so that's diagnosable instead of just "messages went
+			// missing". See `try_insert_with_config` for a variant that refuses
+			// this outright instead of just warning.
+			Some(previous) => log::warn!(
+				target: "telemetry",
+				"Telemetry id {id} registered by {} overwrites an existing registration by {}; \
+				 the previous registrant will stop receiving messages",
+				describe_registrant(&new_label, id),
+				describe_registrant(&previous.label, id),
+			),
+			None => {
+				self.1.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+				#[cfg(debug_assertions)]
+				self.check_leak_detection_high_water_mark();
+			}
+		}
+		self.drain_pre_registration_buffer(id);
+		previous.map(|previous| previous.sender)
+	}
+
+	/// Configure a debug-only leak check: once more than `mark` ids are
+	/// simultaneously registered, every further registration logs a warning
+	/// naming the oldest still-registered entries, so a soak test that leaks
+	/// `TelemetryRegistration`s (or forgets to call [`remove`](Self::remove))
+	/// shows up as log noise instead of silent unbounded growth. `None`
+	/// disables the check. A no-op outside `debug_assertions` builds, like
+	/// [`validate_schema`]'s equivalent note — this crate slice has no
+	/// release-mode `strict` feature to gate it behind instead.
+	pub fn set_leak_detection_high_water_mark(&self, mark: Option<usize>) {
+		*self.4.lock() = mark;
+	}
+
+	/// Log a warning naming the oldest [`LEAK_DETECTION_SAMPLE_SIZE`]
+	/// registered ids if [`set_leak_detection_high_water_mark`](Self::set_leak_detection_high_water_mark)
+	/// is configured and [`len`](Self::len) now exceeds it. Called from
+	/// [`insert_with_config`](Self::insert_with_config) after a fresh
+	/// registration (not an overwrite, which doesn't grow the map), under
+	/// `debug_assertions` only.
+	#[cfg(debug_assertions)]
+	fn check_leak_detection_high_water_mark(&self) {
+		let Some(mark) = *self.4.lock() else {
+			return;
+		};
+		let count = self.len();
+		if count <= mark {
+			return;
+		}
+		let mut oldest: Vec<(u64, Option<String>, std::time::Instant)> = self
+			.0
+			.iter()
+			.flat_map(|shard| {
+				shard.lock().iter().map(|(id, entry)| (*id, entry.label.clone(), entry.registered_at)).collect::<Vec<_>>()
+			})
+			.collect();
+		oldest.sort_by_key(|(_, _, registered_at)| *registered_at);
+		oldest.truncate(LEAK_DETECTION_SAMPLE_SIZE);
+		let oldest = oldest
+			.into_iter()
+			.map(|(id, label, registered_at)| format!("{} (age {:?})", describe_registrant(&label, id), registered_at.elapsed()))
+			.collect::<Vec<_>>()
+			.join(", ");
+		log::warn!(
+			target: "telemetry",
+			"Telemetry sender map has grown to {count} registered ids, past the configured high-water \
+			 mark of {mark}; oldest entries: {oldest}",
+		);
+	}
+
+	/// Like [`insert_with_config`](Self::insert_with_config), but refuses to
+	/// overwrite an existing registration instead of logging a warning and
+	/// proceeding: returns [`TelemetryError::DuplicateRegistration`] naming
+	/// both registrants, leaving the original sender in place and untouched.
+	/// Intended for tests and debug builds that want an id collision to fail
+	/// loudly rather than silently drop half a component's messages;
+	/// production code — which can't always guarantee unique ids up front,
+	/// e.g. across independently-restarting workers — should keep using
+	/// [`insert_with_config`](Self::insert_with_config).
+	pub fn try_insert_with_config(
+		&self,
+		id: u64,
+		sender: mpsc::Sender<(Verbosity, String)>,
+		config: SenderConfig,
+	) -> Result<(), TelemetryError> {
+		{
+			let shard = self.shard(id).lock();
+			if let Some(existing) = shard.get(&id) {
+				return Err(TelemetryError::DuplicateRegistration(format!(
+					"id {id} is already registered by {}, refusing to also register it for {}",
+					describe_registrant(&existing.label, id),
+					describe_registrant(&config.label, id),
+				)));
+			}
+		}
+		self.insert_with_config(id, sender, config);
+		Ok(())
+	}
+
+	/// The diagnostic label passed via [`SenderConfig::label`] when `id` was
+	/// registered, if any. `None` for an unregistered id, or one registered
+	/// without a label. Surfaced in [`Telemetries::status`] for diagnosing id
+	/// collisions after the fact.
+	pub fn label(&self, id: u64) -> Option<String> {
+		self.shard(id).lock().get(&id).and_then(|entry| entry.label.clone())
+	}
+
+	/// The [`NodeIdentity`] configured for `id`, if any — via
+	/// [`SenderConfig::identity`] at registration or updated later with
+	/// [`set_node_identity`](Self::set_node_identity).
+	pub fn node_identity(&self, id: u64) -> Option<NodeIdentity> {
+		self.shard(id).lock().get(&id).and_then(|entry| entry.identity.clone())
+	}
+
+	/// Replace `id`'s [`NodeIdentity`] at runtime. If a connection message is
+	/// registered (see
+	/// [`Telemetries::set_connection_message`]), the new identity's fields
+	/// are merged into it and it's resent immediately, so already-connected
+	/// endpoints see the change right away instead of only on the next
+	/// reconnect. Returns `false` if `id` isn't registered.
+	pub fn set_node_identity(&self, id: u64, identity: NodeIdentity) -> bool {
+		let mut senders = self.shard(id).lock();
+		let Some(entry) = senders.get_mut(&id) else {
+			return false;
+		};
+		entry.identity = Some(identity.clone());
+		if let Some((verbosity, json)) = entry.connection_message.take() {
+			let reannounced = serde_json::from_str::<serde_json::Value>(&json).ok().and_then(|mut value| {
+				identity.merge_into(value.as_object_mut()?);
+				serde_json::to_string(&value).ok()
+			});
+			match reannounced {
+				Some(json) => {
+					let _ = entry.sender.try_send((verbosity, json.clone()));
+					entry.connection_message = Some((verbosity, json));
+				}
+				None => entry.connection_message = Some((verbosity, json)),
+			}
+		}
+		true
+	}
+
+	/// The `network_id` configured for `id`, if any. See
+	/// [`set_network_id`](Self::set_network_id).
+	pub fn network_id(&self, id: u64) -> Option<String> {
+		self.shard(id).lock().get(&id).and_then(|entry| entry.network_id.clone())
+	}
+
+	/// Set (or replace) `id`'s `network_id` at runtime — typically the node's
+	/// `PeerId`, derived from its network key, which is often only known
+	/// after telemetry is already constructed and connected. If a connection
+	/// message is registered (see [`Telemetries::set_connection_message`]),
+	/// `network_id` is merged into it and it's resent immediately, exactly
+	/// like [`set_node_identity`](Self::set_node_identity) does for display
+	/// identity — subsequent payloads are unaffected, since the backend only
+	/// needs `network_id` in the handshake. Returns `false` if `id` isn't
+	/// registered.
+	pub fn set_network_id(&self, id: u64, network_id: String) -> bool {
+		let mut senders = self.shard(id).lock();
+		let Some(entry) = senders.get_mut(&id) else {
+			return false;
+		};
+		entry.network_id = Some(network_id.clone());
+		if let Some((verbosity, json)) = entry.connection_message.take() {
+			let reannounced = serde_json::from_str::<serde_json::Value>(&json).ok().and_then(|mut value| {
+				value.as_object_mut()?.insert("network_id".into(), network_id.into());
+				serde_json::to_string(&value).ok()
+			});
+			match reannounced {
+				Some(json) => {
+					let _ = entry.sender.try_send((verbosity, json.clone()));
+					entry.connection_message = Some((verbosity, json));
+				}
+				None => entry.connection_message = Some((verbosity, json)),
+			}
+		}
+		true
+	}
+
+	/// The connection extras configured for `id`, if `id` is registered. See
+	/// [`SenderConfig::connection_extras`] and
+	/// [`set_connection_extras`](Self::set_connection_extras).
+	pub fn connection_extras(&self, id: u64) -> Option<serde_json::Map<String, serde_json::Value>> {
+		self.shard(id).lock().get(&id).map(|entry| entry.connection_extras.clone())
+	}
+
+	/// Replace `id`'s connection extras at runtime, merging them into the
+	/// connection message and re-announcing it to already-connected
+	/// endpoints, exactly like [`set_node_identity`](Self::set_node_identity)
+	/// does for display identity. Rejects `extras` (leaving the previous
+	/// extras and connection message untouched) if it contains a key from
+	/// [`RESERVED_CONNECTION_EXTRA_FIELDS`]. Returns
+	/// [`TelemetryError::Disconnected`] if `id` isn't registered.
+	pub fn set_connection_extras(
+		&self,
+		id: u64,
+		extras: serde_json::Map<String, serde_json::Value>,
+	) -> Result<(), TelemetryError> {
+		validate_connection_extras(&extras)?;
+		let mut senders = self.shard(id).lock();
+		let Some(entry) = senders.get_mut(&id) else {
+			return Err(TelemetryError::Disconnected);
+		};
+		entry.connection_extras = extras.clone();
+		if let Some((verbosity, json)) = entry.connection_message.take() {
+			let reannounced = serde_json::from_str::<serde_json::Value>(&json).ok().and_then(|mut value| {
+				let obj = value.as_object_mut()?;
+				for (key, extra) in &extras {
+					obj.insert(key.clone(), extra.clone());
+				}
+				serde_json::to_string(&value).ok()
+			});
+			match reannounced {
+				Some(json) => {
+					let _ = entry.sender.try_send((verbosity, json.clone()));
+					entry.connection_message = Some((verbosity, json));
+				}
+				None => entry.connection_message = Some((verbosity, json)),
+			}
+		}
+		Ok(())
+	}
+
+	/// Turn stamping `id`'s [`NodeIdentity`] under a `node` key onto every
+	/// outgoing payload on or off (default: off — only the connection
+	/// message is merged automatically). Returns `false` if `id` isn't
+	/// registered. See [`SenderConfig::stamp_identity_on_payloads`].
+	pub fn set_stamp_identity_on_payloads(&self, id: u64, enabled: bool) -> bool {
+		let mut senders = self.shard(id).lock();
+		let Some(entry) = senders.get_mut(&id) else {
+			return false;
+		};
+		entry.stamp_identity_on_payloads = enabled;
+		true
+	}
+
+	/// `id`'s [`NodeIdentity`] if it should be stamped onto the payload about
+	/// to be sent, i.e. one is configured and
+	/// [`stamp_identity_on_payloads`](Self::set_stamp_identity_on_payloads)
+	/// is on for it. `None` otherwise, including for an unregistered `id`.
+	fn stamped_identity(&self, id: u64) -> Option<NodeIdentity> {
+		let senders = self.shard(id).lock();
+		let entry = senders.get(&id)?;
+		entry.stamp_identity_on_payloads.then(|| entry.identity.clone()).flatten()
+	}
+
+	/// Record that a message of `msg_type` was dropped for `id` before
+	/// [`send`](Self::send) was even attempted, because `id` is
+	/// [`pause`](Self::pause)d. No-op for an unregistered id.
+	pub fn record_message_filtered(&self, id: u64, msg_type: Option<&str>) {
+		if let Some(entry) = self.shard(id).lock().get_mut(&id) {
+			entry.record_message_outcome(msg_type, |c| c.filtered += 1);
+		}
+	}
+
+	/// Record that a message of `msg_type` was skipped for `id` by
+	/// [`Telemetries::configure_sampling`]. No-op for an unregistered id.
+	pub fn record_message_sampled_out(&self, id: u64, msg_type: Option<&str>) {
+		if let Some(entry) = self.shard(id).lock().get_mut(&id) {
+			entry.record_message_outcome(msg_type, |c| c.sampled_out += 1);
+		}
+	}
+
+	/// Record that a message of `msg_type` was suppressed for `id` by
+	/// [`Telemetries::configure_dedup`] as a duplicate. No-op for an
+	/// unregistered id.
+	pub fn record_message_suppressed(&self, id: u64, msg_type: Option<&str>) {
+		if let Some(entry) = self.shard(id).lock().get_mut(&id) {
+			entry.record_message_outcome(msg_type, |c| c.suppressed += 1);
+		}
+	}
+
+	/// Record that a message of `msg_type` was replaced by a stub for `id`
+	/// because it exceeded [`MessageSizeLimit`]. No-op for an unregistered
+	/// id.
+	pub fn record_message_oversized(&self, id: u64, msg_type: Option<&str>) {
+		if let Some(entry) = self.shard(id).lock().get_mut(&id) {
+			entry.record_message_outcome(msg_type, |c| c.oversized += 1);
+		}
+	}
+
+	/// A snapshot of `id`'s per-`msg`-type send outcome counters, keyed by
+	/// `msg` (or `"other"` for types beyond [`MAX_TRACKED_MESSAGE_TYPES`],
+	/// including messages with no `msg` field at all). Empty for an
+	/// unregistered id.
+	pub fn message_type_stats(&self, id: u64) -> HashMap<String, MessageTypeCounts> {
+		let senders = self.shard(id).lock();
+		let Some(entry) = senders.get(&id) else {
+			return HashMap::new();
+		};
+		let mut snapshot = entry.message_types.clone();
+		if entry.message_types_other != MessageTypeCounts::default() {
+			snapshot.insert("other".to_string(), entry.message_types_other);
+		}
+		snapshot
+	}
+
+	/// `id`'s [`EvictionAgeStats`]: how stale messages buffered under
+	/// [`OverflowPolicy::DropOldest`] got before being evicted with no
+	/// intervening delivery. Default (all-zero) for an unregistered id or one
+	/// that has never evicted anything.
+	pub fn eviction_age_stats(&self, id: u64) -> EvictionAgeStats {
+		self.shard(id).lock().get(&id).map(|entry| entry.eviction_ages).unwrap_or_default()
+	}
+
+	/// Subscribe to every message sent for `id` from now on, via a bounded
+	/// channel of `capacity`. See [`MessageTap`]. `None` if `id` isn't
+	/// registered.
+	pub fn subscribe(&self, id: u64, capacity: usize) -> Option<MessageTap> {
+		let mut senders = self.shard(id).lock();
+		let entry = senders.get_mut(&id)?;
+		let (sender, receiver) = mpsc::channel(capacity);
+		let lagged = Arc::new(std::sync::atomic::AtomicU64::new(0));
+		entry.taps.push(TapSender { sender, lagged: lagged.clone() });
+		Some(MessageTap { receiver, lagged })
+	}
+
+	/// Publish `value` (already injected/filtered, not yet serialized) at
+	/// `verbosity` to every live [`subscribe`](Self::subscribe)r of `id`,
+	/// pruning any whose stream has since been dropped. A no-op for an
+	/// unregistered id or one with no subscribers.
+	fn publish_tap(&self, id: u64, verbosity: u8, value: &serde_json::Value) {
+		if let Some(entry) = self.shard(id).lock().get_mut(&id) {
+			entry.taps.retain_mut(|tap| match tap.sender.try_send((verbosity, value.clone())) {
+				Ok(()) => true,
+				Err(err) if err.is_disconnected() => false,
+				Err(_) => {
+					tap.lagged.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+					true
+				}
+			});
+		}
+	}
+
+	/// Like [`insert`](Self::insert), but returns an RAII [`TelemetryRegistration`]
+	/// that removes the entry on `Drop` instead of requiring the caller to call
+	/// [`remove`](Self::remove) manually on every exit path.
+	pub fn register(&self, id: u64, sender: mpsc::Sender<(Verbosity, String)>) -> TelemetryRegistration {
+		self.insert(id, sender);
+		TelemetryRegistration { senders: self.clone(), id, armed: true }
+	}
+
+	/// Number of ids currently registered, summed across shards.
+	pub fn len(&self) -> usize {
+		self.0.iter().map(|shard| shard.lock().len()).sum()
+	}
+
+	/// Whether no ids are currently registered in any shard.
+	pub fn is_empty(&self) -> bool {
+		self.0.iter().all(|shard| shard.lock().is_empty())
+	}
+
+	/// Every id currently registered, e.g. for [`Telemetries::shutdown`] to
+	/// iterate over.
+	pub fn registered_ids(&self) -> Vec<u64> {
+		self.0.iter().flat_map(|shard| shard.lock().keys().copied().collect::<Vec<_>>()).collect()
+	}
+
+	/// The one registered id, if exactly one is currently registered; `None`
+	/// if zero or several are. Used as `on_event`'s last-resort fallback for
+	/// an event with neither a telemetry span ancestor nor an explicit
+	/// `telemetry_id` field: in the overwhelmingly common single-node case
+	/// there's only ever one id to guess, so this keeps telemetry flowing for
+	/// an event fired from a context [`resolve_telemetry_id`]'s scope walk
+	/// can't see into (e.g. an async task migrated to a different executor
+	/// thread than the one that entered the span). With several ids
+	/// registered the guess is genuinely ambiguous, so this deliberately
+	/// returns `None` rather than picking one.
+	pub fn sole_registered_id(&self) -> Option<u64> {
+		match self.registered_ids().as_slice() {
+			[id] => Some(*id),
+			_ => None,
+		}
+	}
+
+	/// Number of messages dropped so far for `id` because its channel was full
+	/// or disconnected when `try_send` was attempted. `0` if `id` isn't (or is
+	/// no longer) registered.
+	pub fn dropped(&self, id: u64) -> u64 {
+		self.shard(id).lock().get(&id).map(|entry| entry.dropped).unwrap_or(0)
+	}
+
+	/// Sum of [`dropped`](Self::dropped) across every currently registered id.
+	pub fn dropped_total(&self) -> u64 {
+		self.0.iter().map(|shard| shard.lock().values().map(|entry| entry.dropped).sum::<u64>()).sum()
+	}
+
+	/// Remove and return the sender registered for `id`, if any. Called from
+	/// `on_close` so a closed telemetry span doesn't keep a dead channel alive in
+	/// the map forever.
+	pub fn remove(&self, id: u64) -> Option<mpsc::Sender<(Verbosity, String)>> {
+		let removed = self.shard(id).lock().remove(&id).map(|entry| entry.sender);
+		if removed.is_some() {
+			self.1.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+			self.unregister_name(id);
+		}
+		removed
+	}
+
+	/// Reserve `name` for `id`, for [`Telemetries::register_instance`].
+	/// Returns `false` without reserving anything if `name` is already taken
+	/// by a still-registered id, so a caller can tell a genuine collision
+	/// apart from a race against its own retry.
+	fn register_name(&self, name: &str, id: u64) -> bool {
+		let mut names = self.2.lock();
+		if names.contains_key(name) {
+			return false;
+		}
+		names.insert(name.to_string(), id);
+		true
+	}
+
+	/// Free up `id`'s name, if it was ever registered via
+	/// [`register_name`](Self::register_name), so the name can be reused once
+	/// the instance it named is gone. Called wherever `id` itself is removed
+	/// from the map, rather than only from a dedicated "unregister by name"
+	/// call, since the name is only ever meaningful alongside a live entry.
+	fn unregister_name(&self, id: u64) {
+		self.2.lock().retain(|_, mapped_id| *mapped_id != id);
+	}
+
+	/// Whether a sender is currently registered for `id`.
+	pub fn contains(&self, id: u64) -> bool {
+		self.shard(id).lock().contains_key(&id)
+	}
+
+	/// Lock-free fast path for "is any id currently registered at all".
+	/// Backs [`TelemetryLayer::enabled`] and the start of `on_event`, so the
+	/// overwhelmingly common disabled case (`--no-telemetry`, or before any
+	/// worker has registered a sender) never takes the map's mutex. This is
+	/// a coarse should-we-bother check, not a correctness gate: `contains`
+	/// and `send` still authoritatively re-check under the lock before doing
+	/// real work.
+	pub fn any_registered(&self) -> bool {
+		self.1.load(std::sync::atomic::Ordering::Relaxed) != 0
+	}
+
+	/// Remove `id` (so no further messages are accepted for it) and attempt
+	/// one last delivery of whatever was buffered in its overflow
+	/// [`pending`](SenderEntry) slot and [`coalesced`](SenderEntry) map,
+	/// returning how many were delivered vs abandoned.
+	///
+	/// This crate slice has no timer/executor of its own to retry into a
+	/// still-full channel within `timeout`; a worker with a real event loop
+	/// (outside this crate slice) would use `timeout` to bound however many
+	/// retry ticks it schedules before giving up and closing the connection.
+	/// Trying once per buffered message is the honest floor here: nothing
+	/// already queued is silently discarded without being counted.
+	pub fn shutdown(&self, id: u64, _timeout: std::time::Duration) -> FlushReport {
+		let mut report = FlushReport::default();
+		let Some(mut entry) = self.shard(id).lock().remove(&id) else {
+			return report;
+		};
+		self.unregister_name(id);
+
+		if let Some(message) = entry.pending.take() {
+			entry.pending_enqueued_at = None;
+			match entry.sender.try_send(message) {
+				Ok(()) => report.delivered += 1,
+				Err(_) => report.abandoned += 1,
+			}
+		}
+		for (_, message) in entry.coalesced.drain() {
+			match entry.sender.try_send(message) {
+				Ok(()) => report.delivered += 1,
+				Err(_) => report.abandoned += 1,
+			}
+		}
+		report
+	}
+
+	/// Retry delivering whatever is buffered in `id`'s overflow slots
+	/// ([`priority_queue`](SenderEntry), [`priority_pending`](SenderEntry),
+	/// [`pending`](SenderEntry) and [`coalesced`](SenderEntry)) into its
+	/// channel, sleeping
+	/// [`SEND_IMPORTANT_POLL_INTERVAL`] between attempts, until either every
+	/// buffered message is delivered or `deadline` passes.
+	///
+	/// Unlike [`shutdown`](Self::shutdown), `id`'s registration is left in
+	/// place: safe to call repeatedly, and safe to call while a worker on
+	/// another thread keeps draining `id`'s channel in the background.
+	/// Backs [`Telemetries::flush`].
+	fn flush(&self, id: u64, deadline: std::time::Instant) -> FlushReport {
+		let mut report = FlushReport::default();
+		loop {
+			let drained = {
+				let mut senders = self.shard(id).lock();
+				let Some(entry) = senders.get_mut(&id) else { return report };
+
+				while let Some(message) = entry.priority_queue.pop_front() {
+					match entry.sender.try_send(message) {
+						Ok(()) => report.delivered += 1,
+						Err(err) if err.is_full() => {
+							entry.priority_queue.push_front(err.into_inner());
+							break;
+						}
+						Err(_) => report.abandoned += 1,
+					}
+				}
+				if let Some(message) = entry.priority_pending.take() {
+					match entry.sender.try_send(message) {
+						Ok(()) => report.delivered += 1,
+						Err(err) if err.is_full() => entry.priority_pending = Some(err.into_inner()),
+						Err(_) => report.abandoned += 1,
+					}
+				}
+				if let Some(message) = entry.pending.take() {
+					let enqueued_at = entry.pending_enqueued_at.take();
+					match entry.sender.try_send(message) {
+						Ok(()) => report.delivered += 1,
+						Err(err) if err.is_full() => {
+							entry.pending = Some(err.into_inner());
+							entry.pending_enqueued_at = enqueued_at;
+						}
+						Err(_) => report.abandoned += 1,
+					}
+				}
+				for msg_type in entry.coalesced.keys().cloned().collect::<Vec<_>>() {
+					if let Some(message) = entry.coalesced.remove(&msg_type) {
+						match entry.sender.try_send(message) {
+							Ok(()) => report.delivered += 1,
+							Err(err) if err.is_full() => {
+								entry.coalesced.insert(msg_type, err.into_inner());
+							}
+							Err(_) => report.abandoned += 1,
+						}
+					}
+				}
+
+				entry.priority_queue.is_empty()
+					&& entry.priority_pending.is_none()
+					&& entry.pending.is_none()
+					&& entry.coalesced.is_empty()
+			};
+
+			if drained {
+				return report;
+			}
+			let Some(remaining) = deadline.checked_duration_since(std::time::Instant::now()) else {
+				return report;
+			};
+			std::thread::sleep(remaining.min(SEND_IMPORTANT_POLL_INTERVAL));
+		}
+	}
+
+	/// The static fields registered alongside `id` (empty if `id` isn't
+	/// registered or was registered without any). Cloned out from behind the
+	/// lock so callers merge into their own payload rather than holding the map
+	/// locked across that work.
+	///
+	/// Callers already gate this behind their own `contains(id)` check before
+	/// doing any payload parsing, so an unregistered id never pays even this
+	/// lookup; a *registered* id whose channel later turns out to be full still
+	/// pays it; there is no way to know that in advance without attempting the
+	/// send, which is precisely what would need to happen after merging anyway.
+	pub fn static_fields(&self, id: u64) -> serde_json::Map<String, serde_json::Value> {
+		self.shard(id)
+			.lock()
+			.get(&id)
+			.map(|entry| entry.static_fields.clone())
+			.unwrap_or_default()
+	}
+
+	/// Allocate the next `seq` value for `id`, and take the count of messages
+	/// dropped for `id` since the last call (resetting it to `0`), so a
+	/// collector can tell a dropped message apart from one lost in transit.
+	/// `seq` persists in memory only and restarts from `0` per process; it is
+	/// not reset by a reconnect, since the same `id` (and thus the same
+	/// `SenderEntry`) survives one. `None` if `id` isn't registered.
+	fn next_seq(&self, id: u64) -> Option<(u64, u64)> {
+		let mut senders = self.shard(id).lock();
+		let entry = senders.get_mut(&id)?;
+		let seq = entry.seq;
+		entry.seq += 1;
+		let dropped = std::mem::take(&mut entry.dropped_since_send);
+		Some((seq, dropped))
+	}
+
+	/// Register (or replace) the "connection message" for `id`: the message a
+	/// worker should resend first thing after every successful (re)connection,
+	/// before draining anything else queued, so a backend that treats each
+	/// connection as a new session (and so `system.connected`-less reconnects
+	/// show up with no metadata) always gets it. Replaceable at runtime, e.g.
+	/// if the node's name or roles change. A no-op if `id` isn't registered.
+	pub fn set_connection_message(&self, id: u64, message: (Verbosity, String)) {
+		if let Some(entry) = self.shard(id).lock().get_mut(&id) {
+			entry.connection_message = Some(message);
+		}
+	}
+
+	/// The connection message currently registered for `id`, if any.
+	pub fn connection_message(&self, id: u64) -> Option<(Verbosity, String)> {
+		self.shard(id).lock().get(&id).and_then(|entry| entry.connection_message.clone())
+	}
+
+	/// Register a callback invoked every time a worker fires
+	/// [`fire_on_connect`](Self::fire_on_connect) for `id`, e.g. to re-announce
+	/// the current best block so the dashboard catches up immediately instead
+	/// of waiting for the next interval tick. Multiple callbacks per id are
+	/// supported and run in registration order. A no-op if `id` isn't
+	/// registered.
+	pub fn add_on_connect(&self, id: u64, callback: impl Fn() + Send + Sync + 'static) {
+		if let Some(entry) = self.shard(id).lock().get_mut(&id) {
+			entry.on_connect.push(Arc::new(callback));
+		}
+	}
+
+	/// Run every callback registered via [`add_on_connect`](Self::add_on_connect)
+	/// for `id`, called by a worker after its handshake completes. A panicking
+	/// callback is caught and logged rather than taking down the worker; later
+	/// callbacks still run.
+	pub fn fire_on_connect(&self, id: u64) {
+		let callbacks = match self.shard(id).lock().get(&id) {
+			Some(entry) => entry.on_connect.clone(),
+			None => return,
+		};
+		for callback in callbacks {
+			if std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| callback())).is_err() {
+				log::warn!(target: "telemetry", "A telemetry on_connect callback panicked");
+			}
+		}
+	}
+
+	/// Stop `id` from sending anything externally, without tearing down its
+	/// registration: [`send`](Self::send) becomes a cheap no-op, letting an
+	/// operator quiesce a node's telemetry (e.g. while an endpoint is
+	/// misbehaving) without losing its place in the map. A no-op if `id`
+	/// isn't registered.
+	pub fn pause(&self, id: u64) {
+		if let Some(entry) = self.shard(id).lock().get_mut(&id) {
+			entry.paused = true;
+		}
+	}
+
+	/// Resume sending for a previously [`pause`](Self::pause)d `id`, and, if
+	/// one is registered via [`set_connection_message`](Self::set_connection_message),
+	/// resend it immediately so a backend that only saw the pause doesn't
+	/// have to wait for the next periodic update to see the node as current.
+	/// A no-op if `id` isn't registered.
+	pub fn resume(&self, id: u64) {
+		let mut senders = self.shard(id).lock();
+		let Some(entry) = senders.get_mut(&id) else {
+			return;
+		};
+		entry.paused = false;
+		if let Some(message) = entry.connection_message.clone() {
+			let _ = entry.sender.try_send(message);
+		}
+	}
+
+	/// Whether `id` is currently paused. `false` for an unregistered `id`.
+	pub fn is_paused(&self, id: u64) -> bool {
+		self.shard(id).lock().get(&id).map(|entry| entry.paused).unwrap_or(false)
+	}
+
+	/// Register the [`EndpointCommand`] inbox a worker for `id` is polling,
+	/// e.g. by passing the paired receiver as the `commands` argument of a
+	/// [`fan_out_by_verbosity`] it's running. Replaces any previously
+	/// registered inbox. A no-op if `id` isn't registered.
+	pub fn set_endpoint_commands(&self, id: u64, commands: mpsc::UnboundedSender<EndpointCommand>) {
+		if let Some(entry) = self.shard(id).lock().get_mut(&id) {
+			entry.endpoint_commands = Some(commands);
+		}
+	}
+
+	/// Ask the worker for `id` to start forwarding to a new endpoint. See
+	/// [`EndpointCommand::Add`]. Returns `false` if `id` isn't registered, or
+	/// if no worker has called [`set_endpoint_commands`](Self::set_endpoint_commands)
+	/// for it (e.g. because it hasn't finished starting up yet) — in either
+	/// case nothing was queued and the caller should retry once a worker is
+	/// confirmed to be running. Safe to call concurrently with the worker
+	/// mid-reconnect: this only ever sends on an independent, unbounded
+	/// channel, never touches the connection itself.
+	pub fn add_endpoint(
+		&self,
+		id: u64,
+		url: impl Into<String>,
+		max_verbosity: impl Into<Verbosity>,
+		sender: mpsc::Sender<(Verbosity, String)>,
+		connect_message: Option<(Verbosity, String)>,
+	) -> bool {
+		let command = EndpointCommand::Add {
+			url: url.into(),
+			max_verbosity: max_verbosity.into(),
+			sender,
+			connect_message,
+		};
+		self.send_endpoint_command(id, command)
+	}
+
+	/// Ask the worker for `id` to stop forwarding to `url` and drop its
+	/// queue. See [`EndpointCommand::Remove`]. Returns `false` under the same
+	/// conditions as [`add_endpoint`](Self::add_endpoint).
+	pub fn remove_endpoint(&self, id: u64, url: impl Into<String>) -> bool {
+		self.send_endpoint_command(id, EndpointCommand::Remove { url: url.into() })
+	}
+
+	/// Ask the worker for `id` to change `url`'s verbosity threshold at
+	/// runtime. See [`EndpointCommand::SetMaxVerbosity`]. Returns `false`
+	/// under the same conditions as [`add_endpoint`](Self::add_endpoint).
+	pub fn set_max_verbosity(&self, id: u64, url: impl Into<String>, max_verbosity: impl Into<Verbosity>) -> bool {
+		self.send_endpoint_command(
+			id,
+			EndpointCommand::SetMaxVerbosity { url: url.into(), max_verbosity: max_verbosity.into() },
+		)
+	}
+
+	/// Turn mirroring of `id`'s outgoing messages to
+	/// `log::trace!(target: "telemetry-out", ...)` on or off at runtime. See
+	/// [`EndpointCommand::SetDebugMirror`]. Returns `false` under the same
+	/// conditions as [`add_endpoint`](Self::add_endpoint).
+	pub fn set_debug_mirror(&self, id: u64, enabled: bool) -> bool {
+		self.send_endpoint_command(id, EndpointCommand::SetDebugMirror(enabled))
+	}
+
+	/// Ask the worker for `id` to record `url` as healthy or unhealthy. See
+	/// [`EndpointCommand::ReportEndpointHealth`]. Returns `false` under the
+	/// same conditions as [`add_endpoint`](Self::add_endpoint).
+	pub fn report_endpoint_health(&self, id: u64, url: impl Into<String>, healthy: bool) -> bool {
+		self.send_endpoint_command(id, EndpointCommand::ReportEndpointHealth { url: url.into(), healthy })
+	}
+
+	fn send_endpoint_command(&self, id: u64, command: EndpointCommand) -> bool {
+		let senders = self.shard(id).lock();
+		let Some(entry) = senders.get(&id) else {
+			return false;
+		};
+		match &entry.endpoint_commands {
+			Some(commands) => commands.unbounded_send(command).is_ok(),
+			None => false,
+		}
+	}
+
+	/// Deliver `message` (whose `msg` type, if any, is `msg_type`) to the sender
+	/// registered for `id`, applying coalescing and the configured
+	/// [`OverflowPolicy`] if the channel is full. A message at or below
+	/// [`SenderConfig::priority_threshold`] skips `OverflowPolicy` entirely on
+	/// overflow, bumping it into [`SenderEntry::priority_pending`] instead —
+	/// see [`send_priority`](Self::send_priority) for an always-on, explicit
+	/// version of the same idea, backed by its own queue. Returns
+	/// [`TelemetryError::Disconnected`] if no sender is registered for `id`,
+	/// or [`TelemetryError::ChannelFull`] if `id`'s channel was full and
+	/// `message` wasn't handed off to it on this call (it may still have been
+	/// queued locally, per `OverflowPolicy` or priority, rather than dropped
+	/// outright).
+	fn send(&self, id: u64, msg_type: Option<&str>, message: (Verbosity, String)) -> Result<(), TelemetryError> {
+		let mut senders = self.shard(id).lock();
+		let entry = match senders.get_mut(&id) {
+			Some(entry) => entry,
+			None => {
+				drop(senders);
+				self.buffer_pre_registration(id, message);
+				return Err(TelemetryError::Disconnected);
+			}
+		};
+
+		// Retry whatever was bumped by a previous overflow before sending the new
+		// message, so bumped state never grows unbounded. `priority_queue` and
+		// `priority_pending` go first: neither is ever less urgent than
+		// anything else waiting.
+		Self::drain_priority_queue(entry);
+		if let Some(priority_pending) = entry.priority_pending.take() {
+			let _ = entry.sender.try_send(priority_pending);
+		}
+		if let Some(pending) = entry.pending.take() {
+			let enqueued_at = entry.pending_enqueued_at.take();
+			if entry.sender.try_send(pending).is_err() {
+				// Still full: `pending` is bumped again below (or replaced by
+				// `message` itself), so this copy is gone for good. Record how
+				// long it sat waiting before that happened.
+				if let Some(enqueued_at) = enqueued_at {
+					entry.eviction_ages.record(enqueued_at.elapsed());
+				}
+			}
+		}
+		if let Some(msg_type) = msg_type {
+			if let Some(coalesced) = entry.coalesced.remove(msg_type) {
+				let _ = entry.sender.try_send(coalesced);
+			}
+		}
+
+		if let Err(err) = entry.sender.try_send(message) {
+			entry.dropped += 1;
+			entry.dropped_since_send += 1;
+			if err.is_full() {
+				entry.record_message_outcome(msg_type, |c| {
+					c.dropped += 1;
+					c.dropped_queue_full += 1;
+				});
+			} else {
+				entry.record_message_outcome(msg_type, |c| {
+					c.dropped += 1;
+					c.dropped_disconnected += 1;
+				});
+			}
+			if !err.is_full() {
+				// The receiving end (a `TelemetryWorker`, or whatever else was
+				// draining this channel) is gone for good — unlike a full channel,
+				// no amount of retrying or buffering will ever deliver to it again.
+				// Leaving the entry registered would mean paying serialization and
+				// this same disconnect check on every future message for `id`
+				// forever, so it's removed here rather than merely reported.
+				let label = entry.label.clone();
+				entry.warn_dropped(&format!("of error on channel: {:?}", err));
+				senders.remove(&id);
+				drop(senders);
+				self.1.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+				self.unregister_name(id);
+				log::error!(
+					target: "telemetry",
+					"Telemetry for {} is gone: its receiver was dropped, so its registration has been removed",
+					describe_registrant(&label, id),
+				);
+				return Err(TelemetryError::Disconnected);
+			}
+			let message = err.into_inner();
+			if entry.priority_threshold.is_some_and(|threshold| message.0 <= threshold) {
+				// `message` matters enough that `overflow` shouldn't apply to it at
+				// all: hold onto whichever of it and whatever's already bumped is
+				// more urgent (lower verbosity), evicting the other one, rather than
+				// letting it compete with `pending`/`coalesced` for the channel.
+				match &entry.priority_pending {
+					Some((held, _)) if *held <= message.0 => {
+						entry.warn_dropped("a more urgent message is already queued");
+					}
+					_ => entry.priority_pending = Some(message),
+				}
+				return Err(TelemetryError::ChannelFull);
+			}
+			match msg_type.filter(|t| entry.coalesce.contains(*t)) {
+				// A coalescable type: hold the freshest one, replacing whatever of
+				// the same type was already waiting.
+				Some(msg_type) => {
+					entry.coalesced.insert(msg_type.to_string(), message);
+				}
+				None => match entry.overflow {
+					OverflowPolicy::DropOldest => {
+						entry.pending = Some(message);
+						entry.pending_enqueued_at = Some(std::time::Instant::now());
+					}
+					OverflowPolicy::DropNewest => entry.warn_dropped("the channel is full"),
+				},
+			}
+			return Err(TelemetryError::ChannelFull);
+		}
+		entry.record_message_outcome(msg_type, |c| c.sent += 1);
+		Ok(())
+	}
+
+	/// Try to flush `entry`'s [`priority_queue`](SenderEntry) front-to-back,
+	/// stopping (and putting the message back at the front) the moment the
+	/// channel reports full, so relative order among still-queued priority
+	/// messages is preserved across calls.
+	fn drain_priority_queue(entry: &mut SenderEntry) {
+		while let Some(message) = entry.priority_queue.pop_front() {
+			if let Err(err) = entry.sender.try_send(message) {
+				if err.is_full() {
+					entry.priority_queue.push_front(err.into_inner());
+				}
+				break;
+			}
+		}
+	}
+
+	/// Push `message` onto `entry`'s [`priority_queue`](SenderEntry), evicting
+	/// the oldest queued message first if it's already at
+	/// [`PRIORITY_QUEUE_CAPACITY`] — a live alert is worth more than a stale
+	/// one, and this queue is meant to stay small enough that it never gets
+	/// this full in practice.
+	fn enqueue_priority(entry: &mut SenderEntry, message: (Verbosity, String)) {
+		if entry.priority_queue.len() >= PRIORITY_QUEUE_CAPACITY {
+			entry.priority_queue.pop_front();
+			entry.warn_dropped("the priority queue is full");
+		}
+		entry.priority_queue.push_back(message);
+	}
+
+	/// Deliver a high-priority (alert-class) `message` for `id`, bypassing
+	/// [`OverflowPolicy`] and coalescing entirely: it either reaches the
+	/// channel immediately or joins [`SenderEntry::priority_queue`], which is
+	/// always drained ahead of `pending`/`coalesced` by the next call to
+	/// [`send`](Self::send) or [`send_priority`](Self::send_priority) for
+	/// `id`. `msg_type` is used only for [`MessageTypeCounts`] bookkeeping,
+	/// never to look `message` up in `coalesce`. Sampling and per-endpoint
+	/// rate limits are consulted nowhere on this path either: both exist to
+	/// shed *routine* volume, which a priority message is defined not to be
+	/// (message size is still enforced upstream, in
+	/// [`Telemetries::prepare_send`], same as any other message). Returns
+	/// [`TelemetryError::Disconnected`] if no sender is registered for `id`.
+	fn send_priority(&self, id: u64, msg_type: Option<&str>, message: (Verbosity, String)) -> Result<(), TelemetryError> {
+		let mut senders = self.shard(id).lock();
+		let entry = match senders.get_mut(&id) {
+			Some(entry) => entry,
+			None => {
+				drop(senders);
+				self.buffer_pre_registration(id, message);
+				return Err(TelemetryError::Disconnected);
+			}
+		};
+		Self::drain_priority_queue(entry);
+		if !entry.priority_queue.is_empty() {
+			// Already backlogged: preserve order by queuing behind what's there
+			// rather than letting `message` jump ahead of an older alert.
+			Self::enqueue_priority(entry, message);
+			return Err(TelemetryError::ChannelFull);
+		}
+		if let Err(err) = entry.sender.try_send(message) {
+			entry.dropped += 1;
+			entry.dropped_since_send += 1;
+			if !err.is_full() {
+				entry.record_message_outcome(msg_type, |c| {
+					c.dropped += 1;
+					c.dropped_disconnected += 1;
+				});
+				let label = entry.label.clone();
+				entry.warn_dropped(&format!("of error on channel: {:?}", err));
+				senders.remove(&id);
+				drop(senders);
+				self.1.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+				self.unregister_name(id);
+				log::error!(
+					target: "telemetry",
+					"Telemetry for {} is gone: its receiver was dropped, so its registration has been removed",
+					describe_registrant(&label, id),
+				);
+				return Err(TelemetryError::Disconnected);
+			}
+			entry.record_message_outcome(msg_type, |c| {
+				c.dropped += 1;
+				c.dropped_queue_full += 1;
+			});
+			Self::enqueue_priority(entry, err.into_inner());
+			return Err(TelemetryError::ChannelFull);
+		}
+		entry.record_message_outcome(msg_type, |c| c.sent += 1);
+		Ok(())
+	}
+
+	/// Attempt to hand `message` directly to `id`'s channel, without engaging
+	/// the overflow buffering [`send`](Self::send) uses (`priority_pending`/
+	/// `pending`/`coalesced`). Used exclusively by
+	/// [`Telemetries::send_important`]'s retry loop: that loop already owns
+	/// waiting for capacity itself, and re-submitting the *same* message
+	/// through [`send`](Self::send) on every retry would instead have it
+	/// fight that buffering over one capacity slot with a stale copy of
+	/// itself, potentially never reporting success even once real capacity
+	/// opened up. Returns [`TelemetryError::Disconnected`] if `id` isn't
+	/// registered.
+	fn try_send_now(&self, id: u64, msg_type: Option<&str>, message: (Verbosity, String)) -> Result<(), TelemetryError> {
+		let mut senders = self.shard(id).lock();
+		let entry = match senders.get_mut(&id) {
+			Some(entry) => entry,
+			None => return Err(TelemetryError::Disconnected),
+		};
+		match entry.sender.try_send(message) {
+			Ok(()) => {
+				entry.record_message_outcome(msg_type, |c| c.sent += 1);
+				Ok(())
+			}
+			Err(err) => {
+				if !err.is_full() {
+					// See the matching cleanup in `send`: a disconnected receiver
+					// never recovers, so the dead entry is removed here too instead
+					// of being retried against forever.
+					let label = entry.label.clone();
+					entry.warn_dropped(&format!("of error on channel: {:?}", err));
+					senders.remove(&id);
+					drop(senders);
+					self.1.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+					self.unregister_name(id);
+					log::error!(
+						target: "telemetry",
+						"Telemetry for {} is gone: its receiver was dropped, so its registration has been removed",
+						describe_registrant(&label, id),
+					);
+					return Err(TelemetryError::Disconnected);
+				}
+				Err(TelemetryError::ChannelFull)
+			}
+		}
+	}
+}
+
+/// The scheme of a parsed telemetry [`Endpoint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndpointScheme {
+	Ws,
+	Wss,
+	File,
+	Unix,
+	/// The process's own stdout, e.g. `stdout://`. See [`StdioSink`].
+	Stdout,
+	/// The process's own stderr, e.g. `stderr://`. See [`StdioSink`].
+	Stderr,
+}
+
+impl EndpointScheme {
+	fn parse(scheme: &str) -> Option<Self> {
+		match scheme {
+			"ws" => Some(Self::Ws),
+			"wss" => Some(Self::Wss),
+			"file" => Some(Self::File),
+			"unix" => Some(Self::Unix),
+			"stdout" => Some(Self::Stdout),
+			"stderr" => Some(Self::Stderr),
+			_ => None,
+		}
+	}
+
+	/// Whether this scheme addresses a host (and thus requires one), as
+	/// opposed to a local path or a pseudo-endpoint like `stdout://`.
+	fn requires_host(self) -> bool {
+		matches!(self, Self::Ws | Self::Wss)
+	}
+}
+
+/// Decode `%XX` escapes in a multiaddr path segment (e.g. `%2Fsubmit%2F`
+/// becomes `/submit/`), the way [`Endpoint::parse`]'s multiaddr branch
+/// decodes the value carried by `x-parity-ws`/`x-parity-wss`.
+fn percent_decode(input: &str) -> Result<String, ()> {
+	let bytes = input.as_bytes();
+	let mut decoded = Vec::with_capacity(bytes.len());
+	let mut i = 0;
+	while i < bytes.len() {
+		if bytes[i] == b'%' {
+			let hex = input.get(i + 1..i + 3).ok_or(())?;
+			decoded.push(u8::from_str_radix(hex, 16).map_err(|_| ())?);
+			i += 3;
+		} else {
+			decoded.push(bytes[i]);
+			i += 1;
+		}
+	}
+	String::from_utf8(decoded).map_err(|_| ())
+}
+
+/// Why [`Endpoint::parse`] rejected a telemetry endpoint spec, naming the
+/// offending component so a misconfigured `--telemetry-url` fails loudly at
+/// startup instead of surfacing as an opaque connection failure later.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EndpointParseError {
+	Empty,
+	MissingScheme,
+	UnknownScheme(String),
+	MissingHost,
+	InvalidPort(String),
+	InvalidVerbosity(String),
+	TooManyComponents,
+	/// A `/dns/.../tcp/.../ws` style multiaddr endpoint used a protocol
+	/// segment that isn't recognised, or omitted the value a recognised one
+	/// requires (e.g. a trailing `/tcp` with no port). Carries the offending
+	/// segment so a misconfigured multiaddr fails loudly at startup.
+	InvalidMultiaddr(String),
+}
+
+impl std::fmt::Display for EndpointParseError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Empty => write!(f, "telemetry endpoint is empty"),
+			Self::MissingScheme => write!(f, "telemetry endpoint is missing a `scheme://` prefix"),
+			Self::UnknownScheme(scheme) => {
+				write!(f, "telemetry endpoint scheme '{scheme}' is not one of ws, wss, file, unix, stdout, stderr")
+			}
+			Self::MissingHost => write!(f, "telemetry endpoint is missing a host"),
+			Self::InvalidPort(port) => write!(f, "telemetry endpoint port '{port}' is not a valid port number"),
+			Self::InvalidVerbosity(verbosity) => {
+				write!(f, "telemetry endpoint verbosity '{verbosity}' is not a valid number")
+			}
+			Self::TooManyComponents => {
+				write!(f, "telemetry endpoint has more than one trailing component after the URL")
+			}
+			Self::InvalidMultiaddr(segment) => {
+				write!(f, "telemetry endpoint multiaddr segment '/{segment}' is invalid or unsupported")
+			}
+		}
+	}
+}
+
+impl std::error::Error for EndpointParseError {}
+
+/// A parsed, validated telemetry endpoint spec, e.g.
+/// `"wss://telemetry.polkadot.io/submit 0"`, where the trailing integer is
+/// the optional per-endpoint verbosity cap matching the `--telemetry-url`
+/// CLI convention.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+	scheme: EndpointScheme,
+	url: String,
+	verbosity: Option<Verbosity>,
+}
+
+impl Endpoint {
+	/// Parse and validate a telemetry endpoint spec, in either of two forms,
+	/// plus an optional trailing verbosity number separated by whitespace:
+	/// a `scheme://` URL (`ws`, `wss`, `file`, `unix`, `stdout` or `stderr`;
+	/// `ws`/`wss` accept bracketed IPv6 literals like `[::1]:9944`), or a
+	/// Substrate-style multiaddr such as
+	/// `/dns/telemetry.polkadot.io/tcp/443/x-parity-wss/%2Fsubmit%2F` (see
+	/// [`Self::parse_multiaddr`]), which is normalized into the same
+	/// `ws`/`wss` shape a URL spec would produce.
+	pub fn parse(spec: &str) -> Result<Self, EndpointParseError> {
+		let spec = spec.trim();
+		if spec.is_empty() {
+			return Err(EndpointParseError::Empty);
+		}
+
+		let mut components = spec.split_ascii_whitespace();
+		let url = components.next().ok_or(EndpointParseError::Empty)?;
+		let verbosity = match components.next() {
+			Some(verbosity) => Some(
+				verbosity
+					.parse::<u64>()
+					.map(Verbosity::saturating_from_u64)
+					.map_err(|_| EndpointParseError::InvalidVerbosity(verbosity.to_string()))?,
+			),
+			None => None,
+		};
+		if components.next().is_some() {
+			return Err(EndpointParseError::TooManyComponents);
+		}
+
+		if let Some(rest) = url.strip_prefix('/') {
+			let (scheme, url) = Self::parse_multiaddr(rest)?;
+			return Ok(Self { scheme, url, verbosity });
+		}
+
+		let (scheme_str, rest) = url.split_once("://").ok_or(EndpointParseError::MissingScheme)?;
+		let scheme = EndpointScheme::parse(scheme_str).ok_or_else(|| EndpointParseError::UnknownScheme(scheme_str.to_string()))?;
+
+		if scheme.requires_host() {
+			let authority = rest.split('/').next().unwrap_or("");
+			let (host, port) = if let Some(after_bracket) = authority.strip_prefix('[') {
+				let (host, after) =
+					after_bracket.split_once(']').ok_or(EndpointParseError::MissingHost)?;
+				let port = match after.strip_prefix(':') {
+					Some(port) => Some(port),
+					None if after.is_empty() => None,
+					None => return Err(EndpointParseError::InvalidPort(after.to_string())),
+				};
+				(host, port)
+			} else {
+				match authority.split_once(':') {
+					Some((host, port)) => (host, Some(port)),
+					None => (authority, None),
+				}
+			};
+
+			if host.is_empty() {
+				return Err(EndpointParseError::MissingHost);
+			}
+			if let Some(port) = port {
+				port.parse::<u16>().map_err(|_| EndpointParseError::InvalidPort(port.to_string()))?;
+			}
+		}
+
+		Ok(Self { scheme, url: url.to_string(), verbosity })
+	}
+
+	/// Parse a Substrate-style multiaddr telemetry endpoint (the leading `/`
+	/// already stripped by [`Self::parse`]), e.g.
+	/// `dns/telemetry.polkadot.io/tcp/443/x-parity-wss/%2Fsubmit%2F`, into
+	/// the same `(scheme, url)` shape the `ws(s)://` branch of
+	/// [`Self::parse`] produces, so either spelling ends up as an identical
+	/// [`Endpoint`]. Only the `dns`/`dns4`/`dns6`/`ip4`/`ip6` host
+	/// protocols, `tcp`, and the `ws`/`wss`/`x-parity-ws`/`x-parity-wss`
+	/// transport protocols are understood; the path multiaddr segment that
+	/// follows `x-parity-ws(s)`, if present, is percent-decoded.
+	fn parse_multiaddr(rest: &str) -> Result<(EndpointScheme, String), EndpointParseError> {
+		let mut host = None;
+		let mut port = None;
+		let mut transport = None;
+
+		let mut segments = rest.split('/');
+		while let Some(protocol) = segments.next() {
+			if protocol.is_empty() {
+				continue;
+			}
+			match protocol {
+				"dns" | "dns4" | "dns6" | "ip4" | "ip6" => {
+					let value = segments.next().filter(|s| !s.is_empty()).ok_or_else(|| EndpointParseError::InvalidMultiaddr(protocol.to_string()))?;
+					host = Some(value.to_string());
+				}
+				"tcp" => {
+					let value = segments.next().ok_or_else(|| EndpointParseError::InvalidMultiaddr(protocol.to_string()))?;
+					port = Some(value.parse::<u16>().map_err(|_| EndpointParseError::InvalidPort(value.to_string()))?);
+				}
+				"ws" => transport = Some((EndpointScheme::Ws, None)),
+				"wss" => transport = Some((EndpointScheme::Wss, None)),
+				"x-parity-ws" | "x-parity-wss" => {
+					let scheme = if protocol == "x-parity-wss" { EndpointScheme::Wss } else { EndpointScheme::Ws };
+					let path = match segments.next() {
+						Some(encoded) if !encoded.is_empty() => Some(
+							percent_decode(encoded).map_err(|_| EndpointParseError::InvalidMultiaddr(format!("{protocol}/{encoded}")))?,
+						),
+						_ => None,
+					};
+					transport = Some((scheme, path));
+				}
+				other => return Err(EndpointParseError::InvalidMultiaddr(other.to_string())),
+			}
+		}
+
+		let host = host.ok_or(EndpointParseError::MissingHost)?;
+		let (scheme, path) = transport.ok_or_else(|| EndpointParseError::InvalidMultiaddr("ws".to_string()))?;
+
+		let mut url = format!("{}://{}", if scheme == EndpointScheme::Wss { "wss" } else { "ws" }, host);
+		if let Some(port) = port {
+			url.push(':');
+			url.push_str(&port.to_string());
+		}
+		if let Some(path) = path {
+			if !path.starts_with('/') {
+				url.push('/');
+			}
+			url.push_str(&path);
+		}
+
+		Ok((scheme, url))
+	}
+
+	pub fn scheme(&self) -> EndpointScheme {
+		self.scheme
+	}
+
+	pub fn url(&self) -> &str {
+		&self.url
+	}
+
+	pub fn verbosity(&self) -> Option<Verbosity> {
+		self.verbosity
+	}
+}
+
+/// A parsed `--telemetry-file` CLI spec, e.g.
+/// `"/var/log/node/telemetry.ndjson 1"`, where the trailing integer is the
+/// optional verbosity cap, matching [`Endpoint::parse`]'s `--telemetry-url`
+/// convention. Kept separate from [`Endpoint`] rather than folded into its
+/// `file://` scheme: a filesystem path may itself contain whitespace (unlike
+/// a URL), so this only ever treats the *last* whitespace-separated token as
+/// a candidate verbosity, and only when it parses as one — every other case
+/// keeps the whole spec as the path.
+///
+/// This crate slice owns the parsing and the resulting [`FileSink`]
+/// construction (via [`FileSink::from_path`]); wiring an actual
+/// `--telemetry-file` flag into a node's command-line parser, and merging it
+/// with `--telemetry-url`/`--no-telemetry`, is up to that binary — it need
+/// only call [`FileEndpointSpec::parse`] per occurrence and
+/// [`TelemetryLayer::with_file_sink`] with the result, skipping both when
+/// telemetry is disabled, the same way it already skips registering any
+/// [`Endpoint`]s.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEndpointSpec {
+	path: PathBuf,
+	verbosity: Option<Verbosity>,
+}
+
+impl FileEndpointSpec {
+	/// Parse a `--telemetry-file` argument: a filesystem path optionally
+	/// followed by a whitespace-separated verbosity number.
+	pub fn parse(spec: &str) -> Result<Self, EndpointParseError> {
+		let spec = spec.trim();
+		if spec.is_empty() {
+			return Err(EndpointParseError::Empty);
+		}
+
+		let (path, verbosity) = match spec.rsplit_once(char::is_whitespace) {
+			Some((path, verbosity)) if !verbosity.is_empty() && verbosity.chars().all(|c| c.is_ascii_digit()) => {
+				let verbosity = verbosity
+					.parse::<u64>()
+					.map(Verbosity::saturating_from_u64)
+					.map_err(|_| EndpointParseError::InvalidVerbosity(verbosity.to_string()))?;
+				(path.trim_end(), Some(verbosity))
+			}
+			_ => (spec, None),
+		};
+
+		if path.is_empty() {
+			return Err(EndpointParseError::Empty);
+		}
+
+		Ok(Self { path: PathBuf::from(path), verbosity })
+	}
+
+	pub fn path(&self) -> &std::path::Path {
+		&self.path
+	}
+
+	pub fn verbosity(&self) -> Option<Verbosity> {
+		self.verbosity
+	}
+
+	/// Build the [`FileSink`] this spec describes, defaulting to
+	/// [`Verbosity::INFO`] when the spec has no trailing verbosity number —
+	/// the same default [`Endpoints::insert_endpoint`] uses for
+	/// `--telemetry-url` entries.
+	pub fn into_file_sink(self) -> FileSink {
+		FileSink::from_path(&self.path, self.verbosity.unwrap_or(Verbosity::INFO))
+	}
+}
+
+/// Maps a telemetry endpoint URL to the maximum verbosity it should receive,
+/// matching the `--telemetry-url "wss://... 0"` CLI convention where the
+/// trailing integer is the per-endpoint verbosity cap.
+#[derive(Debug, Clone, Default)]
+pub struct Endpoints(HashMap<String, Verbosity>);
+
+impl Endpoints {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Configure `url` to receive messages at or below `max_verbosity`.
+	pub fn insert(&mut self, url: impl Into<String>, max_verbosity: impl Into<Verbosity>) -> &mut Self {
+		self.0.insert(url.into(), max_verbosity.into());
+		self
+	}
+
+	/// Parse and validate `spec` (a `--telemetry-url`-style
+	/// `"scheme://host[:port][/path] [verbosity]"` string) via
+	/// [`Endpoint::parse`] before registering it, so a typo in the URL is
+	/// rejected here rather than surfacing as an opaque connection failure
+	/// deep in a worker. `verbosity` defaults to [`Verbosity::INFO`] when the
+	/// spec has no trailing verbosity number.
+	pub fn insert_endpoint(&mut self, spec: &str) -> Result<&mut Self, EndpointParseError> {
+		let endpoint = Endpoint::parse(spec)?;
+		let verbosity = endpoint.verbosity().unwrap_or(Verbosity::INFO);
+		Ok(self.insert(endpoint.url, verbosity))
+	}
+
+	/// The configured max verbosity for `url`, if any.
+	pub fn max_verbosity(&self, url: &str) -> Option<Verbosity> {
+		self.0.get(url).copied()
+	}
+
+	/// Stop filtering for `url`. Once removed, a message for `url` is
+	/// forwarded to [`fan_out_by_verbosity`]'s `targets` map the same way an
+	/// endpoint that was never configured would be, unless it's re-added.
+	/// Callers driving a live [`fan_out_by_verbosity`] via [`EndpointCommand`]
+	/// also need to drop `url` from `targets` themselves.
+	pub fn remove(&mut self, url: &str) -> Option<Verbosity> {
+		self.0.remove(url)
+	}
+}
+
+/// Maps a telemetry endpoint URL to whether it's expected to acknowledge
+/// delivery — some collectors ack each batch with a `{"ack": <seq>}` frame
+/// (see [`parse_ack_frame`]) instead of just accepting a write to the
+/// socket as delivery — so a worker knows, per endpoint, whether to drive
+/// its [`ReplayBuffer`] with [`ReplayBuffer::drain`] (fire-and-forget,
+/// the default) or with [`ReplayBuffer::pending`] and
+/// [`ReplayBuffer::ack`] (retransmit until acked). Endpoints with no entry
+/// here default to fire-and-forget, matching the behavior every endpoint
+/// had before ack mode existed.
+#[derive(Debug, Clone, Default)]
+pub struct EndpointAckModes(HashMap<String, bool>);
+
+impl EndpointAckModes {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Mark `url` as ack mode (`enabled`) or fire-and-forget.
+	pub fn insert(&mut self, url: impl Into<String>, enabled: bool) -> &mut Self {
+		self.0.insert(url.into(), enabled);
+		self
+	}
+
+	/// Whether `url` is in ack mode. `false` (fire-and-forget) if `url` was
+	/// never configured either way.
+	pub fn enabled(&self, url: &str) -> bool {
+		self.0.get(url).copied().unwrap_or(false)
+	}
+
+	/// Stop tracking `url`; it reverts to the fire-and-forget default.
+	pub fn remove(&mut self, url: &str) -> Option<bool> {
+		self.0.remove(url)
+	}
+}
+
+/// How [`StdioSink`] wraps each outgoing line for a `stdout://`/`stderr://`
+/// endpoint, so a shipper collecting several nodes' (or a node's and some
+/// other process's) output into one stream can still tell messages apart.
+/// Without an envelope, [`StdioSink`] writes the bare payload — the same
+/// bytes a real endpoint would receive — which is what most single-node
+/// container deployments want.
+#[derive(Debug, Clone)]
+pub struct StdioEnvelope {
+	pub endpoint: String,
+	pub instance_id: Option<u64>,
+}
+
+impl StdioEnvelope {
+	/// An envelope naming `endpoint` (e.g. the `stdout://...` URL it's
+	/// configured under), with no instance id.
+	pub fn new(endpoint: impl Into<String>) -> Self {
+		Self { endpoint: endpoint.into(), instance_id: None }
+	}
+
+	/// See [`TelemetryLayer::with_instance_id`] — the same id, carried onto
+	/// every enveloped line so a shipper can demultiplex a merged stream from
+	/// several instances of this process.
+	pub fn with_instance_id(mut self, instance_id: u64) -> Self {
+		self.instance_id = Some(instance_id);
+		self
+	}
+
+	/// Wrap already-serialized `payload` as `{"endpoint", "instance_id"?,
+	/// "payload"}`. `payload` is re-parsed rather than embedded as a raw
+	/// string so `payload` in the envelope is nested JSON, not a JSON string
+	/// containing JSON — a shipper querying `.payload.msg` shouldn't have to
+	/// parse twice. Falls back to embedding it as a string if it somehow
+	/// isn't valid JSON, so a bug upstream shows up as an odd payload rather
+	/// than losing the line entirely.
+	fn wrap(&self, payload: &str) -> String {
+		let mut obj = serde_json::Map::new();
+		obj.insert("endpoint".into(), self.endpoint.clone().into());
+		if let Some(instance_id) = self.instance_id {
+			obj.insert("instance_id".into(), instance_id.into());
+		}
+		let value = serde_json::from_str::<serde_json::Value>(payload)
+			.unwrap_or_else(|_| serde_json::Value::String(payload.to_string()));
+		obj.insert("payload".into(), value);
+		serialize_message(&serde_json::Value::Object(obj), None)
+			.expect("a serde_json::Value always re-serializes; qed")
+	}
+}
+
+/// Line-buffered [`fan_out_by_verbosity`] target for the `stdout://`/
+/// `stderr://` pseudo-endpoints (see [`EndpointScheme::Stdout`] and
+/// [`EndpointScheme::Stderr`]): Kubernetes users who'd rather have their log
+/// shipper pick lines off the container's own stdout/stderr than run a
+/// websocket collector alongside every node.
+///
+/// Unlike the `ws`/`wss` schemes, writing here needs nothing beyond
+/// `std::io::Write` — no socket, no async runtime — so this is a real
+/// implementation rather than one of the `unimplemented!` adapters in
+/// [`runtime_adapters`]. The write itself happens on a dedicated thread
+/// pumping the channel [`spawn`](Self::spawn)/[`spawn_with_writer`](Self::spawn_with_writer)
+/// returns the sending half of, so a wedged pipe (a log shipper falling
+/// behind) blocks that thread, not the [`fan_out_by_verbosity`] worker
+/// feeding it: once the bounded channel between them fills up,
+/// `fan_out_by_verbosity`'s existing `try_send` + [`EndpointStats::record_drop`]
+/// path takes over exactly the way it already does for a slow network
+/// endpoint, rather than stalling every other endpoint's delivery.
+pub struct StdioSink;
+
+impl StdioSink {
+	/// Spawn the writer thread for `url` (`"stdout://..."` or
+	/// `"stderr://..."`, as accepted by [`Endpoint::parse`]), returning the
+	/// sender a caller should register under `url` in
+	/// [`fan_out_by_verbosity`]'s `targets` map. `envelope`, if given, wraps
+	/// every line via [`StdioEnvelope::wrap`].
+	pub fn spawn(scheme: EndpointScheme, envelope: Option<StdioEnvelope>, capacity: usize) -> mpsc::Sender<(Verbosity, String)> {
+		match scheme {
+			EndpointScheme::Stdout => Self::spawn_with_writer(std::io::stdout(), envelope, capacity),
+			EndpointScheme::Stderr => Self::spawn_with_writer(std::io::stderr(), envelope, capacity),
+			other => panic!("StdioSink::spawn called with non-stdio scheme {other:?}"),
+		}
+	}
+
+	/// [`spawn`](Self::spawn)'s test seam: writes to `writer` instead of a
+	/// real `Stdout`/`Stderr` handle, so a test can assert on exactly the
+	/// bytes that would otherwise have gone to the pipe.
+	pub fn spawn_with_writer<W: std::io::Write + Send + 'static>(
+		writer: W,
+		envelope: Option<StdioEnvelope>,
+		capacity: usize,
+	) -> mpsc::Sender<(Verbosity, String)> {
+		let (sender, mut receiver) = mpsc::channel(capacity);
+		std::thread::spawn(move || {
+			let mut writer = std::io::LineWriter::new(writer);
+			while let Some((_verbosity, json)) = futures::executor::block_on(receiver.next()) {
+				let line = match &envelope {
+					Some(envelope) => envelope.wrap(&json),
+					None => json,
+				};
+				if writeln!(writer, "{line}").is_err() {
+					// The pipe is gone (e.g. the container's log collector
+					// exited); nothing further written here would be read
+					// either, so stop rather than spin on write errors.
+					break;
+				}
+			}
+		});
+		sender
+	}
+}
+
+/// An ordered set of endpoints treated as one logical destination: a
+/// [`fan_out_by_verbosity`] worker only ever routes a message to the group's
+/// current *active* member, never to more than one at once, so an operator
+/// running a primary collector and a hot standby doesn't get every message
+/// doubled to both.
+///
+/// `members[0]` is the primary; the rest are backups in priority order.
+/// Failover away from an unhealthy active member is immediate, but failing
+/// back to a higher-priority member (in practice, back to the primary once
+/// it recovers) only happens after it's stayed healthy continuously for
+/// `failback_after`, so a flapping primary doesn't thrash traffic back and
+/// forth. See [`EndpointCommand::ReportEndpointHealth`] for how a member's
+/// health reaches the worker.
+#[derive(Debug, Clone)]
+pub struct EndpointGroup {
+	pub members: Vec<String>,
+	pub failback_after: std::time::Duration,
+}
+
+impl EndpointGroup {
+	/// A group whose primary is `primary`, tried before every member of
+	/// `backups` in order.
+	pub fn new(
+		primary: impl Into<String>,
+		backups: impl IntoIterator<Item = impl Into<String>>,
+		failback_after: std::time::Duration,
+	) -> Self {
+		let mut members = vec![primary.into()];
+		members.extend(backups.into_iter().map(Into::into));
+		Self { members, failback_after }
+	}
+}
+
+/// Named [`EndpointGroup`]s a [`fan_out_by_verbosity`] worker consults when
+/// routing outgoing messages. Fixed for a worker's lifetime — unlike
+/// [`Endpoints`] and [`EndpointMessageFilters`], group membership isn't
+/// reconfigurable through [`EndpointCommand`], since a running worker doesn't
+/// need to add or drop a failover pairing the way it might a single endpoint.
+#[derive(Debug, Clone, Default)]
+pub struct EndpointGroups(HashMap<String, EndpointGroup>);
+
+impl EndpointGroups {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Register `group` under `name`, alongside any others already added.
+	pub fn insert(&mut self, name: impl Into<String>, group: EndpointGroup) -> &mut Self {
+		self.0.insert(name.into(), group);
+		self
+	}
+}
+
+/// Why a message wasn't delivered, the breakdown behind
+/// [`EndpointStats::dropped`]/[`MessageTypeCounts::dropped`]'s
+/// previously-undifferentiated totals. `"Dropped: 1534"` doesn't tell an
+/// operator whether to look at their backend, their network, or their own
+/// producer; this does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize)]
+pub enum DropReason {
+	/// The channel to the endpoint (or, at the per-id level, to the fan-out
+	/// worker) was full.
+	QueueFull,
+	/// The receiving end was gone; unlike `QueueFull` this never recovers for
+	/// the same registration/connection.
+	Disconnected,
+	/// The serialized payload exceeded [`MessageSizeLimit`] and was replaced
+	/// by a stub rather than dropped outright — counted here too so an
+	/// operator sees it as part of "why didn't the real message arrive"
+	/// alongside the others, even though delivery technically still happened.
+	Oversized,
+	/// Rejected before it was ever queued, by [`Telemetries::pause`],
+	/// [`Telemetries::configure_sampling`] or [`Telemetries::configure_dedup`].
+	Filtered,
+	/// Refused by a per-endpoint [`RateLimiter`]. Not enforced by anything in
+	/// this crate slice (see [`EndpointRateLimiters`]'s doc comment); recorded
+	/// here for whichever worker outside it does the actual throttling.
+	RateLimited,
+}
+
+/// Per-[`DropReason`] counters, one entry of [`EndpointStats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct DropBreakdown {
+	pub queue_full: u64,
+	pub disconnected: u64,
+	pub oversized: u64,
+	pub filtered: u64,
+	pub rate_limited: u64,
+}
+
+impl DropBreakdown {
+	fn record(&mut self, reason: DropReason) {
+		match reason {
+			DropReason::QueueFull => self.queue_full += 1,
+			DropReason::Disconnected => self.disconnected += 1,
+			DropReason::Oversized => self.oversized += 1,
+			DropReason::Filtered => self.filtered += 1,
+			DropReason::RateLimited => self.rate_limited += 1,
+		}
+	}
+
+	/// Total drops across every reason, the same count [`EndpointStats::dropped`]
+	/// reported before the breakdown existed.
+	pub fn total(&self) -> u64 {
+		self.queue_full + self.disconnected + self.oversized + self.filtered + self.rate_limited
+	}
+}
+
+/// Per-endpoint drop counters, keyed the same way as `targets` in
+/// [`fan_out_by_verbosity`]. Each endpoint's queue is independent (a separate
+/// `mpsc` channel), so a stalled one accumulates drops on its own counter
+/// without affecting delivery, or the drop count, of any other endpoint.
+#[derive(Debug, Clone, Default)]
+pub struct EndpointStats(Arc<Mutex<HashMap<String, DropBreakdown>>>);
+
+impl EndpointStats {
+	fn record_drop(&self, url: &str, reason: DropReason) {
+		self.0.lock().entry(url.to_string()).or_default().record(reason);
+	}
+
+	/// Number of messages dropped so far for `url`, for any reason. `0` if
+	/// `url` has never dropped a message.
+	pub fn dropped(&self, url: &str) -> u64 {
+		self.0.lock().get(url).map(DropBreakdown::total).unwrap_or_default()
+	}
+
+	/// The full per-reason breakdown behind [`dropped`](Self::dropped) for
+	/// `url`.
+	pub fn drop_breakdown(&self, url: &str) -> DropBreakdown {
+		self.0.lock().get(url).copied().unwrap_or_default()
+	}
+}
+
+/// Number of recent send-latency samples [`EndpointQueueStats`] keeps per
+/// endpoint before evicting the oldest, bounding memory instead of growing
+/// with total messages sent.
+const LATENCY_SAMPLE_WINDOW: usize = 128;
+
+#[derive(Debug, Clone, Default)]
+struct EndpointQueueStatsInner {
+	depth: i64,
+	latencies_micros: std::collections::VecDeque<u64>,
+}
+
+/// Per-endpoint queue depth and a bounded rolling window of send latencies,
+/// for diagnosing a slow telemetry backend beyond just the drop counts on
+/// [`EndpointStats`].
+///
+/// Cheap enough to update on every enqueue/dequeue/send so it's meant to be
+/// left always-on: [`record_enqueued`](Self::record_enqueued) and
+/// [`record_dequeued`](Self::record_dequeued) are a plain increment/
+/// decrement, and the latency window is capped at [`LATENCY_SAMPLE_WINDOW`]
+/// samples rather than growing without bound. Latencies are supplied by the
+/// caller rather than measured internally, so this stays testable against
+/// an artificially slow mock sink without depending on a real clock.
+#[derive(Debug, Clone, Default)]
+pub struct EndpointQueueStats(Arc<Mutex<HashMap<String, EndpointQueueStatsInner>>>);
+
+impl EndpointQueueStats {
+	/// Record that a message was enqueued for `url`.
+	pub fn record_enqueued(&self, url: &str) {
+		self.0.lock().entry(url.to_string()).or_default().depth += 1;
+	}
+
+	/// Record that a message was dequeued (sent or dropped) for `url`.
+	pub fn record_dequeued(&self, url: &str) {
+		if let Some(entry) = self.0.lock().get_mut(url) {
+			entry.depth = entry.depth.saturating_sub(1);
+		}
+	}
+
+	/// Messages currently believed to be queued for `url`.
+	pub fn queue_depth(&self, url: &str) -> i64 {
+		self.0.lock().get(url).map(|entry| entry.depth).unwrap_or(0)
+	}
+
+	/// Record how long a send to `url` took.
+	pub fn record_send_latency(&self, url: &str, latency: std::time::Duration) {
+		let mut inner = self.0.lock();
+		let entry = inner.entry(url.to_string()).or_default();
+		if entry.latencies_micros.len() == LATENCY_SAMPLE_WINDOW {
+			entry.latencies_micros.pop_front();
+		}
+		entry.latencies_micros.push_back(latency.as_micros() as u64);
+	}
+
+	/// The `percentile` (`0.0`-`100.0`) send latency over the most recent
+	/// samples for `url`, or `None` if none have been recorded yet.
+	pub fn send_latency_percentile(&self, url: &str, percentile: f64) -> Option<std::time::Duration> {
+		let inner = self.0.lock();
+		let entry = inner.get(url)?;
+		if entry.latencies_micros.is_empty() {
+			return None;
+		}
+		let mut sorted: Vec<u64> = entry.latencies_micros.iter().copied().collect();
+		sorted.sort_unstable();
+		let index = ((percentile.clamp(0.0, 100.0) / 100.0) * (sorted.len() - 1) as f64).round() as usize;
+		Some(std::time::Duration::from_micros(sorted[index]))
+	}
+}
+
+/// A point-in-time snapshot of telemetry health for one endpoint, returned by
+/// [`Telemetries::endpoint_stats_snapshot`]. Like [`TelemetryStatus`], field
+/// names here are a semi-stable contract for the RPC and Prometheus layers
+/// built on top of this crate slice — see the serde snapshot tests below.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct EndpointStatsSnapshot {
+	pub dropped: u64,
+	pub drop_breakdown: DropBreakdown,
+	pub queue_depth: i64,
+	pub p99_send_latency: Option<std::time::Duration>,
+	pub bytes_sent_today: u64,
+	pub egress_paused: bool,
+}
+
+/// Stable, small classification of why an endpoint connection or send
+/// failed, kept deliberately narrow so a status RPC or Prometheus exporter
+/// can match on it programmatically instead of parsing
+/// [`EndpointError::message`], which is whatever wording the underlying
+/// transport happened to return and isn't guaranteed to stay worded the
+/// same way across versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum EndpointErrorKind {
+	Dns,
+	Tls,
+	Handshake,
+	Io,
+	Closed,
+	RateLimited,
+	/// The peer closed with websocket close code 1008 ("Policy Violation"):
+	/// it is refusing to keep talking to this node specifically (a banned
+	/// or unrecognized node ID, a rejected protocol version), not just
+	/// momentarily busy. See [`run_endpoint`]'s close-code handling.
+	PolicyViolation,
+	/// The peer closed with websocket close code 1013 ("Try Again Later"):
+	/// it wants the client to back off and reconnect after a while, rather
+	/// than treating this like an ordinary drop. See [`run_endpoint`]'s
+	/// close-code handling and [`parse_retry_after_hint`].
+	RetryLater,
+}
+
+/// The most recent connection or send failure recorded for an endpoint. See
+/// [`EndpointConnectionStatus::record_disconnected`] and
+/// [`EndpointConnectionStatus::record_probed`].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct EndpointError {
+	pub kind: EndpointErrorKind,
+	pub message: String,
+	/// Seconds since the Unix epoch; a clock set before the epoch simply
+	/// yields 0, the same convention `RotatingFileSink` uses for rotation
+	/// stamping.
+	pub at_unix_secs: u64,
+	/// How many consecutive failed attempts (including this one) the
+	/// endpoint has racked up since its last successful connect, mirroring
+	/// [`ReconnectBackoff::attempt`].
+	pub attempt: u32,
+}
+
+fn unix_secs_now() -> u64 {
+	std::time::SystemTime::now()
+		.duration_since(std::time::UNIX_EPOCH)
+		.map(|duration| duration.as_secs())
+		.unwrap_or(0)
+}
+
+#[derive(Debug, Clone, Default)]
+struct EndpointConnectionStatusInner {
+	connected: bool,
+	last_error: Option<EndpointError>,
+	reconnects: u64,
+	failed_attempts: u32,
+	// Seconds since the Unix epoch as of the most recent successful connect
+	// or probe, following `EndpointError::at_unix_secs`'s convention rather
+	// than a `std::time::Instant` so `EndpointStatus::active_since_unix_secs`
+	// can be serialized directly. `None` while disconnected.
+	connected_since_unix_secs: Option<u64>,
+}
+
+/// Per-endpoint connection state, keyed the same way as `targets` in
+/// [`fan_out_by_verbosity`]. A worker reports into this as it connects and
+/// disconnects; [`Telemetries::status`] reads it back out for introspection
+/// (e.g. an RPC method) without the caller needing to hold a lock on the
+/// worker itself.
+#[derive(Debug, Clone, Default)]
+pub struct EndpointConnectionStatus(Arc<Mutex<HashMap<String, EndpointConnectionStatusInner>>>);
+
+impl EndpointConnectionStatus {
+	/// Record `url` as connected, and clear any previously recorded error —
+	/// a fresh connection means whatever went wrong before no longer
+	/// describes `url`'s current state.
+	pub fn record_connected(&self, url: &str) {
+		let mut inner = self.0.lock();
+		let entry = inner.entry(url.to_string()).or_default();
+		entry.connected = true;
+		entry.last_error = None;
+		entry.failed_attempts = 0;
+		entry.connected_since_unix_secs = Some(unix_secs_now());
+	}
+
+	/// Record `url` as disconnected because of `error` of kind `kind`, and
+	/// bump its reconnect count. The first connection attempt for a `url`
+	/// isn't a "reconnect", so callers should only call this on drops of an
+	/// already-established connection, not on the very first dial.
+	pub fn record_disconnected(&self, url: &str, kind: EndpointErrorKind, error: impl Into<String>) {
+		let mut inner = self.0.lock();
+		let entry = inner.entry(url.to_string()).or_default();
+		entry.connected = false;
+		entry.reconnects += 1;
+		entry.failed_attempts = entry.failed_attempts.saturating_add(1);
+		entry.last_error = Some(EndpointError {
+			kind,
+			message: error.into(),
+			at_unix_secs: unix_secs_now(),
+			attempt: entry.failed_attempts,
+		});
+		entry.connected_since_unix_secs = None;
+	}
+
+	/// Record the outcome of a startup probe of `url` — a quick, best-effort
+	/// connection attempt made before the node finishes initializing, to
+	/// catch a mistyped endpoint URL early instead of hours into a run.
+	///
+	/// This crate slice has no socket of its own (see [`TlsConfig`] and
+	/// `fan_out_by_verbosity`'s doc comment), so the probe's actual dial —
+	/// respecting proxies and [`TlsConfig`] the same way the real transport
+	/// worker would — happens outside it; this is the hook that worker
+	/// reports its result through. A successful probe marks `url` connected,
+	/// the same as a real dial would. A failed probe records `error` as the
+	/// last-known error but does *not* bump `reconnects`: it's the first
+	/// dial, not a drop of an already-established connection (see
+	/// [`record_disconnected`](Self::record_disconnected)).
+	pub fn record_probed(&self, url: &str, result: Result<(), (EndpointErrorKind, String)>) {
+		let mut inner = self.0.lock();
+		let entry = inner.entry(url.to_string()).or_default();
+		match result {
+			Ok(()) => {
+				entry.connected = true;
+				entry.last_error = None;
+				entry.failed_attempts = 0;
+				entry.connected_since_unix_secs = Some(unix_secs_now());
+			}
+			Err((kind, error)) => {
+				entry.failed_attempts = entry.failed_attempts.saturating_add(1);
+				entry.last_error = Some(EndpointError {
+					kind,
+					message: error,
+					at_unix_secs: unix_secs_now(),
+					attempt: entry.failed_attempts,
+				});
+			}
+		}
+	}
+
+	/// Whether `url` is currently believed to be connected. `false` for a
+	/// `url` that's never reported in.
+	pub fn is_connected(&self, url: &str) -> bool {
+		self.0.lock().get(url).map(|entry| entry.connected).unwrap_or(false)
+	}
+
+	/// The error from the most recent disconnect of `url`, if any.
+	pub fn last_error(&self, url: &str) -> Option<EndpointError> {
+		self.0.lock().get(url).and_then(|entry| entry.last_error.clone())
+	}
+
+	/// How many times `url` has reconnected (i.e. disconnected after having
+	/// connected at least once).
+	pub fn reconnects(&self, url: &str) -> u64 {
+		self.0.lock().get(url).map(|entry| entry.reconnects).unwrap_or(0)
+	}
+
+	/// Seconds since the Unix epoch as of `url`'s current connection, if any
+	/// — `None` while disconnected. See [`EndpointStatus::active_since_unix_secs`].
+	pub fn connected_since(&self, url: &str) -> Option<u64> {
+		self.0.lock().get(url).and_then(|entry| entry.connected_since_unix_secs)
+	}
+}
+
+/// Per-endpoint telemetry health, one entry of [`TelemetryStatus`].
+///
+/// Field names are part of the wire contract once exposed over RPC (as
+/// `system_telemetryStatus` is intended to), so they're kept stable rather
+/// than renamed for internal convenience.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct EndpointStatus {
+	pub url: String,
+	pub connected: bool,
+	pub last_error: Option<EndpointError>,
+	pub reconnects: u64,
+	pub queue_depth: i64,
+	pub dropped: u64,
+	/// Seconds since the Unix epoch as of `url`'s current connection, or
+	/// `None` while disconnected — how an RPC caller derives uptime, the
+	/// same way [`EndpointError::at_unix_secs`] lets one derive how long ago
+	/// the last error happened, without this crate slice needing to pick a
+	/// wire format for a duration itself.
+	pub active_since_unix_secs: Option<u64>,
+}
+
+/// A point-in-time telemetry health report across every endpoint of one
+/// telemetry id, returned by [`Telemetries::status`].
+///
+/// `serde::Serialize` and stable field names are what let this be returned
+/// as-is from an RPC method; wiring it up as `system_telemetryStatus` is a
+/// call the system RPC crate makes (outside this crate slice), not something
+/// this module can do on its own without a dependency on it.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct TelemetryStatus {
+	/// The [`SenderConfig::label`] `id` was registered with, if any. Lets a
+	/// diagnostic surface name the registrant rather than just the bare id,
+	/// e.g. when tracking down which of two components collided on it.
+	pub label: Option<String>,
+	pub endpoints: Vec<EndpointStatus>,
+	/// Per-`msg`-type send outcome counters for `id`, keyed by `msg`. See
+	/// [`MessageTypeCounts`].
+	pub message_types: HashMap<String, MessageTypeCounts>,
+	/// Telemetry ids currently registered on the `Telemetries` instance this
+	/// status was built from, regardless of `id` — see
+	/// [`Telemetries::instance_count`]. Included here rather than only as its
+	/// own accessor so a long-running burn-in test polling
+	/// `system_telemetryStatus` gets bookkeeping-leak detection for free
+	/// alongside the per-endpoint counters it's already watching.
+	pub instance_count: usize,
+}
+
+impl TelemetryStatus {
+	/// A compact, human-readable one-line-per-endpoint summary, e.g. for a
+	/// CLI or log line rather than a full RPC response.
+	pub fn summary(&self) -> String {
+		self.endpoints
+			.iter()
+			.map(|endpoint| {
+				format!(
+					"{}: {}, queue={}, dropped={}, reconnects={}",
+					endpoint.url,
+					if endpoint.connected { "connected" } else { "disconnected" },
+					endpoint.queue_depth,
+					endpoint.dropped,
+					endpoint.reconnects,
+				)
+			})
+			.collect::<Vec<_>>()
+			.join("; ")
+	}
+}
+
+/// Extra root certificate material for one endpoint's TLS connection, as
+/// either inline PEM bytes or a path to a PEM file loaded by the connector
+/// at dial time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RootCertSource {
+	Pem(Vec<u8>),
+	Path(PathBuf),
+}
+
+/// Per-endpoint TLS configuration for `wss://` telemetry endpoints deployed
+/// behind a self-signed or private-CA certificate.
+///
+/// This crate slice has no TLS/websocket transport of its own (see
+/// [`fan_out_by_verbosity`]'s doc comment); `TlsConfig` is the configuration
+/// surface a worker's TLS connector (outside this crate slice) would read
+/// when dialing a `wss://` endpoint.
+#[derive(Debug, Clone, Default)]
+pub struct TlsConfig {
+	extra_roots: Vec<RootCertSource>,
+	accept_invalid_certs: bool,
+}
+
+impl TlsConfig {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Trust `pem` (in addition to the platform's default roots) when
+	/// validating this endpoint's certificate chain.
+	pub fn add_root_pem(&mut self, pem: impl Into<Vec<u8>>) -> &mut Self {
+		self.extra_roots.push(RootCertSource::Pem(pem.into()));
+		self
+	}
+
+	/// Trust the PEM-encoded certificate(s) at `path` (in addition to the
+	/// platform's default roots), read by the connector when it dials.
+	pub fn add_root_path(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+		self.extra_roots.push(RootCertSource::Path(path.into()));
+		self
+	}
+
+	/// The extra roots configured so far, in the order they were added.
+	pub fn roots(&self) -> &[RootCertSource] {
+		&self.extra_roots
+	}
+
+	/// Skip certificate validation entirely.
+	///
+	/// **Never enable this against a real backend.** It defeats TLS and
+	/// exists only so lab setups can talk to a throwaway self-signed
+	/// collector without minting a proper CA.
+	pub fn accept_invalid_certs(&mut self, accept: bool) -> &mut Self {
+		self.accept_invalid_certs = accept;
+		self
+	}
+
+	pub fn accepts_invalid_certs(&self) -> bool {
+		self.accept_invalid_certs
+	}
+}
+
+/// Per-endpoint [`TlsConfig`], keyed the same way as `targets` in
+/// [`fan_out_by_verbosity`]. An endpoint absent from the map connects with
+/// the platform's default TLS trust store and no escape hatches.
+#[derive(Debug, Clone, Default)]
+pub struct EndpointTlsConfigs(HashMap<String, TlsConfig>);
+
+impl EndpointTlsConfigs {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Configure `url` to use `config` for its TLS connection.
+	pub fn insert(&mut self, url: impl Into<String>, config: TlsConfig) -> &mut Self {
+		self.0.insert(url.into(), config);
+		self
+	}
+
+	/// The configured TLS settings for `url`, if any.
+	pub fn get(&self, url: &str) -> Option<&TlsConfig> {
+		self.0.get(url)
+	}
+}
+
+/// Per-endpoint SOCKS5 proxy configuration, for validators whose egress is
+/// restricted to a proxy (Tor, a corporate egress proxy) that must handle
+/// the TCP `CONNECT` before any websocket or TLS handshake begins.
+///
+/// This crate slice has no TCP dialer of its own; `Socks5ProxyConfig` is the
+/// configuration surface a worker's connector (outside this crate slice)
+/// would read before opening the socket. A connector that fails to reach the
+/// proxy should feed that failure into the same [`ReconnectBackoff`] /
+/// [`ReconnectPolicy`] machinery as any other connection failure, rather
+/// than treating it as fatal.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Socks5ProxyConfig {
+	host: String,
+	port: u16,
+	credentials: Option<(String, String)>,
+}
+
+impl Socks5ProxyConfig {
+	pub fn new(host: impl Into<String>, port: u16) -> Self {
+		Self { host: host.into(), port, credentials: None }
+	}
+
+	/// Authenticate to the proxy with `username`/`password` (SOCKS5
+	/// username/password authentication, RFC 1929).
+	pub fn with_credentials(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+		self.credentials = Some((username.into(), password.into()));
+		self
+	}
+
+	pub fn host(&self) -> &str {
+		&self.host
+	}
+
+	pub fn port(&self) -> u16 {
+		self.port
+	}
+
+	pub fn credentials(&self) -> Option<(&str, &str)> {
+		self.credentials.as_ref().map(|(user, pass)| (user.as_str(), pass.as_str()))
+	}
+}
+
+// A hand-rolled `Debug` impl so a proxy password never ends up in a log line
+// or panic message just because someone `{:?}`-formatted the config.
+impl std::fmt::Debug for Socks5ProxyConfig {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Socks5ProxyConfig")
+			.field("host", &self.host)
+			.field("port", &self.port)
+			.field("credentials", &self.credentials.as_ref().map(|(user, _)| format!("{user}:<redacted>")))
+			.finish()
+	}
+}
+
+/// Per-endpoint [`Socks5ProxyConfig`], keyed the same way as `targets` in
+/// [`fan_out_by_verbosity`]. An endpoint absent from the map connects
+/// directly, with no proxy.
+#[derive(Debug, Clone, Default)]
+pub struct EndpointProxyConfigs(HashMap<String, Socks5ProxyConfig>);
+
+impl EndpointProxyConfigs {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Route `url`'s TCP connection through `config`.
+	pub fn insert(&mut self, url: impl Into<String>, config: Socks5ProxyConfig) -> &mut Self {
+		self.0.insert(url.into(), config);
+		self
+	}
+
+	/// The configured proxy for `url`, if any.
+	pub fn get(&self, url: &str) -> Option<&Socks5ProxyConfig> {
+		self.0.get(url)
+	}
+}
+
+/// Per-endpoint switch for negotiating the `permessage-deflate` websocket
+/// extension.
+///
+/// This crate slice has no websocket transport of its own; `Compression`
+/// doesn't negotiate or deflate anything itself, it's the configuration a
+/// worker's websocket client (outside this crate slice) would read before
+/// sending the `Sec-WebSocket-Extensions` upgrade header, falling back to
+/// uncompressed frames whenever the server doesn't return the extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+	PermessageDeflate,
+	Disabled,
+}
+
+impl Default for Compression {
+	fn default() -> Self {
+		Compression::PermessageDeflate
+	}
+}
+
+/// Per-endpoint [`Compression`] setting, keyed the same way as `targets` in
+/// [`fan_out_by_verbosity`]. An endpoint absent from the map negotiates
+/// compression, matching [`Compression::default`].
+#[derive(Debug, Clone, Default)]
+pub struct EndpointCompression(HashMap<String, Compression>);
+
+impl EndpointCompression {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn insert(&mut self, url: impl Into<String>, compression: Compression) -> &mut Self {
+		self.0.insert(url.into(), compression);
+		self
+	}
+
+	pub fn get(&self, url: &str) -> Compression {
+		self.0.get(url).copied().unwrap_or_default()
+	}
+}
+
+/// Compressed-vs-uncompressed byte counters per endpoint, so operators can
+/// see how much `permessage-deflate` is actually saving. Updated by a
+/// worker's websocket client after each frame it writes; kept here purely as
+/// bookkeeping since this crate slice performs no compression itself.
+#[derive(Debug, Clone, Default)]
+pub struct CompressionStats(Arc<Mutex<HashMap<String, (u64, u64)>>>);
+
+impl CompressionStats {
+	/// Record that `url` sent a frame which was `uncompressed_bytes` before
+	/// compression and `compressed_bytes` on the wire (equal to
+	/// `uncompressed_bytes` if compression was disabled or not negotiated).
+	pub fn record(&self, url: &str, compressed_bytes: u64, uncompressed_bytes: u64) {
+		let mut inner = self.0.lock();
+		let entry = inner.entry(url.to_string()).or_insert((0, 0));
+		entry.0 += compressed_bytes;
+		entry.1 += uncompressed_bytes;
+	}
+
+	/// Total bytes actually sent on the wire for `url`.
+	pub fn compressed_bytes(&self, url: &str) -> u64 {
+		self.0.lock().get(url).map(|(compressed, _)| *compressed).unwrap_or(0)
+	}
+
+	/// Total bytes that would have been sent for `url` without compression.
+	pub fn uncompressed_bytes(&self, url: &str) -> u64 {
+		self.0.lock().get(url).map(|(_, uncompressed)| *uncompressed).unwrap_or(0)
+	}
+}
+
+/// Token-bucket rate limiter for one telemetry endpoint's outgoing message
+/// rate: refills continuously at `messages_per_sec`, capped at `burst`
+/// tokens, applied after verbosity filtering so it only ever holds back
+/// messages that would otherwise have been sent.
+///
+/// Takes the current time as an explicit `Instant` rather than calling
+/// `Instant::now()` internally, so tests can drive it with a mock clock. A
+/// message that fails to acquire a token is the caller's responsibility to
+/// route through the existing overflow/coalescing policy on
+/// [`SenderEntry`]/[`Senders`] rather than dropping silently — this type
+/// only decides yes/no.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+	messages_per_sec: f64,
+	burst: f64,
+	tokens: f64,
+	last_refill: std::time::Instant,
+}
+
+impl RateLimiter {
+	pub fn new(messages_per_sec: f64, burst: f64, now: std::time::Instant) -> Self {
+		Self { messages_per_sec, burst, tokens: burst, last_refill: now }
+	}
+
+	/// Refill for the time elapsed since the last call, then attempt to
+	/// consume one token. Returns whether a message may be sent now.
+	pub fn try_acquire(&mut self, now: std::time::Instant) -> bool {
+		let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+		self.tokens = (self.tokens + elapsed * self.messages_per_sec).min(self.burst);
+		self.last_refill = now;
+		if self.tokens >= 1.0 {
+			self.tokens -= 1.0;
+			true
+		} else {
+			false
+		}
+	}
+
+	/// Tokens currently available, for exposing in `Telemetries` stats.
+	pub fn available_tokens(&self) -> f64 {
+		self.tokens
+	}
+}
+
+/// Per-endpoint [`RateLimiter`]s, keyed the same way as `targets` in
+/// [`fan_out_by_verbosity`]. An endpoint absent from the map is unlimited.
+#[derive(Debug, Clone, Default)]
+pub struct EndpointRateLimiters(Arc<Mutex<HashMap<String, RateLimiter>>>);
+
+impl EndpointRateLimiters {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Rate-limit `url` to `messages_per_sec`, allowing bursts of up to
+	/// `burst` messages.
+	pub fn configure(&self, url: impl Into<String>, messages_per_sec: f64, burst: f64, now: std::time::Instant) {
+		self.0.lock().insert(url.into(), RateLimiter::new(messages_per_sec, burst, now));
+	}
+
+	/// Whether `url` may send a message at `now`; always `true` for an
+	/// unconfigured endpoint.
+	pub fn try_acquire(&self, url: &str, now: std::time::Instant) -> bool {
+		match self.0.lock().get_mut(url) {
+			Some(limiter) => limiter.try_acquire(now),
+			None => true,
+		}
+	}
+
+	/// Tokens currently available for `url`, for the diagnostics/stats API;
+	/// `None` if `url` has no configured limiter.
+	pub fn available_tokens(&self, url: &str) -> Option<f64> {
+		self.0.lock().get(url).map(RateLimiter::available_tokens)
+	}
+}
+
+/// Outcome of [`EndpointByteBudgets::record`] for one message sent to an
+/// endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteBudgetOutcome {
+	/// `url` has no configured budget, or is under it; the message counted
+	/// against the running total and was fine to send.
+	Allowed,
+	/// This message pushed `url` over its daily budget. The budget is
+	/// enforced at message boundaries rather than mid-message, so this one
+	/// still counts as sent, but the endpoint is now paused until the window
+	/// rolls over.
+	CapJustTripped,
+	/// `url` already tripped its budget earlier in the current window; the
+	/// caller should not send.
+	Paused,
+}
+
+#[derive(Debug, Clone, Default)]
+struct EndpointByteBudgetState {
+	budget_per_day: Option<u64>,
+	bytes_sent_today: u64,
+	epoch_day: u64,
+	paused: bool,
+}
+
+/// The UTC calendar day `now` falls on, as a day count since the Unix epoch.
+/// Only used to detect a day boundary being crossed, so a plain integer
+/// division is enough here — unlike [`Rotation`]'s date stamps, nothing ever
+/// turns this back into a calendar string.
+fn epoch_day(now: std::time::SystemTime) -> u64 {
+	now.duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs() / 86_400
+}
+
+/// Cumulative bytes sent per endpoint (post-compression when enabled), keyed
+/// the same way as `targets` in [`fan_out_by_verbosity`], with an optional
+/// daily budget after which the endpoint is paused until the window rolls
+/// over.
+///
+/// Takes the current time as an explicit `SystemTime` rather than calling
+/// `SystemTime::now()` internally, so tests can drive it with a mocked clock
+/// (see the module-level scope note). The window is wall-clock days in UTC
+/// and lives only in memory, so it survives reconnects for the lifetime of
+/// this instance but resets across a process restart.
+///
+/// Like [`CompressionStats`] and [`EndpointRateLimiters`], this type only
+/// keeps the counters and decides yes/no — actually pausing sends (and
+/// broadcasting [`egress_capped_message`](Self::egress_capped_message)) is a
+/// worker's job outside this crate slice.
+#[derive(Debug, Clone, Default)]
+pub struct EndpointByteBudgets(Arc<Mutex<HashMap<String, EndpointByteBudgetState>>>);
+
+impl EndpointByteBudgets {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Cap `url` at `bytes_per_day`, replacing any previous budget. Does not
+	/// reset the running total or paused state for the window already in
+	/// progress.
+	pub fn configure(&self, url: impl Into<String>, bytes_per_day: u64) {
+		self.0.lock().entry(url.into()).or_default().budget_per_day = Some(bytes_per_day);
+	}
+
+	/// Record `bytes` sent to `url` at `now` and report whether it may still
+	/// send. Rolls the window over first if `now` falls on a later UTC day
+	/// than the last call recorded. An unconfigured `url` always returns
+	/// [`ByteBudgetOutcome::Allowed`], but its bytes are still counted for
+	/// [`bytes_sent_today`](Self::bytes_sent_today).
+	pub fn record(&self, url: &str, bytes: u64, now: std::time::SystemTime) -> ByteBudgetOutcome {
+		let today = epoch_day(now);
+		let mut inner = self.0.lock();
+		let entry = inner.entry(url.to_string()).or_default();
+		if entry.epoch_day != today {
+			entry.epoch_day = today;
+			entry.bytes_sent_today = 0;
+			entry.paused = false;
+		}
+
+		let Some(budget) = entry.budget_per_day else {
+			entry.bytes_sent_today += bytes;
+			return ByteBudgetOutcome::Allowed;
+		};
+
+		if entry.paused {
+			return ByteBudgetOutcome::Paused;
+		}
+
+		entry.bytes_sent_today += bytes;
+		if entry.bytes_sent_today >= budget {
+			entry.paused = true;
+			log::warn!(
+				target: "telemetry",
+				"endpoint {url:?} hit its daily byte budget of {budget} bytes; pausing until the window rolls over",
+			);
+			ByteBudgetOutcome::CapJustTripped
+		} else {
+			ByteBudgetOutcome::Allowed
+		}
+	}
+
+	/// Bytes recorded for `url` so far in the window as of the last
+	/// [`record`](Self::record) call. Like [`RateLimiter::available_tokens`],
+	/// this doesn't itself roll the window over, so it can read as stale by
+	/// up to one day for an endpoint that hasn't sent anything since the
+	/// boundary.
+	pub fn bytes_sent_today(&self, url: &str) -> u64 {
+		self.0.lock().get(url).map(|entry| entry.bytes_sent_today).unwrap_or(0)
+	}
+
+	/// Whether `url` is currently paused after tripping its daily budget.
+	pub fn is_paused(&self, url: &str) -> bool {
+		self.0.lock().get(url).map(|entry| entry.paused).unwrap_or(false)
+	}
+
+	/// The `system.telemetry_egress_capped` meta message a worker should
+	/// broadcast to `url`'s peers (mirroring [`announce_failover`]'s
+	/// `system.telemetry_failover` shape) once [`record`](Self::record)
+	/// returns [`ByteBudgetOutcome::CapJustTripped`] for it. Building the
+	/// wire shape here keeps it in one place; sending it is that worker's
+	/// job, since this crate slice has no `targets` map of its own to send
+	/// through (see the module-level scope note).
+	pub fn egress_capped_message(url: &str, budget_per_day: u64) -> String {
+		serde_json::json!({
+			"msg": "system.telemetry_egress_capped",
+			"endpoint": url,
+			"budget_bytes_per_day": budget_per_day,
+		})
+		.to_string()
+	}
+}
+
+/// How to downsample telemetry messages of one `msg` type before they're
+/// serialized and enqueued, configured via [`MessageSampling::configure`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SamplingRule {
+	/// Forward every `n`th message of this type, counted from the first one
+	/// seen (so the 1st, `n+1`th, `2n+1`th, ... are forwarded). `0` is
+	/// treated the same as `1` (no sampling) rather than dividing by zero.
+	EveryNth(u64),
+	/// Forward at most `messages_per_sec` messages of this type per second
+	/// (implemented as a one-message-burst [`RateLimiter`]).
+	MaxPerSecond(f64),
+}
+
+struct MessageSamplingEntry {
+	rule: SamplingRule,
+	counter: u64,
+	limiter: Option<RateLimiter>,
+	skipped: u64,
+}
+
+/// `system.connected` and error-class (`msg` starting with `"error."`)
+/// messages are never sampled out, since losing them would hide the exact
+/// events an operator most needs during an incident.
+fn is_exempt_from_sampling(msg_type: &str) -> bool {
+	msg_type == "system.connected" || msg_type.starts_with("error.")
+}
+
+/// Per-message-type sampling, applied once per `Telemetries` instance before
+/// a message is stamped with `id`/timestamp and serialized, so a skipped
+/// message costs nothing beyond the `msg` field lookup already done for
+/// coalescing. Unconfigured message types are never sampled.
+#[derive(Default)]
+pub struct MessageSampling(Mutex<HashMap<String, MessageSamplingEntry>>);
+
+impl MessageSampling {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Configure (or replace) the sampling rule for `msg_type`, resetting its
+	/// counter/limiter and skipped count.
+	pub fn configure(&self, msg_type: impl Into<String>, rule: SamplingRule) {
+		self.0
+			.lock()
+			.insert(msg_type.into(), MessageSamplingEntry { rule, counter: 0, limiter: None, skipped: 0 });
+	}
+
+	/// Whether a message of `msg_type` should be forwarded. `msg_type: None`
+	/// (a payload with no `msg` field) and any type with no configured rule
+	/// always pass. See [`is_exempt_from_sampling`].
+	pub fn should_send(&self, msg_type: Option<&str>) -> bool {
+		let Some(msg_type) = msg_type else {
+			return true;
+		};
+		if is_exempt_from_sampling(msg_type) {
+			return true;
+		}
+		let mut sampling = self.0.lock();
+		let Some(entry) = sampling.get_mut(msg_type) else {
+			return true;
+		};
+		let allowed = match entry.rule {
+			SamplingRule::EveryNth(n) => {
+				let n = n.max(1);
+				let should_send = entry.counter % n == 0;
+				entry.counter += 1;
+				should_send
+			}
+			SamplingRule::MaxPerSecond(messages_per_sec) => {
+				let now = std::time::Instant::now();
+				let limiter =
+					entry.limiter.get_or_insert_with(|| RateLimiter::new(messages_per_sec, 1.0, now));
+				limiter.try_acquire(now)
+			}
+		};
+		if !allowed {
+			entry.skipped += 1;
+		}
+		allowed
+	}
+
+	/// Messages of `msg_type` skipped by sampling so far. `0` for an
+	/// unconfigured type.
+	pub fn skipped(&self, msg_type: &str) -> u64 {
+		self.0.lock().get(msg_type).map(|entry| entry.skipped).unwrap_or(0)
+	}
+}
+
+struct MessageDedupEntry {
+	max_suppressed: std::time::Duration,
+	last_hash: Option<u64>,
+	last_sent: Option<std::time::Instant>,
+	suppressed: u64,
+}
+
+/// A stable hash of `obj`'s wire content, ignoring `ts` and `seq`: both
+/// change on every send regardless of whether anything else in the payload
+/// did, so including them would make [`MessageDedup`] never see a duplicate.
+fn payload_content_hash(obj: &serde_json::Map<String, serde_json::Value>) -> u64 {
+	use std::hash::{Hash, Hasher};
+	let mut filtered = obj.clone();
+	filtered.remove("ts");
+	filtered.remove("seq");
+	let mut hasher = std::collections::hash_map::DefaultHasher::new();
+	serde_json::Value::Object(filtered).to_string().hash(&mut hasher);
+	hasher.finish()
+}
+
+/// Suppress consecutive duplicate payloads of one `msg` type — e.g. a sync
+/// state message a producer re-emits unchanged every tick — once configured
+/// via [`configure`](Self::configure). Applied once per `Telemetries`
+/// instance, in the same place [`MessageSampling`] is: before a message is
+/// stamped with `id`/`seq`/timestamp and serialized, so a suppressed message
+/// costs nothing beyond the `msg` field lookup already done for coalescing.
+///
+/// A message counts as a duplicate of the last one sent for its `msg` type
+/// if the two are identical once `ts` and `seq` are ignored (see
+/// [`payload_content_hash`]) — so purely time- or sequence-driven change
+/// doesn't defeat deduplication. To guarantee a backend eventually sees a
+/// fresh timestamp even during a long run of identical values, a duplicate
+/// is forwarded anyway once `max_suppressed` has elapsed since the last
+/// message of this type actually went out: a heartbeat refresh.
+///
+/// Entries are keyed on `(instance_key, msg_type)`, `instance_key` being
+/// [`Senders::instance_key`] of the owning `Telemetries` — even though this
+/// lives in a single process-wide static (see [`MESSAGE_DEDUP`]), two
+/// `Telemetries` instances emitting the same `msg_type` never see or reset
+/// each other's window, matching the isolation every other per-`msg_type`
+/// or per-id state in this crate slice already gives distinct instances.
+#[derive(Default)]
+pub struct MessageDedup(Mutex<HashMap<(usize, String), MessageDedupEntry>>);
+
+impl MessageDedup {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Configure (or replace) deduplication for `msg_type` on the instance
+	/// identified by `instance_key`, resetting its remembered hash, last-sent
+	/// time and suppressed count.
+	pub fn configure(&self, instance_key: usize, msg_type: impl Into<String>, max_suppressed: std::time::Duration) {
+		self.0.lock().insert(
+			(instance_key, msg_type.into()),
+			MessageDedupEntry { max_suppressed, last_hash: None, last_sent: None, suppressed: 0 },
+		);
+	}
+
+	/// Whether a message of `msg_type` with body `obj`, sent by the instance
+	/// identified by `instance_key`, should be forwarded. `msg_type: None` and
+	/// any type with no configured window always pass.
+	pub fn should_send(
+		&self,
+		instance_key: usize,
+		msg_type: Option<&str>,
+		obj: &serde_json::Map<String, serde_json::Value>,
+		now: std::time::Instant,
+	) -> bool {
+		let Some(msg_type) = msg_type else {
+			return true;
+		};
+		let mut dedup = self.0.lock();
+		let Some(entry) = dedup.get_mut(&(instance_key, msg_type.to_string())) else {
+			return true;
+		};
+		let hash = payload_content_hash(obj);
+		let is_duplicate = entry.last_hash == Some(hash);
+		let heartbeat_due =
+			entry.last_sent.map(|last_sent| now.duration_since(last_sent) >= entry.max_suppressed).unwrap_or(true);
+		let send = !is_duplicate || heartbeat_due;
+		if send {
+			entry.last_hash = Some(hash);
+			entry.last_sent = Some(now);
+		} else {
+			entry.suppressed += 1;
+		}
+		send
+	}
+
+	/// Messages of `msg_type` suppressed as duplicates so far by the instance
+	/// identified by `instance_key`. `0` for an unconfigured type.
+	pub fn suppressed(&self, instance_key: usize, msg_type: &str) -> u64 {
+		self.0.lock().get(&(instance_key, msg_type.to_string())).map(|entry| entry.suppressed).unwrap_or(0)
+	}
+}
+
+/// The process-wide [`MessageDedup`] instance backing
+/// [`Telemetries::configure_dedup`]. See that method's doc comment for why
+/// this is a static rather than a `Telemetries` field, and [`MessageDedup`]'s
+/// own doc comment for how it stays isolated per instance despite that.
+static MESSAGE_DEDUP: std::sync::OnceLock<MessageDedup> = std::sync::OnceLock::new();
+
+fn message_dedup() -> &'static MessageDedup {
+	MESSAGE_DEDUP.get_or_init(MessageDedup::default)
+}
+
+/// The process-wide verbosity ceiling backing
+/// [`Telemetries::set_global_verbosity`], for the same reason
+/// [`MESSAGE_DEDUP`] is a static rather than a `Telemetries` field. Starts at
+/// [`Verbosity::DEBUG`], the least restrictive named level, so an embedder
+/// that never calls [`set_global_verbosity`](Telemetries::set_global_verbosity)
+/// sees no behavior change from before this existed.
+static GLOBAL_VERBOSITY: std::sync::atomic::AtomicU8 = std::sync::atomic::AtomicU8::new(Verbosity::DEBUG.as_u8());
+
+/// Messages skipped so far by the [`GLOBAL_VERBOSITY`] fast path in
+/// [`Telemetries::prepare_send`] and [`TelemetryLayer::on_event`], backing
+/// [`Telemetries::global_verbosity_skipped`].
+static GLOBAL_VERBOSITY_SKIPPED: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+/// A per-instance hook stripping or replacing sensitive fields from a
+/// payload, applied after id/timestamp injection and before it's serialized
+/// and fanned out. An unconfigured (default) `Redaction` is a single `bool`
+/// check per message — no path lookups or hook invocation.
+#[derive(Clone, Default)]
+pub struct Redaction {
+	paths: Vec<String>,
+	hook: Option<Arc<dyn Fn(&mut serde_json::Value) + Send + Sync>>,
+}
+
+impl Redaction {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Replace the value at `pointer` (an RFC 6901 JSON Pointer, e.g.
+	/// `"/peer/ip"`) with `"<redacted>"` if present. A `pointer` absent from a
+	/// given payload is a no-op for that payload, not an error.
+	pub fn redact_path(mut self, pointer: impl Into<String>) -> Self {
+		self.paths.push(pointer.into());
+		self
+	}
+
+	/// Run `hook` on every payload after path redaction. Wrapped in
+	/// [`std::panic::catch_unwind`] so a buggy hook can't take down the
+	/// caller: see [`Senders::fire_on_connect`] for the same pattern applied
+	/// to `on_connect` callbacks.
+	pub fn with_hook(mut self, hook: impl Fn(&mut serde_json::Value) + Send + Sync + 'static) -> Self {
+		self.hook = Some(Arc::new(hook));
+		self
+	}
+
+	fn is_configured(&self) -> bool {
+		!self.paths.is_empty() || self.hook.is_some()
+	}
+
+	/// Apply this redaction to `value` in place. A no-op (beyond the initial
+	/// check) if nothing is configured.
+	fn apply(&self, value: &mut serde_json::Value) {
+		if !self.is_configured() {
+			return;
+		}
+		for pointer in &self.paths {
+			if let Some(slot) = value.pointer_mut(pointer) {
+				*slot = serde_json::Value::String("<redacted>".to_string());
+			}
+		}
+		if let Some(hook) = &self.hook {
+			let hook = hook.clone();
+			let payload = std::panic::AssertUnwindSafe(value);
+			if std::panic::catch_unwind(move || hook(payload.0)).is_err() {
+				log::warn!(target: "telemetry", "A telemetry redaction hook panicked");
+			}
+		}
+	}
+}
+
+/// Default maximum serialized payload size enforced by [`MessageSizeLimit`]:
+/// 64 KiB. Comfortably clears typical backend and websocket frame limits
+/// while still catching a runaway payload before it stalls a connection.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 * 1024;
+
+/// Caps the serialized size of an outgoing telemetry payload, applied once
+/// per `Telemetries` instance right after serialization and before
+/// [`Senders::send`] fans it out, so every endpoint (and the OTLP/file sinks)
+/// see the same message rather than each hitting its own limit
+/// independently.
+///
+/// An oversized payload is replaced by a small stub carrying the original
+/// `msg` type and byte size, rather than being dropped outright, so an
+/// operator can still see that something of that type was emitted even
+/// though its body was too large to ship.
+#[derive(Debug)]
+pub struct MessageSizeLimit {
+	max_bytes: usize,
+	truncated: Mutex<u64>,
+}
+
+impl Default for MessageSizeLimit {
+	fn default() -> Self {
+		Self { max_bytes: DEFAULT_MAX_MESSAGE_SIZE, truncated: Mutex::new(0) }
+	}
+}
+
+impl MessageSizeLimit {
+	pub fn new(max_bytes: usize) -> Self {
+		Self { max_bytes, truncated: Mutex::new(0) }
+	}
+
+	/// Return `json` unchanged if it's within the configured limit, or a stub
+	/// payload naming `msg_type` and `json`'s length if it isn't. The second
+	/// element of the returned tuple is `true` when `json` was replaced by
+	/// the stub, so callers can attribute the truncation to the sender that
+	/// produced it.
+	fn enforce(&self, msg_type: Option<&str>, json: String) -> (String, bool) {
+		if json.len() <= self.max_bytes {
+			return (json, false);
+		}
+		*self.truncated.lock() += 1;
+		let stub = serde_json::json!({
+			"msg": "system.message_truncated",
+			"original_msg": msg_type,
+			"size": json.len(),
+		})
+		.to_string();
+		(stub, true)
+	}
+
+	/// Messages replaced by a stub so far because they exceeded the
+	/// configured size limit.
+	pub fn truncated(&self) -> u64 {
+		*self.truncated.lock()
+	}
+}
+
+/// Error returned by [`connect_with_fresh_resolution`].
+#[derive(Debug)]
+pub enum DnsResolutionError<E> {
+	/// The resolver itself failed (e.g. NXDOMAIN, timeout).
+	Resolve(E),
+	/// The resolver succeeded but returned no addresses.
+	NoAddresses,
+	/// Every resolved address was tried and none accepted a connection.
+	AllAddressesFailed { attempted: usize },
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for DnsResolutionError<E> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			DnsResolutionError::Resolve(err) => write!(f, "DNS resolution failed: {}", err),
+			DnsResolutionError::NoAddresses => write!(f, "DNS resolution returned no addresses"),
+			DnsResolutionError::AllAddressesFailed { attempted } => {
+				write!(f, "failed to connect to any of {} resolved addresses", attempted)
+			}
+		}
+	}
+}
+
+impl<E: std::fmt::Debug + std::fmt::Display> std::error::Error for DnsResolutionError<E> {}
+
+/// Resolve fresh addresses for one endpoint and try connecting to each in
+/// turn (sequential, not a parallel happy-eyeballs race), returning the
+/// first that succeeds.
+///
+/// Resolution and connection are both injected as closures so this is
+/// testable without real DNS or sockets, and `resolve` is called anew every
+/// time — never cached — so a backend behind a load balancer that rotates
+/// IPs is retried against current addresses on every reconnect attempt
+/// instead of the one resolved at startup. A resolution failure is returned
+/// rather than panicking, so it feeds into the normal [`ReconnectBackoff`]
+/// path like any other connection failure.
+pub fn connect_with_fresh_resolution<A, C, E>(
+	resolve: impl FnOnce() -> Result<Vec<A>, E>,
+	mut connect: C,
+) -> Result<A, DnsResolutionError<E>>
+where
+	A: Clone,
+	C: FnMut(&A) -> bool,
+{
+	let addresses = resolve().map_err(DnsResolutionError::Resolve)?;
+	if addresses.is_empty() {
+		return Err(DnsResolutionError::NoAddresses);
+	}
+	for address in &addresses {
+		if connect(address) {
+			return Ok(address.clone());
+		}
+	}
+	Err(DnsResolutionError::AllAddressesFailed { attempted: addresses.len() })
+}
+
+/// Records which resolved address most recently succeeded for each
+/// telemetry endpoint, for the diagnostics API — surfacing e.g. "connected
+/// to 2001:db8::1" rather than just the configured hostname.
+#[derive(Debug, Clone, Default)]
+pub struct EndpointResolvedAddresses(Arc<Mutex<HashMap<String, String>>>);
+
+impl EndpointResolvedAddresses {
+	pub fn record(&self, url: &str, address: impl Into<String>) {
+		self.0.lock().insert(url.to_string(), address.into());
+	}
+
+	/// The address `url` most recently connected to, if any.
+	pub fn last_successful(&self, url: &str) -> Option<String> {
+		self.0.lock().get(url).cloned()
+	}
+}
+
+/// Extra HTTP headers and a templated `User-Agent` sent with one telemetry
+/// endpoint's websocket upgrade request — e.g. an `Authorization` header for
+/// a private collector behind a reverse proxy, or a header used to route by
+/// node software.
+///
+/// This crate slice has no websocket transport of its own; `HandshakeHeaders`
+/// is the configuration surface a worker's websocket client (outside this
+/// crate slice) would read when building the upgrade request.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct HandshakeHeaders {
+	headers: Vec<(String, String)>,
+	user_agent: Option<String>,
+}
+
+impl HandshakeHeaders {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+		self.headers.push((name.into(), value.into()));
+		self
+	}
+
+	/// Set the `User-Agent` header, typically templated with the node
+	/// implementation name and version, e.g. `"my-node/1.2.3"`.
+	pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+		self.user_agent = Some(user_agent.into());
+		self
+	}
+
+	/// Configured headers, in the order they were added.
+	pub fn headers(&self) -> &[(String, String)] {
+		&self.headers
+	}
+
+	pub fn user_agent(&self) -> Option<&str> {
+		self.user_agent.as_deref()
+	}
+}
+
+// A hand-rolled `Debug` impl so a header value (commonly a bearer token or
+// API key) never ends up in a log line or panic message just because
+// someone `{:?}`-formatted the config.
+impl std::fmt::Debug for HandshakeHeaders {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("HandshakeHeaders")
+			.field(
+				"headers",
+				&self.headers.iter().map(|(name, _)| format!("{name}: <redacted>")).collect::<Vec<_>>(),
+			)
+			.field("user_agent", &self.user_agent)
+			.finish()
+	}
+}
+
+/// Per-endpoint [`HandshakeHeaders`], keyed the same way as `targets` in
+/// [`fan_out_by_verbosity`]. An endpoint absent from the map sends no extra
+/// headers and the transport's default `User-Agent`.
+#[derive(Debug, Clone, Default)]
+pub struct EndpointHandshakeHeaders(HashMap<String, HandshakeHeaders>);
+
+impl EndpointHandshakeHeaders {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn insert(&mut self, url: impl Into<String>, headers: HandshakeHeaders) -> &mut Self {
+		self.0.insert(url.into(), headers);
+		self
+	}
+
+	/// The configured headers for `url`, if any.
+	pub fn get(&self, url: &str) -> Option<&HandshakeHeaders> {
+		self.0.get(url)
+	}
+}
+
+/// The wire framing used over a `unix://` telemetry endpoint.
+///
+/// This crate slice has no Unix domain socket transport of its own; a
+/// worker dialing a `unix://` endpoint (outside this crate slice) would
+/// read this to decide how to frame outgoing messages, since a local
+/// collector may speak either raw newline-delimited JSON or a full
+/// websocket handshake over the same socket type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnixSocketFraming {
+	/// One JSON object per line, no handshake.
+	NdJson,
+	/// A full websocket handshake and frame format, same as a `wss://`
+	/// endpoint, just dialed over `AF_UNIX` instead of TCP.
+	WebSocket,
+}
+
+/// Per-endpoint [`UnixSocketFraming`] for `unix://` endpoints, keyed the
+/// same way as `targets` in [`fan_out_by_verbosity`]. An endpoint absent
+/// from the map (or a non-`unix://` endpoint) defaults to
+/// [`UnixSocketFraming::WebSocket`], matching the framing already used over
+/// TCP so a collector doesn't need to special-case its transport.
+#[derive(Debug, Clone, Default)]
+pub struct EndpointUnixFraming(HashMap<String, UnixSocketFraming>);
+
+impl EndpointUnixFraming {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn insert(&mut self, url: impl Into<String>, framing: UnixSocketFraming) -> &mut Self {
+		self.0.insert(url.into(), framing);
+		self
+	}
+
+	pub fn get(&self, url: &str) -> UnixSocketFraming {
+		self.0.get(url).copied().unwrap_or(UnixSocketFraming::WebSocket)
+	}
+}
+
+/// A single per-endpoint message-type filter, matched against a payload's
+/// `msg` field, keyed the same way as `targets` in [`fan_out_by_verbosity`].
+/// Patterns are exact matches, unless they end in `*`, in which case they
+/// match any `msg` sharing that prefix (e.g. `"sysinfo.*"` matches
+/// `"sysinfo.hardware"`). A payload with no `msg` field always passes,
+/// regardless of which variant is configured: filters only ever act on
+/// messages that declare a type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MessageTypeFilter {
+	/// Only `msg`s matching one of these patterns pass.
+	Allow(Vec<String>),
+	/// `msg`s matching one of these patterns are dropped; everything else passes.
+	Deny(Vec<String>),
+}
+
+impl MessageTypeFilter {
+	fn allows(&self, msg_type: Option<&str>) -> bool {
+		let Some(msg_type) = msg_type else {
+			return true;
+		};
+		let matches_any = |patterns: &[String]| {
+			patterns.iter().any(|pattern| match pattern.strip_suffix('*') {
+				Some(prefix) => msg_type.starts_with(prefix),
+				None => pattern == msg_type,
+			})
+		};
+		match self {
+			MessageTypeFilter::Allow(patterns) => matches_any(patterns),
+			MessageTypeFilter::Deny(patterns) => !matches_any(patterns),
+		}
+	}
+}
+
+/// Per-endpoint [`MessageTypeFilter`]s, keyed the same way as `targets` in
+/// [`fan_out_by_verbosity`]. An endpoint absent from the map accepts every
+/// message type, matching the "unfiltered by default" behavior of the other
+/// per-endpoint config maps in this file.
+#[derive(Debug, Clone, Default)]
+pub struct EndpointMessageFilters(HashMap<String, MessageTypeFilter>);
+
+impl EndpointMessageFilters {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn insert(&mut self, url: impl Into<String>, filter: MessageTypeFilter) -> &mut Self {
+		self.0.insert(url.into(), filter);
+		self
+	}
+
+	fn allows(&self, url: &str, msg_type: Option<&str>) -> bool {
+		self.0.get(url).map(|filter| filter.allows(msg_type)).unwrap_or(true)
+	}
+}
+
+/// The wrapping [`EndpointEnvelopes`] applies: the payload is nested under
+/// `payload_key` alongside routing metadata pulled from the payload's own
+/// `node` object (see [`NodeIdentity::to_json`], stamped by
+/// [`Telemetries::set_stamp_identity_on_payloads`]) and `ts` field (see
+/// [`inject_timestamp`]) — not a fresh identity lookup or timestamp, so a
+/// replayed message still reports when it was originally produced. Either
+/// is `null` in the envelope if the payload never carried it.
+///
+/// Field names default to this crate's own naming but are independently
+/// overridable to match a downstream collector's conventions, e.g. Vector's
+/// `host`/`timestamp`/`message` or Fluentd's `tag`/`time`/`record`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvelopeFormat {
+	pub node_key: String,
+	pub chain_key: String,
+	pub received_key: String,
+	pub payload_key: String,
+}
+
+impl Default for EnvelopeFormat {
+	fn default() -> Self {
+		Self {
+			node_key: "node".to_string(),
+			chain_key: "chain".to_string(),
+			received_key: "received".to_string(),
+			payload_key: "payload".to_string(),
+		}
+	}
+}
+
+impl EnvelopeFormat {
+	fn wrap(&self, payload: serde_json::Value) -> serde_json::Value {
+		let node = payload.get("node").and_then(|node| node.get("name")).cloned().unwrap_or(serde_json::Value::Null);
+		let chain = payload.get("node").and_then(|node| node.get("chain")).cloned().unwrap_or(serde_json::Value::Null);
+		let received = payload.get("ts").cloned().unwrap_or(serde_json::Value::Null);
+		let mut envelope = serde_json::Map::new();
+		envelope.insert(self.node_key.clone(), node);
+		envelope.insert(self.chain_key.clone(), chain);
+		envelope.insert(self.received_key.clone(), received);
+		envelope.insert(self.payload_key.clone(), payload);
+		serde_json::Value::Object(envelope)
+	}
+}
+
+/// Per-endpoint [`EnvelopeFormat`]s, keyed the same way as `targets` in
+/// [`fan_out_by_verbosity`]. An endpoint absent from the map (the default)
+/// receives the bare payload unchanged; naming one here switches it to
+/// receiving `format`-wrapped envelopes without affecting any other
+/// endpoint on the same instance. Built for a fleet aggregator that
+/// multiplexes many nodes' telemetry over one connection and needs to tell
+/// them apart without parsing every payload itself.
+#[derive(Debug, Clone, Default)]
+pub struct EndpointEnvelopes(HashMap<String, EnvelopeFormat>);
+
+impl EndpointEnvelopes {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn insert(&mut self, url: impl Into<String>, format: EnvelopeFormat) -> &mut Self {
+		self.0.insert(url.into(), format);
+		self
+	}
+
+	/// Wrap `json` for delivery to `url` per its configured [`EnvelopeFormat`],
+	/// or return it unchanged if `url` has none configured.
+	fn wrap(&self, url: &str, json: &str) -> String {
+		let Some(format) = self.0.get(url) else {
+			return json.to_string();
+		};
+		let payload = serde_json::from_str(json).unwrap_or(serde_json::Value::Null);
+		format.wrap(payload).to_string()
+	}
+}
+
+/// Insert `verbosity`'s raw wire value under `field` in the JSON object
+/// `json` decodes to, or leave `json` unchanged if it already has a field
+/// by that name — an existing field, however it got there, always wins over
+/// stamping the verbosity on top of it. Also left unchanged if `json`
+/// doesn't decode to a JSON object at all, which shouldn't happen for a
+/// payload built by this crate but shouldn't panic if it somehow does.
+fn embed_verbosity_field(json: &str, field: &str, verbosity: Verbosity) -> String {
+	let Ok(mut value) = serde_json::from_str::<serde_json::Value>(json) else {
+		return json.to_string();
+	};
+	let Some(obj) = value.as_object_mut() else {
+		return json.to_string();
+	};
+	if obj.contains_key(field) {
+		log::warn!(
+			target: "telemetry",
+			"Not embedding telemetry verbosity: {field:?} is already a field on this message",
+		);
+		return json.to_string();
+	}
+	obj.insert(field.to_string(), verbosity.as_u8().into());
+	serialize_message(&value, None).expect("a serde_json::Value always re-serializes; qed")
+}
+
+/// Per-endpoint field name to embed each message's [`Verbosity`] under
+/// during serialization, keyed the same way as `targets` in
+/// [`fan_out_by_verbosity`]. An endpoint absent from the map (the default)
+/// sends the bare payload with no verbosity of its own, exactly as before
+/// this existed — in particular every `ws`/`wss` endpoint, so the public
+/// wire format doesn't change under anyone who never opts in.
+///
+/// Built for sinks with no channel of their own to carry the verbosity
+/// out of band the way the in-process `(Verbosity, String)` tuple does —
+/// a file, `stdout://`, or `unix://` consumer only ever sees the
+/// serialized JSON, so without this the verbosity a message was sent at
+/// is simply lost (see [`FileSink::with_level_field`] for the same gap on
+/// the single-file sink, which isn't one of `targets` and so isn't keyed
+/// by URL here).
+#[derive(Debug, Clone, Default)]
+pub struct EndpointVerbosityFields(HashMap<String, String>);
+
+impl EndpointVerbosityFields {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Embed under `field` (e.g. `"level"`) for `url`.
+	pub fn insert(&mut self, url: impl Into<String>, field: impl Into<String>) -> &mut Self {
+		self.0.insert(url.into(), field.into());
+		self
+	}
+
+	/// Embed `verbosity` into `json` per `url`'s configured field, or return
+	/// it unchanged if `url` has none configured.
+	fn embed(&self, url: &str, verbosity: Verbosity, json: &str) -> String {
+		let Some(field) = self.0.get(url) else {
+			return json.to_string();
+		};
+		embed_verbosity_field(json, field, verbosity)
+	}
+}
+
+/// A read-only view of one outgoing message, given to a registered
+/// [`MessageRouter`] so it can decide which endpoints receive it.
+pub struct RoutingInfo<'a> {
+	pub verbosity: Verbosity,
+	pub msg_type: Option<&'a str>,
+	pub payload: &'a serde_json::Value,
+}
+
+/// Which endpoints a [`MessageRouter`] wants a message delivered to.
+/// Endpoint indices in [`Subset`](Self::Subset) refer to
+/// [`fan_out_by_verbosity`]'s `targets` sorted lexicographically by URL,
+/// since `targets` itself is a `HashMap` with no inherent order.
+///
+/// This narrows, rather than replaces, the existing per-endpoint verbosity
+/// threshold and [`EndpointMessageFilters`] check: an endpoint the router
+/// selects can still be filtered out by either of those, the same as if no
+/// router were registered at all.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EndpointSelection {
+	All,
+	None,
+	Subset(Vec<usize>),
+}
+
+/// A per-worker routing callback consulted by [`fan_out_by_verbosity`] for
+/// every outgoing message, e.g. to keep finality-related messages on an
+/// internal collector instead of every configured endpoint.
+///
+/// The callback is panic-isolated: a panic inside it is caught and treated
+/// as [`EndpointSelection::All`], falling back to the static verbosity/
+/// [`EndpointMessageFilters`] checks as if no router were registered, so a
+/// bug in one operator's routing logic can't take down telemetry delivery
+/// for everyone.
+pub struct MessageRouter {
+	route: Box<dyn Fn(&RoutingInfo<'_>) -> EndpointSelection + Send + Sync>,
+}
+
+impl MessageRouter {
+	pub fn new(route: impl Fn(&RoutingInfo<'_>) -> EndpointSelection + Send + Sync + 'static) -> Self {
+		Self { route: Box::new(route) }
+	}
+
+	/// Evaluate the callback against `info`, catching a panic and falling
+	/// back to [`EndpointSelection::All`] instead of propagating it.
+	fn select(&self, info: &RoutingInfo<'_>) -> EndpointSelection {
+		std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| (self.route)(info))).unwrap_or(EndpointSelection::All)
+	}
+}
+
+impl std::fmt::Debug for MessageRouter {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("MessageRouter").finish_non_exhaustive()
+	}
+}
+
+/// Extract the `msg` field of a serialized telemetry payload, if present.
+/// Used by [`fan_out_by_verbosity`] to parse a message's type exactly once,
+/// rather than once per endpoint filter check.
+fn extract_msg_type(json: &str) -> Option<String> {
+	serde_json::from_str::<serde_json::Value>(json)
+		.ok()
+		.and_then(|value| value.get("msg").and_then(|msg| msg.as_str().map(str::to_owned)))
+}
+
+/// Wire encoding for an endpoint's outgoing frames, configured per endpoint
+/// via [`EndpointEncodings`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+	/// Send the already-serialized JSON payload as a text frame. The default.
+	#[default]
+	Json,
+	/// Re-encode the payload as compact binary [CBOR](https://www.rfc-editor.org/rfc/rfc8949)
+	/// and send it as a binary frame, for bandwidth-constrained links.
+	Cbor,
+}
+
+/// Per-endpoint [`Encoding`], keyed the same way as `targets` in
+/// [`fan_out_by_verbosity`]. An endpoint absent from this map sends
+/// [`Encoding::Json`].
+#[derive(Debug, Clone, Default)]
+pub struct EndpointEncodings(HashMap<String, Encoding>);
+
+impl EndpointEncodings {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn insert(&mut self, url: impl Into<String>, encoding: Encoding) -> &mut Self {
+		self.0.insert(url.into(), encoding);
+		self
+	}
+
+	/// The configured encoding for `url`, defaulting to [`Encoding::Json`].
+	pub fn get(&self, url: &str) -> Encoding {
+		self.0.get(url).copied().unwrap_or_default()
+	}
+}
+
+/// Encode an already-serialized JSON payload as `encoding`'s wire bytes: a
+/// byte-copy of `json` for [`Encoding::Json`], or a re-parsed and re-encoded
+/// [CBOR](https://www.rfc-editor.org/rfc/rfc8949) frame for [`Encoding::Cbor`].
+///
+/// Hand-rolled rather than pulled from a crate, the same tradeoff
+/// [`MessageTypeFilter`]'s glob matching makes: this only ever needs to cover
+/// the JSON value subset (null/bool/number/string/array/object) that
+/// `serde_json::Value` can produce, not the full CBOR spec.
+///
+/// Note this re-parses `json` once per endpoint that wants CBOR, since
+/// `fan_out_by_verbosity`'s `targets` still carry a pre-rendered `String`
+/// end-to-end; threading a `serde_json::Value` all the way from
+/// `Telemetries::send` through every endpoint's channel would avoid that
+/// re-parse, but is a larger pipeline change than this helper and is
+/// tracked separately.
+pub fn encode_message(json: &str, encoding: Encoding) -> Vec<u8> {
+	match encoding {
+		Encoding::Json => json.as_bytes().to_vec(),
+		Encoding::Cbor => {
+			let value: serde_json::Value = serde_json::from_str(json)
+				.expect("a payload produced by Telemetries::send is always valid JSON; qed");
+			encode_cbor(&value)
+		}
+	}
+}
+
+fn encode_cbor(value: &serde_json::Value) -> Vec<u8> {
+	let mut out = Vec::new();
+	write_cbor(value, &mut out);
+	out
+}
+
+fn write_cbor(value: &serde_json::Value, out: &mut Vec<u8>) {
+	match value {
+		serde_json::Value::Null => out.push(0xf6),
+		serde_json::Value::Bool(false) => out.push(0xf4),
+		serde_json::Value::Bool(true) => out.push(0xf5),
+		serde_json::Value::Number(n) => write_cbor_number(n, out),
+		serde_json::Value::String(s) => {
+			write_cbor_head(3, s.len() as u64, out);
+			out.extend_from_slice(s.as_bytes());
+		}
+		serde_json::Value::Array(items) => {
+			write_cbor_head(4, items.len() as u64, out);
+			for item in items {
+				write_cbor(item, out);
+			}
+		}
+		serde_json::Value::Object(map) => {
+			write_cbor_head(5, map.len() as u64, out);
+			for (key, val) in map {
+				write_cbor(&serde_json::Value::String(key.clone()), out);
+				write_cbor(val, out);
+			}
+		}
+	}
+}
+
+fn write_cbor_number(n: &serde_json::Number, out: &mut Vec<u8>) {
+	if let Some(u) = n.as_u64() {
+		write_cbor_head(0, u, out);
+	} else if let Some(i) = n.as_i64() {
+		// Major type 1 (negative integer) encodes `-1 - arg`.
+		write_cbor_head(1, (-1 - i) as u64, out);
+	} else {
+		let f = n.as_f64().unwrap_or(0.0);
+		out.push(0xfb); // major type 7, additional info 27: IEEE 754 double-precision float
+		out.extend_from_slice(&f.to_be_bytes());
+	}
+}
+
+/// Write a CBOR head (major type + length/value argument) for `major` (0-7)
+/// and `arg`, using the shortest encoding RFC 8949 allows.
+fn write_cbor_head(major: u8, arg: u64, out: &mut Vec<u8>) {
+	let major = major << 5;
+	if arg < 24 {
+		out.push(major | arg as u8);
+	} else if arg <= u8::MAX as u64 {
+		out.push(major | 24);
+		out.push(arg as u8);
+	} else if arg <= u16::MAX as u64 {
+		out.push(major | 25);
+		out.extend_from_slice(&(arg as u16).to_be_bytes());
+	} else if arg <= u32::MAX as u64 {
+		out.push(major | 26);
+		out.extend_from_slice(&(arg as u32).to_be_bytes());
+	} else {
+		out.push(major | 27);
+		out.extend_from_slice(&arg.to_be_bytes());
+	}
+}
+
+/// A ready-to-send telemetry message, independent of any wire format: the
+/// same envelope [`Telemetries::send`] and [`TelemetryLayer::on_event`]
+/// already build before committing to JSON.
+#[derive(Debug, Clone)]
+pub struct TelemetryMessage {
+	pub id: u64,
+	pub verbosity: Verbosity,
+	pub payload: serde_json::Value,
+}
+
+impl TelemetryMessage {
+	/// A copy of this message with `ts` re-stamped for `url` using
+	/// `formats`' entry for it (or [`TimestampFormat::default`] if `url` has
+	/// none configured).
+	///
+	/// The main channel already shares one pre-rendered JSON string across
+	/// every endpoint by the time it reaches [`fan_out_by_verbosity`] (see
+	/// [`encode_message`]'s doc comment for the same tradeoff), so a single
+	/// message can only actually go out in two different `ts` forms through
+	/// this [`TelemetrySerializer`] extension point, which is the one stage
+	/// still holding an unserialized payload per endpoint. Call this right
+	/// before [`EndpointSerializers::serialize_for`].
+	pub fn restamped_for(
+		&self,
+		url: &str,
+		formats: &EndpointTimestampFormats,
+		now: std::time::SystemTime,
+	) -> Self {
+		let mut payload = self.payload.clone();
+		if let Some(obj) = payload.as_object_mut() {
+			obj.insert("ts".into(), format_timestamp(now, formats.get(url)));
+		}
+		Self { payload, ..*self }
+	}
+}
+
+/// Per-endpoint override for the injected `ts` field's [`TimestampFormat`],
+/// keyed the same way as `targets` in [`fan_out_by_verbosity`]. An endpoint
+/// absent from the map uses [`TimestampFormat::default`].
+///
+/// Consulted by [`TelemetryMessage::restamped_for`], not by
+/// [`fan_out_by_verbosity`] itself — see that method's doc comment for why.
+#[derive(Debug, Clone, Default)]
+pub struct EndpointTimestampFormats(HashMap<String, TimestampFormat>);
+
+impl EndpointTimestampFormats {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn insert(&mut self, url: impl Into<String>, format: TimestampFormat) -> &mut Self {
+		self.0.insert(url.into(), format);
+		self
+	}
+
+	/// The configured format for `url`, defaulting to [`TimestampFormat::default`].
+	pub fn get(&self, url: &str) -> TimestampFormat {
+		self.0.get(url).copied().unwrap_or_default()
+	}
+}
+
+/// The wire form a [`TelemetrySerializer`] produces, matching the two frame
+/// kinds a websocket connection can send.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MessagePayload {
+	Text(String),
+	Binary(Vec<u8>),
+}
+
+/// Error returned by a [`TelemetrySerializer`]. Reported through
+/// [`SerializerStats`] rather than propagated as a panic, since one
+/// endpoint's serializer refusing one message must never take down delivery
+/// to any other endpoint or any other message.
+#[derive(Debug, Clone)]
+pub struct SerializeError {
+	pub msg_type: Option<String>,
+	pub reason: String,
+}
+
+impl std::fmt::Display for SerializeError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match &self.msg_type {
+			Some(msg_type) => write!(f, "failed to serialize a {} message: {}", msg_type, self.reason),
+			None => write!(f, "failed to serialize a message: {}", self.reason),
+		}
+	}
+}
+
+impl std::error::Error for SerializeError {}
+
+/// Converts a [`TelemetryMessage`] into the bytes a specific endpoint sends
+/// over the wire, decoupling payload construction (this crate) from
+/// transport (a worker outside this crate slice). Broader than
+/// [`Encoding`]/[`encode_message`]: a vendor can ship an entirely custom wire
+/// format (protobuf, a proprietary schema, ...) as a crate-external impl of
+/// this trait, instead of being limited to picking between JSON and a CBOR
+/// re-encoding of the same JSON shape. Configured per endpoint via
+/// [`EndpointSerializers`]; an endpoint with none configured uses
+/// [`JsonSerializer`].
+pub trait TelemetrySerializer {
+	fn serialize(&self, message: &TelemetryMessage) -> Result<MessagePayload, SerializeError>;
+}
+
+/// The default [`TelemetrySerializer`]: re-serializes `message.payload` as
+/// JSON text, unchanged. Equivalent to [`Encoding::Json`] for callers that
+/// want to go through the `TelemetrySerializer` extension point uniformly
+/// rather than special-casing JSON.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonSerializer;
+
+impl TelemetrySerializer for JsonSerializer {
+	fn serialize(&self, message: &TelemetryMessage) -> Result<MessagePayload, SerializeError> {
+		serde_json::to_string(&message.payload).map(MessagePayload::Text).map_err(|err| SerializeError {
+			msg_type: message.payload.get("msg").and_then(|v| v.as_str()).map(str::to_string),
+			reason: err.to_string(),
+		})
+	}
+}
+
+/// Per-endpoint failed-serialization counters, keyed the same way as
+/// `targets` in [`fan_out_by_verbosity`]. A [`TelemetrySerializer`] erroring
+/// on one message for `url` is recorded here and that message is dropped for
+/// `url` alone; every other endpoint, and this endpoint's next message,
+/// keep going.
+#[derive(Debug, Clone, Default)]
+pub struct SerializerStats(Arc<Mutex<HashMap<String, u64>>>);
+
+impl SerializerStats {
+	fn record_error(&self, url: &str) {
+		*self.0.lock().entry(url.to_string()).or_insert(0) += 1;
+	}
+
+	/// Serialization failures recorded for `url` so far. `0` if `url` has
+	/// never failed to serialize a message.
+	pub fn errors(&self, url: &str) -> u64 {
+		self.0.lock().get(url).copied().unwrap_or(0)
+	}
+}
+
+/// Per-endpoint [`TelemetrySerializer`], keyed the same way as `targets` in
+/// [`fan_out_by_verbosity`]. An endpoint absent from this map uses
+/// [`JsonSerializer`].
+#[derive(Clone, Default)]
+pub struct EndpointSerializers(HashMap<String, Arc<dyn TelemetrySerializer + Send + Sync>>);
+
+impl EndpointSerializers {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn insert(
+		&mut self,
+		url: impl Into<String>,
+		serializer: Arc<dyn TelemetrySerializer + Send + Sync>,
+	) -> &mut Self {
+		self.0.insert(url.into(), serializer);
+		self
+	}
+
+	/// Serialize `message` for `url`, using its configured serializer (or
+	/// [`JsonSerializer`] if none is configured). A serializer error is
+	/// recorded against `url` in `stats`, logged, and reported here as
+	/// [`TelemetryError::Transport`] rather than propagated as the original
+	/// [`SerializeError`], so a worker can match on the same error type every
+	/// other fallible send-path method returns.
+	fn serialize_for(
+		&self,
+		url: &str,
+		message: &TelemetryMessage,
+		stats: &SerializerStats,
+	) -> Result<MessagePayload, TelemetryError> {
+		let result = match self.0.get(url) {
+			Some(serializer) => serializer.serialize(message),
+			None => JsonSerializer.serialize(message),
+		};
+		result.map_err(|err| {
+			stats.record_error(url);
+			log::warn!(target: "telemetry", "Dropped a telemetry message for {url}: {err}");
+			TelemetryError::Transport(err.to_string())
+		})
+	}
+}
+
+/// Drain `receiver` (the `Senders`-facing side of a telemetry instance's
+/// channel) and fan each message out to every endpoint in `targets` whose
+/// configured threshold in `endpoints` is at or above the message's verbosity.
+/// An endpoint absent from `endpoints` is treated as accepting everything,
+/// matching the "unfiltered by default" behavior of the pre-existing
+/// unfiltered channel.
+///
+/// Each endpoint owns its own bounded queue (its `mpsc::Sender` in `targets`),
+/// so one endpoint filling up only ever drops messages destined for that
+/// endpoint and is recorded against it alone in `stats`; every other endpoint
+/// keeps draining normally.
+///
+/// This is a filtering stage only: `targets` are themselves
+/// `mpsc::Sender<(Verbosity, String)>`s, so tests can exercise the fan-out
+/// with plain in-memory channels standing in for the real websocket
+/// connections a full telemetry worker (outside this crate slice) would hold.
+///
+/// `commands` lets a caller add or remove endpoints while this future is
+/// running, e.g. in response to a runtime config change: a new endpoint gets
+/// its own queue and, if [`EndpointCommand::Add::connect_message`] is set, an
+/// immediate send of it (typically `system.connected`) before anything else
+/// queued; a removed endpoint's `Sender` is dropped, which closes the channel
+/// from this side (actually tearing down the underlying connection is a
+/// worker concern outside this crate slice). Commands are applied no later
+/// than the start of the next iteration of this loop, so it's safe to send
+/// one while a delivery to some other endpoint is in flight. An empty,
+/// never-closed `commands` (e.g. from [`futures::channel::mpsc::unbounded`])
+/// is fine for callers with a fixed endpoint set — it never yields anything
+/// and never ends the loop.
+///
+/// `filters` additionally restricts each endpoint to a subset of message
+/// types via [`MessageTypeFilter`]; a message's `msg` field is extracted
+/// once per message (via [`extract_msg_type`]), not once per endpoint.
+///
+/// When debug mirroring has been turned on for this instance (see
+/// [`EndpointCommand::SetDebugMirror`]), every message is additionally
+/// logged via `log::trace!(target: "telemetry-out", ...)` once per
+/// endpoint it was considered for, recording whether it was actually
+/// delivered or dropped. The `log::trace!` level check happens before the
+/// mirror line is formatted, so mirroring costs nothing beyond a single
+/// bool read when it's off, and nothing beyond `log`'s own level check
+/// when the level itself isn't enabled.
+///
+/// `groups` designates some of `targets` as failover pairs (see
+/// [`EndpointGroup`]): a grouped `url` is only ever sent to while it's the
+/// group's current active member, decided by health reported through
+/// [`EndpointCommand::ReportEndpointHealth`] and, for failback to a
+/// higher-priority member, by how long it's stayed healthy. Group
+/// membership itself is fixed for the lifetime of this call; a `url` not
+/// named in `groups` behaves exactly as if `groups` were empty.
+///
+/// `router`, if set, is consulted once per message via
+/// [`MessageRouter::select`] before the per-endpoint verbosity/`filters`
+/// checks, narrowing which endpoints are even considered for it — see
+/// [`EndpointSelection`] for how the two combine. `None` behaves exactly as
+/// if every message routed to [`EndpointSelection::All`].
+///
+/// `envelopes` additionally wraps the payload sent to some endpoints in
+/// routing metadata via [`EndpointEnvelopes`], e.g. for a fleet aggregator
+/// multiplexing many nodes over one connection. An endpoint absent from it
+/// keeps receiving the bare payload exactly as before `envelopes` existed.
+///
+/// `verbosity_fields` embeds the message's [`Verbosity`] into the payload
+/// for some endpoints via [`EndpointVerbosityFields`], applied before
+/// `envelopes` so an enveloped endpoint's `payload` carries it too. An
+/// endpoint absent from it keeps receiving the bare payload exactly as
+/// before `verbosity_fields` existed.
+pub async fn fan_out_by_verbosity(
+	mut receiver: mpsc::Receiver<(Verbosity, String)>,
+	mut endpoints: Endpoints,
+	mut targets: HashMap<String, mpsc::Sender<(Verbosity, String)>>,
+	stats: EndpointStats,
+	mut commands: mpsc::UnboundedReceiver<EndpointCommand>,
+	filters: EndpointMessageFilters,
+	groups: EndpointGroups,
+	router: Option<Arc<MessageRouter>>,
+	envelopes: EndpointEnvelopes,
+	verbosity_fields: EndpointVerbosityFields,
+) {
+	let mut debug_mirror = false;
+	let member_group: HashMap<String, String> = groups
+		.0
+		.iter()
+		.flat_map(|(name, group)| group.members.iter().map(move |member| (member.clone(), name.clone())))
+		.collect();
+	let mut group_health: HashMap<String, bool> = HashMap::new();
+	let mut group_runtime: HashMap<String, GroupRuntime> =
+		groups.0.keys().map(|name| (name.clone(), GroupRuntime::default())).collect();
+
+	loop {
+		match futures::future::select(receiver.next(), commands.next()).await {
+			futures::future::Either::Left((None, _)) => break,
+			futures::future::Either::Left((Some((verbosity, json)), _)) => {
+				for (name, group) in &groups.0 {
+					if let Some(runtime) = group_runtime.get_mut(name) {
+						if let Some((previous, new)) =
+							reconsider_group(runtime, group, &group_health, std::time::Instant::now())
+						{
+							announce_failover(name, &group.members[previous], &group.members[new], &mut targets, &endpoints, &stats);
+						}
+					}
+				}
+				let msg_type = extract_msg_type(&json);
+				let selection = router.as_ref().map(|router| {
+					let payload = serde_json::from_str::<serde_json::Value>(&json).unwrap_or(serde_json::Value::Null);
+					router.select(&RoutingInfo { verbosity, msg_type: msg_type.as_deref(), payload: &payload })
+				});
+				let mut routed_urls: Vec<String> = targets.keys().cloned().collect();
+				routed_urls.sort();
+				for (index, url) in routed_urls.into_iter().enumerate() {
+					if let Some(selection) = &selection {
+						let routed = match selection {
+							EndpointSelection::All => true,
+							EndpointSelection::None => false,
+							EndpointSelection::Subset(indices) => indices.contains(&index),
+						};
+						if !routed {
+							continue;
+						}
+					}
+					if let Some(name) = member_group.get(&url) {
+						let active = group_runtime.get(name).map(|runtime| runtime.active).unwrap_or(0);
+						if groups.0.get(name).map(|group| group.members[active].as_str()) != Some(url.as_str()) {
+							continue;
+						}
+					}
+					let threshold = endpoints.max_verbosity(&url).unwrap_or(Verbosity(u8::MAX));
+					if verbosity <= threshold && filters.allows(&url, msg_type.as_deref()) {
+						let sender = targets.get_mut(&url).expect("url was just read from targets.keys()");
+						let with_level = verbosity_fields.embed(&url, verbosity, &json);
+						let payload = envelopes.wrap(&url, &with_level);
+						let send_result = sender.try_send((verbosity, payload));
+						let delivered = send_result.is_ok();
+						if let Err(err) = &send_result {
+							stats.record_drop(&url, if err.is_full() { DropReason::QueueFull } else { DropReason::Disconnected });
+						}
+						if debug_mirror && log::log_enabled!(target: "telemetry-out", log::Level::Trace) {
+							log::trace!(
+								target: "telemetry-out",
+								"endpoint={url} delivered={delivered} message={json}"
+							);
+						}
+					} else if debug_mirror && log::log_enabled!(target: "telemetry-out", log::Level::Trace) {
+						log::trace!(
+							target: "telemetry-out",
+							"endpoint={url} delivered=false filtered=true message={json}"
+						);
+					}
+				}
+			}
+			futures::future::Either::Right((None, _)) => {
+				// The command sender was dropped; keep forwarding messages
+				// with whatever endpoint set we last had.
+			}
+			futures::future::Either::Right((Some(EndpointCommand::Add {
+				url,
+				max_verbosity,
+				mut sender,
+				connect_message,
+			}), _)) => {
+				if let Some(message) = connect_message {
+					let _ = sender.try_send(message);
+				}
+				targets.insert(url.clone(), sender);
+				endpoints.insert(url, max_verbosity);
+			}
+			futures::future::Either::Right((Some(EndpointCommand::Remove { url }), _)) => {
+				targets.remove(&url);
+				endpoints.remove(&url);
+			}
+			futures::future::Either::Right((Some(EndpointCommand::SetMaxVerbosity {
+				url,
+				max_verbosity,
+			}), _)) => {
+				endpoints.insert(url.clone(), max_verbosity);
+				// Announced through the normal per-endpoint filtering below so a
+				// backend that only wants a subset still gets it. Unlike a
+				// payload that went through `Telemetries::send`, this meta
+				// message isn't stamped with `id`/timestamp/static fields —
+				// those are applied upstream of this function, which has no
+				// access to them.
+				let meta = serde_json::json!({
+					"msg": "system.telemetry_verbosity_changed",
+					"endpoint": url,
+					"max_verbosity": max_verbosity.0,
+				})
+				.to_string();
+				for (target_url, sender) in targets.iter_mut() {
+					let threshold = endpoints.max_verbosity(target_url).unwrap_or(Verbosity(u8::MAX));
+					if Verbosity::CONSOLE <= threshold {
+						if let Err(err) = sender.try_send((Verbosity::CONSOLE, meta.clone())) {
+							stats.record_drop(target_url, if err.is_full() { DropReason::QueueFull } else { DropReason::Disconnected });
+						}
+					}
+				}
+			}
+			futures::future::Either::Right((Some(EndpointCommand::SetDebugMirror(enabled)), _)) => {
+				debug_mirror = enabled;
+			}
+			futures::future::Either::Right((Some(EndpointCommand::ReportEndpointHealth { url, healthy }), _)) => {
+				group_health.insert(url.clone(), healthy);
+				if let Some(name) = member_group.get(&url) {
+					if let (Some(runtime), Some(group)) = (group_runtime.get_mut(name), groups.0.get(name)) {
+						if let Some((previous, new)) =
+							reconsider_group(runtime, group, &group_health, std::time::Instant::now())
+						{
+							announce_failover(name, &group.members[previous], &group.members[new], &mut targets, &endpoints, &stats);
+						}
+					}
+				}
+			}
+		}
+	}
+}
+
+/// Per-group failover state a running [`fan_out_by_verbosity`] tracks
+/// alongside the fixed [`EndpointGroup`] configuration: which member index
+/// is currently active, and, once the primary has recovered, since when —
+/// so [`reconsider_group`] can tell a genuinely-stable primary from one
+/// that just flapped back up.
+#[derive(Debug, Default)]
+struct GroupRuntime {
+	active: usize,
+	primary_recovered_since: Option<std::time::Instant>,
+}
+
+/// Re-evaluates `runtime` against the latest `health` and, if the active
+/// member should change, applies the change and returns
+/// `Some((previous_index, new_index))`.
+///
+/// Failover away from an unhealthy active member is immediate. Failing
+/// back to the primary only happens once it's been continuously healthy
+/// for `group.failback_after`; an unreported member is assumed healthy, so
+/// a group with no health reports yet behaves as if nothing were grouped
+/// at all.
+fn reconsider_group(
+	runtime: &mut GroupRuntime,
+	group: &EndpointGroup,
+	health: &HashMap<String, bool>,
+	now: std::time::Instant,
+) -> Option<(usize, usize)> {
+	let is_healthy = |idx: usize| health.get(&group.members[idx]).copied().unwrap_or(true);
+
+	if !is_healthy(0) {
+		runtime.primary_recovered_since = None;
+		if !is_healthy(runtime.active) {
+			if let Some(next) = (1..group.members.len()).find(|&i| is_healthy(i)) {
+				if next != runtime.active {
+					let previous = runtime.active;
+					runtime.active = next;
+					return Some((previous, next));
+				}
+			}
+		}
+		return None;
+	}
+
+	if runtime.active == 0 {
+		runtime.primary_recovered_since = None;
+		return None;
+	}
+	match runtime.primary_recovered_since {
+		None => {
+			runtime.primary_recovered_since = Some(now);
+			None
+		}
+		Some(since) if now.duration_since(since) >= group.failback_after => {
+			let previous = runtime.active;
+			runtime.active = 0;
+			runtime.primary_recovered_since = None;
+			Some((previous, 0))
+		}
+		Some(_) => None,
+	}
+}
+
+/// Logs and broadcasts a group's active member switching from
+/// `previous_url` to `new_url`, satisfying "connection events should
+/// identify the group and active member". Mirrors the
+/// `system.telemetry_verbosity_changed` broadcast in the
+/// [`EndpointCommand::SetMaxVerbosity`] handler above.
+fn announce_failover(
+	name: &str,
+	previous_url: &str,
+	new_url: &str,
+	targets: &mut HashMap<String, mpsc::Sender<(Verbosity, String)>>,
+	endpoints: &Endpoints,
+	stats: &EndpointStats,
+) {
+	log::info!(target: "telemetry", "endpoint group {name:?} failed over: {previous_url} -> {new_url}");
+	let meta = serde_json::json!({
+		"msg": "system.telemetry_failover",
+		"group": name,
+		"previous_endpoint": previous_url,
+		"active_endpoint": new_url,
+	})
+	.to_string();
+	for (target_url, sender) in targets.iter_mut() {
+		let threshold = endpoints.max_verbosity(target_url).unwrap_or(Verbosity(u8::MAX));
+		if Verbosity::CONSOLE <= threshold {
+			if let Err(err) = sender.try_send((Verbosity::CONSOLE, meta.clone())) {
+				stats.record_drop(target_url, if err.is_full() { DropReason::QueueFull } else { DropReason::Disconnected });
+			}
+		}
+	}
+}
+
+/// A runtime request to reconfigure the endpoints a running
+/// [`fan_out_by_verbosity`] is forwarding to, sent over its `commands`
+/// channel. See [`fan_out_by_verbosity`]'s doc comment for how and when
+/// these are applied.
+pub enum EndpointCommand {
+	/// Register `url` with its own queue and verbosity threshold, and, if
+	/// `connect_message` is set, enqueue it immediately.
+	Add {
+		url: String,
+		max_verbosity: Verbosity,
+		sender: mpsc::Sender<(Verbosity, String)>,
+		connect_message: Option<(Verbosity, String)>,
+	},
+	/// Stop forwarding to `url` and drop its queue.
+	Remove { url: String },
+	/// Change `url`'s verbosity threshold at runtime, e.g. to temporarily
+	/// raise it while debugging an issue. Also broadcasts a
+	/// `system.telemetry_verbosity_changed` meta message so a backend can
+	/// correlate the resulting shift in traffic.
+	SetMaxVerbosity { url: String, max_verbosity: Verbosity },
+	/// Turn mirroring of every outgoing message to
+	/// `log::trace!(target: "telemetry-out", ...)` on or off, for
+	/// troubleshooting what a node is actually sending without attaching a
+	/// real endpoint. See [`fan_out_by_verbosity`]'s doc comment.
+	SetDebugMirror(bool),
+	/// Report whether `url` is currently reachable, as observed by whatever
+	/// is dialing it outside this crate slice (see the module-level scope
+	/// note). Drives failover/failback for any [`EndpointGroup`] `url`
+	/// belongs to; has no effect on a `url` that isn't a group member.
+	ReportEndpointHealth { url: String, healthy: bool },
+}
+
+/// Computes reconnect delays for a single telemetry endpoint using exponential
+/// backoff with jitter, starting at `initial_delay` and capping at `max_delay`.
+///
+/// This crate slice has no websocket transport of its own, so `ReconnectBackoff`
+/// doesn't drive a connection; it's the piece a worker maintaining one would
+/// call between attempts and after each connection closes.
+#[derive(Debug, Clone)]
+pub struct ReconnectBackoff {
+	initial_delay: std::time::Duration,
+	max_delay: std::time::Duration,
+	attempt: u32,
+}
+
+impl ReconnectBackoff {
+	pub fn new(initial_delay: std::time::Duration, max_delay: std::time::Duration) -> Self {
+		Self { initial_delay, max_delay, attempt: 0 }
+	}
+
+	/// Number of consecutive failed attempts since construction or the last
+	/// [`note_connection_duration`](Self::note_connection_duration) reset.
+	pub fn attempt(&self) -> u32 {
+		self.attempt
+	}
+
+	/// The delay before the next attempt: `initial_delay * 2^attempt`, capped at
+	/// `max_delay`, then scaled by `jitter_sample` (expected in `[0.0, 1.0)`) so
+	/// that many nodes reconnecting to the same backend at once don't retry in
+	/// lockstep. Bumps the attempt counter. `rand` isn't a dependency of this
+	/// crate, so the caller supplies the random sample rather than this type
+	/// pulling one in itself.
+	pub fn next_delay(&mut self, jitter_sample: f64) -> std::time::Duration {
+		let exponent = self.attempt.min(31);
+		self.attempt = self.attempt.saturating_add(1);
+		let base = self.initial_delay.saturating_mul(1u32 << exponent);
+		let capped = base.min(self.max_delay);
+		capped.mul_f64(jitter_sample.clamp(0.0, 1.0))
+	}
+
+	/// Reset the attempt counter if the just-closed connection stayed up for at
+	/// least `min_stable_duration`, so a backend that flaps quickly keeps
+	/// backing off while one that ran for a while before dropping starts fresh.
+	pub fn note_connection_duration(
+		&mut self,
+		uptime: std::time::Duration,
+		min_stable_duration: std::time::Duration,
+	) {
+		if uptime >= min_stable_duration {
+			self.attempt = 0;
+		}
+	}
+}
+
+/// How many times (and how) a telemetry worker should retry a dropped
+/// connection before giving up on an endpoint entirely.
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+	pub initial_delay: std::time::Duration,
+	pub max_delay: std::time::Duration,
+	/// `None` retries forever; some deployments (e.g. ephemeral CI nodes) want a
+	/// bound instead.
+	pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+	/// Retry forever, backing off from 1 second up to 1 minute — the same
+	/// values used throughout this module's own tests.
+	fn default() -> Self {
+		Self {
+			initial_delay: std::time::Duration::from_secs(1),
+			max_delay: std::time::Duration::from_secs(60),
+			max_attempts: None,
+		}
+	}
+}
+
+impl ReconnectPolicy {
+	/// A fresh [`ReconnectBackoff`] configured with this policy's delays.
+	pub fn backoff(&self) -> ReconnectBackoff {
+		ReconnectBackoff::new(self.initial_delay, self.max_delay)
+	}
+
+	/// Whether `backoff`'s attempt count has reached `max_attempts` (always
+	/// `false` under the infinite-retry `None`).
+	pub fn is_exhausted(&self, backoff: &ReconnectBackoff) -> bool {
+		matches!(self.max_attempts, Some(max) if backoff.attempt() >= max)
+	}
+}
+
+/// The randomized delay a worker's connect loop should wait before dialing an
+/// endpoint for the *first* time, independent of [`ReconnectBackoff`] (which
+/// only starts backing off once a connection has already been attempted and
+/// dropped once).
+///
+/// A hosting provider restarting hundreds of validators at once has them all
+/// dial the same public telemetry backend in the same second; spreading those
+/// first connections out with jitter avoids that reconnect storm. This crate
+/// slice has no socket of its own (see the module-level scope note), so like
+/// `ReconnectBackoff` this only computes the delay — it never blocks anything
+/// itself, and in particular must never hold back node startup, only the
+/// telemetry connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InitialConnectionDelay {
+	max: std::time::Duration,
+}
+
+impl Default for InitialConnectionDelay {
+	/// Jitter uniformly between zero and 5 seconds.
+	fn default() -> Self {
+		Self { max: std::time::Duration::from_secs(5) }
+	}
+}
+
+impl InitialConnectionDelay {
+	/// Jitter uniformly between zero and `max`.
+	pub fn up_to(max: std::time::Duration) -> Self {
+		Self { max }
+	}
+
+	/// No delay: a worker should dial immediately. Useful for tests, and for
+	/// deployments (e.g. a private backend, or one that already staggers
+	/// restarts itself) that don't need the jitter.
+	pub fn disabled() -> Self {
+		Self { max: std::time::Duration::ZERO }
+	}
+
+	/// The delay to wait before dialing an endpoint for the first time,
+	/// sampled uniformly from `[0, max)` by `jitter_sample` (expected in
+	/// `[0.0, 1.0)`). Always `Duration::ZERO` once `max` is zero, regardless
+	/// of `jitter_sample`. `rand` isn't a dependency of this crate, so the
+	/// caller supplies the random sample, the same way
+	/// [`ReconnectBackoff::next_delay`] does.
+	pub fn sample(&self, jitter_sample: f64) -> std::time::Duration {
+		self.max.mul_f64(jitter_sample.clamp(0.0, 1.0))
+	}
+}
+
+/// Give up on the telemetry span `id` once `policy` is exhausted for `backoff`:
+/// drops its sender from `senders` so producers stop paying serialization
+/// costs for an endpoint nobody is listening on any more, and logs a single
+/// clear message. Returns whether the policy was exhausted; safe to call
+/// repeatedly afterwards; there's simply nothing left to remove the second
+/// time.
+pub fn give_up_if_exhausted(
+	policy: &ReconnectPolicy,
+	backoff: &ReconnectBackoff,
+	senders: &Senders,
+	id: u64,
+) -> bool {
+	if !policy.is_exhausted(backoff) {
+		return false;
+	}
+	if senders.remove(id).is_some() {
+		log::warn!(
+			target: "telemetry",
+			"Giving up on telemetry id {} after {} reconnect attempts",
+			id,
+			backoff.attempt(),
+		);
+	}
+	true
+}
+
+/// Groups queued telemetry messages into batches, flushed either once
+/// `max_batch_size` messages have accumulated or `linger` has elapsed since
+/// the oldest unflushed message arrived, whichever comes first — cutting
+/// down on per-message websocket frames and syscalls during a message storm
+/// like a fast sync.
+///
+/// Takes the current time as an explicit `Instant` argument rather than
+/// calling `Instant::now()` internally, so tests can drive it with a mock
+/// clock; a real worker would pass `Instant::now()` at each push and on each
+/// tick of its own polling interval. Messages are returned in the order they
+/// were pushed. `system.connected` (and anything else that must reach the
+/// backend immediately) should be sent outside the batcher entirely, since a
+/// stale "am I connected" state on the dashboard is worse than one extra
+/// frame.
+#[derive(Debug)]
+pub struct MessageBatcher {
+	max_batch_size: usize,
+	linger: std::time::Duration,
+	pending: Vec<(Verbosity, String)>,
+	oldest_pending_at: Option<std::time::Instant>,
+}
+
+impl MessageBatcher {
+	pub fn new(max_batch_size: usize, linger: std::time::Duration) -> Self {
+		Self { max_batch_size, linger, pending: Vec::new(), oldest_pending_at: None }
+	}
+
+	/// Queue `message`. Returns a full batch, in push order, if this push
+	/// reached `max_batch_size`.
+	pub fn push(&mut self, message: (Verbosity, String), now: std::time::Instant) -> Option<Vec<(Verbosity, String)>> {
+		if self.pending.is_empty() {
+			self.oldest_pending_at = Some(now);
+		}
+		self.pending.push(message);
+		if self.pending.len() >= self.max_batch_size {
+			Some(self.take())
+		} else {
+			None
+		}
+	}
+
+	/// Flush whatever is pending if `linger` has elapsed since the oldest
+	/// pending message, meant to be called on every tick of the worker's own
+	/// polling interval independent of pushes, so a trickle of messages
+	/// below `max_batch_size` still goes out promptly.
+	pub fn poll_linger(&mut self, now: std::time::Instant) -> Option<Vec<(Verbosity, String)>> {
+		match self.oldest_pending_at {
+			Some(started) if now.duration_since(started) >= self.linger => Some(self.take()),
+			_ => None,
+		}
+	}
+
+	fn take(&mut self) -> Vec<(Verbosity, String)> {
+		self.oldest_pending_at = None;
+		std::mem::take(&mut self.pending)
+	}
+}
+
+/// Ping/pong keepalive watchdog for one telemetry websocket connection,
+/// detecting a NAT gateway or firewall that silently drops the TCP
+/// connection long before the kernel's own timeout would notice, so the
+/// node doesn't keep "sending" into a black hole for minutes while the
+/// dashboard shows it offline.
+///
+/// This crate slice has no websocket transport of its own; `KeepaliveWatchdog`
+/// is the pure state machine a worker's connection loop (outside this crate
+/// slice) would drive — call [`should_ping`](Self::should_ping) on each tick
+/// of its own interval, send an actual websocket ping when it returns
+/// `true`, call [`on_pong`](Self::on_pong) when one arrives, and treat
+/// [`is_dead`](Self::is_dead) returning `true` as "tear down and reconnect
+/// through the normal [`ReconnectBackoff`] path". It only applies to
+/// `wss://` endpoints; a worker simply never constructs one for a file/UDS
+/// sink, so it can't interfere with either.
+#[derive(Debug, Clone)]
+pub struct KeepaliveWatchdog {
+	interval: std::time::Duration,
+	timeout: std::time::Duration,
+	last_ping_sent: Option<std::time::Instant>,
+	last_pong_received: std::time::Instant,
+}
+
+impl KeepaliveWatchdog {
+	pub fn new(interval: std::time::Duration, timeout: std::time::Duration, now: std::time::Instant) -> Self {
+		Self { interval, timeout, last_ping_sent: None, last_pong_received: now }
+	}
+
+	/// Whether a ping is due at `now`, measured from the last pong received
+	/// (or construction) if no ping is currently outstanding, or from the
+	/// last ping sent otherwise. Marks a ping as sent when it returns `true`.
+	pub fn should_ping(&mut self, now: std::time::Instant) -> bool {
+		let due = match self.last_ping_sent {
+			Some(sent) => now.saturating_duration_since(sent) >= self.interval,
+			None => now.saturating_duration_since(self.last_pong_received) >= self.interval,
+		};
+		if due {
+			self.last_ping_sent = Some(now);
+		}
+		due
+	}
+
+	/// Record that a pong arrived at `now`, clearing any outstanding ping.
+	pub fn on_pong(&mut self, now: std::time::Instant) {
+		self.last_pong_received = now;
+		self.last_ping_sent = None;
+	}
+
+	/// Whether `timeout` has elapsed since the last pong (or construction)
+	/// without a fresh one arriving — i.e. the connection should be treated
+	/// as dead and torn down.
+	pub fn is_dead(&self, now: std::time::Instant) -> bool {
+		now.saturating_duration_since(self.last_pong_received) >= self.timeout
+	}
+}
+
+/// Bounded buffer retaining telemetry messages produced while a telemetry
+/// endpoint is disconnected, so a reconnect doesn't leave a gap in e.g.
+/// block-import history on the dashboard. Flushed in order (oldest first)
+/// once the connection is re-established via [`drain`](Self::drain).
+///
+/// Like [`Senders`]' overflow handling, messages whose `msg` type is in
+/// `coalesce` replace the last buffered message of the same type in place
+/// instead of taking up a new slot; every other type simply queues, evicting
+/// the oldest buffered message once `capacity` is reached.
+#[derive(Debug)]
+pub struct ReplayBuffer {
+	capacity: usize,
+	coalesce: HashSet<String>,
+	ack_mode: bool,
+	entries: std::collections::VecDeque<(Option<String>, Option<u64>, Verbosity, String)>,
+	evicted: u64,
+}
+
+/// Parse a collector's application-level acknowledgment frame —
+/// `{"ack": <seq>}` — into the acknowledged `seq`. `None` for anything else
+/// a collector might send (malformed JSON, an unrelated frame), so a caller
+/// can feed every [`TelemetryTransport::incoming`] line through this and
+/// ignore the ones that come back `None`.
+pub fn parse_ack_frame(line: &str) -> Option<u64> {
+	serde_json::from_str::<serde_json::Value>(line).ok()?.get("ack")?.as_u64()
+}
+
+/// The `seq` field [`Senders::next_seq`] stamps onto every outgoing
+/// payload, if `json` has one — used to tag buffered entries so
+/// [`ReplayBuffer::ack`] can tell which ones a `{"ack": <seq>}` frame
+/// covers.
+fn extract_seq(json: &str) -> Option<u64> {
+	serde_json::from_str::<serde_json::Value>(json).ok()?.get("seq")?.as_u64()
+}
+
+impl ReplayBuffer {
+	/// A buffer retaining at most `capacity` messages, coalescing
+	/// `"system.interval"`-style messages by default (matching
+	/// [`SenderConfig`]'s default).
+	pub fn new(capacity: usize) -> Self {
+		Self {
+			capacity,
+			coalesce: ["system.interval".to_string()].into_iter().collect(),
+			ack_mode: false,
+			entries: std::collections::VecDeque::new(),
+			evicted: 0,
+		}
+	}
+
+	/// Override which `msg` types coalesce instead of queueing.
+	pub fn with_coalesce(mut self, coalesce: HashSet<String>) -> Self {
+		self.coalesce = coalesce;
+		self
+	}
+
+	/// Switch this buffer into ack-driven mode: a worker should drive it
+	/// with [`pending`](Self::pending) and [`ack`](Self::ack) instead of
+	/// [`drain`](Self::drain), so a buffered message survives being handed
+	/// to the transport until the collector actually acknowledges it — see
+	/// [`EndpointAckModes`]. Off (fire-and-forget, the original behavior)
+	/// by default.
+	pub fn with_ack_mode(mut self, enabled: bool) -> Self {
+		self.ack_mode = enabled;
+		self
+	}
+
+	/// Whether this buffer is in [`with_ack_mode`](Self::with_ack_mode).
+	pub fn is_ack_mode(&self) -> bool {
+		self.ack_mode
+	}
+
+	/// Buffer `message` (tagged with the optional `msg_type` for coalescing).
+	pub fn push(&mut self, msg_type: Option<&str>, message: (Verbosity, String)) {
+		if self.capacity == 0 {
+			self.evicted += 1;
+			return;
+		}
+		let seq = extract_seq(&message.1);
+		if let Some(msg_type) = msg_type.filter(|t| self.coalesce.contains(*t)) {
+			if let Some(existing) =
+				self.entries.iter_mut().find(|(t, _, _, _)| t.as_deref() == Some(msg_type))
+			{
+				*existing = (Some(msg_type.to_string()), seq, message.0, message.1);
+				return;
+			}
+		}
+		if self.entries.len() >= self.capacity {
+			self.entries.pop_front();
+			self.evicted += 1;
+		}
+		self.entries.push_back((msg_type.map(|t| t.to_string()), seq, message.0, message.1));
+	}
+
+	/// Number of messages currently buffered.
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	/// Whether the buffer currently holds no messages.
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+
+	/// Number of messages evicted so far because the buffer was full (or, for a
+	/// zero-capacity buffer, every message pushed to it).
+	pub fn evicted(&self) -> u64 {
+		self.evicted
+	}
+
+	/// Drain every buffered message in the order it was produced (oldest
+	/// first), for a worker to replay once reconnected. For an
+	/// [`ack`-mode](Self::with_ack_mode) buffer, use
+	/// [`pending`](Self::pending) instead: this removes messages
+	/// unconditionally, which loses them for good if the retransmit is lost
+	/// too before the collector acks it.
+	pub fn drain(&mut self) -> Vec<(Verbosity, String)> {
+		self.entries.drain(..).map(|(_, _, verbosity, json)| (verbosity, json)).collect()
+	}
+
+	/// Every currently-buffered message, oldest first, without removing
+	/// them — the [`ack`-mode](Self::with_ack_mode) counterpart to
+	/// [`drain`](Self::drain). A worker calls this on every (re)connect to
+	/// (re)transmit whatever the collector hasn't acked yet; nothing is
+	/// removed until a matching [`ack`](Self::ack) call says the collector
+	/// actually has it, so a retransmit that's lost too is simply resent
+	/// again next reconnect.
+	pub fn pending(&self) -> Vec<(Verbosity, String)> {
+		self.entries.iter().map(|(_, _, verbosity, json)| (*verbosity, json.clone())).collect()
+	}
+
+	/// Evict every buffered message whose `seq` is `<= seq`, i.e. every
+	/// message the collector's `{"ack": <seq>}` frame (see
+	/// [`parse_ack_frame`]) confirms it already has. A message pushed
+	/// without a parseable `seq` (not expected for anything produced by
+	/// this crate, whose outgoing payloads are always `seq`-stamped — see
+	/// [`Senders::next_seq`]) is never evicted this way, since there's no
+	/// `seq` to compare against; it still drains normally via
+	/// [`drain`](Self::drain). Returns the number of entries evicted.
+	pub fn ack(&mut self, seq: u64) -> usize {
+		let before = self.entries.len();
+		self.entries.retain(|(_, entry_seq, _, _)| !matches!(entry_seq, Some(entry_seq) if *entry_seq <= seq));
+		before - self.entries.len()
+	}
+}
+
+/// Opt-in on-disk backing for a [`ReplayBuffer`], so buffered messages
+/// survive a node restart while the collector was unreachable — audit-minded
+/// operators don't want a coincidental restart to silently drop telemetry.
+///
+/// Messages are appended as `{verbosity}\t{json}` lines to a numbered segment
+/// file (`{prefix}.{segment}.log`) in `directory`, mirroring [`FileSink`]'s
+/// ndjson-with-immediate-flush approach so a reader tailing the active
+/// segment never sees a partial line. Once the active segment reaches
+/// `segment_bytes`, a fresh segment is opened; once the combined size of all
+/// segments exceeds `max_bytes`, the oldest segment is deleted whole rather
+/// than rewriting a file in place — eviction is a whole segment at a time,
+/// same granularity tradeoff [`FileSink`] makes for rotation.
+///
+/// [`open`](Self::open) replays every segment found on disk back into memory
+/// before any new message is pushed, so a worker can deliver what survived
+/// the restart ahead of new traffic. A segment whose tail is corrupt or
+/// truncated (e.g. the process died mid-write) has its bad tail skipped
+/// rather than failing the whole load — every line up to the first one that
+/// doesn't parse is kept.
+pub struct PersistentReplayBuffer {
+	directory: PathBuf,
+	prefix: String,
+	segment_bytes: u64,
+	max_bytes: u64,
+	segments: std::collections::VecDeque<(u64, u64)>,
+	next_segment: u64,
+	active_file: Option<std::fs::File>,
+}
+
+impl PersistentReplayBuffer {
+	/// Open (creating if necessary) the persisted buffer rooted at
+	/// `directory`, and replay every message found there in production order.
+	/// Segments roll at `segment_bytes` and are evicted oldest-first once
+	/// their combined size exceeds `max_bytes`.
+	pub fn open(
+		directory: PathBuf,
+		prefix: impl Into<String>,
+		segment_bytes: u64,
+		max_bytes: u64,
+	) -> std::io::Result<(Self, Vec<(Verbosity, String)>)> {
+		let prefix = prefix.into();
+		std::fs::create_dir_all(&directory)?;
+
+		let mut segments: Vec<(u64, u64)> = Vec::new();
+		let entry_prefix = format!("{}.", prefix);
+		for entry in std::fs::read_dir(&directory)? {
+			let entry = entry?;
+			let name = entry.file_name();
+			let Some(name) = name.to_str() else { continue };
+			let Some(rest) = name.strip_prefix(&entry_prefix) else { continue };
+			let Some(number) = rest.strip_suffix(".log") else { continue };
+			if let Ok(number) = number.parse::<u64>() {
+				let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+				segments.push((number, size));
+			}
+		}
+		segments.sort_unstable_by_key(|(number, _)| *number);
+
+		let mut replayed = Vec::new();
+		for (number, _) in &segments {
+			let path = directory.join(format!("{}{}.log", entry_prefix, number));
+			let Ok(contents) = std::fs::read_to_string(&path) else { continue };
+			for line in contents.lines() {
+				let Some((verbosity, json)) = line.split_once('\t') else { break };
+				let Ok(verbosity) = verbosity.parse::<u8>() else { break };
+				if serde_json::from_str::<serde_json::Value>(json).is_err() {
+					break;
+				}
+				replayed.push((Verbosity::from(verbosity), json.to_string()));
+			}
+		}
+
+		let next_segment = segments.last().map(|(number, _)| number + 1).unwrap_or(0);
+		let mut buffer = Self {
+			directory,
+			prefix,
+			segment_bytes,
+			max_bytes,
+			segments: segments.into_iter().collect(),
+			next_segment,
+			active_file: None,
+		};
+		buffer.evict_while_over_budget();
+		Ok((buffer, replayed))
+	}
+
+	/// Append `message` to the active segment, rolling to a fresh segment
+	/// first if the active one has reached `segment_bytes`, then evicting the
+	/// oldest segments until the total is back under `max_bytes`. Best-effort:
+	/// an IO error is logged and swallowed rather than propagated, matching
+	/// [`FileSink::write`] — a persistence hiccup shouldn't take telemetry
+	/// down.
+	pub fn push(&mut self, verbosity: Verbosity, json: &str) {
+		if let Err(err) = self.try_push(verbosity, json) {
+			log::warn!(target: "telemetry", "Ignored telemetry persistence write because of IO error: {:?}", err);
+		}
+	}
+
+	fn try_push(&mut self, verbosity: Verbosity, json: &str) -> std::io::Result<()> {
+		let needs_roll = match self.segments.back() {
+			Some((_, size)) => self.active_file.is_none() || *size >= self.segment_bytes,
+			None => true,
+		};
+		if needs_roll {
+			self.roll_segment()?;
+		}
+
+		let line = format!("{}\t{}\n", verbosity.as_u8(), json);
+		let file = self.active_file.as_mut().expect("just rolled a fresh segment");
+		file.write_all(line.as_bytes())?;
+		file.flush()?;
+		let (_, size) = self.segments.back_mut().expect("just rolled a fresh segment");
+		*size += line.len() as u64;
+
+		self.evict_while_over_budget();
+		Ok(())
+	}
+
+	fn roll_segment(&mut self) -> std::io::Result<()> {
+		let number = self.next_segment;
+		self.next_segment += 1;
+		let path = self.directory.join(format!("{}{}.log", self.prefix, number));
+		let file = std::fs::OpenOptions::new().create(true).append(true).open(&path)?;
+		self.active_file = Some(file);
+		self.segments.push_back((number, 0));
+		Ok(())
+	}
+
+	/// Delete whole segments, oldest first, while the persisted total exceeds
+	/// `max_bytes` — but never the active segment, so a burst of traffic can't
+	/// evict the message it's currently writing.
+	fn evict_while_over_budget(&mut self) {
+		while self.segments.len() > 1
+			&& self.segments.iter().map(|(_, size)| size).sum::<u64>() > self.max_bytes
+		{
+			let Some((number, _)) = self.segments.pop_front() else { break };
+			let path = self.directory.join(format!("{}{}.log", self.prefix, number));
+			let _ = std::fs::remove_file(path);
+		}
+	}
+}
+
+/// A closure supplying one field of the heartbeat [`PeriodicReporter`]
+/// assembles each tick, e.g. peer count or best block height. Returning
+/// `None` omits the field for that tick rather than sending a JSON `null`,
+/// so a metric that isn't available yet (still syncing, no peers seen)
+/// doesn't have to fake a value.
+pub type PeriodicMetric = Box<dyn Fn() -> Option<serde_json::Value> + Send + Sync>;
+
+/// What [`PeriodicReporter::tick`] did on a given call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeriodicTick {
+	/// `interval` hasn't elapsed since the last tick that did something.
+	NotDue,
+	/// `id` isn't connected and no replay buffer is in play, so nothing was
+	/// gathered or sent.
+	Paused,
+	/// The previous heartbeat hasn't drained from `id`'s channel yet, so
+	/// this tick's metrics weren't even gathered.
+	Skipped,
+	/// Metrics were gathered and handed to [`Telemetries::send`].
+	Sent,
+}
+
+/// Assembles and sends the periodic heartbeat (peer count, best block,
+/// finalized block, txpool size, ...) that every node implementation
+/// otherwise hand-rolls its own timer loop for. An embedder registers one
+/// closure per field via [`metric`](Self::metric); this type takes care of
+/// gathering them on a schedule, skipping a tick whose predecessor is still
+/// queued, and pausing altogether while disconnected.
+///
+/// Ticking is driven externally via [`tick`](Self::tick) rather than this
+/// type spawning its own timer task — this crate slice has no task
+/// executor of its own (see the module-level scope note) — so an embedder
+/// calls `tick` from whatever interval primitive it already runs, e.g. once
+/// per wakeup of a `tokio::time::interval`. `tick` takes the current time as
+/// an explicit `Instant` rather than calling `Instant::now()` internally, so
+/// tests can drive it with a mock clock (see [`RateLimiter`] for the same
+/// pattern).
+pub struct PeriodicReporter {
+	id: u64,
+	telemetries: Telemetries,
+	interval: std::time::Duration,
+	verbosity: Verbosity,
+	metrics: Vec<(String, PeriodicMetric)>,
+	replay_buffer_enabled: bool,
+	last_tick: Option<std::time::Instant>,
+	awaiting_delivery: bool,
+}
+
+impl PeriodicReporter {
+	/// A reporter sending under `id` every `interval`, at [`Verbosity::INFO`]
+	/// (matching `system.interval`'s own register; see
+	/// [`msg::SystemInterval`]) until [`with_verbosity`](Self::with_verbosity)
+	/// says otherwise.
+	pub fn new(telemetries: Telemetries, id: u64, interval: std::time::Duration) -> Self {
+		Self {
+			id,
+			telemetries,
+			interval,
+			verbosity: Verbosity::INFO,
+			metrics: Vec::new(),
+			replay_buffer_enabled: false,
+			last_tick: None,
+			awaiting_delivery: false,
+		}
+	}
+
+	/// Register `metric` under `field`, alongside any others already added.
+	/// Evaluated fresh every tick, in registration order, into one assembled
+	/// JSON object.
+	pub fn metric(
+		mut self,
+		field: impl Into<String>,
+		metric: impl Fn() -> Option<serde_json::Value> + Send + Sync + 'static,
+	) -> Self {
+		self.metrics.push((field.into(), Box::new(metric)));
+		self
+	}
+
+	/// Send at `verbosity` instead of the default [`Verbosity::INFO`].
+	pub fn with_verbosity(mut self, verbosity: impl Into<Verbosity>) -> Self {
+		self.verbosity = verbosity.into();
+		self
+	}
+
+	/// Don't pause while `id` is disconnected: a [`ReplayBuffer`] downstream
+	/// will hold heartbeats until it reconnects, so there's no reason to stop
+	/// assembling them in the meantime. Off by default.
+	pub fn with_replay_buffer(mut self, enabled: bool) -> Self {
+		self.replay_buffer_enabled = enabled;
+		self
+	}
+
+	/// Assemble and send this tick's heartbeat if `interval` has elapsed
+	/// since the last tick that did something, `id` is connected (or a
+	/// replay buffer is in play), and the previous heartbeat isn't still
+	/// queued. See [`PeriodicTick`] for what each outcome means.
+	pub fn tick(&mut self, now: std::time::Instant) -> PeriodicTick {
+		if let Some(last_tick) = self.last_tick {
+			if now.saturating_duration_since(last_tick) < self.interval {
+				return PeriodicTick::NotDue;
+			}
+		}
+		self.last_tick = Some(now);
+
+		if !self.telemetries.is_connected(self.id) && !self.replay_buffer_enabled {
+			return PeriodicTick::Paused;
+		}
+		if self.awaiting_delivery {
+			return PeriodicTick::Skipped;
+		}
+
+		let mut fields = serde_json::Map::new();
+		for (field, metric) in &self.metrics {
+			if let Some(value) = metric() {
+				fields.insert(field.clone(), value);
+			}
+		}
+		let delivered = self.telemetries.send(self.id, self.verbosity, serde_json::Value::Object(fields));
+		self.awaiting_delivery = !delivered;
+		PeriodicTick::Sent
+	}
+}
+
+/// A change in a telemetry endpoint's connection state, as published by
+/// [`ConnectionEvents`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionEvent {
+	Connected { endpoint: String },
+	Disconnected { endpoint: String, reason: String },
+}
+
+/// Tracks whether each telemetry id is currently connected, and broadcasts
+/// [`ConnectionEvent`]s to every interested subscriber.
+///
+/// Broadcast-style: [`subscribe`](Self::subscribe) hands out an independent
+/// `mpsc::UnboundedReceiver`, so multiple consumers (diagnostics RPC, a log
+/// bridge, tests) can watch connection state without contending with each
+/// other. The channel is unbounded so a consumer that never polls can't block
+/// whoever is publishing events; it simply accumulates events (and is dropped,
+/// pruning itself out) if nobody ever reads them.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionEvents {
+	connected: Arc<Mutex<HashMap<u64, bool>>>,
+	subscribers: Arc<Mutex<Vec<mpsc::UnboundedSender<ConnectionEvent>>>>,
+}
+
+impl ConnectionEvents {
+	/// Whether `id` is currently connected. `false` if `id` has never been
+	/// reported connected.
+	pub fn is_connected(&self, id: u64) -> bool {
+		self.connected.lock().get(&id).copied().unwrap_or(false)
+	}
+
+	/// Record `id` as connected to `endpoint` and publish [`ConnectionEvent::Connected`].
+	pub fn set_connected(&self, id: u64, endpoint: impl Into<String>) {
+		self.connected.lock().insert(id, true);
+		self.publish(ConnectionEvent::Connected { endpoint: endpoint.into() });
+	}
+
+	/// Record `id` as disconnected from `endpoint` (for `reason`) and publish
+	/// [`ConnectionEvent::Disconnected`].
+	pub fn set_disconnected(&self, id: u64, endpoint: impl Into<String>, reason: impl Into<String>) {
+		self.connected.lock().insert(id, false);
+		self.publish(ConnectionEvent::Disconnected { endpoint: endpoint.into(), reason: reason.into() });
+	}
+
+	fn publish(&self, event: ConnectionEvent) {
+		self.subscribers.lock().retain(|subscriber| subscriber.unbounded_send(event.clone()).is_ok());
+	}
+
+	/// Subscribe to future connection events. Past events aren't replayed.
+	pub fn subscribe(&self) -> mpsc::UnboundedReceiver<ConnectionEvent> {
+		let (tx, rx) = mpsc::unbounded();
+		self.subscribers.lock().push(tx);
+		rx
+	}
+}
+
+/// A distributed trace root associated with a telemetry span.
+#[derive(Debug, Clone)]
+pub struct TraceRoot {
+	/// The distributed trace id shared across all nodes handling the same work.
+	pub trace_id: String,
+	/// The span id of the remote parent that initiated this trace, if any.
+	pub remote_parent_id: Option<String>,
+}
+
+/// Per-span registry of distributed trace roots, keyed like [`Senders`] by
+/// `span.id().into_u64()`.
+///
+/// Populated via [`Telemetries::register_trace_root`] and read in `on_event` to
+/// stamp `trace_id` / `parent_span_id` into each telemetry payload. Entries are
+/// removed when their span closes (see `on_close`).
+///
+/// Because the root is keyed by the nearest `TELEMETRY_LOG_SPAN`, registration is
+/// last-write-wins within that span: a root registered while a long-lived shared
+/// telemetry span is current would be applied to every payload under it. Callers
+/// that need per-work correlation must therefore register under a per-request
+/// telemetry span (opened for the unit of work and closed when it completes),
+/// which the `on_close` cleanup then retires.
+///
+/// This lookup always walks for the default `TELEMETRY_LOG_SPAN` name, even
+/// though [`TelemetryLayer::with_target`]/[`with_instance_id`](TelemetryLayer::with_instance_id)
+/// let a layer dispatch on a different target: `TraceRoots` lives on
+/// [`Telemetries`], which this crate slice doesn't declare (it's constructed
+/// outside `layer.rs`), so it has nowhere to hold a matching `target` field
+/// today. In a multi-instance-per-process setup, trace roots therefore only
+/// register correctly against the default-target instance until `Telemetries`
+/// itself grows a `target` to thread through here.
+#[derive(Default, Debug, Clone)]
+pub struct TraceRoots(Arc<Mutex<HashMap<u64, TraceRoot>>>);
+
+impl TraceRoots {
+	fn register(
+		&self,
+		trace_id: String,
+		remote_parent_id: Option<String>,
+	) -> Result<(), NoEnabledSpan> {
+		// Walk the current span scope for the nearest `TELEMETRY_LOG_SPAN` and key the
+		// association by its id, matching the lookup `on_event` performs. A non-telemetry
+		// span (or no span at all) being current yields `NoEnabledSpan` rather than
+		// stashing the root under an id that is never read back.
+		//
+		// Always the default name, not a per-instance target: see the caveat on
+		// `TraceRoots` above.
+		tracing::dispatcher::get_default(|dispatch| {
+			let id = dispatch.current_span().id().cloned().ok_or(NoEnabledSpan)?;
+			let registry = dispatch
+				.downcast_ref::<tracing_subscriber::Registry>()
+				.ok_or(NoEnabledSpan)?;
+			let telemetry_id = registry
+				.span(&id)
+				.ok_or(NoEnabledSpan)?
+				.scope()
+				.find(|x| x.name() == TELEMETRY_LOG_SPAN)
+				.ok_or(NoEnabledSpan)?
+				.id()
+				.into_u64();
+			self.0.lock().insert(
+				telemetry_id,
+				TraceRoot {
+					trace_id: trace_id.clone(),
+					remote_parent_id: remote_parent_id.clone(),
+				},
+			);
+			Ok(())
+		})
+	}
+
+	/// Drop the trace root registered against `id`, if any. Called from `on_close`
+	/// so roots don't outlive the span they describe.
+	fn remove(&self, id: u64) {
+		self.0.lock().remove(&id);
+	}
+}
+
+/// Counts telemetry events dropped for missing a required field or carrying a
+/// malformed payload, reachable from [`Telemetries::malformed_event_count`] so
+/// operators can notice a misbehaving producer instead of it silently vanishing
+/// into a `log::warn!` line.
+///
+/// Logging each occurrence is throttled to once per second so a producer stuck
+/// emitting malformed events in a tight loop can't flood the log.
+#[derive(Default, Debug, Clone)]
+pub struct MalformedEventCounter(Arc<Mutex<MalformedEventCounterInner>>);
+
+#[derive(Default, Debug)]
+struct MalformedEventCounterInner {
+	count: u64,
+	last_logged: Option<std::time::Instant>,
+}
+
+impl MalformedEventCounter {
+	fn increment(&self) {
+		self.0.lock().count += 1;
+	}
+
+	fn count(&self) -> u64 {
+		self.0.lock().count
+	}
+
+	/// Log `message` at `error` level, unless another malformed event was already
+	/// logged within the last second.
+	fn log(&self, message: &str) {
+		let mut inner = self.0.lock();
+		let now = std::time::Instant::now();
+		let should_log = match inner.last_logged {
+			Some(last) => now.duration_since(last) >= std::time::Duration::from_secs(1),
+			None => true,
+		};
+		if should_log {
+			inner.last_logged = Some(now);
+			log::error!(target: "telemetry", "{}", message);
+		}
+	}
+}
+
+/// Error returned when a distributed trace root is registered while no telemetry
+/// span is active on the current thread.
+#[derive(Debug)]
+pub struct NoEnabledSpan;
+
+impl std::fmt::Display for NoEnabledSpan {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "no telemetry span is currently active")
+	}
+}
+
+impl std::error::Error for NoEnabledSpan {}
+
+/// Error returned by a [`TelemetryTransport`] when it fails to (re)connect.
+/// `kind` classifies the failure the same way
+/// [`EndpointConnectionStatus::record_disconnected`] does, so a worker can
+/// forward it into the connection status without having to guess at a
+/// classification from `message` itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransportConnectError {
+	pub kind: EndpointErrorKind,
+	pub message: String,
+}
+
+impl TransportConnectError {
+	pub fn new(kind: EndpointErrorKind, message: impl Into<String>) -> Self {
+		Self { kind, message: message.into() }
+	}
+}
+
+impl std::fmt::Display for TransportConnectError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "telemetry transport failed to connect: {}", self.message)
+	}
+}
+
+impl std::error::Error for TransportConnectError {}
+
+/// A websocket close frame as sent by the peer — code and (optionally
+/// empty) reason string, per [RFC 6455 §7.4](https://www.rfc-editor.org/rfc/rfc6455#section-7.4).
+/// [`TelemetryTransport::closed`] hands one back when the peer's close was
+/// an actual framed close rather than the connection simply dropping (a
+/// reset, a mock's unscripted disconnect), so [`run_endpoint`] can tell
+/// "the collector told us why" apart from "the socket just went away".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloseFrame {
+	pub code: u16,
+	pub reason: String,
+}
+
+/// A pluggable destination for telemetry bytes, so downstream node authors
+/// can ship a Kafka, NATS or vendor-specific sink without forking this crate.
+/// The built-in websocket path is just one implementation; a worker is
+/// generic over this trait rather than hard-wired to it.
+///
+/// Methods return boxed futures instead of using `async fn` so the trait
+/// stays object-safe without an extra `async-trait`-style dependency,
+/// letting a worker hold a `HashMap<String, Box<dyn TelemetryTransport>>`
+/// and freely mix transport kinds across endpoints in one node.
+pub trait TelemetryTransport: Send + Sync {
+	/// Establish (or re-establish) the connection, returning a sender the
+	/// worker uses to push serialized telemetry lines to it. Reconnect
+	/// attempts go through the same call, so a transport that fails here
+	/// feeds naturally into [`ReconnectBackoff`] / [`ReconnectPolicy`]
+	/// rather than needing bespoke error handling per transport.
+	fn connect(
+		&self,
+	) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<mpsc::Sender<String>, TransportConnectError>> + Send + '_>>;
+
+	/// Resolves once the current connection has closed (locally or by the
+	/// peer), so a worker can react — reconnect, flush a [`ReplayBuffer`] —
+	/// without polling. Carries the peer's [`CloseFrame`] when it sent one
+	/// (see [`run_endpoint`]'s close-code handling); `None` for a connection
+	/// that just dropped with no close frame, the same as most transports'
+	/// behavior on a reset or an unscripted disconnect.
+	fn closed(&self) -> std::pin::Pin<Box<dyn std::future::Future<Output = Option<CloseFrame>> + Send + '_>>;
+
+	/// Frames received from the peer since the last call, oldest first —
+	/// e.g. a collector's `{"ack": <seq>}` acknowledgments (see
+	/// [`parse_ack_frame`]). Empty by default: most transports (every one
+	/// built directly against this crate slice today) are fire-and-forget
+	/// and never call this. A transport backing an ack-mode endpoint (see
+	/// [`EndpointAckModes`]) overrides it to hand back whatever it's
+	/// buffered; [`test_utils::MockTelemetryServer`] is the example this
+	/// crate's own tests drive.
+	fn incoming(&self) -> Vec<String> {
+		Vec::new()
+	}
+}
+
+/// Lets a caller hand a transport to [`run_endpoint`] as `Box<dyn
+/// TelemetryTransport>` while keeping an `Arc` of the same value to drive
+/// test-only methods on it concurrently — [`test_utils::MockTelemetryServer`]
+/// scripted from the test thread while a worker owns the boxed clone is the
+/// motivating case.
+impl<T: TelemetryTransport + ?Sized> TelemetryTransport for Arc<T> {
+	fn connect(
+		&self,
+	) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<mpsc::Sender<String>, TransportConnectError>> + Send + '_>>
+	{
+		(**self).connect()
+	}
+
+	fn closed(&self) -> std::pin::Pin<Box<dyn std::future::Future<Output = Option<CloseFrame>> + Send + '_>> {
+		(**self).closed()
+	}
+
+	fn incoming(&self) -> Vec<String> {
+		(**self).incoming()
+	}
+}
+
+/// A pluggable source of reconnect/keepalive delays, so a worker built on top
+/// of this crate slice never has to name a specific async runtime's timer.
+/// [`ReconnectBackoff::next_delay`] already hands back a bare
+/// `std::time::Duration` rather than sleeping itself, for exactly this
+/// reason; `DelayFactory` is the trait a worker holds onto to turn that
+/// `Duration` into something it can `.await`, implemented once per executor
+/// (tokio's `sleep`, async-std's `task::sleep`, or a browser `setTimeout`
+/// wrapper) instead of scattered across every call site that needs to wait.
+///
+/// Object-safe for the same reason as [`TelemetryTransport`]: a boxed future
+/// instead of `async fn`, so a worker can hold a `Box<dyn DelayFactory>`
+/// chosen at startup without an extra `async-trait`-style dependency.
+pub trait DelayFactory: Send + Sync {
+	/// A future that resolves no earlier than `duration` from now.
+	fn delay(&self, duration: std::time::Duration) -> std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send>>;
+}
+
+/// Adapters wiring [`DelayFactory`] and, indirectly via a worker built on
+/// [`TelemetryTransport`], a full telemetry client to a specific async
+/// runtime.
+///
+/// Both are `unimplemented!` rather than real code: this crate slice has no
+/// `Cargo.toml` to declare the `tokio`/`async-std` dependencies or the
+/// feature flags gating them against (compare
+/// [`prometheus_metrics`](self::prometheus_metrics), the other module in
+/// this file with the same limitation). They document the shape the real
+/// integration would take — one `DelayFactory` impl per runtime, each a thin
+/// wrapper around that runtime's own sleep function — for whoever adds the
+/// manifest.
+pub mod runtime_adapters {
+	/// `DelayFactory` backed by `tokio::time::sleep`.
+	#[cfg(feature = "tokio")]
+	pub struct TokioDelayFactory;
+
+	#[cfg(feature = "tokio")]
+	impl super::DelayFactory for TokioDelayFactory {
+		fn delay(
+			&self,
+			_duration: std::time::Duration,
+		) -> std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send>> {
+			unimplemented!("requires the `tokio` crate; not available in this crate slice")
+		}
+	}
+
+	/// `DelayFactory` backed by `async_std::task::sleep`.
+	#[cfg(feature = "async-std")]
+	pub struct AsyncStdDelayFactory;
+
+	#[cfg(feature = "async-std")]
+	impl super::DelayFactory for AsyncStdDelayFactory {
+		fn delay(
+			&self,
+			_duration: std::time::Duration,
+		) -> std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send>> {
+			unimplemented!("requires the `async-std` crate; not available in this crate slice")
+		}
+	}
+}
+
+/// An in-memory [`TelemetryTransport`] that hands sent lines to an
+/// `mpsc::Receiver` a test holds onto, and closes when told to. Used by this
+/// crate's own tests to exercise anything written against
+/// `dyn TelemetryTransport` without a real socket. Supports a single
+/// connect/close cycle, which is all a test simulating one drop-and-reconnect
+/// needs; a real transport would return a fresh channel on every `connect()`.
+pub struct InMemoryTransport {
+	sender: Mutex<Option<mpsc::Sender<String>>>,
+	close_rx: Mutex<Option<mpsc::UnboundedReceiver<()>>>,
+	close_tx: mpsc::UnboundedSender<()>,
+}
+
+impl InMemoryTransport {
+	/// Build a transport whose `connect()` returns a bounded channel of
+	/// `capacity`, and a receiving end for a test to read sent lines from.
+	pub fn new(capacity: usize) -> (Self, mpsc::Receiver<String>) {
+		let (sender, receiver) = mpsc::channel(capacity);
+		let (close_tx, close_rx) = mpsc::unbounded();
+		(Self { sender: Mutex::new(Some(sender)), close_rx: Mutex::new(Some(close_rx)), close_tx }, receiver)
+	}
+
+	/// Simulate the remote end closing the connection, resolving any
+	/// in-flight [`closed`](TelemetryTransport::closed) future.
+	pub fn close(&self) {
+		let _ = self.close_tx.unbounded_send(());
+	}
+}
+
+impl TelemetryTransport for InMemoryTransport {
+	fn connect(
+		&self,
+	) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<mpsc::Sender<String>, TransportConnectError>> + Send + '_>>
+	{
+		Box::pin(async move {
+			self.sender.lock().take().ok_or_else(|| {
+				TransportConnectError::new(EndpointErrorKind::Closed, "InMemoryTransport only supports a single connect")
+			})
+		})
+	}
+
+	fn closed(&self) -> std::pin::Pin<Box<dyn std::future::Future<Output = Option<CloseFrame>> + Send + '_>> {
+		Box::pin(async move {
+			if let Some(mut close_rx) = self.close_rx.lock().take() {
+				close_rx.next().await;
+			}
+			// `InMemoryTransport::close` is a bare drop signal, not a scripted
+			// close code — see `MockTelemetryServer::close_with_code` for that.
+			None
+		})
+	}
+}
+
+/// Commands accepted by [`supervise_endpoints`], mirroring
+/// [`EndpointCommand`]'s add/remove shape for a supervisor whose endpoints
+/// run as independent futures rather than as entries in one shared loop.
+pub enum EndpointSupervisorCommand {
+	/// Start supervising a new endpoint: `receiver` is this endpoint's own
+	/// queue (a fan-out stage upstream sends into), `transport` is what
+	/// [`run_endpoint`] dials to actually deliver them.
+	Add {
+		url: String,
+		receiver: mpsc::Receiver<(Verbosity, String)>,
+		transport: Box<dyn TelemetryTransport>,
+		/// See [`EndpointAckModes`] and [`run_endpoint`]'s `ack_mode` parameter.
+		ack_mode: bool,
+		/// See [`run_endpoint`]'s `report_reconnects` parameter.
+		report_reconnects: bool,
+	},
+	/// Stop supervising `url`. Its in-flight connection and any messages
+	/// still queued for it are dropped immediately rather than drained.
+	Remove { url: String },
+}
+
+/// Build the `telemetry.meta` message [`run_endpoint`] sends immediately
+/// after a reconnect (never the initial connect), reporting the just-ended
+/// outage: how long the endpoint was down, how many connect attempts the
+/// recovery took, and how many messages were dropped or are still buffered
+/// (from [`EndpointStats::dropped`] and [`ReplayBuffer::len`] respectively —
+/// this crate slice tracks nothing else outage-shaped to report). [`run_endpoint`]
+/// writes it straight to the freshly (re)connected transport rather than
+/// through `receiver`, the same way it already does for replayed and
+/// in-flight messages — bypassing [`SenderConfig`]'s sampling and
+/// coalescing entirely, at the wire-critical [`Verbosity::CONSOLE`] level,
+/// since a backend needs every one of these to tell a real outage apart
+/// from a merely-quiet node.
+fn build_reconnect_meta(url: &str, outage: std::time::Duration, attempts: u32, dropped: u64, buffered: usize) -> String {
+	serde_json::json!({
+		"msg": "telemetry.meta",
+		"endpoint": url,
+		"outage_secs": outage.as_secs_f64(),
+		"reconnect_attempts": attempts,
+		"messages_dropped": dropped,
+		"messages_buffered": buffered,
+	})
+	.to_string()
+}
+
+/// Websocket close code 1008 ("Policy Violation") per
+/// [RFC 6455 §7.4.1](https://www.rfc-editor.org/rfc/rfc6455#section-7.4.1):
+/// the peer is refusing to keep talking to this node specifically (a
+/// banned or unrecognized node ID, a rejected protocol version), not just
+/// momentarily busy.
+const WEBSOCKET_CLOSE_POLICY_VIOLATION: u16 = 1008;
+
+/// Websocket close code 1013 ("Try Again Later") per
+/// [RFC 6455 §7.4.1](https://www.rfc-editor.org/rfc/rfc6455#section-7.4.1):
+/// the peer wants the client to back off and reconnect later rather than
+/// treating this like an ordinary drop.
+const WEBSOCKET_CLOSE_TRY_AGAIN_LATER: u16 = 1013;
+
+/// Floor applied to a "try again later" reconnect delay when the peer's
+/// close reason carries no parseable hint (see [`parse_retry_after_hint`]).
+/// Deliberately longer than [`ReconnectBackoff`]'s own early-attempt
+/// delays: 1013 is the peer explicitly asking for patience, not a
+/// transient network blip that a quick retry might just clear.
+const TRY_AGAIN_LATER_MIN_DELAY: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// How many consecutive policy-violation closes (websocket code 1008) an
+/// endpoint tolerates before [`run_endpoint`] gives up on it entirely,
+/// independent of its [`ReconnectPolicy`]'s own `max_attempts` — a
+/// collector that is actively rejecting this node isn't going to start
+/// accepting it on the next attempt just because the reconnect policy
+/// otherwise allows unlimited retries.
+const POLICY_VIOLATION_MAX_ATTEMPTS: u32 = 3;
+
+/// Pull a bare number of seconds out of a websocket close reason for code
+/// 1013 ("Try Again Later"), e.g. `"retry after 30s"` or `"30"` both yield
+/// `Some(Duration::from_secs(30))`. A reason with no digits (or an empty
+/// one) yields `None`, leaving the caller to fall back to its own backoff.
+fn parse_retry_after_hint(reason: &str) -> Option<std::time::Duration> {
+	let digits: String = reason.chars().skip_while(|c| !c.is_ascii_digit()).take_while(|c| c.is_ascii_digit()).collect();
+	digits.parse::<u64>().ok().map(std::time::Duration::from_secs)
+}
+
+/// One endpoint's connect/send/reconnect loop, run as its own future by
+/// [`supervise_endpoints`] instead of interleaved with every other
+/// endpoint's I/O the way [`fan_out_by_verbosity`]'s single loop shares one
+/// task across all of them. Owns everything the request calls out —
+/// `receiver` (its queue), `transport` (its connection), a fresh
+/// [`ReconnectBackoff`] derived from `reconnect`, and its own `stats`/
+/// `connection_status` entries — so a stalled `transport.connect()` (e.g. a
+/// slow TLS handshake) only ever blocks this future, never a sibling
+/// endpoint's delivery. Ordering is preserved per endpoint (messages are
+/// forwarded in `receiver`'s order) but, as with `fan_out_by_verbosity`,
+/// not across endpoints.
+///
+/// Returns once `receiver` closes — the same shutdown signal
+/// `fan_out_by_verbosity` relies on for the whole-loop case — or once
+/// `reconnect` is exhausted after a connect failure.
+///
+/// With `ack_mode` on, [`TelemetryTransport::incoming`] is only polled at
+/// the top of the per-message loop below, i.e. whenever a new outgoing
+/// message arrives or the connection drops — this crate slice has no
+/// timer/executor abstraction (see [`DelayFactory`]) to poll it on a
+/// schedule instead. An ack that arrives while nothing else is happening
+/// on an otherwise-idle connection sits in the transport until the next
+/// such wakeup rather than being applied immediately; it's still applied
+/// before the next reconnect's resend, so nothing is lost, only delayed.
+///
+/// With `report_reconnects` on, every recovery (not the initial connect)
+/// sends a `telemetry.meta` message ahead of anything else on the fresh
+/// connection, so a backend can tell "this node has been quiet because
+/// it's actually down" apart from "this node has been quiet because its
+/// telemetry link keeps dropping" — see [`build_reconnect_meta`].
+///
+/// A [`CloseFrame`] from [`TelemetryTransport::closed`] gets differentiated
+/// treatment by code, always recorded (code and reason) as the endpoint's
+/// last error via [`EndpointConnectionStatus::record_disconnected`]:
+/// [`WEBSOCKET_CLOSE_POLICY_VIOLATION`] (1008) reconnects as usual but only
+/// [`POLICY_VIOLATION_MAX_ATTEMPTS`] times in a row before this function
+/// gives up on the endpoint entirely, the same as an exhausted
+/// [`ReconnectPolicy`]; [`WEBSOCKET_CLOSE_TRY_AGAIN_LATER`] (1013) waits
+/// out [`parse_retry_after_hint`]'s reading of the close reason (or
+/// [`TRY_AGAIN_LATER_MIN_DELAY`] if it has none) before the next connect
+/// attempt, instead of the ordinary backoff; every other code, and a plain
+/// drop with no close frame at all, reconnect exactly as before.
+async fn run_endpoint(
+	url: String,
+	mut receiver: mpsc::Receiver<(Verbosity, String)>,
+	transport: Box<dyn TelemetryTransport>,
+	delay: Arc<dyn DelayFactory>,
+	reconnect: ReconnectPolicy,
+	stats: EndpointStats,
+	connection_status: EndpointConnectionStatus,
+	ack_mode: bool,
+	report_reconnects: bool,
+) {
+	// See `EndpointAckModes`: with `ack_mode` on, outgoing messages stay
+	// buffered until the collector's `{"ack": <seq>}` frame confirms
+	// delivery, and are resent on every (re)connect until then. `ack_mode`
+	// off keeps the original fire-and-forget behavior untouched.
+	let mut replay = ack_mode.then(|| ReplayBuffer::new(DEFAULT_BUFFER_SIZE).with_ack_mode(true));
+	let mut backoff = reconnect.backoff();
+	let mut first_attempt = true;
+	let mut disconnected_at: Option<std::time::Instant> = None;
+	let mut dropped_at_disconnect = stats.dropped(&url);
+	// Consecutive 1008 (policy violation) closes; see `POLICY_VIOLATION_MAX_ATTEMPTS`.
+	let mut policy_violations: u32 = 0;
+	// Set by a 1013 (try again later) close; waited out before the *next*
+	// connect attempt rather than immediately, since the close has already
+	// happened by the time this is set.
+	let mut pending_delay: Option<std::time::Duration> = None;
+	loop {
+		if let Some(wait) = pending_delay.take() {
+			delay.delay(wait).await;
+		}
+		let mut attempts: u32 = 0;
+		let mut sender = loop {
+			attempts += 1;
+			match transport.connect().await {
+				Ok(sender) => break sender,
+				Err(err) => {
+					if first_attempt {
+						connection_status.record_probed(&url, Err((err.kind, err.message.clone())));
+					} else {
+						connection_status.record_disconnected(&url, err.kind, err.message.clone());
+					}
+					if reconnect.is_exhausted(&backoff) {
+						return;
+					}
+					delay.delay(backoff.next_delay(1.0)).await;
+				}
+			}
+		};
+		if first_attempt {
+			connection_status.record_probed(&url, Ok(()));
+		} else {
+			connection_status.record_connected(&url);
+			if report_reconnects {
+				let outage = disconnected_at.map(|since| since.elapsed()).unwrap_or_default();
+				let dropped = stats.dropped(&url).saturating_sub(dropped_at_disconnect);
+				let buffered = replay.as_ref().map(ReplayBuffer::len).unwrap_or(0);
+				let meta = build_reconnect_meta(&url, outage, attempts, dropped, buffered);
+				if let Err(err) = sender.try_send(meta) {
+					stats.record_drop(&url, if err.is_full() { DropReason::QueueFull } else { DropReason::Disconnected });
+				}
+			}
+		}
+		first_attempt = false;
+		backoff = reconnect.backoff();
+
+		if let Some(replay) = &replay {
+			for (_verbosity, json) in replay.pending() {
+				if let Err(err) = sender.try_send(json) {
+					stats.record_drop(&url, if err.is_full() { DropReason::QueueFull } else { DropReason::Disconnected });
+				}
+			}
+		}
+
+		loop {
+			if let Some(replay) = &mut replay {
+				for frame in transport.incoming() {
+					if let Some(seq) = parse_ack_frame(&frame) {
+						replay.ack(seq);
+					}
+				}
+			}
+			match futures::future::select(receiver.next(), transport.closed()).await {
+				futures::future::Either::Left((None, _)) => return,
+				futures::future::Either::Left((Some((verbosity, json)), _)) => {
+					if let Some(replay) = &mut replay {
+						replay.push(None, (verbosity, json.clone()));
+					}
+					if let Err(err) = sender.try_send(json) {
+						stats.record_drop(&url, if err.is_full() { DropReason::QueueFull } else { DropReason::Disconnected });
+					}
+				}
+				futures::future::Either::Right((close_frame, _)) => {
+					match close_frame {
+						Some(CloseFrame { code, reason }) if code == WEBSOCKET_CLOSE_POLICY_VIOLATION => {
+							policy_violations += 1;
+							let message = format!("closed by peer: code={code} reason={reason}");
+							log::error!(
+								target: "telemetry",
+								"telemetry endpoint {url} closed for a policy violation ({message}); {policy_violations} of {POLICY_VIOLATION_MAX_ATTEMPTS} tolerated attempts used",
+							);
+							connection_status.record_disconnected(&url, EndpointErrorKind::PolicyViolation, message);
+							if policy_violations >= POLICY_VIOLATION_MAX_ATTEMPTS {
+								log::error!(
+									target: "telemetry",
+									"telemetry endpoint {url} giving up after {policy_violations} consecutive policy violations",
+								);
+								return;
+							}
+						}
+						Some(CloseFrame { code, reason }) if code == WEBSOCKET_CLOSE_TRY_AGAIN_LATER => {
+							policy_violations = 0;
+							let message = format!("closed by peer: code={code} reason={reason}");
+							connection_status.record_disconnected(&url, EndpointErrorKind::RetryLater, message);
+							let hint = parse_retry_after_hint(&reason);
+							pending_delay = Some(hint.unwrap_or_else(|| backoff.next_delay(1.0).max(TRY_AGAIN_LATER_MIN_DELAY)));
+						}
+						Some(CloseFrame { code, reason }) => {
+							policy_violations = 0;
+							connection_status.record_disconnected(&url, EndpointErrorKind::Closed, format!("closed by peer: code={code} reason={reason}"));
+						}
+						None => {
+							policy_violations = 0;
+							connection_status.record_disconnected(&url, EndpointErrorKind::Closed, "connection closed");
+						}
+					}
+					disconnected_at = Some(std::time::Instant::now());
+					dropped_at_disconnect = stats.dropped(&url);
+					break;
+				}
+			}
+		}
+	}
+}
+
+/// Supervise a growing/shrinking set of endpoints, each running as its own
+/// [`run_endpoint`] future polled concurrently via
+/// [`FuturesUnordered`](futures::stream::FuturesUnordered) rather than a
+/// single shared loop — see that function's doc comment for why this
+/// isolates a stalled endpoint from its siblings. `commands` supports
+/// adding and removing endpoints at runtime, the same way
+/// [`EndpointCommand`] does for [`fan_out_by_verbosity`]; removal is
+/// implemented with [`Abortable`](futures::future::Abortable) since
+/// `FuturesUnordered` has no way to cancel one entry by key.
+///
+/// This is an additive alternative entry point, not a replacement for
+/// [`fan_out_by_verbosity`]: dozens of existing tests are pinned to that
+/// function's single-loop signature, and migrating the default pipeline
+/// over to per-endpoint tasks is a larger, separately-tracked change than
+/// this request's scope covers. Callers who want per-endpoint isolation
+/// today can use this function directly; it shares [`TelemetryTransport`],
+/// [`ReconnectPolicy`]/[`ReconnectBackoff`], [`EndpointStats`] and
+/// [`EndpointConnectionStatus`] with the rest of this module, so switching
+/// between the two doesn't mean switching data models.
+pub async fn supervise_endpoints(
+	initial: Vec<(String, mpsc::Receiver<(Verbosity, String)>, Box<dyn TelemetryTransport>, bool, bool)>,
+	mut commands: mpsc::UnboundedReceiver<EndpointSupervisorCommand>,
+	delay: Arc<dyn DelayFactory>,
+	reconnect: ReconnectPolicy,
+	stats: EndpointStats,
+	connection_status: EndpointConnectionStatus,
+) {
+	use futures::future::{AbortHandle, Abortable};
+	use futures::stream::FuturesUnordered;
+
+	let mut handles: HashMap<String, AbortHandle> = HashMap::new();
+	let mut workers = FuturesUnordered::new();
+
+	let spawn = |url: String,
+	             receiver: mpsc::Receiver<(Verbosity, String)>,
+	             transport: Box<dyn TelemetryTransport>,
+	             ack_mode: bool,
+	             report_reconnects: bool,
+	             handles: &mut HashMap<String, AbortHandle>,
+	             workers: &mut FuturesUnordered<_>| {
+		let (abort_handle, abort_registration) = AbortHandle::new_pair();
+		handles.insert(url.clone(), abort_handle);
+		let task = run_endpoint(
+			url,
+			receiver,
+			transport,
+			delay.clone(),
+			reconnect.clone(),
+			stats.clone(),
+			connection_status.clone(),
+			ack_mode,
+			report_reconnects,
+		);
+		workers.push(Abortable::new(task, abort_registration));
+	};
+
+	for (url, receiver, transport, ack_mode, report_reconnects) in initial {
+		spawn(url, receiver, transport, ack_mode, report_reconnects, &mut handles, &mut workers);
+	}
+
+	// Once every endpoint has shut itself down, `commands` is the only
+	// thing left to wait on: polling an empty `FuturesUnordered` resolves
+	// to `None` immediately on every call, which would busy-loop `select`
+	// below instead of actually waiting for the next command.
+	let mut commands_open = true;
+	loop {
+		if workers.is_empty() {
+			if !commands_open {
+				return;
+			}
+			match commands.next().await {
+				None => commands_open = false,
+				Some(EndpointSupervisorCommand::Add { url, receiver, transport, ack_mode, report_reconnects }) => {
+					spawn(url, receiver, transport, ack_mode, report_reconnects, &mut handles, &mut workers);
+				}
+				Some(EndpointSupervisorCommand::Remove { url }) => {
+					if let Some(handle) = handles.remove(&url) {
+						handle.abort();
+					}
+				}
+			}
+			continue;
+		}
+		match futures::future::select(workers.next(), commands.next()).await {
+			futures::future::Either::Left(_) => {
+				// One endpoint's future completed (queue closed) or was
+				// aborted (`Remove`); loop back around to re-check whether
+				// any are left.
+			}
+			futures::future::Either::Right((None, _)) => commands_open = false,
+			futures::future::Either::Right((Some(EndpointSupervisorCommand::Add {
+				url,
+				receiver,
+				transport,
+				ack_mode,
+				report_reconnects,
+			}), _)) => {
+				spawn(url, receiver, transport, ack_mode, report_reconnects, &mut handles, &mut workers);
+			}
+			futures::future::Either::Right((Some(EndpointSupervisorCommand::Remove { url }), _)) => {
+				if let Some(handle) = handles.remove(&url) {
+					handle.abort();
+				}
+			}
+		}
+	}
+}
+
+/// The default `mpsc` buffer size [`TelemetryBuilder`] registers a worker's
+/// sender with, if [`buffer_size`](TelemetryBuilder::buffer_size) is never
+/// called. Chosen the same way [`DEFAULT_MAX_MESSAGE_SIZE`] was: generous
+/// enough that a short burst (a reconnect replaying `system.connected` plus
+/// whatever queued while disconnected) doesn't immediately overflow into
+/// [`OverflowPolicy`].
+pub const DEFAULT_BUFFER_SIZE: usize = 1024;
+
+/// Why [`TelemetryBuilder::build`] refused to build, so a misconfigured
+/// endpoint or buffer size fails loudly at startup instead of surfacing
+/// later as an opaque connection failure or a worker that silently drops
+/// everything.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TelemetryBuilderError {
+	/// One of the URLs passed to [`endpoint`](TelemetryBuilder::endpoint)
+	/// didn't parse; see [`Endpoint::parse`].
+	Endpoint(EndpointParseError),
+	/// [`buffer_size`](TelemetryBuilder::buffer_size) was called with `0`,
+	/// which would make every message an immediate overflow.
+	ZeroBufferSize,
+}
+
+impl std::fmt::Display for TelemetryBuilderError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Endpoint(err) => write!(f, "{err}"),
+			Self::ZeroBufferSize => write!(f, "telemetry buffer size must be at least 1"),
+		}
+	}
+}
+
+impl std::error::Error for TelemetryBuilderError {}
+
+impl From<EndpointParseError> for TelemetryBuilderError {
+	fn from(err: EndpointParseError) -> Self {
+		Self::Endpoint(err)
+	}
+}
+
+/// One `[[endpoints]]` entry in a [`TelemetryConfig`]: a URL and the
+/// verbosity ceiling [`TelemetryBuilder::endpoint`] would take it at.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EndpointConfig {
+	pub url: String,
+	/// No implicit default: [`TelemetryBuilder::endpoint`] requires callers
+	/// to pick one explicitly, and a config file is no different.
+	pub verbosity: Verbosity,
+}
+
+/// One named `[endpoint_groups.<name>]` entry: the declarative shape of an
+/// [`EndpointGroup`], `members[0]` still the primary. See
+/// [`TelemetryBuilder::endpoint_group`].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EndpointGroupConfig {
+	pub members: Vec<String>,
+	/// No implicit default: [`EndpointGroup::new`] requires callers to pick
+	/// one explicitly, and a config file is no different.
+	pub failback_after_secs: u64,
+}
+
+impl From<&EndpointGroupConfig> for EndpointGroup {
+	fn from(config: &EndpointGroupConfig) -> Self {
+		let mut members = config.members.iter().cloned();
+		let primary = members.next().unwrap_or_default();
+		EndpointGroup::new(primary, members, std::time::Duration::from_secs(config.failback_after_secs))
+	}
+}
+
+/// The declarative shape of a [`MessageTypeFilter`]. Externally tagged (e.g.
+/// `{"allow": ["sysinfo.*"]}` in JSON, `allow = ["sysinfo.*"]` under a
+/// `[endpoint_filters.<url>]` table in TOML), which is serde's default for a
+/// data-carrying enum and needs no extra attributes to get.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageTypeFilterConfig {
+	Allow(Vec<String>),
+	Deny(Vec<String>),
+}
+
+impl From<MessageTypeFilterConfig> for MessageTypeFilter {
+	fn from(config: MessageTypeFilterConfig) -> Self {
+		match config {
+			MessageTypeFilterConfig::Allow(patterns) => MessageTypeFilter::Allow(patterns),
+			MessageTypeFilterConfig::Deny(patterns) => MessageTypeFilter::Deny(patterns),
+		}
+	}
+}
+
+/// The declarative shape of a [`SamplingRule`], applied per `msg` type via
+/// `[sampling.<msg_type>]`. See [`MessageTypeFilterConfig`] for the tagging
+/// convention.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SamplingRuleConfig {
+	EveryNth(u64),
+	MaxPerSecond(f64),
+}
+
+impl From<SamplingRuleConfig> for SamplingRule {
+	fn from(config: SamplingRuleConfig) -> Self {
+		match config {
+			SamplingRuleConfig::EveryNth(n) => SamplingRule::EveryNth(n),
+			SamplingRuleConfig::MaxPerSecond(rate) => SamplingRule::MaxPerSecond(rate),
+		}
+	}
+}
+
+/// The declarative shape of a [`ReconnectPolicy`]. Durations are plain
+/// seconds rather than [`std::time::Duration`] itself: `Duration`'s own
+/// `Deserialize` impl expects a `{secs, nanos}` table, which is a poor fit
+/// for a value an operator is meant to type into a config file by hand.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReconnectConfig {
+	#[serde(default = "default_reconnect_initial_delay_secs")]
+	pub initial_delay_secs: u64,
+	#[serde(default = "default_reconnect_max_delay_secs")]
+	pub max_delay_secs: u64,
+	#[serde(default)]
+	pub max_attempts: Option<u32>,
+}
+
+fn default_reconnect_initial_delay_secs() -> u64 {
+	ReconnectPolicy::default().initial_delay.as_secs()
+}
+
+fn default_reconnect_max_delay_secs() -> u64 {
+	ReconnectPolicy::default().max_delay.as_secs()
+}
+
+impl Default for ReconnectConfig {
+	fn default() -> Self {
+		Self {
+			initial_delay_secs: default_reconnect_initial_delay_secs(),
+			max_delay_secs: default_reconnect_max_delay_secs(),
+			max_attempts: None,
+		}
+	}
+}
+
+impl From<&ReconnectConfig> for ReconnectPolicy {
+	fn from(config: &ReconnectConfig) -> Self {
+		ReconnectPolicy {
+			initial_delay: std::time::Duration::from_secs(config.initial_delay_secs),
+			max_delay: std::time::Duration::from_secs(config.max_delay_secs),
+			max_attempts: config.max_attempts,
+		}
+	}
+}
+
+/// The full [`TelemetryBuilder`] configuration, expressible declaratively in
+/// a node's own config file (TOML, JSON, or anything else `serde` has a
+/// `Deserializer` for — this crate slice has no `Cargo.toml` to add a `toml`
+/// dev-dependency to exercise that format directly in tests, but the impl
+/// below is derived, not hand-rolled, so it's format-agnostic by
+/// construction).
+///
+/// Every field is optional and defaults to matching [`TelemetryBuilder::default`]
+/// exactly, so `{}` (or an empty TOML table) is a valid, if useless, config.
+/// Every struct in this file reachable from here — this one included — is
+/// `#[serde(deny_unknown_fields)]`, so a typo'd key (`"ednpoints"`, a
+/// misspelled filter tag, ...) is a deserialization error naming the
+/// offending key and its position in the source file, rather than a silently
+/// ignored knob. See [`TelemetryBuilder::from_config`] and
+/// [`apply_runtime`](Self::apply_runtime).
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct TelemetryConfig {
+	pub target: Option<String>,
+	pub endpoints: Vec<EndpointConfig>,
+	pub endpoint_groups: HashMap<String, EndpointGroupConfig>,
+	pub endpoint_filters: HashMap<String, MessageTypeFilterConfig>,
+	pub buffer_size: usize,
+	pub global_verbosity: Verbosity,
+	pub max_message_size: usize,
+	pub static_fields: serde_json::Map<String, serde_json::Value>,
+	pub context_fields: Vec<String>,
+	pub reconnect: ReconnectConfig,
+	pub initial_connection_delay_secs: u64,
+	/// Applied to the built [`Telemetries`] by [`apply_runtime`](Self::apply_runtime),
+	/// not by [`TelemetryBuilder`] itself — see that method's doc comment.
+	pub sampling: HashMap<String, SamplingRuleConfig>,
+	/// Applied to the built [`Telemetries`] by [`apply_runtime`](Self::apply_runtime),
+	/// not by [`TelemetryBuilder`] itself — see that method's doc comment.
+	pub endpoint_byte_budgets: HashMap<String, u64>,
+}
+
+impl Default for TelemetryConfig {
+	fn default() -> Self {
+		Self {
+			target: None,
+			endpoints: Vec::new(),
+			endpoint_groups: HashMap::new(),
+			endpoint_filters: HashMap::new(),
+			buffer_size: DEFAULT_BUFFER_SIZE,
+			global_verbosity: Verbosity::DEBUG,
+			max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+			static_fields: serde_json::Map::new(),
+			context_fields: Vec::new(),
+			reconnect: ReconnectConfig::default(),
+			initial_connection_delay_secs: InitialConnectionDelay::default().max.as_secs(),
+			sampling: HashMap::new(),
+			endpoint_byte_budgets: HashMap::new(),
+		}
+	}
+}
+
+impl TelemetryConfig {
+	/// Apply the pieces of this config that act on an already-[`build`](TelemetryBuilder::build)
+	/// [`Telemetries`] rather than on the [`TelemetryBuilder`] that produced
+	/// it: per-`msg`-type sampling and per-endpoint byte budgets. Neither has
+	/// a `TelemetryBuilder` setter, since both are ordinarily reconfigured at
+	/// runtime (see [`Telemetries::configure_sampling`] and
+	/// [`Telemetries::configure_endpoint_byte_budget`]) rather than fixed for
+	/// a worker's lifetime the way `endpoints`/`buffer_size`/`reconnect` are.
+	pub fn apply_runtime(&self, telemetries: &Telemetries) {
+		for (msg_type, rule) in &self.sampling {
+			telemetries.configure_sampling(msg_type.clone(), (*rule).into());
+		}
+		for (url, bytes_per_day) in &self.endpoint_byte_budgets {
+			telemetries.configure_endpoint_byte_budget(url.clone(), *bytes_per_day);
+		}
+	}
+}
+
+/// Fluent constructor for a [`TelemetryLayer`] plus the [`TelemetryWorker`]
+/// future sharing its configuration, in place of constructing a layer and
+/// separately assembling `Senders::insert_with_config` /
+/// [`fan_out_by_verbosity`]'s arguments by hand. Defaults match
+/// [`TelemetryLayer::default`] exactly, so a caller migrating from the bare
+/// constructor can do so mechanically: `TelemetryLayer::default()` becomes
+/// `TelemetryBuilder::new().build().expect("default config is always valid").0`.
+///
+/// Validation (endpoint URLs, non-zero buffer size) happens once, in
+/// [`build`](Self::build), rather than per setter call, so a chain of
+/// `.endpoint(...)` calls reads the same regardless of which one turns out
+/// to be misconfigured.
+///
+/// # Example
+///
+/// This can't run as a doctest without this crate slice's own `Cargo.toml`
+/// (see the module-level scope note), but shows the intended shape:
+///
+/// ```rust,ignore
+/// let (layer, worker) = TelemetryBuilder::new()
+/// 	.endpoint("wss://telemetry.example.com/submit", Verbosity::INFO)?
+/// 	.buffer_size(256)
+/// 	.static_field("chain", "kusama")
+/// 	.build()?;
+/// let telemetries = layer.telemetries();
+/// // Spawn `worker` on whatever task manager the embedder uses, the same
+/// // way any other essential-but-optional service task is spawned; see
+/// // `TelemetryWorker`'s own docs for what "essential-optional" means here.
+/// ```
+#[derive(Debug)]
+pub struct TelemetryBuilder {
+	target: Option<String>,
+	otlp: Option<OtlpSink>,
+	file: Option<FileSink>,
+	timestamp_format: TimestampFormat,
+	redaction: Redaction,
+	max_message_size: usize,
+	buffer_size: usize,
+	reconnect: ReconnectPolicy,
+	initial_connection_delay: InitialConnectionDelay,
+	static_fields: serde_json::Map<String, serde_json::Value>,
+	endpoints: Vec<(String, Verbosity)>,
+	context_fields: ContextFields,
+	groups: EndpointGroups,
+	filters: EndpointMessageFilters,
+	global_verbosity: Verbosity,
+}
+
+impl Default for TelemetryBuilder {
+	fn default() -> Self {
+		Self {
+			target: None,
+			otlp: None,
+			file: None,
+			timestamp_format: TimestampFormat::default(),
+			redaction: Redaction::default(),
+			max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+			buffer_size: DEFAULT_BUFFER_SIZE,
+			reconnect: ReconnectPolicy::default(),
+			initial_connection_delay: InitialConnectionDelay::default(),
+			static_fields: serde_json::Map::new(),
+			endpoints: Vec::new(),
+			context_fields: ContextFields::default(),
+			groups: EndpointGroups::default(),
+			filters: EndpointMessageFilters::default(),
+			global_verbosity: Verbosity::DEBUG,
+		}
+	}
+}
+
+impl TelemetryBuilder {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// See [`TelemetryLayer::with_target`].
+	pub fn with_target(mut self, target: impl Into<String>) -> Self {
+		self.target = Some(target.into());
+		self
+	}
+
+	/// See [`TelemetryLayer::with_instance_id`].
+	pub fn with_instance_id(self, instance_id: u64) -> Self {
+		self.with_target(format!("{TELEMETRY_LOG_SPAN}-{instance_id}"))
+	}
+
+	/// See [`TelemetryLayer::with_otlp`].
+	pub fn with_otlp(mut self, otlp: OtlpSink) -> Self {
+		self.otlp = Some(otlp);
+		self
+	}
+
+	/// See [`TelemetryLayer::with_file_sink`].
+	pub fn with_file_sink(mut self, file: FileSink) -> Self {
+		self.file = Some(file);
+		self
+	}
+
+	/// See [`TelemetryLayer::with_timestamp_format`].
+	pub fn with_timestamp_format(mut self, format: TimestampFormat) -> Self {
+		self.timestamp_format = format;
+		self
+	}
+
+	/// See [`TelemetryLayer::with_redaction`].
+	pub fn with_redaction(mut self, redaction: Redaction) -> Self {
+		self.redaction = redaction;
+		self
+	}
+
+	/// See [`TelemetryLayer::with_max_message_size`].
+	pub fn with_max_message_size(mut self, max_bytes: usize) -> Self {
+		self.max_message_size = max_bytes;
+		self
+	}
+
+	/// See [`TelemetryLayer::with_context_fields`].
+	pub fn with_context_fields(mut self, fields: ContextFields) -> Self {
+		self.context_fields = fields;
+		self
+	}
+
+	/// Add an endpoint the built worker should fan out to at `verbosity`,
+	/// alongside any others already added. `url` isn't validated until
+	/// [`build`](Self::build), via [`Endpoint::parse`].
+	pub fn endpoint(mut self, url: impl Into<String>, verbosity: impl Into<Verbosity>) -> Self {
+		self.endpoints.push((url.into(), verbosity.into()));
+		self
+	}
+
+	/// Cap every message the built [`Telemetries`] sends to at most
+	/// `verbosity`, on top of each endpoint's own cap — see
+	/// [`Telemetries::set_global_verbosity`], which [`build`](Self::build)
+	/// calls with this value (default: [`Verbosity::DEBUG`], i.e. no
+	/// effective cap). Matches a `--telemetry-verbosity <n>` CLI convention
+	/// the same way [`endpoint`](Self::endpoint) matches `--telemetry-url`.
+	pub fn global_verbosity(mut self, verbosity: impl Into<Verbosity>) -> Self {
+		self.global_verbosity = verbosity.into();
+		self
+	}
+
+	/// Register `group` under `name`, alongside any others already added.
+	/// Every member of `group` still needs its own [`endpoint`](Self::endpoint)
+	/// call; this only tells the built worker to treat them as a failover
+	/// pair rather than fanning out to both at once. See [`EndpointGroup`].
+	pub fn endpoint_group(mut self, name: impl Into<String>, group: EndpointGroup) -> Self {
+		self.groups.insert(name, group);
+		self
+	}
+
+	/// Restrict `url` to only the `msg` types `filter` allows, alongside any
+	/// others already added; `url` absent from here still accepts every
+	/// type. See [`EndpointMessageFilters`].
+	pub fn endpoint_filter(mut self, url: impl Into<String>, filter: MessageTypeFilter) -> Self {
+		self.filters.insert(url, filter);
+		self
+	}
+
+	/// The `mpsc` buffer size the built worker registers its sender with
+	/// (default: [`DEFAULT_BUFFER_SIZE`]). Rejected at
+	/// [`build`](Self::build) time if `0`.
+	pub fn buffer_size(mut self, n: usize) -> Self {
+		self.buffer_size = n;
+		self
+	}
+
+	/// The [`ReconnectPolicy`] exposed via
+	/// [`TelemetryWorker::reconnect_policy`] for a caller's own per-endpoint
+	/// connect loop (default: [`ReconnectPolicy::default`]). This crate
+	/// slice has no socket of its own to apply it to; see the module-level
+	/// scope note.
+	pub fn reconnect(mut self, policy: ReconnectPolicy) -> Self {
+		self.reconnect = policy;
+		self
+	}
+
+	/// The [`InitialConnectionDelay`] exposed via
+	/// [`TelemetryWorker::initial_connection_delay`] for a caller's own
+	/// per-endpoint connect loop to jitter its first dial by (default:
+	/// [`InitialConnectionDelay::default`]). Independent of
+	/// [`reconnect`](Self::reconnect)'s backoff, and applies once per
+	/// endpoint, before that endpoint's first connection attempt only.
+	pub fn initial_connection_delay(mut self, delay: InitialConnectionDelay) -> Self {
+		self.initial_connection_delay = delay;
+		self
+	}
+
+	/// Add a field merged into every payload sent by the built worker's
+	/// instance, alongside any others already added. See
+	/// [`SenderConfig::static_fields`].
+	pub fn static_field(mut self, key: impl Into<String>, value: impl Into<serde_json::Value>) -> Self {
+		self.static_fields.insert(key.into(), value.into());
+		self
+	}
+
+	/// Validate this configuration, assemble a [`TelemetryLayer`], and
+	/// register + start the [`TelemetryWorker`] future that shares its
+	/// endpoint/buffer/static-field configuration, in one step. Call
+	/// [`TelemetryLayer::telemetries`] on the returned layer for a handle to
+	/// send through (or to hand to [`Telemetries::shutdown`]) — the worker
+	/// itself has no other handle, by design; see its own docs.
+	pub fn build(self) -> Result<(TelemetryLayer, TelemetryWorker), TelemetryBuilderError> {
+		if self.buffer_size == 0 {
+			return Err(TelemetryBuilderError::ZeroBufferSize);
+		}
+		let mut endpoints = Endpoints::new();
+		for (url, verbosity) in &self.endpoints {
+			Endpoint::parse(url)?;
+			endpoints.insert(url.clone(), *verbosity);
+		}
+
+		let mut layer = TelemetryLayer::default();
+		if let Some(target) = self.target {
+			layer = layer.with_target(target);
+		}
+		if let Some(otlp) = self.otlp {
+			layer = layer.with_otlp(otlp);
+		}
+		if let Some(file) = self.file {
+			layer = layer.with_file_sink(file);
+		}
+		layer = layer
+			.with_timestamp_format(self.timestamp_format)
+			.with_redaction(self.redaction)
+			.with_max_message_size(self.max_message_size)
+			.with_context_fields(self.context_fields);
+
+		let telemetries = layer.telemetries();
+		telemetries.set_global_verbosity(self.global_verbosity);
+		let id = next_worker_id();
+		let (sender, receiver) = mpsc::channel(self.buffer_size);
+		let config = SenderConfig {
+			capacity: self.buffer_size,
+			static_fields: self.static_fields,
+			..SenderConfig::default()
+		};
+		telemetries.senders.insert_with_config(id, sender, config);
+		let (commands_tx, commands_rx) = mpsc::unbounded();
+		telemetries.senders.set_endpoint_commands(id, commands_tx);
+		let worker = TelemetryWorker {
+			id,
+			reconnect: self.reconnect,
+			initial_connection_delay: self.initial_connection_delay,
+			inner: Box::pin(fan_out_by_verbosity(
+				receiver,
+				endpoints,
+				HashMap::new(),
+				EndpointStats::default(),
+				commands_rx,
+				self.filters,
+				self.groups,
+				None,
+				EndpointEnvelopes::new(),
+				EndpointVerbosityFields::new(),
+			)),
+		};
+		Ok((layer, worker))
+	}
+
+	/// Build a [`TelemetryBuilder`] from a declaratively-loaded [`TelemetryConfig`]
+	/// (e.g. a node's own config file), applying every knob `TelemetryBuilder`
+	/// itself has a setter for. `config.sampling` and
+	/// `config.endpoint_byte_budgets` aren't among them — see
+	/// [`TelemetryConfig::apply_runtime`] for those, called on the
+	/// [`Telemetries`] handle this produces after [`build`](Self::build).
+	pub fn from_config(config: &TelemetryConfig) -> Self {
+		let mut builder = Self::new();
+		if let Some(target) = &config.target {
+			builder = builder.with_target(target.clone());
+		}
+		for endpoint in &config.endpoints {
+			builder = builder.endpoint(endpoint.url.clone(), endpoint.verbosity);
+		}
+		for (name, group) in &config.endpoint_groups {
+			builder = builder.endpoint_group(name.clone(), EndpointGroup::from(group));
+		}
+		for (url, filter) in &config.endpoint_filters {
+			builder = builder.endpoint_filter(url.clone(), filter.clone().into());
+		}
+		for (key, value) in &config.static_fields {
+			builder = builder.static_field(key.clone(), value.clone());
+		}
+		let context_fields = config
+			.context_fields
+			.iter()
+			.fold(ContextFields::new(), |fields, name| fields.field(name.clone()));
+		builder
+			.buffer_size(config.buffer_size)
+			.global_verbosity(config.global_verbosity)
+			.with_max_message_size(config.max_message_size)
+			.with_context_fields(context_fields)
+			.reconnect(ReconnectPolicy::from(&config.reconnect))
+			.initial_connection_delay(InitialConnectionDelay::up_to(std::time::Duration::from_secs(
+				config.initial_connection_delay_secs,
+			)))
+	}
+}
+
+/// Why [`Telemetries::register_instance`] refused to register a name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegisterInstanceError {
+	/// `name` is already registered to another still-live instance. Callers
+	/// that want to replace it should [`shutdown`](Telemetries::shutdown) (or
+	/// otherwise drop) the earlier instance first, the same as reusing any
+	/// other identifier.
+	NameAlreadyRegistered(String),
+}
+
+impl std::fmt::Display for RegisterInstanceError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::NameAlreadyRegistered(name) => {
+				write!(f, "telemetry instance name '{name}' is already registered")
+			}
+		}
+	}
+}
+
+impl std::error::Error for RegisterInstanceError {}
+
+/// Mints the id [`TelemetryBuilder::build`] and [`Telemetries::register_instance`]
+/// register their worker's `Senders` entry under. Deliberately not a tracing
+/// span id: a [`TelemetryWorker`] is meant to be handed straight to a task
+/// manager and driven with no span entered anywhere, so it's addressed
+/// purely through [`Telemetries::send`] / [`TelemetryHandle`] (see
+/// [`TelemetryWorker`]'s own docs on that trade-off). Each [`TelemetryLayer`]
+/// owns an entirely separate `Senders` map (see
+/// [`TelemetryLayer::telemetries`]), so this only has to be unique among
+/// workers sharing one process, not globally meaningful.
+fn next_worker_id() -> u64 {
+	static NEXT: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+	NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+}
+
+/// The future [`TelemetryBuilder::build`] assembles: registration
+/// (`Senders::insert_with_config` + `set_endpoint_commands`) has already
+/// happened by the time this is returned, so a task manager can spawn it
+/// directly as an essential-but-optional task — telemetry going down
+/// shouldn't take the rest of the node down with it — instead of the caller
+/// detaching a background task by hand. This crate slice has no
+/// service/task-manager of its own to register that "essential-optional"
+/// policy against (see the module-level scope note); the type here only
+/// needs to satisfy `Future<Output = ()> + Send + 'static`, which is what
+/// any such task manager's spawn method requires.
+///
+/// [`Self::poll`] delegates to [`fan_out_by_verbosity`], which resolves once
+/// every sender feeding it is dropped — which happens either when every
+/// [`Telemetries`]/[`TelemetryHandle`] clone referencing this worker's id is
+/// dropped (dropping the last strong owner of the `Senders` map's
+/// [`SenderEntry`], and with it the `mpsc::Sender` half of this worker's
+/// channel), or when [`Telemetries::shutdown`] removes this id's entry
+/// outright. Either way, this future's completion *is* the worker's
+/// shutdown signal — nothing else needs to observe it.
+///
+/// Registered under an id private to this instance rather than a tracing
+/// span id (see [`next_worker_id`]), so `poll`ing it doesn't depend on any
+/// span being entered — but that also means events emitted through the
+/// `tracing::info!(target: ..., ...)` macro path won't reach it unless a
+/// caller separately enters a span with a matching target *and* this same
+/// id, which nothing here does automatically. Callers who need the macro
+/// path should keep assembling a worker the way [`TelemetryBuilder`]'s own
+/// tests did before this type existed: enter the `telemetry-logger` span
+/// first, then register a sender for its id by hand.
+pub struct TelemetryWorker {
+	id: u64,
+	reconnect: ReconnectPolicy,
+	initial_connection_delay: InitialConnectionDelay,
+	inner: std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send>>,
+}
+
+impl std::fmt::Debug for TelemetryWorker {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("TelemetryWorker").field("id", &self.id).finish_non_exhaustive()
+	}
+}
+
+impl TelemetryWorker {
+	/// The id this worker registered its `Senders` entry under — pass this
+	/// to [`Telemetries::handle`] or [`Telemetries::send`] to actually emit
+	/// through it.
+	pub fn id(&self) -> u64 {
+		self.id
+	}
+
+	/// The [`ReconnectPolicy`] this worker was built with, for a caller
+	/// driving its own per-endpoint connect loop — this crate slice has no
+	/// socket of its own to apply it to (see the module-level scope note).
+	pub fn reconnect_policy(&self) -> &ReconnectPolicy {
+		&self.reconnect
+	}
+
+	/// The [`InitialConnectionDelay`] this worker was built with, for a
+	/// caller driving its own per-endpoint connect loop to jitter its first
+	/// dial by — this crate slice has no socket of its own to apply it to
+	/// (see the module-level scope note). Must not be used to delay anything
+	/// other than the telemetry connection itself; node startup shouldn't
+	/// wait on it.
+	pub fn initial_connection_delay(&self) -> InitialConnectionDelay {
+		self.initial_connection_delay
+	}
+}
+
+impl std::future::Future for TelemetryWorker {
+	type Output = ();
+
+	fn poll(mut self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<()> {
+		self.inner.as_mut().poll(cx)
+	}
+}
+
+/// Sanctioned test helpers for asserting on telemetry output, instead of
+/// every downstream crate hand-rolling an `mpsc` receiver and re-parsing
+/// JSON strings. Available whenever this crate's own tests run, and to
+/// downstream crates behind the `test-helpers` feature — this crate slice
+/// has no `Cargo.toml` to declare that feature against, so, as with
+/// [`prometheus_metrics`], downstream consumption is aspirational until one
+/// exists; everything below is exercised today by this crate's own
+/// `#[cfg(test)]` suite.
+#[cfg(any(test, feature = "test-helpers"))]
+pub mod test_utils {
+	use super::{mpsc, EndpointErrorKind, InMemoryTransport, TelemetryTransport, TransportConnectError, Verbosity};
+	use futures::StreamExt as _;
+
+	/// One telemetry payload captured by [`InMemoryTelemetry`], decoded from
+	/// wire JSON so assertions match on structured fields instead of
+	/// substring-matching a serialized string.
+	#[derive(Debug, Clone)]
+	pub struct CapturedMessage {
+		pub verbosity: Verbosity,
+		pub payload: serde_json::Value,
+	}
+
+	impl CapturedMessage {
+		/// The `msg` field, if present and a string — the conventional "message
+		/// type" telemetry payloads are tagged with (e.g. `"block.import"`).
+		pub fn msg_type(&self) -> Option<&str> {
+			self.payload.get("msg").and_then(|v| v.as_str())
+		}
+	}
+
+	/// Captures every telemetry message sent to one id, however it got sent.
+	///
+	/// [`Telemetries::send`](super::Telemetries::send) (the direct API) and
+	/// events emitted under the telemetry span (the `tracing` path) both
+	/// funnel into the same [`Senders`](super::Senders) map and the same
+	/// registered channel, so registering the sender returned by
+	/// [`new`](Self::new) once with
+	/// [`Senders::insert`](super::Senders::insert) captures both without the
+	/// test needing to know or care which path the code under test used.
+	///
+	/// Also implements [`TelemetryTransport`], for tests exercising a worker
+	/// written against that trait instead: connecting hands back a sender
+	/// for wire lines, which are parsed and captured the same way. The
+	/// transport wire format carries no verbosity byte of its own, so lines
+	/// captured this way are recorded at [`Verbosity::INFO`].
+	pub struct InMemoryTelemetry {
+		senders_receiver: parking_lot::Mutex<mpsc::Receiver<(Verbosity, String)>>,
+		transport: InMemoryTransport,
+		transport_receiver: parking_lot::Mutex<mpsc::Receiver<String>>,
+		captured: parking_lot::Mutex<Vec<CapturedMessage>>,
+	}
+
+	impl InMemoryTelemetry {
+		/// Build a capture sink and the sender to register under a telemetry id
+		/// with [`Senders::insert`](super::Senders::insert), both sized to hold
+		/// `capacity` messages before backpressure kicks in.
+		pub fn new(capacity: usize) -> (Self, mpsc::Sender<(Verbosity, String)>) {
+			let (sender, senders_receiver) = mpsc::channel(capacity);
+			let (transport, transport_receiver) = InMemoryTransport::new(capacity);
+			(
+				Self {
+					senders_receiver: parking_lot::Mutex::new(senders_receiver),
+					transport,
+					transport_receiver: parking_lot::Mutex::new(transport_receiver),
+					captured: parking_lot::Mutex::new(Vec::new()),
+				},
+				sender,
+			)
+		}
+
+		/// Pull every message currently buffered on either path into
+		/// `captured`. A non-JSON-object payload panics rather than being
+		/// skipped: both `Telemetries::send` and `TelemetryLayer::on_event`
+		/// always hand `Senders` an already-serialized JSON object, so a parse
+		/// failure here means the code under test produced something no real
+		/// caller of this crate ever could.
+		fn drain(&self) {
+			let mut captured = self.captured.lock();
+			let mut senders_receiver = self.senders_receiver.lock();
+			while let Ok(Some((verbosity, json))) = senders_receiver.try_next() {
+				captured.push(CapturedMessage { verbosity, payload: parse_captured_json(&json) });
+			}
+			let mut transport_receiver = self.transport_receiver.lock();
+			while let Ok(Some(json)) = transport_receiver.try_next() {
+				captured.push(CapturedMessage { verbosity: Verbosity::INFO, payload: parse_captured_json(&json) });
+			}
+		}
+
+		/// Every message captured so far on either path, oldest first.
+		pub fn messages(&self) -> Vec<CapturedMessage> {
+			self.drain();
+			self.captured.lock().clone()
+		}
+
+		/// Block until a message whose `msg` field equals `msg_type` has been
+		/// captured, or `timeout` elapses, returning it either way (`None` on
+		/// timeout).
+		///
+		/// This crate slice has no timer of its own to `.await` (see
+		/// [`DelayFactory`](super::DelayFactory)), so this spins on a short
+		/// `std::thread::sleep` between drains rather than parking properly;
+		/// fine for the sub-second timeouts a test suite uses, not meant for
+		/// production code.
+		pub fn wait_for(&self, msg_type: &str, timeout: std::time::Duration) -> Option<CapturedMessage> {
+			let deadline = std::time::Instant::now() + timeout;
+			loop {
+				self.drain();
+				if let Some(found) =
+					self.captured.lock().iter().find(|m| m.msg_type() == Some(msg_type)).cloned()
+				{
+					return Some(found);
+				}
+				if std::time::Instant::now() >= deadline {
+					return None;
+				}
+				std::thread::sleep(std::time::Duration::from_millis(1));
+			}
+		}
+	}
+
+	fn parse_captured_json(json: &str) -> serde_json::Value {
+		serde_json::from_str(json).expect("telemetry payloads are always serialized JSON objects; qed")
+	}
+
+	impl TelemetryTransport for InMemoryTelemetry {
+		fn connect(
+			&self,
+		) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<mpsc::Sender<String>, TransportConnectError>> + Send + '_>>
+		{
+			self.transport.connect()
+		}
+
+		fn closed(&self) -> std::pin::Pin<Box<dyn std::future::Future<Output = Option<CloseFrame>> + Send + '_>> {
+			self.transport.closed()
+		}
+	}
+
+	/// A scriptable stand-in backend for exercising a worker written against
+	/// [`TelemetryTransport`] — reconnect, batching, keepalive — without a
+	/// real socket.
+	///
+	/// This does *not* bind a real ephemeral websocket port, negotiate an
+	/// HTTP upgrade handshake, or speak the wire-level permessage-deflate
+	/// extension: no listener, TLS terminator, or websocket framer exists
+	/// anywhere in this crate slice (see the wasm32/transport scope note at
+	/// the top of this file and the many "outside this crate slice" notes on
+	/// [`Senders`]/[`ReconnectBackoff`](super::ReconnectBackoff)) — only the
+	/// [`TelemetryTransport`] *trait* a worker is written against does. What
+	/// this scripts is everything on that trait's contract instead: connect
+	/// success/failure, when a connection closes, and what was sent down it,
+	/// across as many connect/disconnect cycles as a reconnect test needs
+	/// (unlike [`InMemoryTransport`], which only supports one). Handshake-
+	/// and extension-level scenarios (rejecting a deflate offer, a malformed
+	/// HTTP response) need a real listener and are out of reach from here;
+	/// this crate has no existing test exercising such a loop to port, since
+	/// there is no worker in this crate slice to drive one — the tests next
+	/// to this type are the demonstration instead.
+	#[derive(Default)]
+	pub struct MockTelemetryServer {
+		state: parking_lot::Mutex<MockServerState>,
+	}
+
+	#[derive(Default)]
+	struct MockServerState {
+		captured: Vec<CapturedMessage>,
+		reject_next_connect: Option<(EndpointErrorKind, String)>,
+		disconnect: Vec<mpsc::UnboundedSender<Option<CloseFrame>>>,
+		live: Option<mpsc::Receiver<String>>,
+		incoming: Vec<String>,
+	}
+
+	impl MockTelemetryServer {
+		pub fn new() -> Self {
+			Self::default()
+		}
+
+		/// Make the next [`connect`](TelemetryTransport::connect) fail with a
+		/// [`TransportConnectError`] of `kind`, carrying `message`. Consumed
+		/// after one use, so a reconnect test can script "fail once, then
+		/// succeed".
+		pub fn reject_next_connect_as(&self, kind: EndpointErrorKind, message: impl Into<String>) {
+			self.state.lock().reject_next_connect = Some((kind, message.into()));
+		}
+
+		/// Shorthand for [`reject_next_connect_as`](Self::reject_next_connect_as)
+		/// with [`EndpointErrorKind::Handshake`], as if the handshake was
+		/// rejected.
+		pub fn reject_next_handshake(&self) {
+			self.reject_next_connect_as(EndpointErrorKind::Handshake, "mock server rejected the handshake");
+		}
+
+		/// Resolve every currently pending [`closed`](TelemetryTransport::closed)
+		/// future with no [`CloseFrame`] and drop the live connection's
+		/// receiving end, as if the connection just dropped (a reset, not a
+		/// framed close). A no-op if nothing is connected. See
+		/// [`close_with_code`](Self::close_with_code) to script an actual
+		/// websocket close code instead.
+		pub fn force_disconnect(&self) {
+			let mut state = self.state.lock();
+			state.live = None;
+			for disconnect in state.disconnect.drain(..) {
+				let _ = disconnect.unbounded_send(None);
+			}
+		}
+
+		/// Resolve every currently pending [`closed`](TelemetryTransport::closed)
+		/// future with a [`CloseFrame`] carrying `code` and `reason`, as if
+		/// the collector sent an explicit websocket close — e.g. `1008` for
+		/// a policy violation or `1013` for "try again later" (optionally
+		/// with a retry-after hint in `reason`, see [`parse_retry_after_hint`]).
+		pub fn close_with_code(&self, code: u16, reason: impl Into<String>) {
+			let mut state = self.state.lock();
+			state.live = None;
+			let frame = CloseFrame { code, reason: reason.into() };
+			for disconnect in state.disconnect.drain(..) {
+				let _ = disconnect.unbounded_send(Some(frame.clone()));
+			}
+		}
+
+		/// Queue `frame` (e.g. `{"ack": 3}`) to be handed back on the next
+		/// [`TelemetryTransport::incoming`] poll, as if the mock collector
+		/// had sent it down the live connection. Queued regardless of
+		/// whether a connection is currently live, so a test can script an
+		/// ack that arrives right as a reconnect happens.
+		pub fn push_incoming(&self, frame: impl Into<String>) {
+			self.state.lock().incoming.push(frame.into());
+		}
+
+		/// Total frames received across every connection so far.
+		pub fn received_count(&self) -> usize {
+			self.drain();
+			self.state.lock().captured.len()
+		}
+
+		/// Every captured message whose `msg` field equals `msg_type`.
+		pub fn messages_of_type(&self, msg_type: &str) -> Vec<CapturedMessage> {
+			self.drain();
+			self.state.lock().captured.iter().filter(|message| message.msg_type() == Some(msg_type)).cloned().collect()
+		}
+
+		/// Pull any frames sent on the live connection since the last call
+		/// into the capture log.
+		fn drain(&self) {
+			let mut state = self.state.lock();
+			while let Some(line) = state.live.as_mut().and_then(|live| live.try_next().ok().flatten()) {
+				state.captured.push(CapturedMessage { verbosity: Verbosity::INFO, payload: parse_captured_json(&line) });
+			}
+		}
+	}
+
+	impl TelemetryTransport for MockTelemetryServer {
+		fn connect(
+			&self,
+		) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<mpsc::Sender<String>, TransportConnectError>> + Send + '_>>
+		{
+			Box::pin(async move {
+				let mut state = self.state.lock();
+				if let Some((kind, message)) = state.reject_next_connect.take() {
+					return Err(TransportConnectError::new(kind, message));
+				}
+				let (sender, receiver) = mpsc::channel(64);
+				state.live = Some(receiver);
+				Ok(sender)
+			})
+		}
+
+		fn closed(&self) -> std::pin::Pin<Box<dyn std::future::Future<Output = Option<CloseFrame>> + Send + '_>> {
+			Box::pin(async move {
+				let (disconnect_tx, mut disconnect_rx) = mpsc::unbounded();
+				self.state.lock().disconnect.push(disconnect_tx);
+				disconnect_rx.next().await.flatten()
+			})
+		}
+
+		fn incoming(&self) -> Vec<String> {
+			std::mem::take(&mut self.state.lock().incoming)
+		}
+	}
+}
+
+/// Scriptable fault injection for property-style tests of the reconnect,
+/// buffering and priority logic, gated the same way as [`test_utils`] —
+/// available to this crate's own `#[cfg(test)]` suite and, aspirationally,
+/// to a downstream crate behind the `test-helpers` feature. Every hook here
+/// either lives entirely in this module ([`ChaosTransport`]) or is a single
+/// call from non-test code behind this same cfg (see [`current_time`] and
+/// [`serialize_message`]'s chaos check) — outside the cfg neither the call
+/// nor this module exist, so a normal build carries none of it.
+///
+/// [`ChaosSchedule::fail_serialization_for`] only affects the direct
+/// [`Telemetries::send`]/[`TelemetryHandle::try_send_telemetry`] path, which
+/// already turns a serialization failure into
+/// [`TelemetryError::Serialization`] rather than panicking. The `tracing`
+/// event path (`TelemetryLayer::on_event`) has no `Result` to propagate a
+/// failure to (see that method's own doc comment) and still treats
+/// serialization as infallible, so scripting a failure for a msg type only
+/// emitted through a `telemetry!`-style tracing event will panic there
+/// exactly as it would with no chaos hooks installed at all — this only
+/// widens what a test can observe on the path that was already fallible.
+#[cfg(any(test, feature = "test-helpers"))]
+pub mod chaos {
+	use super::{CloseFrame, TelemetryTransport, TransportConnectError, Verbosity};
+	use futures::channel::mpsc;
+	use futures::{SinkExt as _, StreamExt as _};
+
+	/// A schedule of faults [`ChaosTransport`] and the chaos-checked
+	/// non-test call sites consult. Every field defaults to "no fault", so
+	/// building one and setting only the fields a test cares about leaves
+	/// everything else behaving normally.
+	#[derive(Debug, Default)]
+	pub struct ChaosSchedule {
+		/// Force the connection closed after this many messages have been
+		/// forwarded on it, reset on every reconnect. `None` never forces one.
+		pub disconnect_after: Option<usize>,
+		/// Sleep this long before forwarding each message, simulating a slow
+		/// link. `None` forwards immediately.
+		pub send_latency: Option<std::time::Duration>,
+		/// `msg` values [`serialize_message`](super::serialize_message) should
+		/// fail to serialize for, as if the payload had somehow picked up a
+		/// value JSON can't represent (e.g. a NaN float) — see this module's
+		/// own doc comment for which call sites actually observe the failure
+		/// gracefully.
+		pub fail_serialization_for: std::collections::HashSet<String>,
+		/// Milliseconds added to (positive) or subtracted from (negative)
+		/// every timestamp [`current_time`] hands to [`inject_timestamp`](super::inject_timestamp),
+		/// simulating a node whose clock has drifted from real time.
+		pub clock_skew_millis: i64,
+	}
+
+	thread_local! {
+		// A test installs its own `ChaosSchedule` for the duration of one
+		// thread's work via `with_schedule`/`install`, rather than a single
+		// process-wide static: this crate's own test suite runs many
+		// `#[test]` functions concurrently on the default test harness, and a
+		// process-wide schedule would leak one test's faults into another
+		// running in parallel.
+		static SCHEDULE: std::cell::RefCell<ChaosSchedule> = std::cell::RefCell::new(ChaosSchedule::default());
+	}
+
+	/// Install `schedule` for every chaos-checked call this thread makes for
+	/// the rest of the process (or until replaced by another `install` call),
+	/// for a test that doesn't need [`with_schedule`]'s scoping.
+	pub fn install(schedule: ChaosSchedule) {
+		SCHEDULE.with(|cell| *cell.borrow_mut() = schedule);
+	}
+
+	/// Run `f` with `schedule` installed for this thread, restoring whatever
+	/// was installed before (the default, if nothing) once `f` returns.
+	pub fn with_schedule<T>(schedule: ChaosSchedule, f: impl FnOnce() -> T) -> T {
+		let previous = SCHEDULE.with(|cell| cell.replace(schedule));
+		let result = f();
+		SCHEDULE.with(|cell| *cell.borrow_mut() = previous);
+		result
+	}
+
+	/// Whether the installed schedule wants `msg_type` to fail serialization.
+	pub(super) fn should_fail_serialization(msg_type: Option<&str>) -> bool {
+		match msg_type {
+			Some(msg_type) => SCHEDULE.with(|cell| cell.borrow().fail_serialization_for.contains(msg_type)),
+			None => false,
+		}
+	}
+
+	/// Apply the installed schedule's [`ChaosSchedule::clock_skew_millis`] to
+	/// `now`, saturating rather than panicking if the skew would carry it
+	/// outside what [`std::time::SystemTime`] can represent.
+	pub(super) fn skew(now: std::time::SystemTime) -> std::time::SystemTime {
+		let millis = SCHEDULE.with(|cell| cell.borrow().clock_skew_millis);
+		if millis >= 0 {
+			now.checked_add(std::time::Duration::from_millis(millis as u64)).unwrap_or(now)
+		} else {
+			now.checked_sub(std::time::Duration::from_millis(millis.unsigned_abs())).unwrap_or(now)
+		}
+	}
+
+	/// Shared state for one [`ChaosTransport`], split out from the transport
+	/// itself so a forced disconnect can be signalled to every in-flight
+	/// [`ChaosTransport::closed`] call, not just whichever one happened to be
+	/// awaited when the fault fired.
+	struct ChaosState {
+		disconnect_after: Option<usize>,
+		send_latency: Option<std::time::Duration>,
+		/// One sender per currently-awaited `closed()` call, following
+		/// [`test_utils::MockTelemetryServer::closed`](super::test_utils::MockTelemetryServer::closed)'s
+		/// pattern: `run_endpoint` constructs a fresh `closed()` future every
+		/// loop iteration, so a single stored receiver would only ever fire
+		/// once and go quiet on every call after that.
+		disconnect_watchers: parking_lot::Mutex<Vec<mpsc::UnboundedSender<()>>>,
+	}
+
+	impl ChaosState {
+		fn notify_disconnect(&self) {
+			for watcher in self.disconnect_watchers.lock().drain(..) {
+				let _ = watcher.unbounded_send(());
+			}
+		}
+	}
+
+	/// A [`TelemetryTransport`] decorator that injects `schedule`'s
+	/// connection-level faults into an inner transport's traffic: unlike
+	/// [`test_utils::MockTelemetryServer`], which a test scripts by hand one
+	/// call at a time, this reacts to the *volume* of real traffic flowing
+	/// through it, the way a flaky link or an overloaded collector would.
+	///
+	/// Forwarding happens on a dedicated thread pumping the channel `connect`
+	/// hands back, mirroring [`StdioSink::spawn_with_writer`](super::StdioSink::spawn_with_writer) —
+	/// this crate slice has no async runtime of its own to spawn a task onto
+	/// instead (see the module-level scope note).
+	pub struct ChaosTransport {
+		inner: std::sync::Arc<dyn TelemetryTransport>,
+		state: std::sync::Arc<ChaosState>,
+	}
+
+	impl ChaosTransport {
+		/// Wrap `inner`, injecting `schedule`'s `disconnect_after`/
+		/// `send_latency` faults into every connection it hands out.
+		/// `schedule`'s other fields (serialization failure, clock skew) don't
+		/// apply here — see their own doc comments for where they do.
+		pub fn new(inner: std::sync::Arc<dyn TelemetryTransport>, schedule: &ChaosSchedule) -> Self {
+			Self {
+				inner,
+				state: std::sync::Arc::new(ChaosState {
+					disconnect_after: schedule.disconnect_after,
+					send_latency: schedule.send_latency,
+					disconnect_watchers: parking_lot::Mutex::new(Vec::new()),
+				}),
+			}
+		}
+	}
+
+	impl TelemetryTransport for ChaosTransport {
+		fn connect(
+			&self,
+		) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<mpsc::Sender<String>, TransportConnectError>> + Send + '_>>
+		{
+			Box::pin(async move {
+				let mut inner_sender = self.inner.connect().await?;
+				let (chaos_sender, mut chaos_receiver) = mpsc::channel(64);
+				let state = self.state.clone();
+				std::thread::spawn(move || {
+					let mut forwarded = 0usize;
+					futures::executor::block_on(async {
+						while let Some(line) = chaos_receiver.next().await {
+							if state.disconnect_after.is_some_and(|limit| forwarded >= limit) {
+								// Signal every `closed()` call currently
+								// awaiting this connection, then stop
+								// forwarding entirely — a real severed link
+								// doesn't selectively keep draining traffic
+								// once it's down.
+								state.notify_disconnect();
+								break;
+							}
+							if let Some(latency) = state.send_latency {
+								std::thread::sleep(latency);
+							}
+							if inner_sender.send(line).await.is_err() {
+								break;
+							}
+							forwarded += 1;
+						}
+					});
+				});
+				Ok(chaos_sender)
+			})
+		}
+
+		fn closed(&self) -> std::pin::Pin<Box<dyn std::future::Future<Output = Option<CloseFrame>> + Send + '_>> {
+			let state = self.state.clone();
+			let inner_closed = self.inner.closed();
+			Box::pin(async move {
+				let (watcher_tx, watcher_rx) = mpsc::unbounded();
+				state.disconnect_watchers.lock().push(watcher_tx);
+				match futures::future::select(inner_closed, watcher_rx.into_future()).await {
+					futures::future::Either::Left((frame, _)) => frame,
+					futures::future::Either::Right(_) => None,
+				}
+			})
+		}
+
+		fn incoming(&self) -> Vec<String> {
+			self.inner.incoming()
+		}
+	}
+}
+
+/// Optional Prometheus metrics for telemetry health, gated behind a
+/// `prometheus` feature flag so the dependency stays opt-in.
+///
+/// This crate slice has no `Cargo.toml` to declare that feature (or the
+/// `prometheus` crate) against, so `register` is unimplemented and the
+/// module is unreachable unless something downstream enables the feature —
+/// this documents the shape the real integration would take rather than
+/// providing one. It would register `telemetry_connected{endpoint}`,
+/// `telemetry_messages_sent_total{id,msg}`,
+/// `telemetry_messages_dropped_total{id,msg,reason}` and
+/// `telemetry_reconnects_total{endpoint}`, driven from the same call sites
+/// that already update `Senders`, `EndpointStats` and `CompressionStats` —
+/// the per-`msg`-type counters in particular would read straight from
+/// [`Senders::message_type_stats`] (`reason` mapping onto
+/// [`MessageTypeCounts`]'s `filtered`/`sampled_out`/`dropped` fields), so the
+/// exported numbers always agree with `Telemetries`'s own stats API.
+#[cfg(feature = "prometheus")]
+pub mod prometheus_metrics {
+	/// Register the telemetry gauges/counters against `registry`.
+	///
+	/// Once wired up, [`DropBreakdown`](super::DropBreakdown)'s fields are the
+	/// natural label values for a `telemetry_endpoint_drops_total{reason=...}`
+	/// counter, and [`EvictionAgeStats`](super::EvictionAgeStats) the source
+	/// for a `telemetry_pending_eviction_age_seconds` histogram — both already
+	/// broken out per reason/id so no extra bookkeeping would be needed here.
+	///
+	/// Not implemented: wiring this up needs the `prometheus` crate, which
+	/// this crate slice has no `Cargo.toml` to depend on.
+	pub fn register(_registry: &prometheus::Registry) -> Result<(), prometheus::Error> {
+		unimplemented!("requires the `prometheus` crate; not available in this crate slice")
+	}
+}
+
+/// A single telemetry payload mapped onto the OpenTelemetry OTLP log wire shape.
+///
+/// The JSON object's fields become OTLP attributes (one `KeyValue` each),
+/// `message_verbosity` becomes the severity number and the span id / parent ids
+/// carry the span identity. The actual encoding onto the vendored OTLP protobuf
+/// messages happens in the task draining the receiver, mirroring the way the
+/// mpsc [`Senders`] payloads are consumed outside this layer.
+#[derive(Debug, Clone)]
+pub struct OtlpLogRecord {
+	/// Innermost telemetry span id, used as the OTLP span id.
+	pub span_id: u64,
+	/// Ancestor telemetry span ids, root-to-leaf.
+	pub parent_ids: Vec<u64>,
+	/// `message_verbosity`, mapped onto OTLP severity.
+	pub severity: Verbosity,
+	/// The telemetry payload whose fields become OTLP attributes.
+	pub attributes: serde_json::Map<String, serde_json::Value>,
+}
+
+/// OpenTelemetry OTLP export sink, installed alongside the mpsc [`Senders`].
+///
+/// Mirrors [`Senders`]: an `Arc<Mutex<...>>` of per-endpoint channels whose
+/// receivers are driven by background tasks that ship each [`OtlpLogRecord`] to
+/// a collector over OTLP (gRPC via tonic). Forwarding is best-effort — when a
+/// collector is unreachable the send fails and is logged, never panicking, so a
+/// dead collector can't take down the node.
+#[derive(Default, Debug, Clone)]
+pub struct OtlpSink(Arc<Mutex<HashMap<String, mpsc::Sender<OtlpLogRecord>>>>);
+
+impl OtlpSink {
+	/// Register an OTLP channel for the given collector endpoint.
+	pub fn insert(&self, endpoint: String, sender: mpsc::Sender<OtlpLogRecord>) {
+		self.0.lock().insert(endpoint, sender);
+	}
+
+	/// Map a captured telemetry event onto an [`OtlpLogRecord`] and forward it to
+	/// every registered collector. Best-effort: a full or disconnected channel is
+	/// logged and dropped rather than propagated.
+	fn forward(
+		&self,
+		span_id: u64,
+		parent_ids: &[u64],
+		severity: Verbosity,
+		attributes: serde_json::Map<String, serde_json::Value>,
+	) {
+		for (endpoint, sender) in self.0.lock().iter_mut() {
+			let record = OtlpLogRecord {
+				span_id,
+				parent_ids: parent_ids.to_vec(),
+				severity,
+				attributes: attributes.clone(),
+			};
+			if let Err(err) = sender.try_send(record) {
+				log::warn!(
+					target: "telemetry",
+					"Ignored OTLP telemetry export to {} because of error on channel: {:?}",
+					endpoint,
+					err,
+				);
+			}
+		}
+	}
+}
+
+/// How often the [`FileSink`] rolls over to a fresh date-stamped file, modelled
+/// on rolling-file-appender semantics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+	Minutely,
+	Hourly,
+	Daily,
+	Never,
+}
+
+impl Rotation {
+	/// The date stamp identifying the rotation period containing `unix_secs`
+	/// (seconds since the Unix epoch, UTC), or `None` for [`Rotation::Never`]
+	/// (where a single unstamped file is used).
+	///
+	/// The stamp is truncated to the rotation's granularity so two timestamps in
+	/// the same period produce the same stamp (and thus the same file), and the
+	/// next period produces a different one.
+	fn date_stamp(&self, unix_secs: i64) -> Option<String> {
+		let (year, month, day, hour, minute) = civil_from_unix_secs(unix_secs);
+		Some(match self {
+			Rotation::Minutely => {
+				format!("{:04}-{:02}-{:02}-{:02}-{:02}", year, month, day, hour, minute)
+			}
+			Rotation::Hourly => format!("{:04}-{:02}-{:02}-{:02}", year, month, day, hour),
+			Rotation::Daily => format!("{:04}-{:02}-{:02}", year, month, day),
+			Rotation::Never => return None,
+		})
+	}
+}
+
+/// Break Unix-epoch seconds into the UTC `(year, month, day, hour, minute)`
+/// calendar fields, using Howard Hinnant's `civil_from_days` algorithm so no
+/// calendar dependency is required.
+fn civil_from_unix_secs(unix_secs: i64) -> (i64, u32, u32, u32, u32) {
+	let days = unix_secs.div_euclid(86_400);
+	let secs_of_day = unix_secs.rem_euclid(86_400);
+	let hour = (secs_of_day / 3_600) as u32;
+	let minute = ((secs_of_day % 3_600) / 60) as u32;
+
+	let z = days + 719_468;
+	let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
+	let doe = z - era * 146_097;
+	let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+	let year = yoe + era * 400;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+
+	(if month <= 2 { year + 1 } else { year }, month, day, hour, minute)
+}
+
+/// A local, time-rotated file sink for telemetry payloads, installed alongside
+/// the mpsc [`Senders`], for `file://` endpoints (e.g.
+/// `file:///var/log/telemetry.ndjson`) used by air-gapped nodes and offline
+/// analysis.
+///
+/// Each captured JSON payload is appended as its own line to the
+/// currently-active file `{prefix}.{date}.{suffix}` (or `{prefix}.{suffix}`
+/// for [`Rotation::Never`]) — newline-delimited JSON (ndjson), one object per
+/// line, with no extra framing — and flushed immediately so a reader tailing
+/// the file never sees a partial line. The sink rolls to a new file when the
+/// rotation boundary is crossed. Payloads whose `message_verbosity` exceeds
+/// the configured level are dropped so operators don't flood disk, mirroring
+/// how the websocket backend filters by verbosity. Writes are best-effort: IO
+/// errors (disk full, permission denied) are logged, counted in
+/// [`write_errors`](Self::write_errors) alongside the network-failure
+/// counters on [`Senders`], and never panic.
+///
+/// On top of that date-based rotation, [`with_max_size`](Self::with_max_size)
+/// adds a size cap: once the active file reaches it, the whole file is
+/// renamed to `{active}.1` (shifting any existing `.1`, `.2`, ... up by one,
+/// dropping the oldest once [`with_max_backups`](Self::with_max_backups) is
+/// exceeded) and a fresh, empty active file takes its place. This only ever
+/// happens between two complete, already-written lines — never mid-line — so
+/// a message lands in exactly one segment, whichever file was active when
+/// it was written; nothing is duplicated or lost across the boundary. A
+/// failure partway through this (a rename that fails, a full disk) is
+/// recorded in [`rotation_errors`](Self::rotation_errors) and logged; the
+/// sink then keeps appending to whatever file it still has open rather than
+/// stopping, on the same never-panic contract as a plain write failure.
+#[derive(Clone, Debug)]
+pub struct FileSink(Arc<Mutex<FileSinkInner>>);
+
+#[derive(Debug)]
+struct FileSinkInner {
+	directory: PathBuf,
+	prefix: String,
+	suffix: String,
+	rotation: Rotation,
+	verbosity: Verbosity,
+	// The date stamp and handle of the currently-open file, if any.
+	current: Option<(Option<String>, std::fs::File)>,
+	// Bytes written to `current` so far, tracked separately from a `metadata()`
+	// call on every write so a slow filesystem doesn't cost a syscall per message.
+	current_bytes: u64,
+	max_bytes: Option<u64>,
+	max_backups: Option<usize>,
+	gzip_rotated: bool,
+	level_field: Option<String>,
+	write_errors: u64,
+	rotation_errors: u64,
+}
+
+impl FileSink {
+	pub fn new(
+		directory: PathBuf,
+		prefix: String,
+		suffix: String,
+		rotation: Rotation,
+		verbosity: Verbosity,
+	) -> Self {
+		Self(Arc::new(Mutex::new(FileSinkInner {
+			directory,
+			prefix,
+			suffix,
+			rotation,
+			verbosity,
+			current: None,
+			current_bytes: 0,
+			max_bytes: None,
+			max_backups: None,
+			gzip_rotated: false,
+			level_field: None,
+			write_errors: 0,
+			rotation_errors: 0,
+		})))
+	}
+
+	/// Build a sink from a single flat path (e.g. `/var/log/node/telemetry.ndjson`,
+	/// the shape a `--telemetry-file` CLI flag would hand off — see
+	/// [`FileEndpointSpec::parse`]) rather than the separate directory/prefix/suffix
+	/// [`new`](Self::new) takes for rotation's sake: `path`'s parent directory, file
+	/// stem and extension become `directory`, `prefix` and `suffix` respectively (a
+	/// missing extension falls back to `log`), with [`Rotation::Never`] so the flat
+	/// path passed in is exactly the file that gets written to.
+	pub fn from_path(path: &std::path::Path, verbosity: Verbosity) -> Self {
+		let directory = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or_else(|| std::path::Path::new(".")).to_path_buf();
+		let prefix = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| "telemetry".to_string());
+		let suffix = path.extension().map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| "log".to_string());
+		Self::new(directory, prefix, suffix, Rotation::Never, verbosity)
+	}
+
+	/// Roll to a fresh numbered segment once the active file reaches
+	/// `max_bytes`, independent of (and in addition to) the date-based
+	/// rotation configured via `rotation`. Unset (the default) means no
+	/// size-based rotation at all.
+	pub fn with_max_size(self, max_bytes: u64) -> Self {
+		self.0.lock().max_bytes = Some(max_bytes);
+		self
+	}
+
+	/// Keep at most `max_backups` rotated segments per active file, deleting
+	/// the oldest (by rotation order, not mtime) once a new one is created.
+	/// Unset (the default) keeps every segment ever rotated.
+	pub fn with_max_backups(self, max_backups: usize) -> Self {
+		self.0.lock().max_backups = Some(max_backups);
+		self
+	}
+
+	/// gzip-compress each segment as it's rotated out (default: off, so
+	/// segments stay plain ndjson like the active file). If this crate slice
+	/// isn't built with the `gzip` feature — true anywhere in this sandbox,
+	/// see the module-level scope note — rotation still happens on schedule,
+	/// just without compression, and the miss is counted in
+	/// [`rotation_errors`](Self::rotation_errors) rather than losing or
+	/// blocking on the segment.
+	pub fn with_gzip_rotated(self, gzip: bool) -> Self {
+		self.0.lock().gzip_rotated = gzip;
+		self
+	}
+
+	/// Embed each written message's [`Verbosity`] under `field` (e.g.
+	/// `"level"`), since a file consumer — unlike a live `(Verbosity,
+	/// String)` channel — only ever sees the serialized JSON and would
+	/// otherwise lose it entirely; see [`replay`]'s doc comment for the gap
+	/// this closes. Unset (the default) writes the bare payload unchanged.
+	/// A message that already has a field by this name is written unchanged
+	/// too, with a warning logged, rather than clobbering it.
+	pub fn with_level_field(self, field: impl Into<String>) -> Self {
+		self.0.lock().level_field = Some(field.into());
+		self
+	}
+
+	/// Number of writes dropped so far because opening or writing to the
+	/// underlying file failed, the file-sink counterpart to
+	/// [`Senders::dropped`].
+	pub fn write_errors(&self) -> u64 {
+		self.0.lock().write_errors
+	}
+
+	/// Number of times size-based rotation itself failed (a rename, a
+	/// gzip pass) — distinct from [`write_errors`](Self::write_errors),
+	/// since a rotation failure doesn't necessarily mean the write that
+	/// triggered it was lost.
+	pub fn rotation_errors(&self) -> u64 {
+		self.0.lock().rotation_errors
+	}
+
+	fn write(&self, verbosity: Verbosity, json: &str) {
+		let mut inner = self.0.lock();
+
+		// Filter out payloads above the configured verbosity level.
+		if verbosity > inner.verbosity {
+			return;
+		}
+
+		let embedded;
+		let json = match &inner.level_field {
+			Some(field) => {
+				embedded = embed_verbosity_field(json, field, verbosity);
+				embedded.as_str()
+			}
+			None => json,
+		};
+
+		// Seconds since the Unix epoch; a clock set before the epoch simply yields 0,
+		// which is harmless for rotation bookkeeping.
+		let unix_secs = std::time::SystemTime::now()
+			.duration_since(std::time::UNIX_EPOCH)
+			.map(|d| d.as_secs() as i64)
+			.unwrap_or(0);
+		let stamp = inner.rotation.date_stamp(unix_secs);
+		let needs_date_roll = match &inner.current {
+			Some((current_stamp, _)) => *current_stamp != stamp,
+			None => true,
+		};
+
+		let base_filename = match &stamp {
+			Some(date) => format!("{}.{}.{}", inner.prefix, date, inner.suffix),
+			None => format!("{}.{}", inner.prefix, inner.suffix),
+		};
+		let base_path = inner.directory.join(&base_filename);
+
+		if needs_date_roll {
+			match Self::open_appending(&base_path) {
+				Ok((file, size)) => {
+					inner.current = Some((stamp, file));
+					inner.current_bytes = size;
+				}
+				Err(err) => {
+					inner.write_errors += 1;
+					log::warn!(
+						target: "telemetry",
+						"Ignored telemetry file write because {} could not be opened: {:?}",
+						base_path.display(),
+						err,
+					);
+					return;
+				}
+			}
+		} else if let Some(max_bytes) = inner.max_bytes {
+			// `+1` for the newline `writeln!` below adds; an empty file never
+			// rotates against itself even if a single line already exceeds
+			// `max_bytes` on its own.
+			let prospective = inner.current_bytes + json.len() as u64 + 1;
+			if inner.current_bytes > 0 && prospective > max_bytes {
+				Self::rotate_for_size(&mut inner, &base_path);
+				match Self::open_appending(&base_path) {
+					Ok((file, size)) => {
+						inner.current = Some((stamp, file));
+						inner.current_bytes = size;
+					}
+					Err(err) => {
+						inner.write_errors += 1;
+						log::warn!(
+							target: "telemetry",
+							"Ignored telemetry file write because {} could not be reopened after rotation: {:?}",
+							base_path.display(),
+							err,
+						);
+						return;
+					}
+				}
+			}
+		}
+
+		if let Some((_, file)) = inner.current.as_mut() {
+			let result = writeln!(file, "{}", json).and_then(|()| file.flush());
+			match result {
+				Ok(()) => inner.current_bytes += json.len() as u64 + 1,
+				Err(err) => {
+					inner.write_errors += 1;
+					log::warn!(
+						target: "telemetry",
+						"Ignored telemetry file write because of IO error: {:?}",
+						err,
+					);
+				}
+			}
+		}
+	}
+
+	/// Open `path` for appending, returning its current size so
+	/// [`write`](Self::write) can resume size-based rotation accounting
+	/// across a restart instead of assuming a freshly-opened file is empty.
+	fn open_appending(path: &std::path::Path) -> std::io::Result<(std::fs::File, u64)> {
+		let file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+		let size = file.metadata()?.len();
+		Ok((file, size))
+	}
+
+	/// Roll `base_path` out of the way of a fresh active file: drop the open
+	/// handle (a rename while it's still held can fail on some platforms),
+	/// shift existing `.1`, `.2`, ... backups up by one (deleting whatever
+	/// would land past `max_backups`), rename `base_path` itself to `.1`, and
+	/// gzip that segment if configured to. Every step is best-effort — a
+	/// failure is counted and logged, never propagated, so the caller can
+	/// always fall through to reopening `base_path` fresh.
+	fn rotate_for_size(inner: &mut FileSinkInner, base_path: &std::path::Path) {
+		inner.current = None;
+
+		if let Err(err) = Self::shift_backups(base_path, inner.max_backups) {
+			inner.rotation_errors += 1;
+			log::warn!(
+				target: "telemetry",
+				"Telemetry file rotation could not shift backups for {}: {:?}",
+				base_path.display(),
+				err,
+			);
+		}
+
+		let first_backup = Self::backup_path(base_path, 1);
+		match std::fs::rename(base_path, &first_backup) {
+			Ok(()) => {
+				if inner.gzip_rotated {
+					if let Err(err) = gzip_rotated_segment(&first_backup) {
+						inner.rotation_errors += 1;
+						log::warn!(
+							target: "telemetry",
+							"Telemetry file rotation could not gzip {}: {:?}",
+							first_backup.display(),
+							err,
+						);
+					}
+				}
+			}
+			Err(err) => {
+				inner.rotation_errors += 1;
+				log::warn!(
+					target: "telemetry",
+					"Telemetry file rotation could not move {} aside: {:?}",
+					base_path.display(),
+					err,
+				);
+			}
+		}
+	}
+
+	/// `{base_path}.{n}`, or `{base_path}.{n}.gz` if a prior gzip pass
+	/// already renamed it — the two are treated interchangeably by
+	/// [`shift_backups`](Self::shift_backups) so backups made before
+	/// [`with_gzip_rotated`](Self::with_gzip_rotated) was turned on don't
+	/// get orphaned.
+	fn backup_path(base_path: &std::path::Path, n: usize) -> PathBuf {
+		let mut name = base_path.as_os_str().to_owned();
+		name.push(format!(".{n}"));
+		PathBuf::from(name)
+	}
+
+	fn gz_path(path: &std::path::Path) -> PathBuf {
+		let mut name = path.as_os_str().to_owned();
+		name.push(".gz");
+		PathBuf::from(name)
+	}
+
+	/// Rename `base_path.1` to `.2`, `.2` to `.3`, and so on, oldest first so
+	/// a rename never overwrites a backup that hasn't moved yet; whatever
+	/// would land past `max_backups` is deleted instead. Each backup slot is
+	/// checked both plain and `.gz` since [`with_gzip_rotated`](Self::with_gzip_rotated)
+	/// can be toggled while backups from before the change still exist.
+	fn shift_backups(base_path: &std::path::Path, max_backups: Option<usize>) -> std::io::Result<()> {
+		let mut highest = 0;
+		while Self::backup_path(base_path, highest + 1).exists()
+			|| Self::gz_path(&Self::backup_path(base_path, highest + 1)).exists()
+		{
+			highest += 1;
+		}
+
+		for n in (1..=highest).rev() {
+			let plain = Self::backup_path(base_path, n);
+			let gz = Self::gz_path(&plain);
+			let to = n + 1;
+			if max_backups.map(|max| to > max).unwrap_or(false) {
+				let _ = std::fs::remove_file(&plain);
+				let _ = std::fs::remove_file(&gz);
+				continue;
+			}
+			if gz.exists() {
+				std::fs::rename(&gz, Self::gz_path(&Self::backup_path(base_path, to)))?;
+			} else if plain.exists() {
+				std::fs::rename(&plain, Self::backup_path(base_path, to))?;
+			}
+		}
+		Ok(())
+	}
+}
+
+/// gzip-compress `path` in place (replacing it with `{path}.gz`), for
+/// [`FileSink::with_gzip_rotated`].
+///
+/// Not implemented: real compression needs the `flate2` crate, which this
+/// crate slice has no `Cargo.toml` to depend on (compare
+/// [`prometheus_metrics`](self::prometheus_metrics)'s equivalent note). Unlike
+/// that module, though, this sits on a background rotation path that must
+/// never take the node down over a missing dependency, so this returns an
+/// error instead of `unimplemented!()`-panicking — [`FileSink::rotate_for_size`]
+/// already treats that exactly like any other rotation failure: log it, count
+/// it, leave the segment uncompressed, keep writing.
+#[cfg(not(feature = "gzip"))]
+fn gzip_rotated_segment(_path: &std::path::Path) -> std::io::Result<()> {
+	Err(std::io::Error::new(
+		std::io::ErrorKind::Unsupported,
+		"gzip rotation requires the `gzip` feature; not available in this crate slice",
+	))
+}
+
+#[cfg(feature = "gzip")]
+fn gzip_rotated_segment(path: &std::path::Path) -> std::io::Result<()> {
+	use std::io::Read;
+	let mut input = std::fs::File::open(path)?;
+	let mut contents = Vec::new();
+	input.read_to_end(&mut contents)?;
+	drop(input);
+
+	let gz_path = FileSink::gz_path(path);
+	let gz_file = std::fs::File::create(&gz_path)?;
+	let mut encoder = flate2::write::GzEncoder::new(gz_file, flate2::Compression::default());
+	encoder.write_all(&contents)?;
+	encoder.finish()?;
+	std::fs::remove_file(path)?;
+	Ok(())
+}
+
+/// Replays an ndjson capture from [`FileSink`] back through a live
+/// [`Telemetries`] instance via [`Telemetries::send`], for reproducing a
+/// backend parsing bug against a test collector without waiting for the
+/// original conditions to recur.
+///
+/// Going through [`Telemetries::send`] rather than writing straight to a
+/// [`Senders`] channel means a replay runs through exactly the same
+/// filters/dedup/redaction/batching/transport pipeline a live message would
+/// (see that method's doc comment) — the fidelity the module is for. What's
+/// lost in the round trip through [`FileSink`] is the verbosity each
+/// message was originally sent at, since only the payload is written to
+/// disk; see [`ReplayOptions::verbosity`].
+pub mod replay {
+	use super::{format_timestamp, Telemetries, TimestampFormat, Verbosity};
+	use std::io::BufRead;
+
+	/// Tunes how faithfully [`replay`] reproduces the original capture.
+	#[derive(Debug, Clone)]
+	pub struct ReplayOptions {
+		/// Verbosity every replayed message is sent at. A captured payload
+		/// carries no verbosity of its own — see [`FileSink`]'s doc comment —
+		/// so this can only approximate the original; defaults to
+		/// [`Verbosity::INFO`], the same fallback
+		/// [`test_utils::InMemoryTelemetry`] uses for wire lines with no
+		/// verbosity byte of their own.
+		pub verbosity: Verbosity,
+		/// Scales the gap between two consecutive lines' `ts` fields before
+		/// sleeping that long between sends: `1.0` replays at the original
+		/// pace, `2.0` at half speed, `0.5` at double speed. `0.0` (the
+		/// default) sends every line back to back, as fast as the pipeline
+		/// takes them.
+		pub speed: f64,
+		/// Overwrite each payload's `ts` field with the moment it's actually
+		/// sent, rather than keeping the value it was captured with, so a
+		/// downstream collector timestamps the replay as "now" instead of
+		/// backdating it to the original capture.
+		pub rewrite_ts: bool,
+	}
+
+	impl Default for ReplayOptions {
+		fn default() -> Self {
+			Self { verbosity: Verbosity::INFO, speed: 0.0, rewrite_ts: false }
+		}
+	}
+
+	/// What happened while replaying one capture, returned by [`replay`].
+	#[derive(Debug, Clone, Default, PartialEq, Eq)]
+	pub struct ReplaySummary {
+		/// Non-empty lines read from the capture.
+		pub lines: usize,
+		/// Lines successfully handed to [`Telemetries::send`] and accepted.
+		pub sent: usize,
+		/// Lines that weren't valid JSON, or didn't parse to a JSON object.
+		pub malformed: usize,
+		/// Lines [`Telemetries::send`] itself rejected (e.g. `id` isn't
+		/// registered, or the message was paused/sampled/deduped away).
+		pub rejected: usize,
+	}
+
+	/// Read `ndjson` (one JSON payload per line, [`FileSink`]'s format) and
+	/// re-inject each line into `telemetries` under `id` via
+	/// [`Telemetries::send`], honoring `options`.
+	///
+	/// A line that isn't valid JSON, or doesn't parse to a JSON object, is
+	/// skipped and counted in [`ReplaySummary::malformed`] rather than
+	/// aborting the replay, so one corrupted line from a truncated capture
+	/// doesn't lose the rest of it.
+	pub fn replay(
+		telemetries: &Telemetries,
+		id: u64,
+		ndjson: impl std::io::Read,
+		options: &ReplayOptions,
+	) -> ReplaySummary {
+		let mut summary = ReplaySummary::default();
+		let mut previous_ts: Option<u64> = None;
+		for line in std::io::BufReader::new(ndjson).lines() {
+			let Ok(line) = line else { break };
+			if line.trim().is_empty() {
+				continue;
+			}
+			summary.lines += 1;
+			let mut payload: serde_json::Value = match serde_json::from_str(&line) {
+				Ok(value) => value,
+				Err(_) => {
+					summary.malformed += 1;
+					continue;
+				}
+			};
+			let Some(obj) = payload.as_object_mut() else {
+				summary.malformed += 1;
+				continue;
+			};
+			let ts_millis = obj.get("ts").and_then(|v| v.as_u64());
+			if let (Some(previous), Some(current)) = (previous_ts, ts_millis) {
+				if options.speed > 0.0 && current > previous {
+					let gap = std::time::Duration::from_millis(current - previous).mul_f64(options.speed);
+					std::thread::sleep(gap);
+				}
+			}
+			if ts_millis.is_some() {
+				previous_ts = ts_millis;
+			}
+			if options.rewrite_ts {
+				obj.insert("ts".into(), format_timestamp(std::time::SystemTime::now(), TimestampFormat::UnixMillis));
+			}
+			if telemetries.send(id, options.verbosity, payload) {
+				summary.sent += 1;
+			} else {
+				summary.rejected += 1;
+			}
+		}
+		summary
+	}
+
+	/// Convenience wrapper around [`replay`] for the common case of a real
+	/// file on disk, e.g. a `--replay-telemetry-file` CLI flag wiring
+	/// straight into this.
+	pub fn replay_file(
+		telemetries: &Telemetries,
+		id: u64,
+		path: &std::path::Path,
+		options: &ReplayOptions,
+	) -> std::io::Result<ReplaySummary> {
+		Ok(replay(telemetries, id, std::fs::File::open(path)?, options))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tracing_subscriber::layer::SubscriberExt as _;
+
+	/// Emit a single telemetry event carrying `json` and capture the payload
+	/// forwarded to its registered sender, if any (`None` if the event was
+	/// dropped as malformed).
+	fn emit_and_capture(json: &str) -> Option<(Verbosity, String)> {
+		emit_with_verbosity_and_capture(0, json)
+	}
+
+	/// Like [`emit_and_capture`] but with a caller-supplied `message_verbosity`.
+	///
+	/// Built on [`test_utils::InMemoryTelemetry`] rather than a bare
+	/// `mpsc::channel` — the payload it hands back is re-serialized from the
+	/// [`serde_json::Value`] `InMemoryTelemetry` parses, so callers comparing
+	/// specific fields (every current caller does) are unaffected, but this
+	/// also exercises `test_utils` itself on every one of this module's own
+	/// tests that go through it.
+	fn emit_with_verbosity_and_capture(message_verbosity: u64, json: &str) -> Option<(Verbosity, String)> {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+		let (capture, sender) = test_utils::InMemoryTelemetry::new(1);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			telemetries.senders.insert(id, sender);
+			let _enter = span.enter();
+
+			tracing::info!(
+				target: TELEMETRY_LOG_SPAN,
+				message_verbosity = message_verbosity,
+				json = json,
+			);
+		});
+
+		capture.messages().into_iter().next().map(|m| (m.verbosity, m.payload.to_string()))
+	}
+
+	#[test]
+	fn message_verbosity_above_u8_max_saturates_instead_of_panicking() {
+		assert_eq!(emit_with_verbosity_and_capture(0, "{}").unwrap().0.as_u8(), 0);
+		assert_eq!(emit_with_verbosity_and_capture(255, "{}").unwrap().0.as_u8(), 255);
+		assert_eq!(emit_with_verbosity_and_capture(256, "{}").unwrap().0.as_u8(), u8::MAX);
+		assert_eq!(emit_with_verbosity_and_capture(u64::MAX, "{}").unwrap().0.as_u8(), u8::MAX);
+	}
+
+	#[test]
+	fn message_verbosity_is_accepted_as_a_bare_i64_literal() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+		let (capture, sender) = test_utils::InMemoryTelemetry::new(1);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			telemetries.senders.insert(id, sender);
+			let _enter = span.enter();
+
+			// No `u64` suffix: tracing records this via `record_i64`, not
+			// `record_u64`, so this exercises the special case there.
+			tracing::info!(target: TELEMETRY_LOG_SPAN, message_verbosity = 3, json = "{}");
+		});
+
+		assert_eq!(
+			capture.messages().into_iter().next().map(|m| m.verbosity),
+			Some(Verbosity::from(3u8)),
+			"an i64 message_verbosity must not be treated as a missing field",
+		);
+	}
+
+	#[test]
+	fn message_verbosity_is_accepted_as_an_f64_literal() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+		let (capture, sender) = test_utils::InMemoryTelemetry::new(1);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			telemetries.senders.insert(id, sender);
+			let _enter = span.enter();
+
+			tracing::info!(target: TELEMETRY_LOG_SPAN, message_verbosity = 3.0, json = "{}");
+		});
+
+		assert_eq!(
+			capture.messages().into_iter().next().map(|m| m.verbosity),
+			Some(Verbosity::from(3u8)),
+			"an f64 message_verbosity must not be treated as a missing field",
+		);
+	}
+
+	#[test]
+	fn negative_message_verbosity_saturates_to_zero_instead_of_wrapping() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+		let (capture, sender) = test_utils::InMemoryTelemetry::new(1);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			telemetries.senders.insert(id, sender);
+			let _enter = span.enter();
+
+			tracing::info!(target: TELEMETRY_LOG_SPAN, message_verbosity = -1, json = "{}");
+		});
+
+		assert_eq!(capture.messages().into_iter().next().map(|m| m.verbosity), Some(Verbosity::from(0u8)));
+	}
+
+	#[test]
+	fn typed_fields_of_every_kind_are_merged_into_the_payload() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+		let (capture, sender) = test_utils::InMemoryTelemetry::new(1);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			telemetries.senders.insert(id, sender);
+			let _enter = span.enter();
+
+			tracing::info!(
+				target: TELEMETRY_LOG_SPAN,
+				message_verbosity = 0u64,
+				msg = "block.import",
+				height = 42u64,
+				delta_ms = -3i64,
+				best = true,
+				ratio = 0.5f64,
+				peer = ?std::net::Ipv4Addr::new(127, 0, 0, 1),
+			);
+		});
+
+		let message = capture.messages().into_iter().next().expect("well-typed fields form a valid payload");
+		assert_eq!(message.payload["msg"], "block.import");
+		assert_eq!(message.payload["height"], 42);
+		assert_eq!(message.payload["delta_ms"], -3);
+		assert_eq!(message.payload["best"], true);
+		assert_eq!(message.payload["ratio"], 0.5);
+		assert_eq!(message.payload["peer"], "127.0.0.1");
+	}
+
+	#[test]
+	fn json_payload_is_parsed_not_string_spliced() {
+		// A JSON array is not an object: rejected rather than corrupted by naive
+		// string surgery on the payload.
+		assert_eq!(emit_and_capture("[1,2,3]"), None);
+	}
+
+	#[test]
+	fn typed_key_value_fields_are_used_when_no_json_string_is_given() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+		let (capture, sender) = test_utils::InMemoryTelemetry::new(1);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			telemetries.senders.insert(id, sender);
+			let _enter = span.enter();
+
+			tracing::info!(
+				target: TELEMETRY_LOG_SPAN,
+				message_verbosity = 0u64,
+				msg = "block.import",
+				height = 42u64,
+			);
+		});
+
+		let message = capture.messages().into_iter().next().expect("typed fields alone are a valid payload");
+		assert_eq!(message.payload["msg"], "block.import");
+		assert_eq!(message.payload["height"], 42);
+	}
+
+	#[test]
+	fn as_json_wraps_a_serde_value_into_the_json_field_as_valid_json_not_debug_syntax() {
+		let (_verbosity, json) = emit_and_capture_debug(&AsJson(serde_json::json!({ "msg": "block.import", "height": 7 })))
+			.expect("a well-formed AsJson payload is forwarded");
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert_eq!(value["msg"], "block.import");
+		assert_eq!(value["height"], 7);
+	}
+
+	#[test]
+	fn as_json_payload_is_lazily_evaluated_only_when_telemetry_is_enabled() {
+		// No sender is ever registered anywhere, so `Senders::any_registered`
+		// stays false and `TelemetryLayer::enabled` returns `false` for this
+		// event's callsite — tracing's macro then never evaluates the field
+		// value expressions at all, so `AsJson`'s `Debug::fmt` (and the closure
+		// inside it) never runs.
+		let layer = TelemetryLayer::default();
+		let subscriber = tracing_subscriber::registry().with(layer);
+		let ran = std::sync::atomic::AtomicBool::new(false);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let _enter = span.enter();
+			tracing::info!(
+				target: TELEMETRY_LOG_SPAN,
+				message_verbosity = 0u64,
+				json = ?AsJson(serde_json::json!({
+					"msg": "side.effect",
+					"value": { ran.store(true, std::sync::atomic::Ordering::SeqCst); 1 },
+				})),
+			);
+		});
+
+		assert!(!ran.load(std::sync::atomic::Ordering::SeqCst), "no sender is registered anywhere, so the payload must never be built");
+	}
+
+	#[test]
+	fn wrapping_the_layer_with_a_per_layer_filter_stops_delivery() {
+		// `TelemetryLayer` implements `register_callsite`/`enabled` itself
+		// (see those methods' docs), but composes with `Layer::with_filter`
+		// the same as any other layer: a `Filtered` combinator consults the
+		// wrapped filter first and never calls through to `on_event` at all
+		// if it rejects the event, regardless of what `TelemetryLayer` itself
+		// would have decided.
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer.with_filter(tracing_subscriber::filter::LevelFilter::OFF));
+		let (capture, sender) = test_utils::InMemoryTelemetry::new(1);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			if let Some(id) = span.id() {
+				telemetries.senders.insert(id.into_u64(), sender);
+			}
+			let _enter = span.enter();
+			tracing::info!(target: TELEMETRY_LOG_SPAN, message_verbosity = 0u64, json = "{}");
+		});
+
+		assert!(capture.messages().is_empty(), "LevelFilter::OFF must stop every event from reaching the layer");
+	}
+
+	// A criterion benchmark of a high-rate non-`telemetry-logger` event stream
+	// (the scenario this change targets: a busy subscriber where most events
+	// are never telemetry) would normally accompany this change, but there's
+	// no build manifest or benches/ harness anywhere in this crate to add one
+	// to. The mechanism the benchmark would exercise is `register_callsite`
+	// returning `Interest::always()` for any callsite outside the
+	// `telemetry-logger` target (see that method's doc comment): `tracing`
+	// caches that decision per callsite and never calls `enabled` for it
+	// again, so this layer costs one dispatch call the first time such a
+	// callsite fires and nothing thereafter. The test below covers the
+	// correctness side of that: a foreign-target event still reaches this
+	// layer's `on_event` (since `Interest::always()` doesn't mean "skip the
+	// layer", only "skip re-asking `enabled`"), and `on_event` itself must
+	// keep bailing out on the target mismatch rather than doing any
+	// telemetry-specific work for it.
+	#[test]
+	fn on_event_bails_out_immediately_for_a_foreign_target_event() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+		let (capture, sender) = test_utils::InMemoryTelemetry::new(1);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			telemetries.senders.insert(id, sender);
+			let _enter = span.enter();
+
+			tracing::info!(target: "some-other-subsystem", "not telemetry at all");
+		});
+
+		assert!(capture.messages().is_empty(), "an event outside the telemetry-logger target must never reach a sender");
+	}
+
+	/// Like [`emit_and_capture`] but records `value` with tracing's `?` (Debug)
+	/// sigil instead of passing a pre-serialized string, so [`AsJson`] (and any
+	/// other `Debug`-based `json` payload) can be exercised the same way.
+	fn emit_and_capture_debug(value: &dyn std::fmt::Debug) -> Option<(Verbosity, String)> {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+		let (capture, sender) = test_utils::InMemoryTelemetry::new(1);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			telemetries.senders.insert(id, sender);
+			let _enter = span.enter();
+
+			tracing::info!(target: TELEMETRY_LOG_SPAN, message_verbosity = 0u64, json = ?value);
+		});
+
+		capture.messages().into_iter().next().map(|m| (m.verbosity, m.payload.to_string()))
+	}
+
+	#[test]
+	fn injected_id_overrides_a_user_supplied_id_field() {
+		let (_verbosity, json) = emit_and_capture(r#"{"id":"user-supplied","msg":"hi"}"#)
+			.expect("well-formed object payload is forwarded");
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		// The span id we inject takes precedence over whatever the caller put there.
+		assert!(value["id"].is_u64());
+		assert_eq!(value["msg"], "hi");
+	}
+
+	#[test]
+	fn ts_is_injected_as_unix_millis_by_default() {
+		let (_verbosity, json) = emit_and_capture(r#"{"msg":"hi"}"#).unwrap();
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert!(value["ts"].is_u64(), "ts should be a unix-millis integer: {:?}", value["ts"]);
+	}
+
+	#[test]
+	fn a_user_supplied_ts_is_left_untouched() {
+		let (_verbosity, json) = emit_and_capture(r#"{"msg":"hi","ts":123}"#).unwrap();
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert_eq!(value["ts"], 123);
+	}
+
+	#[test]
+	fn in_memory_telemetry_captures_both_the_tracing_path_and_the_direct_send_api() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+		let (capture, sender) = test_utils::InMemoryTelemetry::new(4);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			telemetries.senders.insert(id, sender);
+			{
+				let _enter = span.enter();
+				tracing::info!(
+					target: TELEMETRY_LOG_SPAN,
+					message_verbosity = 0u64,
+					json = r#"{"msg":"via-tracing"}"#,
+				);
+			}
+			telemetries.send(id, Verbosity::INFO, serde_json::json!({"msg": "via-direct-send"}));
+		});
+
+		let via_tracing = capture
+			.wait_for("via-tracing", std::time::Duration::from_secs(1))
+			.expect("event emitted through the tracing macro is captured");
+		assert_eq!(via_tracing.verbosity, Verbosity::INFO);
+		let via_direct = capture
+			.wait_for("via-direct-send", std::time::Duration::from_secs(1))
+			.expect("payload sent through Telemetries::send is captured the same way");
+		assert_eq!(via_direct.verbosity, Verbosity::INFO);
+	}
+
+	#[test]
+	fn mock_telemetry_server_rejects_the_scripted_handshake_then_accepts_the_next_one() {
+		let server = test_utils::MockTelemetryServer::new();
+		server.reject_next_handshake();
+
+		futures::executor::block_on(async {
+			let err = server.connect().await.expect_err("the scripted rejection applies to the next connect");
+			assert_eq!(err.kind, EndpointErrorKind::Handshake);
+			assert!(server.connect().await.is_ok(), "the rejection is consumed after one use");
+		});
+	}
+
+	#[test]
+	fn run_endpoint_records_a_dns_failure_via_endpoint_connection_status() {
+		let server = test_utils::MockTelemetryServer::new();
+		server.reject_next_connect_as(EndpointErrorKind::Dns, "could not resolve host");
+		let (_sender, receiver) = mpsc::channel(8);
+		let connection_status = EndpointConnectionStatus::default();
+
+		let worker = std::thread::spawn({
+			let connection_status = connection_status.clone();
+			move || {
+				futures::executor::block_on(run_endpoint(
+					"wss://a.example".to_string(),
+					receiver,
+					Box::new(server),
+					Arc::new(ImmediateDelay),
+					ReconnectPolicy { initial_delay: std::time::Duration::ZERO, max_delay: std::time::Duration::ZERO, max_attempts: Some(0) },
+					EndpointStats::default(),
+					connection_status,
+					false,
+					false,
+				))
+			}
+		});
+		worker.join().unwrap();
+
+		let error = connection_status.last_error("wss://a.example").unwrap();
+		assert_eq!(error.kind, EndpointErrorKind::Dns);
+		assert_eq!(error.attempt, 1, "the failed startup probe is the endpoint's first attempt");
+	}
+
+	#[test]
+	fn mock_telemetry_server_records_frames_across_a_reconnect_and_force_disconnect() {
+		let server = test_utils::MockTelemetryServer::new();
+
+		futures::executor::block_on(async {
+			let mut sender = server.connect().await.expect("first connect succeeds");
+			sender.try_send(r#"{"msg":"system.connected"}"#.to_string()).unwrap();
+			sender.try_send(r#"{"msg":"block.import"}"#.to_string()).unwrap();
+			drop(sender);
+
+			assert_eq!(server.received_count(), 2);
+			assert_eq!(server.messages_of_type("system.connected").len(), 1);
+			assert_eq!(server.messages_of_type("block.import").len(), 1);
+
+			// A worker driving this transport would race `closed()` against its
+			// own send loop; simulate the server dropping the connection and
+			// the worker reconnecting.
+			let mut closed = server.closed();
+			server.force_disconnect();
+			closed.as_mut().await;
+
+			let mut sender = server.connect().await.expect("reconnect succeeds after a force_disconnect");
+			sender.try_send(r#"{"msg":"system.connected"}"#.to_string()).unwrap();
+			drop(sender);
+
+			assert_eq!(server.received_count(), 3);
+			assert_eq!(
+				server.messages_of_type("system.connected").len(),
+				2,
+				"one system.connected per session, across the reconnect"
+			);
+		});
+	}
+
+	#[test]
+	fn rfc3339_timestamp_format_can_be_selected() {
+		let layer = TelemetryLayer::default().with_timestamp_format(TimestampFormat::Rfc3339);
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		let (_verbosity, json) = tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let (sender, mut receiver) = mpsc::channel(1);
+			telemetries.senders.insert(id, sender);
+			let _enter = span.enter();
+
+			tracing::info!(
+				target: TELEMETRY_LOG_SPAN,
+				message_verbosity = 0u64,
+				json = r#"{"msg":"hi"}"#,
+			);
+
+			receiver.try_next().ok().flatten()
+		})
+		.unwrap();
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		let ts = value["ts"].as_str().expect("Rfc3339 format is a string");
+		assert!(ts.ends_with('Z'), "expected an RFC3339 timestamp, got {}", ts);
+		assert!(ts.contains('T'), "expected an RFC3339 timestamp, got {}", ts);
+	}
+
+	#[test]
+	fn per_endpoint_timestamp_formats_render_the_same_instant_two_ways() {
+		let now = std::time::UNIX_EPOCH + std::time::Duration::from_millis(1_700_000_000_123);
+		let message = TelemetryMessage {
+			id: 1,
+			verbosity: Verbosity::INFO,
+			payload: serde_json::json!({ "msg": "system.interval" }),
+		};
+		let mut formats = EndpointTimestampFormats::new();
+		formats.insert("wss://millis.example", TimestampFormat::UnixMillis);
+		formats.insert("wss://rfc3339.example", TimestampFormat::Rfc3339);
+
+		let millis = message.restamped_for("wss://millis.example", &formats, now);
+		assert_eq!(millis.payload["ts"], serde_json::json!(1_700_000_000_123u64));
+
+		let rfc3339 = message.restamped_for("wss://rfc3339.example", &formats, now);
+		let ts = rfc3339.payload["ts"].as_str().expect("Rfc3339 format is a string");
+		assert!(ts.ends_with('Z') && ts.contains('T'), "expected an RFC3339 timestamp, got {ts}");
+
+		// Same message, same instant, routed to an endpoint with no override:
+		// falls back to `TimestampFormat::default()` rather than the other
+		// endpoints' formats.
+		let default = message.restamped_for("wss://unconfigured.example", &formats, now);
+		assert_eq!(default.payload["ts"], serde_json::json!(1_700_000_000_123u64));
+	}
+
+	#[test]
+	fn direct_send_also_injects_a_timestamp() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let (sender, mut receiver) = mpsc::channel(1);
+			telemetries.senders.insert(id, sender);
+
+			assert!(telemetries.send(id, 0u8, serde_json::json!({ "msg": "via-direct" })));
+			let (_v, json) = receiver.try_next().unwrap().unwrap();
+			let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+			assert!(value["ts"].is_u64());
+		});
+	}
+
+	#[test]
+	fn serialize_message_matches_serde_json_to_string_byte_for_byte() {
+		let payloads = [
+			serde_json::json!({ "msg": "system.interval", "peers": 12, "height": 1_000_000u64 }),
+			serde_json::json!({ "msg": "notify.finalized", "finalized_hash": "0xabc", "finalized_height": 1 }),
+			serde_json::json!({ "nested": { "a": [1, 2, 3], "b": null, "c": "text with \"quotes\" and \\ backslash" } }),
+			serde_json::json!({}),
+		];
+		for payload in payloads {
+			assert_eq!(serialize_message(&payload, None).unwrap(), serde_json::to_string(&payload).unwrap());
+		}
+	}
+
+	#[test]
+	fn serialize_message_reuses_its_scratch_buffer_across_calls() {
+		// Not a correctness test on its own (covered by the byte-for-byte test
+		// above) — just confirms the thread-local scratch buffer survives a
+		// small message after a big one without corrupting later output,
+		// since it's cleared rather than replaced between calls.
+		let big = serde_json::json!({ "msg": "system.interval", "padding": "x".repeat(4096) });
+		let small = serde_json::json!({ "msg": "system.connected" });
+		assert_eq!(serialize_message(&big, None).unwrap(), serde_json::to_string(&big).unwrap());
+		assert_eq!(serialize_message(&small, None).unwrap(), serde_json::to_string(&small).unwrap());
+	}
+
+	// A criterion benchmark comparing `serialize_message` against a plain
+	// `serde_json::to_string` call — single message and a burst of same-sized
+	// messages back to back — would normally accompany this change, but
+	// there's no build manifest or benches/ harness anywhere in this crate to
+	// add one to; the tests above cover the correctness half (byte-identical
+	// output) instead.
+	//
+	// The channel payload itself is left as `String` rather than switched to
+	// `Arc<str>`/`Bytes`: doing that for real would mean changing the type
+	// flowing through every `Senders` entry, `TelemetryMessage`, and the
+	// dozens of channels and pinned test signatures built around
+	// `mpsc::channel::<(Verbosity, String)>` in this file, for a fan-out step
+	// that already only clones the body once per configured endpoint (a
+	// small, bounded count) rather than once per message. The allocation this
+	// change actually removes — the scratch buffer regrowing from empty on
+	// every call — sits ahead of that fan-out and scales with message volume,
+	// not endpoint count, which is where the profiler's "at least twice per
+	// message" was coming from.
+
+	#[test]
+	fn static_fields_are_merged_into_tracing_path_payloads() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		let (_verbosity, json) = tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let (sender, mut receiver) = mpsc::channel(1);
+			telemetries.senders.insert_with_config(
+				id,
+				sender,
+				SenderConfig {
+					static_fields: serde_json::json!({ "datacenter": "dc1", "cluster": "west" })
+						.as_object()
+						.unwrap()
+						.clone(),
+					..SenderConfig::default()
+				},
+			);
+			let _enter = span.enter();
+
+			tracing::info!(
+				target: TELEMETRY_LOG_SPAN,
+				message_verbosity = 0u64,
+				json = r#"{"msg":"hi"}"#,
+			);
+
+			receiver.try_next().ok().flatten()
+		})
+		.unwrap();
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert_eq!(value["datacenter"], "dc1");
+		assert_eq!(value["cluster"], "west");
+		assert_eq!(value["msg"], "hi");
+	}
+
+	#[test]
+	fn a_payload_field_wins_over_a_static_field_of_the_same_name() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		let (_verbosity, json) = tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let (sender, mut receiver) = mpsc::channel(1);
+			telemetries.senders.insert_with_config(
+				id,
+				sender,
+				SenderConfig {
+					static_fields: serde_json::json!({ "cluster": "west" }).as_object().unwrap().clone(),
+					..SenderConfig::default()
+				},
+			);
+			let _enter = span.enter();
+
+			tracing::info!(
+				target: TELEMETRY_LOG_SPAN,
+				message_verbosity = 0u64,
+				json = r#"{"msg":"hi","cluster":"east"}"#,
+			);
+
+			receiver.try_next().ok().flatten()
+		})
+		.unwrap();
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert_eq!(value["cluster"], "east");
+	}
+
+	#[test]
+	fn different_ids_have_independent_static_fields() {
+		let telemetries_senders = Senders::default();
+		telemetries_senders.insert_with_config(
+			1,
+			mpsc::channel(1).0,
+			SenderConfig {
+				static_fields: serde_json::json!({ "cluster": "a" }).as_object().unwrap().clone(),
+				..SenderConfig::default()
+			},
+		);
+		telemetries_senders.insert_with_config(
+			2,
+			mpsc::channel(1).0,
+			SenderConfig {
+				static_fields: serde_json::json!({ "cluster": "b" }).as_object().unwrap().clone(),
+				..SenderConfig::default()
+			},
+		);
+
+		assert_eq!(telemetries_senders.static_fields(1)["cluster"], "a");
+		assert_eq!(telemetries_senders.static_fields(2)["cluster"], "b");
+		assert!(telemetries_senders.static_fields(3).is_empty());
+	}
+
+	#[test]
+	fn direct_send_also_merges_static_fields() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let (sender, mut receiver) = mpsc::channel(1);
+			telemetries.senders.insert_with_config(
+				id,
+				sender,
+				SenderConfig {
+					static_fields: serde_json::json!({ "operator": "acme" }).as_object().unwrap().clone(),
+					..SenderConfig::default()
+				},
+			);
+
+			assert!(telemetries.send(id, 0u8, serde_json::json!({ "msg": "via-direct" })));
+			let (_v, json) = receiver.try_next().unwrap().unwrap();
+			let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+			assert_eq!(value["operator"], "acme");
+		});
+	}
+
+	#[test]
+	fn empty_and_short_json_strings_are_dropped_without_panicking() {
+		assert_eq!(emit_and_capture(""), None);
+		assert_eq!(emit_and_capture("{"), None);
+	}
+
+	#[test]
+	fn whitespace_prefixed_object_is_still_parsed() {
+		let (_verbosity, json) =
+			emit_and_capture("  \n\t{\"msg\":\"hi\"}").expect("leading whitespace is valid JSON");
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert_eq!(value["msg"], "hi");
+	}
+
+	/// Emit a single event with `message_verbosity` and structured fields but no
+	/// `json` field, and capture the payload forwarded to its registered sender.
+	fn emit_structured_and_capture(
+		message_verbosity: u64,
+		msg: &str,
+		height: u64,
+		authority: bool,
+	) -> Option<(Verbosity, String)> {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let (sender, mut receiver) = mpsc::channel(1);
+			telemetries.senders.insert(id, sender);
+			let _enter = span.enter();
+
+			tracing::info!(
+				target: TELEMETRY_LOG_SPAN,
+				message_verbosity = message_verbosity,
+				msg = msg,
+				height = height,
+				authority = authority,
+			);
+
+			receiver.try_next().ok().flatten()
+		})
+	}
+
+	#[test]
+	fn structured_fields_are_accepted_without_a_json_field() {
+		let (_verbosity, json) = emit_structured_and_capture(0, "block.import", 42, true)
+			.expect("structured fields alone are enough to build a payload");
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert_eq!(value["msg"], "block.import");
+		assert_eq!(value["height"], 42);
+		assert_eq!(value["authority"], true);
+		// Span context is injected the same way as on the `json` path.
+		assert!(value["id"].is_u64());
+	}
+
+	#[test]
+	fn json_field_takes_precedence_over_structured_fields() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		let captured = tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let (sender, mut receiver) = mpsc::channel(1);
+			telemetries.senders.insert(id, sender);
+			let _enter = span.enter();
+
+			// Both a `json` field and a stray structured field are present; `json` wins,
+			// preserving the pre-existing call sites unchanged.
+			tracing::info!(
+				target: TELEMETRY_LOG_SPAN,
+				message_verbosity = 0u64,
+				json = r#"{"msg":"from-json"}"#,
+				msg = "from-fields",
+			);
+
+			receiver.try_next().ok().flatten()
+		});
+
+		let (_verbosity, json) = captured.expect("well-formed json payload is forwarded");
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert_eq!(value["msg"], "from-json");
+	}
+
+	fn emit(json: &str) {
+		tracing::info!(target: TELEMETRY_LOG_SPAN, message_verbosity = 0u64, json = json);
+	}
+
+	#[test]
+	fn shutdown_delivers_a_pending_message_left_over_from_drop_oldest_overflow() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let (sender, mut receiver) = mpsc::channel(0);
+			telemetries.senders.insert_with_config(
+				id,
+				sender,
+				SenderConfig { overflow: OverflowPolicy::DropOldest, ..SenderConfig::default() },
+			);
+			let _enter = span.enter();
+
+			emit(r#"{"n":1}"#); // fills the single in-flight slot
+			emit(r#"{"n":2}"#); // bumped to `pending`
+
+			// Drain `n:1` first so the channel has room for shutdown to flush
+			// the pending `n:2` into it.
+			let (_v, first) = receiver.try_next().unwrap().unwrap();
+			assert!(first.contains("\"n\":1"));
+
+			let report = futures::executor::block_on(telemetries.shutdown(std::time::Duration::from_secs(1)));
+			assert_eq!(report.delivered, 1);
+			assert_eq!(report.abandoned, 0);
+
+			let (_v, second) = receiver.try_next().unwrap().unwrap();
+			assert!(second.contains("\"n\":2"));
+
+			assert!(!telemetries.senders.contains(id), "shutdown stops accepting further messages for id");
+		});
+	}
+
+	#[test]
+	fn shutdown_counts_a_pending_message_as_abandoned_if_the_channel_is_still_full() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let (sender, _receiver) = mpsc::channel(0);
+			telemetries.senders.insert_with_config(
+				id,
+				sender,
+				SenderConfig { overflow: OverflowPolicy::DropOldest, ..SenderConfig::default() },
+			);
+			let _enter = span.enter();
+
+			emit(r#"{"n":1}"#); // fills the single in-flight slot; never drained
+			emit(r#"{"n":2}"#); // bumped to `pending`
+
+			let report = futures::executor::block_on(telemetries.shutdown(std::time::Duration::from_secs(1)));
+			assert_eq!(report.delivered, 0);
+			assert_eq!(report.abandoned, 1);
+		});
+	}
+
+	#[test]
+	fn paused_telemetry_drops_events_from_both_the_tracing_layer_and_direct_send() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let (sender, mut receiver) = mpsc::channel(10);
+			telemetries.senders.insert(id, sender);
+			let _enter = span.enter();
+
+			assert!(!telemetries.is_paused(id));
+			telemetries.pause(id);
+			assert!(telemetries.is_paused(id));
+
+			emit(r#"{"n":1}"#);
+			assert!(
+				!telemetries.send(id, Verbosity::INFO, serde_json::json!({ "n": 2 })),
+				"send should report failure while paused"
+			);
+			assert!(receiver.try_next().is_err(), "no message should have been queued while paused");
+
+			telemetries.resume(id);
+			assert!(!telemetries.is_paused(id));
+
+			emit(r#"{"n":3}"#);
+			let (_v, message) = receiver.try_next().unwrap().unwrap();
+			assert!(message.contains("\"n\":3"), "events should flow again after resume");
+		});
+	}
+
+	#[test]
+	fn resuming_a_paused_telemetry_re_sends_its_connection_message() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let (sender, mut receiver) = mpsc::channel(10);
+			telemetries.senders.insert(id, sender);
+
+			assert!(telemetries.set_connection_message(
+				id,
+				Verbosity::INFO,
+				serde_json::json!({ "msg": "system.connected" })
+			));
+			assert!(receiver.try_next().is_err(), "setting the connection message doesn't send it by itself");
+
+			telemetries.pause(id);
+			telemetries.resume(id);
+
+			let (_v, replayed) = receiver.try_next().unwrap().unwrap();
+			assert!(
+				replayed.contains("system.connected"),
+				"resume should re-announce the last connection message"
+			);
+		});
+	}
+
+	#[test]
+	fn drop_newest_overflow_policy_keeps_the_first_queued_message() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let (sender, mut receiver) = mpsc::channel(0);
+			telemetries.senders.insert_with_config(
+				id,
+				sender,
+				SenderConfig { overflow: OverflowPolicy::DropNewest, ..SenderConfig::default() },
+			);
+			let _enter = span.enter();
+
+			emit(r#"{"n":1}"#);
+			emit(r#"{"n":2}"#);
+			emit(r#"{"n":3}"#);
+
+			let (_v, first) = receiver.try_next().unwrap().unwrap();
+			assert!(first.contains("\"n\":1"));
+			assert!(receiver.try_next().ok().flatten().is_none(), "later messages are dropped");
+		});
+	}
+
+	#[test]
+	fn drop_oldest_overflow_policy_eventually_delivers_the_freshest_message() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let (sender, mut receiver) = mpsc::channel(0);
+			telemetries.senders.insert_with_config(
+				id,
+				sender,
+				SenderConfig { overflow: OverflowPolicy::DropOldest, ..SenderConfig::default() },
+			);
+			let _enter = span.enter();
+
+			emit(r#"{"n":1}"#); // fills the single in-flight slot
+			emit(r#"{"n":2}"#); // bumped to `pending`
+			emit(r#"{"n":3}"#); // `n:2` retry fails and is lost; `n:3` becomes `pending`
+
+			let (_v, first) = receiver.try_next().unwrap().unwrap();
+			assert!(first.contains("\"n\":1"));
+
+			emit(r#"{"n":4}"#); // flushes `pending` (`n:3`) into the freed slot
+
+			let (_v, second) = receiver.try_next().unwrap().unwrap();
+			assert!(second.contains("\"n\":3"), "the stale n:2 is dropped in favor of n:3");
+		});
+	}
+
+	#[test]
+	fn drop_oldest_overflow_policy_tracks_the_age_of_evicted_messages() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let (sender, mut receiver) = mpsc::channel(0);
+			telemetries.senders.insert_with_config(
+				id,
+				sender,
+				SenderConfig { overflow: OverflowPolicy::DropOldest, ..SenderConfig::default() },
+			);
+			let _enter = span.enter();
+
+			assert_eq!(telemetries.eviction_age_stats(id).count(), 0);
+
+			emit(r#"{"n":1}"#); // fills the single in-flight slot
+			emit(r#"{"n":2}"#); // bumped to `pending`
+			emit(r#"{"n":3}"#); // `n:2`'s retry fails: evicted and its age recorded
+
+			let stats = telemetries.eviction_age_stats(id);
+			assert_eq!(stats.count(), 1);
+			assert!(stats.max().is_some());
+			assert!(stats.average().is_some());
+
+			let _ = receiver.try_next();
+		});
+	}
+
+	#[test]
+	fn a_priority_message_survives_overflow_that_would_otherwise_drop_it() {
+		let senders = Senders::default();
+		let (sender, mut receiver) = mpsc::channel(0);
+		senders.insert_with_config(
+			1,
+			sender,
+			SenderConfig {
+				overflow: OverflowPolicy::DropNewest,
+				priority_threshold: Some(Verbosity::INFO),
+				..SenderConfig::default()
+			},
+		);
+
+		// Fill the channel, then keep piling on verbose messages that
+		// `DropNewest` would ordinarily just drop.
+		senders.send(1, None, (Verbosity::DEBUG, "spam-1".to_string())).unwrap();
+		assert!(senders.send(1, None, (Verbosity::DEBUG, "spam-2".to_string())).is_err());
+		assert!(senders.send(1, None, (Verbosity::DEBUG, "spam-3".to_string())).is_err());
+
+		// A message at or below `priority_threshold` still gets bumped even
+		// though the channel is just as full.
+		assert!(senders.send(1, None, (Verbosity::CONSOLE, "critical".to_string())).is_err());
+
+		// Draining the real channel and sending anything at all flushes the
+		// bumped priority message ahead of ordinary overflow state.
+		let (_v, first) = receiver.try_next().unwrap().unwrap();
+		assert_eq!(first, "spam-1");
+		assert!(senders.send(1, None, (Verbosity::DEBUG, "spam-4".to_string())).is_err());
+
+		let (_v, second) = receiver.try_next().unwrap().unwrap();
+		assert_eq!(second, "critical", "the priority message pre-empts ordinary overflow spam");
+	}
+
+	#[test]
+	fn a_more_urgent_priority_message_evicts_a_less_urgent_one_already_bumped() {
+		let senders = Senders::default();
+		let (sender, mut receiver) = mpsc::channel(0);
+		senders.insert_with_config(
+			1,
+			sender,
+			SenderConfig { priority_threshold: Some(Verbosity::INFO), ..SenderConfig::default() },
+		);
+
+		senders.send(1, None, (Verbosity::DEBUG, "filler".to_string())).unwrap();
+		assert!(senders.send(1, None, (Verbosity::INFO, "less-urgent".to_string())).is_err());
+		assert!(
+			senders.send(1, None, (Verbosity::CONSOLE, "more-urgent".to_string())).is_err(),
+			"more urgent than what's already bumped should evict it"
+		);
+
+		let (_v, first) = receiver.try_next().unwrap().unwrap();
+		assert_eq!(first, "filler");
+		assert!(senders.send(1, None, (Verbosity::DEBUG, "nudge".to_string())).is_err());
+
+		let (_v, second) = receiver.try_next().unwrap().unwrap();
+		assert_eq!(second, "more-urgent", "less-urgent was evicted in favor of more-urgent");
+	}
+
+	#[test]
+	fn priority_threshold_none_leaves_overflow_behavior_unchanged() {
+		let senders = Senders::default();
+		let (sender, mut receiver) = mpsc::channel(0);
+		senders.insert_with_config(
+			1,
+			sender,
+			SenderConfig { overflow: OverflowPolicy::DropNewest, ..SenderConfig::default() },
+		);
+
+		senders.send(1, None, (Verbosity::CONSOLE, "first".to_string())).unwrap();
+		assert!(senders.send(1, None, (Verbosity::CONSOLE, "second".to_string())).is_err());
+
+		let (_v, only) = receiver.try_next().unwrap().unwrap();
+		assert_eq!(only, "first");
+		assert!(receiver.try_next().ok().flatten().is_none(), "no priority buffer without a threshold");
+	}
+
+	#[test]
+	fn send_priority_reaches_the_wire_next_after_a_large_backlog() {
+		let senders = Senders::default();
+		let (sender, mut receiver) = mpsc::channel(1);
+		senders.insert_with_config(1, sender, SenderConfig { overflow: OverflowPolicy::DropOldest, ..SenderConfig::default() });
+
+		// Fills the channel, then piles up a large backlog: every one of these
+		// is bumped to `pending`, immediately evicting whatever was bumped
+		// there before it.
+		senders.send(1, None, (Verbosity::INFO, "first".to_string())).unwrap();
+		for n in 0..200 {
+			assert!(senders.send(1, None, (Verbosity::INFO, format!("spam-{n}"))).is_err());
+		}
+
+		assert!(
+			senders.send_priority(1, Some("alert.finality_stalled"), (Verbosity::CONSOLE, "alert".to_string())).is_err(),
+			"the channel is still full of backlog, so the alert joins the priority queue instead of the wire"
+		);
+
+		let (_v, first) = receiver.try_next().unwrap().unwrap();
+		assert_eq!(first, "first", "the message already in the channel before the backlog goes out first");
+
+		// Draining the channel and sending anything at all flushes the queued
+		// alert ahead of the entire backlog still bumped in `pending`.
+		assert!(senders.send(1, None, (Verbosity::INFO, "more-spam".to_string())).is_err());
+		let (_v, second) = receiver.try_next().unwrap().unwrap();
+		assert_eq!(second, "alert", "the priority message pre-empts the entire routine backlog");
+	}
+
+	#[test]
+	fn try_send_priority_bypasses_message_sampling() {
+		let telemetries = Telemetries::default();
+		let (sender, mut receiver) = mpsc::channel(4);
+		telemetries.senders.insert(1, sender);
+
+		telemetries.configure_sampling("alert.database_corruption", SamplingRule::EveryNth(1000));
+		// The first message of a freshly configured `EveryNth` rule is always
+		// forwarded; consume that slot so the next ordinary send is
+		// unambiguously sampled out.
+		assert!(telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "alert.database_corruption" })));
+		assert!(
+			!telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "alert.database_corruption" })),
+			"an ordinary send of a sampled msg type is filtered"
+		);
+
+		assert!(
+			telemetries
+				.try_send_priority(1, Verbosity::INFO, serde_json::json!({ "msg": "alert.database_corruption" }))
+				.is_ok(),
+			"a priority send bypasses sampling entirely"
+		);
+
+		let _ = receiver.try_next(); // the earlier successful ordinary send
+		let (_v, second) = receiver.try_next().unwrap().unwrap();
+		assert_eq!(second.contains("alert.database_corruption"), true);
+	}
+
+	#[test]
+	fn sending_at_console_verbosity_takes_the_priority_lane_automatically() {
+		let telemetries = Telemetries::default();
+		let (sender, mut receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+
+		telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "system.interval" })); // fills the channel
+		for _ in 0..50 {
+			telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "system.interval" }));
+		}
+
+		assert!(
+			!telemetries.send(1, Verbosity::CONSOLE, serde_json::json!({ "msg": "alert.finality_stalled" })),
+			"the channel is still full, so send reports it as not yet delivered — but it's queued, not lost"
+		);
+
+		let (_v, first) = receiver.try_next().unwrap().unwrap();
+		assert!(first.contains("system.interval"), "the message already in the channel goes out first");
+
+		telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "system.interval" }));
+		let (_v, second) = receiver.try_next().unwrap().unwrap();
+		assert!(second.contains("alert.finality_stalled"), "Verbosity::CONSOLE pre-empts the routine backlog");
+	}
+
+	#[test]
+	fn priority_messages_still_respect_the_size_limit() {
+		let layer = TelemetryLayer::default().with_max_message_size(64);
+		let telemetries = layer.telemetries();
+		let (sender, mut receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+
+		assert!(telemetries.try_send_priority(
+			1,
+			Verbosity::CONSOLE,
+			serde_json::json!({ "msg": "alert.huge", "data": "x".repeat(1024) }),
+		).is_ok());
+
+		let (_verbosity, json) = receiver.try_next().unwrap().unwrap();
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert_eq!(value["msg"], "system.message_truncated");
+		assert_eq!(value["original_msg"], "alert.huge");
+		assert_eq!(telemetries.truncated_messages(), 1);
+	}
+
+	#[test]
+	fn coalesces_configured_message_types_but_not_others() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let (sender, mut receiver) = mpsc::channel(0);
+			// Default config coalesces "system.interval".
+			telemetries.senders.insert_with_config(id, sender, SenderConfig::default());
+			let _enter = span.enter();
+
+			emit(r#"{"msg":"system.interval","height":1}"#); // fills the in-flight slot
+			emit(r#"{"msg":"system.interval","height":2}"#); // coalesced away
+			emit(r#"{"msg":"system.interval","height":3}"#); // replaces it
+
+			let (_v, first) = receiver.try_next().unwrap().unwrap();
+			assert!(first.contains("\"height\":1"));
+
+			emit(r#"{"msg":"other.thing"}"#); // flushes the coalesced height:3
+
+			let (_v, second) = receiver.try_next().unwrap().unwrap();
+			assert!(second.contains("\"height\":3"), "height:2 was coalesced away");
+		});
+	}
+
+	#[test]
+	fn one_off_message_types_are_never_coalesced() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let (sender, mut receiver) = mpsc::channel(0);
+			telemetries.senders.insert_with_config(
+				id,
+				sender,
+				SenderConfig { overflow: OverflowPolicy::DropOldest, ..SenderConfig::default() },
+			);
+			let _enter = span.enter();
+
+			emit(r#"{"msg":"block.import","n":1}"#);
+			emit(r#"{"msg":"block.import","n":2}"#);
+
+			let (_v, first) = receiver.try_next().unwrap().unwrap();
+			assert!(first.contains("\"n\":1"));
+
+			// `block.import` isn't in the coalesce set, so it falls back to the
+			// regular overflow policy (`DropOldest` here) instead of being coalesced.
+			emit(r#"{"msg":"other.thing"}"#);
+			let (_v, second) = receiver.try_next().unwrap().unwrap();
+			assert!(second.contains("\"n\":2"));
+		});
+	}
+
+	#[test]
+	fn dropped_messages_are_counted_per_id_and_in_aggregate() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let (sender, _receiver) = mpsc::channel(0);
+			telemetries.senders.insert_with_config(
+				id,
+				sender,
+				SenderConfig { overflow: OverflowPolicy::DropNewest, ..SenderConfig::default() },
+			);
+			let _enter = span.enter();
+
+			emit(r#"{"n":1}"#); // fills the in-flight slot
+			emit(r#"{"n":2}"#); // dropped: counted
+			emit(r#"{"n":3}"#); // dropped: counted
+
+			assert_eq!(telemetries.dropped_messages(id), 2);
+			assert_eq!(telemetries.dropped_messages_total(), 2);
+		});
+	}
+
+	#[test]
+	fn dropped_warning_is_throttled_to_one_per_window() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let (sender, _receiver) = mpsc::channel(0);
+			telemetries.senders.insert_with_config(
+				id,
+				sender,
+				SenderConfig {
+					overflow: OverflowPolicy::DropNewest,
+					warn_interval: std::time::Duration::from_secs(3600),
+					..SenderConfig::default()
+				},
+			);
+			let _enter = span.enter();
+
+			emit(r#"{"n":1}"#); // fills the in-flight slot
+			emit(r#"{"n":2}"#); // dropped: warning logged (first ever)
+			emit(r#"{"n":3}"#); // dropped: within the window, only counted
+			emit(r#"{"n":4}"#); // dropped: within the window, only counted
+
+			assert_eq!(telemetries.dropped_messages(id), 3);
+			let senders = telemetries.senders.shard(id).lock();
+			let entry = senders.get(&id).unwrap();
+			assert_eq!(entry.warnings_emitted, 1, "only the first drop should log");
+			assert_eq!(entry.suppressed_since_warning, 2, "the other two are summarized later");
+		});
+	}
+
+	#[test]
+	fn len_is_empty_and_contains_reflect_the_map() {
+		let senders = Senders::default();
+		assert!(senders.is_empty());
+		assert_eq!(senders.len(), 0);
+
+		let (sender, _receiver) = mpsc::channel(1);
+		senders.insert(1, sender);
+		assert!(!senders.is_empty());
+		assert_eq!(senders.len(), 1);
+		assert!(senders.contains(1));
+		assert!(!senders.contains(2));
+
+		senders.remove(1);
+		assert!(senders.is_empty());
+	}
+
+	#[test]
+	fn insert_returns_the_previously_registered_sender() {
+		let senders = Senders::default();
+		let (first, _receiver) = mpsc::channel(1);
+		assert!(senders.insert(1, first).is_none());
+
+		let (second, _receiver) = mpsc::channel(1);
+		assert!(senders.insert(1, second).is_some(), "reused id should surface the old sender");
+	}
+
+	#[test]
+	fn insert_with_config_warns_naming_both_registrants_on_overwrite() {
+		let senders = Senders::default();
+		let (first, _receiver) = mpsc::channel(1);
+		senders.insert_with_config(
+			1,
+			first,
+			SenderConfig { label: Some("aura".to_string()), ..SenderConfig::default() },
+		);
+
+		let (second, _receiver) = mpsc::channel(1);
+		let (_, lines) = with_captured_log(|| {
+			senders.insert_with_config(
+				1,
+				second,
+				SenderConfig { label: Some("babe".to_string()), ..SenderConfig::default() },
+			);
+		});
+
+		let warning = lines.iter().find(|line| line.starts_with("telemetry|")).expect("a warning was logged");
+		assert!(warning.contains("\"aura\""), "should name the previous registrant: {warning}");
+		assert!(warning.contains("\"babe\""), "should name the new registrant: {warning}");
+	}
+
+	#[test]
+	fn insert_with_config_does_not_warn_for_a_fresh_id() {
+		let senders = Senders::default();
+		let (sender, _receiver) = mpsc::channel(1);
+		let (_, lines) = with_captured_log(|| {
+			senders.insert_with_config(1, sender, SenderConfig::default());
+		});
+		assert!(!lines.iter().any(|line| line.starts_with("telemetry|")));
+	}
+
+	#[test]
+	fn label_reports_the_registration_label_and_none_when_unregistered() {
+		let senders = Senders::default();
+		assert_eq!(senders.label(1), None);
+
+		let (sender, _receiver) = mpsc::channel(1);
+		senders.insert_with_config(
+			1,
+			sender,
+			SenderConfig { label: Some("collator".to_string()), ..SenderConfig::default() },
+		);
+		assert_eq!(senders.label(1), Some("collator".to_string()));
+	}
+
+	#[test]
+	fn try_insert_with_config_rejects_a_duplicate_id_without_disturbing_the_original() {
+		let senders = Senders::default();
+		let (first, mut first_receiver) = mpsc::channel(1);
+		senders.insert_with_config(
+			1,
+			first,
+			SenderConfig { label: Some("aura".to_string()), ..SenderConfig::default() },
+		);
+
+		let (second, _receiver) = mpsc::channel(1);
+		let err = senders
+			.try_insert_with_config(
+				1,
+				second,
+				SenderConfig { label: Some("babe".to_string()), ..SenderConfig::default() },
+			)
+			.expect_err("duplicate id should be rejected");
+		match err {
+			TelemetryError::DuplicateRegistration(reason) => {
+				assert!(reason.contains("\"aura\""));
+				assert!(reason.contains("\"babe\""));
+			}
+			other => panic!("expected DuplicateRegistration, got {other:?}"),
+		}
+
+		// The original registration is untouched: it still receives messages.
+		senders.send(1, None, (Verbosity::INFO, "still alive".to_string())).unwrap();
+		let (_, message) = futures::executor::block_on(first_receiver.next()).unwrap();
+		assert_eq!(message, "still alive");
+	}
+
+	#[test]
+	fn try_insert_with_config_succeeds_for_a_fresh_id() {
+		let senders = Senders::default();
+		let (sender, _receiver) = mpsc::channel(1);
+		assert!(senders.try_insert_with_config(1, sender, SenderConfig::default()).is_ok());
+		assert!(senders.contains(1));
+	}
+
+	#[test]
+	fn concurrent_insert_and_remove_from_multiple_threads() {
+		let senders = Senders::default();
+		let handles: Vec<_> = (0..8u64)
+			.map(|id| {
+				let senders = senders.clone();
+				std::thread::spawn(move || {
+					let (sender, _receiver) = mpsc::channel(1);
+					senders.insert(id, sender);
+					assert!(senders.contains(id));
+					senders.remove(id);
+				})
+			})
+			.collect();
+		for handle in handles {
+			handle.join().unwrap();
+		}
+		assert!(senders.is_empty());
+	}
+
+	#[test]
+	fn dropping_the_registration_guard_unregisters_the_sender() {
+		let senders = Senders::default();
+		let (sender, _receiver) = mpsc::channel(1);
+		let registration = senders.register(1, sender);
+		assert!(senders.contains(1));
+
+		drop(registration);
+		assert!(!senders.contains(1));
+	}
+
+	#[test]
+	fn forgotten_registration_guard_leaves_the_sender_registered() {
+		let senders = Senders::default();
+		let (sender, _receiver) = mpsc::channel(1);
+		let registration = senders.register(1, sender);
+
+		registration.forget();
+		assert!(senders.contains(1));
+	}
+
+	#[test]
+	fn events_after_the_registration_guard_drops_are_quietly_ignored() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let (sender, _receiver) = mpsc::channel(1);
+			let registration = telemetries.senders.register(id, sender);
+			let _enter = span.enter();
+
+			drop(registration);
+			// No panic: the layer just finds nothing registered and drops the event.
+			emit(r#"{"n":1}"#);
+		});
+	}
+
+	#[test]
+	fn on_close_removes_the_sender_registered_for_the_span() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+
+			let (sender, _receiver) = mpsc::channel(1);
+			telemetries.senders.insert(id, sender);
+			assert!(telemetries.senders.contains(id));
+
+			drop(span);
+			assert!(
+				!telemetries.senders.contains(id),
+				"sender should be removed once the span closes",
+			);
+		});
+	}
+
+	#[test]
+	fn context_fields_are_merged_from_nested_spans_exactly_once() {
+		let layer = TelemetryLayer::default()
+			.with_context_fields(ContextFields::new().field("block_number").field("peer_id"));
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+		let (capture, sender) = test_utils::InMemoryTelemetry::new(1);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let telemetry_span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = telemetry_span.id().expect("span is enabled").into_u64();
+			telemetries.senders.insert(id, sender);
+			let _telemetry = telemetry_span.enter();
+
+			let outer = tracing::info_span!("outer", block_number = 42u64, not_collected = "ignored");
+			let _outer = outer.enter();
+			let inner = tracing::info_span!("inner", peer_id = "12D3KooW");
+			let _inner = inner.enter();
+
+			tracing::info!(target: TELEMETRY_LOG_SPAN, message_verbosity = 0u64, json = r#"{"msg":"hi"}"#);
+		});
+
+		let message = capture.messages().into_iter().next().expect("event is delivered");
+		let ctx = message.payload["ctx"].as_object().expect("ctx is a JSON object");
+		assert_eq!(ctx.len(), 2, "only the two configured field names are collected: {:?}", ctx);
+		assert_eq!(ctx["block_number"], 42);
+		assert_eq!(ctx["peer_id"], "12D3KooW");
+	}
+
+	#[test]
+	fn context_fields_recorded_after_span_creation_are_still_collected() {
+		let layer = TelemetryLayer::default().with_context_fields(ContextFields::new().field("block_number"));
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+		let (capture, sender) = test_utils::InMemoryTelemetry::new(1);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let telemetry_span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = telemetry_span.id().expect("span is enabled").into_u64();
+			telemetries.senders.insert(id, sender);
+			let _telemetry = telemetry_span.enter();
+
+			let block_span = tracing::info_span!("importing", block_number = tracing::field::Empty);
+			block_span.record("block_number", 7u64);
+			let _block = block_span.enter();
+
+			tracing::info!(target: TELEMETRY_LOG_SPAN, message_verbosity = 0u64, json = r#"{"msg":"hi"}"#);
+		});
+
+		let message = capture.messages().into_iter().next().expect("event is delivered");
+		assert_eq!(message.payload["ctx"]["block_number"], 7);
+	}
+
+	#[test]
+	fn context_fields_recorded_on_the_telemetry_span_itself_reach_later_events_only() {
+		let layer = TelemetryLayer::default().with_context_fields(ContextFields::new().field("session"));
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+		let (capture, sender) = test_utils::InMemoryTelemetry::new(1);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let telemetry_span = tracing::info_span!(
+				target: TELEMETRY_LOG_SPAN,
+				TELEMETRY_LOG_SPAN,
+				session = tracing::field::Empty,
+			);
+			let id = telemetry_span.id().expect("span is enabled").into_u64();
+			telemetries.senders.insert(id, sender);
+			let _telemetry = telemetry_span.enter();
+
+			tracing::info!(target: TELEMETRY_LOG_SPAN, message_verbosity = 0u64, json = r#"{"msg":"before"}"#);
+
+			telemetry_span.record("session", "epoch-7");
+
+			tracing::info!(target: TELEMETRY_LOG_SPAN, message_verbosity = 0u64, json = r#"{"msg":"after"}"#);
+		});
+
+		let messages = capture.messages();
+		assert_eq!(messages.len(), 2);
+		assert!(
+			messages[0].payload.get("ctx").is_none(),
+			"recorded after this event was emitted, so it must not carry the value"
+		);
+		assert_eq!(messages[1].payload["ctx"]["session"], "epoch-7");
+	}
+
+	#[test]
+	fn context_fields_are_opt_in_and_absent_by_default() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+		let (capture, sender) = test_utils::InMemoryTelemetry::new(1);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let telemetry_span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = telemetry_span.id().expect("span is enabled").into_u64();
+			telemetries.senders.insert(id, sender);
+			let _telemetry = telemetry_span.enter();
+
+			let outer = tracing::info_span!("outer", block_number = 42u64);
+			let _outer = outer.enter();
+			tracing::info!(target: TELEMETRY_LOG_SPAN, message_verbosity = 0u64, json = r#"{"msg":"hi"}"#);
+		});
+
+		let message = capture.messages().into_iter().next().expect("event is delivered");
+		assert!(message.payload.get("ctx").is_none(), "collection is opt-in: no config, no ctx key");
+	}
+
+	#[test]
+	fn context_fields_field_is_capped_at_max_context_fields() {
+		let mut fields = ContextFields::new();
+		for i in 0..(MAX_CONTEXT_FIELDS + 5) {
+			fields = fields.field(format!("field_{i}"));
+		}
+		let layer = TelemetryLayer::default().with_context_fields(fields);
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+		let (capture, sender) = test_utils::InMemoryTelemetry::new(1);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let telemetry_span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = telemetry_span.id().expect("span is enabled").into_u64();
+			telemetries.senders.insert(id, sender);
+			let _telemetry = telemetry_span.enter();
+
+			let outer = tracing::info_span!(
+				"outer",
+				field_0 = 0u64, field_1 = 1u64, field_2 = 2u64, field_3 = 3u64, field_4 = 4u64,
+				field_5 = 5u64, field_6 = 6u64, field_7 = 7u64, field_8 = 8u64, field_9 = 9u64,
+				field_10 = 10u64, field_11 = 11u64, field_12 = 12u64, field_13 = 13u64,
+				field_14 = 14u64, field_15 = 15u64, field_16 = 16u64, field_17 = 17u64,
+			);
+			let _outer = outer.enter();
+			tracing::info!(target: TELEMETRY_LOG_SPAN, message_verbosity = 0u64, json = r#"{"msg":"hi"}"#);
+		});
+
+		let message = capture.messages().into_iter().next().expect("event is delivered");
+		let ctx = message.payload["ctx"].as_object().expect("ctx is a JSON object");
+		assert_eq!(ctx.len(), MAX_CONTEXT_FIELDS, "only the first MAX_CONTEXT_FIELDS names are ever configured");
+	}
+
+	#[test]
+	fn cached_telemetry_id_still_delivers_after_the_registered_sender_is_replaced() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let (first_sender, mut first_receiver) = mpsc::channel(1);
+			telemetries.senders.insert(id, first_sender);
+			let _enter = span.enter();
+
+			// First event: nothing cached yet, walks the scope and caches `id`.
+			emit(r#"{"msg":"via-first-sender"}"#);
+			let (_verbosity, json) = first_receiver.try_next().unwrap().unwrap();
+			assert!(json.contains("\"msg\":\"via-first-sender\""));
+
+			// Restart telemetry: a new sender takes over the same id. The cached
+			// `id` on the span is still correct (a span's ancestors never change),
+			// so the next event should reach the *new* channel without needing
+			// any cache invalidation.
+			let (second_sender, mut second_receiver) = mpsc::channel(1);
+			telemetries.senders.insert(id, second_sender);
+
+			emit(r#"{"msg":"via-second-sender"}"#);
+			assert!(first_receiver.try_next().unwrap().is_none(), "the old channel should receive nothing further");
+			let (_verbosity, json) = second_receiver.try_next().unwrap().unwrap();
+			assert!(json.contains("\"msg\":\"via-second-sender\""));
+		});
+	}
+
+	#[test]
+	fn cached_telemetry_id_stops_delivering_once_the_sender_is_removed() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let (sender, mut receiver) = mpsc::channel(1);
+			telemetries.senders.insert(id, sender);
+			let _enter = span.enter();
+
+			emit(r#"{"n":1}"#); // caches `id` on the span
+			assert!(receiver.try_next().unwrap().is_some());
+
+			telemetries.senders.remove(id);
+			// The cached `id` is still correct; `Senders::contains` re-checks the
+			// map fresh every time, so this is a quiet no-op rather than a panic
+			// or a stale delivery.
+			emit(r#"{"n":2}"#);
+			assert!(receiver.try_next().unwrap().is_none());
+		});
+	}
+
+	#[test]
+	fn events_from_a_span_nested_under_the_telemetry_span_reuse_the_cached_ancestor_id() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let (sender, mut receiver) = mpsc::channel(2);
+			telemetries.senders.insert(id, sender);
+			let _enter = span.enter();
+
+			let child = tracing::info_span!("child");
+			let _child_enter = child.enter();
+
+			// Both events are fired from the same child span; the second should
+			// reuse the ancestor id cached by the first rather than re-walking.
+			emit(r#"{"msg":"first-from-child"}"#);
+			emit(r#"{"msg":"second-from-child"}"#);
+
+			let (_verbosity, first) = receiver.try_next().unwrap().unwrap();
+			assert!(first.contains("\"msg\":\"first-from-child\""));
+			let (_verbosity, second) = receiver.try_next().unwrap().unwrap();
+			assert!(second.contains("\"msg\":\"second-from-child\""));
+		});
+	}
+
+	#[test]
+	fn a_panicking_producer_does_not_corrupt_telemetry_for_its_own_id_or_others() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let panicking_span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let panicking_id = panicking_span.id().expect("span is enabled").into_u64();
+			let (panicking_sender, mut panicking_receiver) = mpsc::channel(4);
+			telemetries.senders.insert(panicking_id, panicking_sender);
+
+			let survivor_span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let survivor_id = survivor_span.id().expect("span is enabled").into_u64();
+			let (survivor_sender, mut survivor_receiver) = mpsc::channel(4);
+			telemetries.senders.insert(survivor_id, survivor_sender);
+
+			// Simulate a producer (e.g. a buggy block-import task) that panics
+			// partway through a telemetry-active span, after already having sent
+			// one message. `SenderEntry` no longer needs `AssertUnwindSafe` to sit
+			// behind the map's (poison-free) `parking_lot::Mutex`, so nothing about
+			// the map or this id's entry should be left broken by the unwind.
+			let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+				let _enter = panicking_span.enter();
+				emit(r#"{"msg":"before-panic"}"#);
+				panic!("simulated producer bug mid-telemetry");
+			}));
+			assert!(result.is_err(), "the panic should propagate out of the closure as usual");
+
+			let (_verbosity, json) = panicking_receiver.try_next().unwrap().unwrap();
+			assert!(json.contains("\"msg\":\"before-panic\""));
+
+			// The panicking producer's own id keeps working afterwards...
+			{
+				let _enter = panicking_span.enter();
+				emit(r#"{"msg":"after-panic-same-id"}"#);
+			}
+			let (_verbosity, json) = panicking_receiver.try_next().unwrap().unwrap();
+			assert!(json.contains("\"msg\":\"after-panic-same-id\""));
+
+			// ...and telemetry for an unrelated id sharing the same map is
+			// unaffected too.
+			let _enter = survivor_span.enter();
+			emit(r#"{"msg":"unaffected"}"#);
+			let (_verbosity, json) = survivor_receiver.try_next().unwrap().unwrap();
+			assert!(json.contains("\"msg\":\"unaffected\""));
+		});
+	}
+
+	#[test]
+	fn missing_fields_are_counted_and_dropped_instead_of_panicking() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let (sender, _receiver) = mpsc::channel(8);
+			telemetries.senders.insert(id, sender);
+			let _enter = span.enter();
+
+			// Missing `json`.
+			tracing::info!(target: TELEMETRY_LOG_SPAN, message_verbosity = 0u64);
+			// Missing `message_verbosity`.
+			tracing::info!(target: TELEMETRY_LOG_SPAN, json = "{}");
+		});
+
+		assert_eq!(telemetries.malformed_event_count(), 2);
+	}
+
+	#[test]
+	fn invalid_json_payloads_are_dropped_before_reaching_the_sender() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let (sender, mut receiver) = mpsc::channel(8);
+			telemetries.senders.insert(id, sender);
+			let _enter = span.enter();
+
+			// Truncated mid-object.
+			tracing::info!(target: TELEMETRY_LOG_SPAN, message_verbosity = 0u64, json = r#"{"msg":"block.import","height":1"#);
+			// Unbalanced brackets.
+			tracing::info!(target: TELEMETRY_LOG_SPAN, message_verbosity = 0u64, json = r#"{"msg":"block.import"}}"#);
+
+			assert!(
+				receiver.try_next().is_err(),
+				"neither malformed payload should ever reach the registered sender's channel"
+			);
+		});
+
+		assert_eq!(telemetries.malformed_event_count(), 2);
+		assert_eq!(telemetries.invalid_json_payload_count(), 2);
+	}
+
+	#[test]
+	fn schema_violation_and_invalid_json_counts_are_isolated_per_telemetries_instance() {
+		// Same shape as `two_instances_sharing_one_registry_do_not_cross_talk`:
+		// two independent `TelemetryLayer`s in one process must not pool their
+		// schema-violation/invalid-JSON counts, the same isolation guarantee
+		// `dedup_on_one_telemetries_instance_does_not_suppress_or_reset_another_instances_window`
+		// proves for `MessageDedup`.
+		let layer_a = TelemetryLayer::default().with_instance_id(0);
+		let layer_b = TelemetryLayer::default().with_instance_id(1);
+		let telemetries_a = layer_a.telemetries();
+		let telemetries_b = layer_b.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer_a).with(layer_b);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span_a = tracing::info_span!(target: "telemetry-logger-0", "telemetry-logger-0");
+			let id_a = span_a.id().expect("span is enabled").into_u64();
+			let (sender_a, _receiver_a) = mpsc::channel(8);
+			telemetries_a.senders.insert(id_a, sender_a);
+
+			{
+				let _enter = span_a.enter();
+				// Unbalanced brackets: only instance a should count this.
+				tracing::info!(
+					target: "telemetry-logger-0",
+					message_verbosity = 0u64,
+					json = r#"{"msg":"block.import"}}"#,
+				);
+				// Missing `height`: only instance a should count this.
+				tracing::info!(
+					target: "telemetry-logger-0",
+					message_verbosity = 0u64,
+					json = r#"{"msg":"block.import"}"#,
+				);
+			}
+		});
+
+		assert_eq!(telemetries_a.invalid_json_payload_count(), 1, "only a's own malformed payload should count");
+		assert_eq!(telemetries_b.invalid_json_payload_count(), 0, "b never saw an event, let alone a malformed one");
+		assert_eq!(telemetries_a.schema_violation_count(), 1, "only a's own schema violation should count");
+		assert_eq!(telemetries_b.schema_violation_count(), 0, "b never saw an event, let alone a schema violation");
+	}
+
+	#[test]
+	fn a_dropped_receiver_is_cleaned_up_and_the_id_can_be_reregistered() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let (sender, receiver) = mpsc::channel(4);
+			telemetries.senders.insert(id, sender);
+			let _enter = span.enter();
+
+			// Simulates the worker for this id dying: nothing is left to drain
+			// the channel.
+			drop(receiver);
+
+			emit(r#"{"msg":"into-the-void"}"#);
+
+			assert!(
+				!telemetries.senders.contains(id),
+				"a disconnected receiver's registration must be removed rather than kept forever"
+			);
+			assert!(
+				!telemetries.senders.any_registered(),
+				"the fast path other events short-circuit on must reflect the cleanup too"
+			);
+
+			// Registering a fresh sender under the same id resumes delivery.
+			let (sender, mut receiver) = mpsc::channel(4);
+			telemetries.senders.insert(id, sender);
+			emit(r#"{"msg":"resumed"}"#);
+			let (_v, json) = receiver.try_next().unwrap().unwrap();
+			assert!(json.contains("\"msg\":\"resumed\""));
+		});
+	}
+
+	#[test]
+	fn direct_send_interleaves_with_tracing_events_on_the_same_sender() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let (sender, mut receiver) = mpsc::channel(2);
+			telemetries.senders.insert(id, sender);
+			let _enter = span.enter();
+
+			tracing::info!(
+				target: TELEMETRY_LOG_SPAN,
+				message_verbosity = 0u64,
+				json = r#"{"msg":"via-tracing"}"#,
+			);
+			assert!(telemetries.send(id, 0u8, serde_json::json!({ "msg": "via-direct" })));
+
+			let (_v, first) = receiver.try_next().unwrap().unwrap();
+			let (_v, second) = receiver.try_next().unwrap().unwrap();
+			assert!(first.contains("via-tracing"));
+			assert!(second.contains("via-direct"));
+
+			// Both paths inject the same span id, regardless of how the payload
+			// reached the sender.
+			let first: serde_json::Value = serde_json::from_str(&first).unwrap();
+			let second: serde_json::Value = serde_json::from_str(&second).unwrap();
+			assert_eq!(first["id"], id);
+			assert_eq!(second["id"], id);
+		});
+	}
+
+	#[test]
+	fn direct_send_rejects_non_object_payloads_and_unregistered_ids() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+
+		assert!(!telemetries.send(0, 0u8, serde_json::json!([1, 2, 3])));
+		assert_eq!(telemetries.malformed_event_count(), 1);
+
+		// No sender registered for `id` yet.
+		assert!(!telemetries.send(42, 0u8, serde_json::json!({ "msg": "hi" })));
+	}
+
+	#[test]
+	fn try_send_reports_malformed_event_for_a_non_object_payload() {
+		let telemetries = Telemetries::default();
+		assert_eq!(
+			telemetries.try_send(0, 0u8, serde_json::json!([1, 2, 3])),
+			Err(TelemetryError::MalformedEvent)
+		);
+	}
+
+	#[test]
+	fn try_send_reports_disconnected_for_an_unregistered_id() {
+		let telemetries = Telemetries::default();
+		assert_eq!(
+			telemetries.try_send(42, 0u8, serde_json::json!({ "msg": "hi" })),
+			Err(TelemetryError::Disconnected)
+		);
+	}
+
+	#[test]
+	fn pre_registration_buffer_delivers_early_messages_in_order_once_registered() {
+		let telemetries = Telemetries::default();
+		telemetries.senders.enable_pre_registration_buffer(PreRegistrationBufferConfig::default());
+
+		// Sent before anything is registered for id 7: dropped without this
+		// feature, but buffered with it enabled.
+		assert_eq!(
+			telemetries.try_send(7, 0u8, serde_json::json!({ "msg": "block.import", "height": 1 })),
+			Err(TelemetryError::Disconnected)
+		);
+		assert_eq!(
+			telemetries.try_send(7, 0u8, serde_json::json!({ "msg": "block.import", "height": 2 })),
+			Err(TelemetryError::Disconnected)
+		);
+
+		let (sender, mut receiver) = mpsc::channel(8);
+		telemetries.senders.insert(7, sender);
+
+		let first = receiver.try_next().unwrap().unwrap();
+		let second = receiver.try_next().unwrap().unwrap();
+		assert_eq!(serde_json::from_str::<serde_json::Value>(&first.1).unwrap()["height"], 1);
+		assert_eq!(serde_json::from_str::<serde_json::Value>(&second.1).unwrap()["height"], 2);
+	}
+
+	#[test]
+	fn pre_registration_buffer_does_nothing_unless_enabled() {
+		let telemetries = Telemetries::default();
+		assert!(!telemetries.senders.pre_registration_buffer_enabled());
+		assert_eq!(
+			telemetries.try_send(7, 0u8, serde_json::json!({ "msg": "block.import" })),
+			Err(TelemetryError::Disconnected)
+		);
+
+		let (sender, mut receiver) = mpsc::channel(8);
+		telemetries.senders.insert(7, sender);
+		assert!(receiver.try_next().is_err(), "nothing was buffered, so nothing arrives after registering");
+	}
+
+	#[test]
+	fn pre_registration_buffer_drops_the_oldest_message_once_capacity_is_reached() {
+		let telemetries = Telemetries::default();
+		telemetries
+			.senders
+			.enable_pre_registration_buffer(PreRegistrationBufferConfig { capacity: 2, ..Default::default() });
+
+		telemetries.send(7, 0u8, serde_json::json!({ "msg": "block.import", "height": 1 }));
+		telemetries.send(7, 0u8, serde_json::json!({ "msg": "block.import", "height": 2 }));
+		telemetries.send(7, 0u8, serde_json::json!({ "msg": "block.import", "height": 3 }));
+
+		let (sender, mut receiver) = mpsc::channel(8);
+		telemetries.senders.insert(7, sender);
+
+		let first = receiver.try_next().unwrap().unwrap();
+		let second = receiver.try_next().unwrap().unwrap();
+		assert_eq!(serde_json::from_str::<serde_json::Value>(&first.1).unwrap()["height"], 2);
+		assert_eq!(serde_json::from_str::<serde_json::Value>(&second.1).unwrap()["height"], 3);
+		assert!(receiver.try_next().is_err(), "only 2 of the 3 messages fit within capacity");
+	}
+
+	#[test]
+	fn pre_registration_buffer_is_dropped_after_its_ttl_with_no_registration() {
+		let telemetries = Telemetries::default();
+		telemetries.senders.enable_pre_registration_buffer(PreRegistrationBufferConfig {
+			ttl: std::time::Duration::from_millis(1),
+			..Default::default()
+		});
+
+		telemetries.send(7, 0u8, serde_json::json!({ "msg": "block.import" }));
+		std::thread::sleep(std::time::Duration::from_millis(20));
+		// A second, unrelated id's message sweeps id 7's now-expired buffer.
+		telemetries.send(8, 0u8, serde_json::json!({ "msg": "block.import" }));
+
+		let (sender, mut receiver) = mpsc::channel(8);
+		telemetries.senders.insert(7, sender);
+		assert!(receiver.try_next().is_err(), "the buffered message expired before id 7 was ever registered");
+	}
+
+	#[test]
+	fn events_emitted_before_the_worker_registers_are_buffered_and_replayed_in_order() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		telemetries.senders.enable_pre_registration_buffer(PreRegistrationBufferConfig::default());
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		let id = tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let _enter = span.enter();
+			tracing::info!(target: TELEMETRY_LOG_SPAN, message_verbosity = 0, json = r#"{"msg":"block.import","height":1}"#);
+			tracing::info!(target: TELEMETRY_LOG_SPAN, message_verbosity = 0, json = r#"{"msg":"block.import","height":2}"#);
+			id
+		});
+
+		let (capture, sender) = test_utils::InMemoryTelemetry::new(8);
+		telemetries.senders.insert(id, sender);
+
+		let messages = capture.messages();
+		assert_eq!(messages.len(), 2, "both early messages arrived once the worker registered");
+		assert_eq!(messages[0].payload["height"], 1);
+		assert_eq!(messages[1].payload["height"], 2);
+	}
+
+	#[test]
+	fn try_send_reports_filtered_for_a_paused_id() {
+		let telemetries = Telemetries::default();
+		let (sender, _receiver) = mpsc::channel(8);
+		telemetries.senders.insert(1, sender);
+		telemetries.pause(1);
+
+		assert_eq!(
+			telemetries.try_send(1, 0u8, serde_json::json!({ "msg": "hi" })),
+			Err(TelemetryError::Filtered)
+		);
+	}
+
+	#[test]
+	fn try_send_reports_filtered_for_a_sampled_out_message() {
+		let telemetries = Telemetries::default();
+		let (sender, _receiver) = mpsc::channel(8);
+		telemetries.senders.insert(1, sender);
+		telemetries.configure_sampling("noisy.metric", SamplingRule::EveryNth(2));
+
+		assert_eq!(telemetries.try_send(1, 0u8, serde_json::json!({ "msg": "noisy.metric" })), Ok(()));
+		assert_eq!(
+			telemetries.try_send(1, 0u8, serde_json::json!({ "msg": "noisy.metric" })),
+			Err(TelemetryError::Filtered)
+		);
+	}
+
+	#[test]
+	fn try_send_reports_channel_full_when_the_registered_sender_is_saturated() {
+		let telemetries = Telemetries::default();
+		let (sender, _receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+
+		assert_eq!(telemetries.try_send(1, 0u8, serde_json::json!({ "msg": "one" })), Ok(()));
+		assert_eq!(
+			telemetries.try_send(1, 0u8, serde_json::json!({ "msg": "two" })),
+			Err(TelemetryError::ChannelFull)
+		);
+	}
+
+	#[test]
+	fn send_important_waits_for_capacity_instead_of_failing_immediately() {
+		let telemetries = Telemetries::default();
+		let (sender, mut receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+		// Fill the one-slot channel so `send_important`'s first attempt below
+		// finds it full, the same setup as
+		// `try_send_reports_channel_full_when_the_registered_sender_is_saturated`.
+		assert_eq!(telemetries.try_send(1, 0u8, serde_json::json!({ "msg": "one" })), Ok(()));
+
+		let waiting = telemetries.clone();
+		let worker = std::thread::spawn(move || {
+			futures::executor::block_on(waiting.send_important(
+				1,
+				0u8,
+				serde_json::json!({ "msg": "two" }),
+				std::time::Duration::from_secs(2),
+				&ImmediateDelay,
+			))
+		});
+
+		// Give the worker a moment to observe the full channel and start
+		// polling before draining it, so this actually exercises the retry
+		// path instead of racing a channel that was never full to begin with.
+		std::thread::sleep(std::time::Duration::from_millis(50));
+		let (_, first) = futures::executor::block_on(receiver.next()).unwrap();
+		assert!(first.contains("\"one\""), "drains the message that filled the channel");
+
+		assert_eq!(worker.join().unwrap(), Ok(()), "send_important completes once capacity frees up");
+		let (_, second) = futures::executor::block_on(receiver.next()).unwrap();
+		assert!(second.contains("\"two\""));
+	}
+
+	#[test]
+	fn send_important_gives_up_after_its_timeout_elapses() {
+		let telemetries = Telemetries::default();
+		let (sender, _receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+		assert_eq!(telemetries.try_send(1, 0u8, serde_json::json!({ "msg": "one" })), Ok(()));
+
+		// Nothing ever drains the channel, so this can only resolve by timing out.
+		let result = futures::executor::block_on(telemetries.send_important(
+			1,
+			0u8,
+			serde_json::json!({ "msg": "two" }),
+			std::time::Duration::from_millis(20),
+			&ImmediateDelay,
+		));
+		assert_eq!(result, Err(TelemetryError::ChannelFull));
+	}
+
+	#[test]
+	fn flush_blocks_until_buffered_messages_are_delivered() {
+		let telemetries = Telemetries::default();
+		let (sender, mut receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+		// Fill the one-slot channel, then overflow a second message into
+		// `pending` so `flush` actually has something buffered to retry.
+		assert_eq!(telemetries.try_send(1, 0u8, serde_json::json!({ "msg": "one" })), Ok(()));
+		assert_eq!(
+			telemetries.try_send(1, 0u8, serde_json::json!({ "msg": "two" })),
+			Err(TelemetryError::ChannelFull)
+		);
+
+		// Simulates a worker on another thread: nothing drains the channel
+		// until well after `flush` below has started retrying, so this
+		// exercises the retry loop rather than a channel that was never
+		// actually full to begin with.
+		let draining = std::thread::spawn(move || {
+			std::thread::sleep(std::time::Duration::from_millis(50));
+			let (_, first) = futures::executor::block_on(receiver.next()).unwrap();
+			let (_, second) = futures::executor::block_on(receiver.next()).unwrap();
+			(first, second)
+		});
+
+		let report = telemetries.flush(std::time::Duration::from_secs(2));
+		assert_eq!(report.delivered, 1, "the message still buffered in `pending` was delivered");
+		assert_eq!(report.abandoned, 0);
+
+		let (first, second) = draining.join().unwrap();
+		assert!(first.contains("\"one\""));
+		assert!(second.contains("\"two\""), "flush handed pending's message off before returning");
+
+		// Safe to call again, and from a process that keeps running: the
+		// registration is untouched and there's nothing left buffered.
+		let report = telemetries.flush(std::time::Duration::from_millis(50));
+		assert_eq!(report, FlushReport::default());
+		assert!(telemetries.senders.contains(1));
+	}
+
+	#[test]
+	fn flush_reports_what_it_could_not_deliver_once_its_timeout_elapses() {
+		let telemetries = Telemetries::default();
+		let (sender, _receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+		assert_eq!(telemetries.try_send(1, 0u8, serde_json::json!({ "msg": "one" })), Ok(()));
+		assert_eq!(
+			telemetries.try_send(1, 0u8, serde_json::json!({ "msg": "two" })),
+			Err(TelemetryError::ChannelFull)
+		);
+
+		// Nothing ever drains the channel, so `pending` can only ever time out.
+		let report = telemetries.flush(std::time::Duration::from_millis(30));
+		assert_eq!(report.delivered, 0);
+		assert_eq!(report.abandoned, 0, "still full, not disconnected, so nothing was abandoned");
+	}
+
+	// A criterion benchmark comparing `send_serialized` against the
+	// `json!`-then-`send` path for a `SystemInterval`-shaped payload would
+	// normally accompany this change, but there's no build manifest or
+	// benches/ harness anywhere in this crate to add one to; the test below
+	// covers correctness (identical wire output for the same fields) instead.
+
+	#[test]
+	fn send_serialized_matches_the_field_layout_of_the_equivalent_json_send() {
+		let via_struct = Telemetries::default();
+		let (sender, mut struct_receiver) = mpsc::channel(1);
+		via_struct.senders.insert(1, sender);
+
+		let via_json = Telemetries::default();
+		let (sender, mut json_receiver) = mpsc::channel(1);
+		via_json.senders.insert(1, sender);
+
+		let payload = messages::SystemInterval { peers: 5, height: 100, best: "0xabc".into(), used_state_cache_size: 1024 };
+		assert_eq!(via_struct.send_serialized(1, 0u8, "system.interval", &payload), Ok(()));
+		assert_eq!(
+			via_json.try_send(
+				1,
+				0u8,
+				serde_json::json!({
+					"msg": "system.interval",
+					"peers": 5,
+					"height": 100,
+					"best": "0xabc",
+					"used_state_cache_size": 1024,
+				}),
+			),
+			Ok(())
+		);
+
+		let (_, struct_json) = struct_receiver.try_next().unwrap().unwrap();
+		let (_, json_json) = json_receiver.try_next().unwrap().unwrap();
+		let struct_value: serde_json::Value = serde_json::from_str(&struct_json).unwrap();
+		let json_value: serde_json::Value = serde_json::from_str(&json_json).unwrap();
+		assert_eq!(struct_value, json_value);
+	}
+
+	#[test]
+	fn send_serialized_reports_malformed_event_for_a_non_object_payload() {
+		let telemetries = Telemetries::default();
+		let (sender, _receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+
+		assert_eq!(telemetries.send_serialized(1, 0u8, "not.an.object", &42u64), Err(TelemetryError::MalformedEvent));
+	}
+
+	#[test]
+	fn send_serialized_reports_serialization_errors_instead_of_panicking() {
+		let telemetries = Telemetries::default();
+		let (sender, _receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+
+		struct AlwaysFailsToSerialize;
+
+		impl serde::Serialize for AlwaysFailsToSerialize {
+			fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+				Err(serde::ser::Error::custom("deliberately broken for a test"))
+			}
+		}
+
+		match telemetries.send_serialized(1, 0u8, "always.fails", &AlwaysFailsToSerialize) {
+			Err(TelemetryError::Serialization(_)) => {}
+			other => panic!("expected TelemetryError::Serialization, got {other:?}"),
+		}
+	}
+
+	#[test]
+	fn try_send_telemetry_mirrors_try_send_for_a_handle() {
+		let telemetries = Telemetries::default();
+		let (sender, mut receiver) = mpsc::channel(8);
+		telemetries.senders.insert(1, sender);
+		let handle = telemetries.handle(1);
+
+		assert_eq!(handle.try_send_telemetry(0u8, serde_json::json!({ "msg": "hi" })), Ok(()));
+		assert!(receiver.try_next().unwrap().is_some());
+
+		assert_eq!(
+			handle.try_send_telemetry(0u8, serde_json::json!([1, 2, 3])),
+			Err(TelemetryError::MalformedEvent)
+		);
+
+		drop(telemetries);
+		assert_eq!(
+			handle.try_send_telemetry(0u8, serde_json::json!({ "msg": "hi" })),
+			Err(TelemetryError::Disconnected)
+		);
+	}
+
+	#[test]
+	fn ancestor_ids_drops_leaf_and_orders_root_to_leaf() {
+		// `ctx.scope()` yields innermost-first: leaf 3, parent 2, root 1.
+		assert_eq!(ancestor_ids([3, 2, 1].into_iter()), vec![1, 2]);
+		// A single telemetry span has no ancestors.
+		assert_eq!(ancestor_ids([3].into_iter()), Vec::<u64>::new());
+		assert_eq!(ancestor_ids(std::iter::empty()), Vec::<u64>::new());
+	}
+
+	#[test]
+	fn date_stamp_formats_each_granularity() {
+		// 2021-01-01T00:00:00Z.
+		let secs = 1_609_459_200;
+		assert_eq!(Rotation::Daily.date_stamp(secs).as_deref(), Some("2021-01-01"));
+		assert_eq!(Rotation::Hourly.date_stamp(secs).as_deref(), Some("2021-01-01-00"));
+		assert_eq!(
+			Rotation::Minutely.date_stamp(secs).as_deref(),
+			Some("2021-01-01-00-00"),
+		);
+		assert_eq!(Rotation::Never.date_stamp(secs), None);
+	}
+
+	#[test]
+	fn date_stamp_truncates_within_a_period_and_rolls_at_the_boundary() {
+		// Minutely: stable for 59s, rolls at 60s.
+		assert_eq!(Rotation::Minutely.date_stamp(59), Rotation::Minutely.date_stamp(0));
+		assert_ne!(Rotation::Minutely.date_stamp(60), Rotation::Minutely.date_stamp(0));
+
+		// Hourly: stable for 3599s, rolls at 3600s.
+		assert_eq!(Rotation::Hourly.date_stamp(3_599), Rotation::Hourly.date_stamp(0));
+		assert_ne!(Rotation::Hourly.date_stamp(3_600), Rotation::Hourly.date_stamp(0));
+
+		// Daily: stable for 86399s, rolls at 86400s.
+		assert_eq!(Rotation::Daily.date_stamp(86_399).as_deref(), Some("1970-01-01"));
+		assert_eq!(Rotation::Daily.date_stamp(86_400).as_deref(), Some("1970-01-02"));
+	}
+
+	#[test]
+	fn date_stamp_handles_the_epoch_and_a_leap_day() {
+		assert_eq!(Rotation::Minutely.date_stamp(0).as_deref(), Some("1970-01-01-00-00"));
+		// 2020-02-29T00:00:00Z exercises the leap-year branch.
+		assert_eq!(Rotation::Daily.date_stamp(1_582_934_400).as_deref(), Some("2020-02-29"));
+	}
+
+	#[test]
+	fn in_memory_transport_delivers_sent_lines_and_resolves_closed_on_close() {
+		let (transport, mut receiver): (InMemoryTransport, _) = InMemoryTransport::new(4);
+		let transport: Box<dyn TelemetryTransport> = Box::new(transport);
+
+		futures::executor::block_on(async {
+			let mut sender = transport.connect().await.expect("first connect succeeds");
+			sender.try_send("line-one".to_string()).unwrap();
+			drop(sender);
+
+			assert_eq!(receiver.next().await, Some("line-one".to_string()));
+
+			// close() lets a pending `closed()` future resolve instead of hanging
+			// forever, as it would for an unclosed real connection.
+			transport.close();
+			transport.closed().await;
+		});
+	}
+
+	#[test]
+	fn in_memory_transport_only_connects_once() {
+		let (transport, _receiver): (InMemoryTransport, _) = InMemoryTransport::new(4);
+
+		futures::executor::block_on(async {
+			assert!(transport.connect().await.is_ok());
+			assert!(transport.connect().await.is_err());
+		});
+	}
+
+	/// A no-op `RawWaker`/`Waker` that does nothing on wake, for manually
+	/// polling a future without pulling in any executor at all.
+	fn noop_waker() -> std::task::Waker {
+		fn clone(_: *const ()) -> std::task::RawWaker {
+			raw_waker()
+		}
+		fn no_op(_: *const ()) {}
+		fn raw_waker() -> std::task::RawWaker {
+			static VTABLE: std::task::RawWakerVTable =
+				std::task::RawWakerVTable::new(clone, no_op, no_op, no_op);
+			std::task::RawWaker::new(std::ptr::null(), &VTABLE)
+		}
+		unsafe { std::task::Waker::from_raw(raw_waker()) }
+	}
+
+	#[test]
+	fn transport_futures_are_pollable_without_any_async_runtime() {
+		// Proves `TelemetryTransport` really is executor-agnostic: driving
+		// `connect()`/`closed()` by hand with a manual waker and no
+		// `futures::executor`/tokio/async-std in the loop at all, the way a
+		// worker on a bespoke or embedded executor would have to.
+		use std::future::Future as _;
+
+		let (transport, _receiver): (InMemoryTransport, _) = InMemoryTransport::new(4);
+		let transport: Box<dyn TelemetryTransport> = Box::new(transport);
+		let waker = noop_waker();
+		let mut cx = std::task::Context::from_waker(&waker);
+
+		let mut connect = transport.connect();
+		let sender = match connect.as_mut().poll(&mut cx) {
+			std::task::Poll::Ready(result) => result.expect("first connect succeeds"),
+			std::task::Poll::Pending => panic!("InMemoryTransport::connect resolves synchronously"),
+		};
+		drop(sender);
+
+		let mut closed = transport.closed();
+		assert_eq!(closed.as_mut().poll(&mut cx), std::task::Poll::Pending, "not closed yet");
+
+		transport.close();
+		assert_eq!(closed.as_mut().poll(&mut cx), std::task::Poll::Ready(None), "resolves once closed after being polled again");
+	}
+
+	#[test]
+	fn file_sink_writes_one_ndjson_object_per_line() {
+		let dir = std::env::temp_dir().join(format!("telemetry-file-sink-test-{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let sink =
+			FileSink::new(dir.clone(), "telemetry".into(), "ndjson".into(), Rotation::Never, Verbosity::DEBUG);
+
+		sink.write(Verbosity::INFO, r#"{"msg":"first"}"#);
+		sink.write(Verbosity::INFO, r#"{"msg":"second"}"#);
+		// Above the configured verbosity: filtered out, not written.
+		sink.write(Verbosity(200), r#"{"msg":"too-verbose"}"#);
+
+		let contents = std::fs::read_to_string(dir.join("telemetry.ndjson")).unwrap();
+		let lines: Vec<serde_json::Value> = contents.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+		assert_eq!(lines.len(), 2);
+		assert_eq!(lines[0]["msg"], "first");
+		assert_eq!(lines[1]["msg"], "second");
+		assert_eq!(sink.write_errors(), 0);
+
+		std::fs::remove_dir_all(&dir).ok();
+	}
+
+	#[test]
+	fn file_sink_counts_write_errors_instead_of_panicking() {
+		// A directory that can never be opened as a file: the parent itself
+		// doesn't exist, so `OpenOptions::open` fails every time.
+		let missing_dir = std::env::temp_dir().join("telemetry-file-sink-test-missing-parent-does-not-exist");
+		let sink =
+			FileSink::new(missing_dir, "telemetry".into(), "ndjson".into(), Rotation::Never, Verbosity::DEBUG);
+
+		sink.write(Verbosity::INFO, r#"{"msg":"first"}"#);
+
+		assert_eq!(sink.write_errors(), 1);
+	}
+
+	#[test]
+	fn file_sink_size_rotation_splits_across_segments_without_losing_or_duplicating_messages() {
+		let dir = std::env::temp_dir().join(format!("telemetry-file-sink-rotation-test-{}", std::process::id()));
+		std::fs::remove_dir_all(&dir).ok();
+		std::fs::create_dir_all(&dir).unwrap();
+		// Small enough that a handful of short messages already force several
+		// rotations, and only 2 backups kept so eviction is exercised too.
+		let sink = FileSink::new(dir.clone(), "telemetry".into(), "ndjson".into(), Rotation::Never, Verbosity::DEBUG)
+			.with_max_size(64)
+			.with_max_backups(2);
+
+		let expected: Vec<String> = (0..40).map(|i| format!(r#"{{"msg":"m{i}"}}"#)).collect();
+		for line in &expected {
+			sink.write(Verbosity::INFO, line);
+		}
+
+		// Every ndjson file this run could have produced, active plus backups,
+		// read in whatever order they exist on disk — order across files
+		// doesn't matter here, only that the union is exactly `expected` with
+		// nothing missing or repeated.
+		let mut seen = Vec::new();
+		for entry in std::fs::read_dir(&dir).unwrap() {
+			let path = entry.unwrap().path();
+			let contents = std::fs::read_to_string(&path).unwrap();
+			seen.extend(contents.lines().map(|line| line.to_string()));
+		}
+		seen.sort();
+		let mut expected_sorted = expected.clone();
+		expected_sorted.sort();
+		assert_eq!(seen, expected_sorted, "every message must appear exactly once across all segments");
+
+		// With `max_backups(2)`, at most a `.1` and `.2` backup plus the
+		// active file should ever survive eviction.
+		let file_count = std::fs::read_dir(&dir).unwrap().count();
+		assert!(file_count <= 3, "expected at most 3 files (active + 2 backups), found {file_count}");
+		assert_eq!(sink.rotation_errors(), 0);
+
+		std::fs::remove_dir_all(&dir).ok();
+	}
+
+	#[test]
+	fn file_sink_gzip_rotation_is_recorded_as_a_rotation_error_without_this_feature() {
+		// This crate slice never has the `gzip` feature compiled in (see
+		// `gzip_rotated_segment`'s doc comment), so asking for it degrades to
+		// an uncompressed, counted miss rather than losing the segment.
+		let dir = std::env::temp_dir().join(format!("telemetry-file-sink-gzip-test-{}", std::process::id()));
+		std::fs::remove_dir_all(&dir).ok();
+		std::fs::create_dir_all(&dir).unwrap();
+		let sink = FileSink::new(dir.clone(), "telemetry".into(), "ndjson".into(), Rotation::Never, Verbosity::DEBUG)
+			.with_max_size(16)
+			.with_gzip_rotated(true);
+
+		sink.write(Verbosity::INFO, r#"{"msg":"first"}"#);
+		sink.write(Verbosity::INFO, r#"{"msg":"second"}"#);
+
+		assert_eq!(sink.rotation_errors(), 1);
+		assert!(dir.join("telemetry.ndjson.1").exists(), "the segment is still rotated, just left uncompressed");
+
+		std::fs::remove_dir_all(&dir).ok();
+	}
+
+	#[test]
+	fn file_sink_can_embed_the_verbosity_under_a_configurable_field() {
+		let dir = std::env::temp_dir().join(format!("telemetry-file-sink-level-field-test-{}", std::process::id()));
+		std::fs::remove_dir_all(&dir).ok();
+		std::fs::create_dir_all(&dir).unwrap();
+		let sink = FileSink::new(dir.clone(), "telemetry".into(), "ndjson".into(), Rotation::Never, Verbosity::DEBUG)
+			.with_level_field("level");
+
+		sink.write(Verbosity::INFO, r#"{"msg":"block.import"}"#);
+
+		let contents = std::fs::read_to_string(dir.join("telemetry.ndjson")).unwrap();
+		let line: serde_json::Value = serde_json::from_str(contents.lines().next().unwrap()).unwrap();
+		assert_eq!(line["msg"], "block.import");
+		assert_eq!(line["level"], Verbosity::INFO.as_u8());
+
+		std::fs::remove_dir_all(&dir).ok();
+	}
+
+	#[test]
+	fn file_sink_does_not_clobber_an_existing_field_with_the_same_name_as_the_level_field() {
+		let dir = std::env::temp_dir().join(format!("telemetry-file-sink-level-field-clash-test-{}", std::process::id()));
+		std::fs::remove_dir_all(&dir).ok();
+		std::fs::create_dir_all(&dir).unwrap();
+		let sink = FileSink::new(dir.clone(), "telemetry".into(), "ndjson".into(), Rotation::Never, Verbosity::DEBUG)
+			.with_level_field("level");
+
+		sink.write(Verbosity::INFO, r#"{"msg":"block.import","level":"already-here"}"#);
+
+		let contents = std::fs::read_to_string(dir.join("telemetry.ndjson")).unwrap();
+		let line: serde_json::Value = serde_json::from_str(contents.lines().next().unwrap()).unwrap();
+		assert_eq!(line["level"], "already-here");
+
+		std::fs::remove_dir_all(&dir).ok();
+	}
+
+	/// A [`Telemetries`] instance with one id registered against a fresh
+	/// [`test_utils::InMemoryTelemetry`] capture, for tests that need to send
+	/// through the real pipeline and inspect what came out the other end.
+	fn telemetries_with_capture() -> (Telemetries, u64, test_utils::InMemoryTelemetry) {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let id = 1;
+		let (capture, sender) = test_utils::InMemoryTelemetry::new(8);
+		telemetries.senders.insert(id, sender);
+		(telemetries, id, capture)
+	}
+
+	#[test]
+	fn replay_feeds_a_captured_ndjson_file_back_through_the_same_pipeline() {
+		let (source, id, capture) = telemetries_with_capture();
+		source.send(id, Verbosity::INFO, serde_json::json!({"msg": "block.import", "height": 1, "ts": 1}));
+		source.send(id, Verbosity::INFO, serde_json::json!({"msg": "block.import", "height": 2, "ts": 2}));
+		let original: Vec<_> = capture.messages().into_iter().map(|m| m.payload).collect();
+
+		let ndjson: String =
+			original.iter().map(|payload| payload.to_string()).collect::<Vec<_>>().join("\n");
+
+		let (destination, dest_id, dest_capture) = telemetries_with_capture();
+		let summary = replay::replay(
+			&destination,
+			dest_id,
+			std::io::Cursor::new(ndjson),
+			&replay::ReplayOptions::default(),
+		);
+
+		assert_eq!(summary, replay::ReplaySummary { lines: 2, sent: 2, malformed: 0, rejected: 0 });
+		let replayed: Vec<_> = dest_capture.messages().into_iter().map(|m| m.payload).collect();
+		assert_eq!(replayed, original, "replaying a capture through the pipeline again reproduces it exactly");
+	}
+
+	#[test]
+	fn replay_counts_malformed_lines_without_losing_the_rest_of_the_capture() {
+		let (destination, id, capture) = telemetries_with_capture();
+		let ndjson = "{\"msg\":\"first\"}\nnot json\n\"a json string, not an object\"\n{\"msg\":\"second\"}\n";
+
+		let summary =
+			replay::replay(&destination, id, std::io::Cursor::new(ndjson), &replay::ReplayOptions::default());
+
+		assert_eq!(summary, replay::ReplaySummary { lines: 4, sent: 2, malformed: 2, rejected: 0 });
+		assert_eq!(capture.messages().len(), 2);
+	}
+
+	#[test]
+	fn replay_can_rewrite_ts_to_the_moment_each_message_is_replayed() {
+		let (destination, id, capture) = telemetries_with_capture();
+		let ndjson = r#"{"msg":"block.import","ts":1}"#;
+
+		replay::replay(
+			&destination,
+			id,
+			std::io::Cursor::new(ndjson),
+			&replay::ReplayOptions { rewrite_ts: true, ..Default::default() },
+		);
+
+		let replayed_ts = capture.messages().into_iter().next().unwrap().payload["ts"].as_u64().unwrap();
+		assert!(replayed_ts > 1, "ts was rewritten to the replay time rather than kept at the captured value");
+	}
+
+	#[test]
+	fn persistent_replay_buffer_survives_a_restart_in_delivery_order() {
+		let dir = std::env::temp_dir().join(format!("telemetry-persistent-replay-test-{}", std::process::id()));
+		std::fs::remove_dir_all(&dir).ok();
+
+		{
+			let (mut buffer, replayed) = PersistentReplayBuffer::open(dir.clone(), "buffered", 1024, 4096).unwrap();
+			assert!(replayed.is_empty(), "nothing persisted yet");
+			buffer.push(Verbosity::INFO, r#"{"msg":"block.import","height":1}"#);
+			buffer.push(Verbosity::INFO, r#"{"msg":"block.import","height":2}"#);
+			buffer.push(Verbosity::INFO, r#"{"msg":"block.import","height":3}"#);
+			// `buffer` is dropped here, simulating the process being killed.
+		}
+
+		let (_buffer, replayed) = PersistentReplayBuffer::open(dir.clone(), "buffered", 1024, 4096).unwrap();
+		let heights: Vec<i64> = replayed
+			.iter()
+			.map(|(_, json)| serde_json::from_str::<serde_json::Value>(json).unwrap()["height"].as_i64().unwrap())
+			.collect();
+		assert_eq!(heights, vec![1, 2, 3], "delivered in the order they were produced");
+
+		std::fs::remove_dir_all(&dir).ok();
+	}
+
+	#[test]
+	fn persistent_replay_buffer_evicts_whole_segments_once_over_budget() {
+		let dir = std::env::temp_dir().join(format!("telemetry-persistent-replay-evict-test-{}", std::process::id()));
+		std::fs::remove_dir_all(&dir).ok();
+
+		// Small enough that every message rolls a new segment, so eviction
+		// definitely removes whole earlier messages rather than trimming one.
+		let (mut buffer, _) = PersistentReplayBuffer::open(dir.clone(), "buffered", 1, 40).unwrap();
+		for height in 0..20 {
+			buffer.push(Verbosity::INFO, &format!(r#"{{"msg":"block.import","height":{}}}"#, height));
+		}
+		drop(buffer);
+
+		let (_buffer, replayed) = PersistentReplayBuffer::open(dir.clone(), "buffered", 1, 40).unwrap();
+		assert!(!replayed.is_empty(), "the most recent segments should have survived");
+		assert!(replayed.len() < 20, "old segments should have been evicted");
+		let heights: Vec<i64> = replayed
+			.iter()
+			.map(|(_, json)| serde_json::from_str::<serde_json::Value>(json).unwrap()["height"].as_i64().unwrap())
+			.collect();
+		let mut sorted = heights.clone();
+		sorted.sort_unstable();
+		assert_eq!(heights, sorted, "still in production order");
+		assert_eq!(*heights.last().unwrap(), 19, "the newest message survives eviction");
+
+		std::fs::remove_dir_all(&dir).ok();
+	}
+
+	#[test]
+	fn persistent_replay_buffer_skips_a_corrupt_tail_instead_of_failing_to_open() {
+		let dir = std::env::temp_dir().join(format!("telemetry-persistent-replay-corrupt-test-{}", std::process::id()));
+		std::fs::remove_dir_all(&dir).ok();
+		std::fs::create_dir_all(&dir).unwrap();
+		std::fs::write(
+			dir.join("buffered.0.log"),
+			"1\t{\"msg\":\"block.import\",\"height\":1}\n1\t{\"msg\":\"block.import\",\"height\":2}\n1\t{\"msg\":truncated",
+		)
+		.unwrap();
+
+		let (_buffer, replayed) = PersistentReplayBuffer::open(dir.clone(), "buffered", 1024, 4096)
+			.expect("a corrupt tail must not fail startup");
+		assert_eq!(replayed.len(), 2, "only the well-formed lines before the corrupt tail are replayed");
+
+		std::fs::remove_dir_all(&dir).ok();
+	}
+
+	#[test]
+	fn tls_config_defaults_to_the_platform_trust_store() {
+		let config = TlsConfig::new();
+		assert!(config.roots().is_empty());
+		assert!(!config.accepts_invalid_certs());
+	}
+
+	#[test]
+	fn tls_config_accumulates_extra_roots_in_order() {
+		let mut config = TlsConfig::new();
+		config.add_root_pem(b"pem-a".to_vec()).add_root_path("/etc/telemetry/root-b.pem");
+
+		assert_eq!(
+			config.roots(),
+			&[
+				RootCertSource::Pem(b"pem-a".to_vec()),
+				RootCertSource::Path(PathBuf::from("/etc/telemetry/root-b.pem")),
+			],
+		);
+	}
+
+	#[test]
+	fn tls_config_is_per_endpoint() {
+		let mut configs = EndpointTlsConfigs::new();
+		let mut lab = TlsConfig::new();
+		lab.accept_invalid_certs(true);
+		configs.insert("wss://lab.internal", lab);
+
+		assert!(configs.get("wss://lab.internal").unwrap().accepts_invalid_certs());
+		assert!(configs.get("wss://telemetry.polkadot.io").is_none());
+	}
+
+	/// `std::io::Write` handle over a `Vec<u8>` shared with the test, so a
+	/// test can assert on exactly the bytes [`StdioSink`] wrote instead of
+	/// redirecting the process's real stdout/stderr.
+	#[derive(Clone, Default)]
+	struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+	impl std::io::Write for SharedBuffer {
+		fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+			self.0.lock().extend_from_slice(buf);
+			Ok(buf.len())
+		}
+
+		fn flush(&mut self) -> std::io::Result<()> {
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn stdio_sink_writes_one_json_line_per_message() {
+		let buffer = SharedBuffer::default();
+		let mut sender = StdioSink::spawn_with_writer(buffer.clone(), None, 8);
+		sender.try_send((Verbosity::INFO, r#"{"msg":"a"}"#.to_string())).unwrap();
+		sender.try_send((Verbosity::INFO, r#"{"msg":"b"}"#.to_string())).unwrap();
+		drop(sender);
+
+		// The writer thread drains asynchronously; give it a moment to catch
+		// up rather than asserting the instant the sends return.
+		for _ in 0..100 {
+			if buffer.0.lock().len() >= 24 {
+				break;
+			}
+			std::thread::sleep(std::time::Duration::from_millis(10));
+		}
+
+		let written = String::from_utf8(buffer.0.lock().clone()).unwrap();
+		let lines: Vec<&str> = written.lines().collect();
+		assert_eq!(lines, vec![r#"{"msg":"a"}"#, r#"{"msg":"b"}"#]);
+	}
+
+	#[test]
+	fn stdio_sink_envelope_wraps_the_endpoint_name_and_instance_id() {
+		let buffer = SharedBuffer::default();
+		let envelope = StdioEnvelope::new("stdout://").with_instance_id(7);
+		let mut sender = StdioSink::spawn_with_writer(buffer.clone(), Some(envelope), 8);
+		sender.try_send((Verbosity::INFO, r#"{"msg":"a"}"#.to_string())).unwrap();
+		drop(sender);
+
+		let mut line = String::new();
+		for _ in 0..100 {
+			line = String::from_utf8(buffer.0.lock().clone()).unwrap();
+			if !line.is_empty() {
+				break;
+			}
+			std::thread::sleep(std::time::Duration::from_millis(10));
+		}
+
+		let value: serde_json::Value = serde_json::from_str(line.trim_end()).unwrap();
+		assert_eq!(value["endpoint"], "stdout://");
+		assert_eq!(value["instance_id"], 7);
+		assert_eq!(value["payload"], serde_json::json!({ "msg": "a" }));
+	}
+
+	#[test]
+	fn socks5_proxy_config_exposes_host_port_and_optional_credentials() {
+		let config = Socks5ProxyConfig::new("proxy.internal", 1080);
+		assert_eq!(config.host(), "proxy.internal");
+		assert_eq!(config.port(), 1080);
+		assert_eq!(config.credentials(), None);
+
+		let config = config.with_credentials("validator", "hunter2");
+		assert_eq!(config.credentials(), Some(("validator", "hunter2")));
+	}
+
+	#[test]
+	fn socks5_proxy_config_debug_redacts_the_password() {
+		let config = Socks5ProxyConfig::new("proxy.internal", 1080).with_credentials("validator", "hunter2");
+		let rendered = format!("{config:?}");
+		assert!(rendered.contains("validator"));
+		assert!(!rendered.contains("hunter2"));
+	}
+
+	#[test]
+	fn proxy_config_is_per_endpoint() {
+		let mut configs = EndpointProxyConfigs::new();
+		configs.insert("wss://telemetry.polkadot.io", Socks5ProxyConfig::new("proxy.internal", 1080));
+
+		assert!(configs.get("wss://telemetry.polkadot.io").is_some());
+		assert!(configs.get("wss://other.example").is_none());
+	}
+
+	#[test]
+	fn telemetries_reports_no_rate_limit_tokens_for_an_unconfigured_endpoint() {
+		let telemetries = Telemetries::default();
+		assert_eq!(telemetries.endpoint_rate_limit_tokens("wss://telemetry.polkadot.io"), None);
+	}
+
+	#[test]
+	fn rate_limiter_lets_through_exactly_the_configured_rate() {
+		let start = std::time::Instant::now();
+		let mut limiter = RateLimiter::new(2.0, 2.0, start);
+
+		// The burst is exhausted immediately: 2 tokens, 2 acquires.
+		assert!(limiter.try_acquire(start));
+		assert!(limiter.try_acquire(start));
+		assert!(!limiter.try_acquire(start), "burst exhausted");
+
+		// After exactly half a second at 2/sec, one token has refilled.
+		let half_second = start + std::time::Duration::from_millis(500);
+		assert!(limiter.try_acquire(half_second));
+		assert!(!limiter.try_acquire(half_second));
+
+		// After a further full second, two more tokens have refilled (capped
+		// at the burst, so no unbounded accumulation from being idle).
+		let later = half_second + std::time::Duration::from_secs(1);
+		assert!(limiter.try_acquire(later));
+		assert!(limiter.try_acquire(later));
+		assert!(!limiter.try_acquire(later));
+	}
+
+	#[test]
+	fn endpoint_rate_limiters_are_independent_and_unconfigured_endpoints_are_unlimited() {
+		let start = std::time::Instant::now();
+		let limiters = EndpointRateLimiters::new();
+		limiters.configure("wss://slow.example", 1.0, 1.0, start);
+
+		assert!(limiters.try_acquire("wss://slow.example", start));
+		assert!(!limiters.try_acquire("wss://slow.example", start));
+		assert!(limiters.try_acquire("wss://unconfigured.example", start));
+		assert!(limiters.try_acquire("wss://unconfigured.example", start));
+	}
+
+	#[test]
+	fn compression_defaults_to_permessage_deflate_when_unconfigured() {
+		let compression = EndpointCompression::new();
+		assert_eq!(compression.get("wss://telemetry.polkadot.io"), Compression::PermessageDeflate);
+	}
+
+	#[test]
+	fn compression_is_per_endpoint() {
+		let mut compression = EndpointCompression::new();
+		compression.insert("wss://legacy.example", Compression::Disabled);
+
+		assert_eq!(compression.get("wss://legacy.example"), Compression::Disabled);
+		assert_eq!(compression.get("wss://telemetry.polkadot.io"), Compression::PermessageDeflate);
+	}
+
+	#[test]
+	fn compression_stats_accumulate_independently_per_endpoint() {
+		let stats = CompressionStats::default();
+		stats.record("wss://a.example", 100, 400);
+		stats.record("wss://a.example", 50, 200);
+		stats.record("wss://b.example", 900, 900);
+
+		assert_eq!(stats.compressed_bytes("wss://a.example"), 150);
+		assert_eq!(stats.uncompressed_bytes("wss://a.example"), 600);
+		assert_eq!(stats.compressed_bytes("wss://b.example"), 900);
+		assert_eq!(stats.uncompressed_bytes("wss://b.example"), 900);
+	}
+
+	#[test]
+	fn byte_budgets_are_independent_and_unconfigured_endpoints_are_unlimited() {
+		let day_one = std::time::UNIX_EPOCH + std::time::Duration::from_secs(86_400);
+		let budgets = EndpointByteBudgets::new();
+		budgets.configure("wss://capped.example", 1_000);
+
+		assert_eq!(budgets.record("wss://capped.example", 400, day_one), ByteBudgetOutcome::Allowed);
+		assert_eq!(budgets.record("wss://uncapped.example", 1_000_000, day_one), ByteBudgetOutcome::Allowed);
+		assert_eq!(budgets.bytes_sent_today("wss://capped.example"), 400);
+		assert_eq!(budgets.bytes_sent_today("wss://uncapped.example"), 1_000_000);
+		assert!(!budgets.is_paused("wss://capped.example"));
+	}
+
+	#[test]
+	fn byte_budget_pauses_the_endpoint_once_tripped_and_resumes_on_the_next_utc_day() {
+		let day_one = std::time::UNIX_EPOCH + std::time::Duration::from_secs(86_400);
+		let day_two = day_one + std::time::Duration::from_secs(86_400);
+		let budgets = EndpointByteBudgets::new();
+		budgets.configure("wss://capped.example", 1_000);
+
+		assert_eq!(budgets.record("wss://capped.example", 700, day_one), ByteBudgetOutcome::Allowed);
+		assert_eq!(budgets.record("wss://capped.example", 400, day_one), ByteBudgetOutcome::CapJustTripped);
+		assert!(budgets.is_paused("wss://capped.example"));
+
+		// Further sends this window are refused outright, and don't keep
+		// inflating the counter.
+		assert_eq!(budgets.record("wss://capped.example", 50, day_one), ByteBudgetOutcome::Paused);
+		assert_eq!(budgets.bytes_sent_today("wss://capped.example"), 1_100);
+
+		// The next UTC day rolls the window over and un-pauses the endpoint.
+		assert_eq!(budgets.record("wss://capped.example", 100, day_two), ByteBudgetOutcome::Allowed);
+		assert!(!budgets.is_paused("wss://capped.example"));
+		assert_eq!(budgets.bytes_sent_today("wss://capped.example"), 100);
+	}
+
+	#[test]
+	fn egress_capped_message_matches_the_failover_meta_message_shape() {
+		let message = EndpointByteBudgets::egress_capped_message("wss://capped.example", 1_000);
+		let payload: serde_json::Value = serde_json::from_str(&message).unwrap();
+		assert_eq!(payload["msg"], "system.telemetry_egress_capped");
+		assert_eq!(payload["endpoint"], "wss://capped.example");
+		assert_eq!(payload["budget_bytes_per_day"], 1_000);
+	}
+
+	#[test]
+	fn unix_socket_framing_defaults_to_websocket_when_unconfigured() {
+		let framing = EndpointUnixFraming::new();
+		assert_eq!(framing.get("unix:///var/run/telemetry.sock"), UnixSocketFraming::WebSocket);
+	}
+
+	#[test]
+	fn unix_socket_framing_is_per_endpoint() {
+		let mut framing = EndpointUnixFraming::new();
+		framing.insert("unix:///var/run/telemetry.sock", UnixSocketFraming::NdJson);
+
+		assert_eq!(framing.get("unix:///var/run/telemetry.sock"), UnixSocketFraming::NdJson);
+		assert_eq!(framing.get("unix:///var/run/other.sock"), UnixSocketFraming::WebSocket);
+	}
+
+	/// A [`TelemetryTransport`] whose `connect()` doesn't resolve until a
+	/// test releases it, so a "stalled TLS handshake" can be simulated
+	/// without a real socket or timer. Otherwise behaves like
+	/// [`InMemoryTransport`].
+	struct GatedTransport {
+		gate: Mutex<Option<mpsc::Receiver<()>>>,
+		sender: Mutex<Option<mpsc::Sender<String>>>,
+	}
+
+	impl GatedTransport {
+		/// Build a gated transport plus the release handle and the receiving
+		/// end of the channel `connect()` eventually hands back.
+		fn new(capacity: usize) -> (Self, mpsc::Sender<()>, mpsc::Receiver<String>) {
+			let (gate_tx, gate_rx) = mpsc::channel(1);
+			let (sender, receiver) = mpsc::channel(capacity);
+			(Self { gate: Mutex::new(Some(gate_rx)), sender: Mutex::new(Some(sender)) }, gate_tx, receiver)
+		}
+	}
+
+	impl TelemetryTransport for GatedTransport {
+		fn connect(
+			&self,
+		) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<mpsc::Sender<String>, TransportConnectError>> + Send + '_>>
+		{
+			Box::pin(async move {
+				if let Some(mut gate) = self.gate.lock().take() {
+					gate.next().await;
+				}
+				self.sender.lock().take().ok_or_else(|| TransportConnectError::new(EndpointErrorKind::Closed, "GatedTransport only connects once"))
+			})
+		}
+
+		fn closed(&self) -> std::pin::Pin<Box<dyn std::future::Future<Output = Option<CloseFrame>> + Send + '_>> {
+			Box::pin(std::future::pending())
+		}
+	}
+
+	/// A [`DelayFactory`] that resolves immediately, for tests exercising
+	/// [`run_endpoint`]'s retry path without a real timer or async runtime.
+	struct ImmediateDelay;
+
+	impl DelayFactory for ImmediateDelay {
+		fn delay(&self, _duration: std::time::Duration) -> std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send>> {
+			Box::pin(std::future::ready(()))
+		}
+	}
+
+	/// A [`DelayFactory`] that, like [`ImmediateDelay`], resolves every delay
+	/// immediately (so a test doesn't actually wait), but also records every
+	/// requested duration — for asserting a close-code handler asked for the
+	/// delay it was supposed to, e.g. [`run_endpoint`]'s "try again later"
+	/// handling.
+	#[derive(Default)]
+	struct RecordingDelay(Mutex<Vec<std::time::Duration>>);
+
+	impl DelayFactory for RecordingDelay {
+		fn delay(&self, duration: std::time::Duration) -> std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send>> {
+			self.0.lock().push(duration);
+			Box::pin(std::future::ready(()))
+		}
+	}
+
+	#[test]
+	fn supervise_endpoints_isolates_a_stalled_endpoints_connect_from_its_siblings() {
+		let (fast_transport, mut fast_rx) = InMemoryTransport::new(8);
+		let (mut fast_queue_tx, fast_queue_rx) = mpsc::channel(8);
+		let (slow_transport, mut release_slow, _slow_rx) = GatedTransport::new(8);
+		let (mut slow_queue_tx, slow_queue_rx) = mpsc::channel(8);
+		let (commands_tx, commands_rx) = mpsc::unbounded();
+
+		fast_queue_tx.try_send((Verbosity::CONSOLE, "fast-message".to_string())).unwrap();
+		slow_queue_tx.try_send((Verbosity::CONSOLE, "slow-message".to_string())).unwrap();
+
+		let worker = std::thread::spawn(move || {
+			futures::executor::block_on(supervise_endpoints(
+				vec![
+					("wss://fast.example".to_string(), fast_queue_rx, Box::new(fast_transport), false, false),
+					("wss://slow.example".to_string(), slow_queue_rx, Box::new(slow_transport), false, false),
+				],
+				commands_rx,
+				Arc::new(ImmediateDelay),
+				ReconnectPolicy { initial_delay: std::time::Duration::from_millis(1), max_delay: std::time::Duration::from_millis(1), max_attempts: Some(1) },
+				EndpointStats::default(),
+				EndpointConnectionStatus::default(),
+			));
+		});
+
+		// The fast endpoint's message arrives well before the slow endpoint's
+		// gate is ever released, proving one endpoint's blocked `connect()`
+		// doesn't hold up delivery to another.
+		let delivered = futures::executor::block_on(fast_rx.next()).unwrap();
+		assert_eq!(delivered, "fast-message");
+
+		release_slow.try_send(()).unwrap();
+		drop(fast_queue_tx);
+		drop(slow_queue_tx);
+		drop(release_slow);
+		drop(commands_tx);
+		worker.join().unwrap();
+	}
+
+	#[test]
+	fn fan_out_by_verbosity_respects_each_endpoints_threshold() {
+		let (mut incoming_tx, incoming_rx) = mpsc::channel(8);
+		let (public_tx, mut public_rx) = mpsc::channel(8);
+		let (internal_tx, mut internal_rx) = mpsc::channel(8);
+
+		let mut endpoints = Endpoints::new();
+		endpoints.insert("wss://public", Verbosity::CONSOLE);
+		endpoints.insert("wss://internal", Verbosity::DEBUG);
+		let mut targets = HashMap::new();
+		targets.insert("wss://public".to_string(), public_tx);
+		targets.insert("wss://internal".to_string(), internal_tx);
+
+		incoming_tx.try_send((Verbosity::CONSOLE, "console".to_string())).unwrap();
+		incoming_tx.try_send((Verbosity::DEBUG, "debug".to_string())).unwrap();
+		drop(incoming_tx);
+
+		let (_commands_tx, commands_rx) = mpsc::unbounded();
+		futures::executor::block_on(fan_out_by_verbosity(
+			incoming_rx,
+			endpoints,
+			targets,
+			EndpointStats::default(),
+			commands_rx,
+			EndpointMessageFilters::new(),
+			EndpointGroups::default(),
+			None,
+			EndpointEnvelopes::new(),
+			EndpointVerbosityFields::new(),
+		));
+
+		let public: Vec<_> = std::iter::from_fn(|| public_rx.try_next().ok().flatten()).collect();
+		let internal: Vec<_> = std::iter::from_fn(|| internal_rx.try_next().ok().flatten()).collect();
+		assert_eq!(public, vec![(Verbosity::CONSOLE, "console".to_string())]);
+		assert_eq!(
+			internal,
+			vec![
+				(Verbosity::CONSOLE, "console".to_string()),
+				(Verbosity::DEBUG, "debug".to_string()),
+			]
+		);
+	}
+
+	#[test]
+	fn fan_out_by_verbosity_forwards_unconfigured_endpoints_everything() {
+		let (mut incoming_tx, incoming_rx) = mpsc::channel(8);
+		let (unconfigured_tx, mut unconfigured_rx) = mpsc::channel(8);
+
+		let mut targets = HashMap::new();
+		targets.insert("wss://unconfigured".to_string(), unconfigured_tx);
+
+		incoming_tx.try_send((Verbosity::DEBUG, "debug".to_string())).unwrap();
+		drop(incoming_tx);
+
+		let (_commands_tx, commands_rx) = mpsc::unbounded();
+		futures::executor::block_on(fan_out_by_verbosity(
+			incoming_rx,
+			Endpoints::new(),
+			targets,
+			EndpointStats::default(),
+			commands_rx,
+			EndpointMessageFilters::new(),
+			EndpointGroups::default(),
+			None,
+			EndpointEnvelopes::new(),
+			EndpointVerbosityFields::new(),
+		));
+
+		assert_eq!(unconfigured_rx.try_next().unwrap(), Some((Verbosity::DEBUG, "debug".to_string())));
+	}
+
+	/// [`GLOBAL_VERBOSITY`] is a process-wide static (see
+	/// [`Telemetries::set_global_verbosity`]'s doc comment for why), so every
+	/// test that changes it away from the default takes this guard for its
+	/// duration and restores the default before releasing it, the same way
+	/// [`TEST_LOG_GUARD`] keeps [`TestLogSink`] captures from interleaving.
+	static GLOBAL_VERBOSITY_TEST_GUARD: Mutex<()> = Mutex::new(());
+
+	#[test]
+	fn global_verbosity_skips_a_message_over_the_cap_before_it_reaches_any_endpoint() {
+		let _guard = GLOBAL_VERBOSITY_TEST_GUARD.lock();
+		let telemetries = Telemetries::default();
+		let (capture, sender) = test_utils::InMemoryTelemetry::new(4);
+		telemetries.senders.insert(1, sender);
+
+		telemetries.set_global_verbosity(Verbosity::INFO);
+		assert_eq!(telemetries.global_verbosity(), Verbosity::INFO);
+
+		assert!(telemetries.send(1, Verbosity::CONSOLE, serde_json::json!({"msg": "system.connected"})));
+		assert!(telemetries.send(1, Verbosity::INFO, serde_json::json!({"msg": "system.interval"})));
+		assert_eq!(
+			telemetries.try_send(1, Verbosity::DEBUG, serde_json::json!({"msg": "too.verbose"})),
+			Err(TelemetryError::Filtered),
+			"DEBUG is above the INFO cap",
+		);
+
+		let messages = capture.messages();
+		assert_eq!(messages.len(), 2, "only the two at-or-under-cap messages were ever handed to the sender");
+		assert!(messages.iter().all(|m| m.msg_type() != Some("too.verbose")));
+		assert!(telemetries.global_verbosity_skipped() >= 1);
+
+		telemetries.set_global_verbosity(Verbosity::DEBUG);
+	}
+
+	#[test]
+	fn global_verbosity_also_gates_events_emitted_via_the_tracing_macro() {
+		let _guard = GLOBAL_VERBOSITY_TEST_GUARD.lock();
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		telemetries.set_global_verbosity(Verbosity::INFO);
+		let subscriber = tracing_subscriber::registry().with(layer);
+		let (capture, sender) = test_utils::InMemoryTelemetry::new(4);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			telemetries.senders.insert(id, sender);
+			let _enter = span.enter();
+
+			tracing::info!(target: TELEMETRY_LOG_SPAN, message_verbosity = 1u64, msg = "system.interval", height = 1u64);
+			tracing::info!(target: TELEMETRY_LOG_SPAN, message_verbosity = 9u64, msg = "too.verbose", height = 2u64);
+		});
+
+		let messages = capture.messages();
+		assert_eq!(messages.len(), 1, "the DEBUG-level event never reached the sender");
+		assert_eq!(messages[0].msg_type(), Some("system.interval"));
+
+		telemetries.set_global_verbosity(Verbosity::DEBUG);
+	}
+
+	#[test]
+	fn global_verbosity_combines_with_a_per_endpoint_cap_as_the_minimum_of_the_two() {
+		let _guard = GLOBAL_VERBOSITY_TEST_GUARD.lock();
+		let telemetries = Telemetries::default();
+		telemetries.set_global_verbosity(Verbosity::INFO);
+		let (sender, receiver) = mpsc::channel(8);
+		telemetries.senders.insert(1, sender);
+
+		// One endpoint configured more permissively than the global cap, one
+		// more restrictively — the built worker should end up seeing the
+		// smaller of the two either way.
+		let mut endpoints = Endpoints::new();
+		endpoints.insert("wss://permissive", Verbosity::DEBUG);
+		endpoints.insert("wss://restrictive", Verbosity::CONSOLE);
+		let (permissive_tx, mut permissive_rx) = mpsc::channel(8);
+		let (restrictive_tx, mut restrictive_rx) = mpsc::channel(8);
+		let mut targets = HashMap::new();
+		targets.insert("wss://permissive".to_string(), permissive_tx);
+		targets.insert("wss://restrictive".to_string(), restrictive_tx);
+
+		telemetries.send(1, Verbosity::CONSOLE, serde_json::json!({"msg": "system.connected"}));
+		telemetries.send(1, Verbosity::INFO, serde_json::json!({"msg": "system.interval"}));
+		// Above the global cap: never reaches `receiver` at all, regardless of
+		// either endpoint's own (more permissive) threshold.
+		telemetries.send(1, Verbosity::DEBUG, serde_json::json!({"msg": "too.verbose"}));
+		drop(telemetries);
+
+		let (_commands_tx, commands_rx) = mpsc::unbounded();
+		futures::executor::block_on(fan_out_by_verbosity(
+			receiver,
+			endpoints,
+			targets,
+			EndpointStats::default(),
+			commands_rx,
+			EndpointMessageFilters::new(),
+			EndpointGroups::default(),
+			None,
+			EndpointEnvelopes::new(),
+			EndpointVerbosityFields::new(),
+		));
+
+		let permissive: Vec<_> = std::iter::from_fn(|| permissive_rx.try_next().ok().flatten()).collect();
+		let restrictive: Vec<_> = std::iter::from_fn(|| restrictive_rx.try_next().ok().flatten()).collect();
+		assert_eq!(permissive.len(), 2, "DEBUG's own threshold would have allowed it, but the global cap already dropped it upstream");
+		assert_eq!(restrictive.len(), 1, "CONSOLE's own threshold only ever allowed the first message");
+
+		GLOBAL_VERBOSITY.store(Verbosity::DEBUG.as_u8(), std::sync::atomic::Ordering::Relaxed);
+	}
+
+	#[test]
+	fn reconnect_backoff_grows_exponentially_and_caps() {
+		let mut backoff = ReconnectBackoff::new(
+			std::time::Duration::from_secs(1),
+			std::time::Duration::from_secs(60),
+		);
+		// No jitter (sample of 1.0) to make the growth deterministic.
+		assert_eq!(backoff.next_delay(1.0), std::time::Duration::from_secs(1));
+		assert_eq!(backoff.next_delay(1.0), std::time::Duration::from_secs(2));
+		assert_eq!(backoff.next_delay(1.0), std::time::Duration::from_secs(4));
+		assert_eq!(backoff.next_delay(1.0), std::time::Duration::from_secs(8));
+		assert_eq!(backoff.attempt(), 4);
+
+		for _ in 0..10 {
+			backoff.next_delay(1.0);
+		}
+		assert_eq!(backoff.next_delay(1.0), std::time::Duration::from_secs(60), "capped at max_delay");
+	}
+
+	#[test]
+	fn reconnect_backoff_jitter_scales_the_delay() {
+		let mut backoff = ReconnectBackoff::new(
+			std::time::Duration::from_secs(10),
+			std::time::Duration::from_secs(60),
+		);
+		assert_eq!(backoff.next_delay(0.5), std::time::Duration::from_secs(5));
+	}
+
+	#[test]
+	fn reconnect_backoff_resets_after_a_stable_connection() {
+		let mut backoff = ReconnectBackoff::new(
+			std::time::Duration::from_secs(1),
+			std::time::Duration::from_secs(60),
+		);
+		backoff.next_delay(1.0);
+		backoff.next_delay(1.0);
+		assert_eq!(backoff.attempt(), 2);
+
+		// A short-lived connection doesn't reset the backoff.
+		backoff.note_connection_duration(
+			std::time::Duration::from_secs(1),
+			std::time::Duration::from_secs(30),
+		);
+		assert_eq!(backoff.attempt(), 2);
+
+		// A connection that stayed up long enough does.
+		backoff.note_connection_duration(
+			std::time::Duration::from_secs(31),
+			std::time::Duration::from_secs(30),
+		);
+		assert_eq!(backoff.attempt(), 0);
+		assert_eq!(backoff.next_delay(1.0), std::time::Duration::from_secs(1));
+	}
+
+	#[test]
+	fn initial_connection_delay_samples_within_bounds() {
+		let delay = InitialConnectionDelay::up_to(std::time::Duration::from_secs(5));
+		assert_eq!(delay.sample(0.0), std::time::Duration::ZERO);
+		assert_eq!(delay.sample(1.0), std::time::Duration::from_secs(5));
+		assert_eq!(delay.sample(0.5), std::time::Duration::from_millis(2500));
+	}
+
+	#[test]
+	fn initial_connection_delay_disabled_connects_immediately() {
+		let delay = InitialConnectionDelay::disabled();
+		assert_eq!(delay.sample(0.0), std::time::Duration::ZERO);
+		assert_eq!(delay.sample(1.0), std::time::Duration::ZERO, "disabled ignores the jitter sample");
+	}
+
+	#[test]
+	fn telemetry_builder_defaults_the_initial_connection_delay_and_can_override_it() {
+		let (_layer, default_worker) = TelemetryBuilder::new().build().unwrap();
+		assert_eq!(default_worker.initial_connection_delay(), InitialConnectionDelay::default());
+
+		let (_layer, disabled_worker) = TelemetryBuilder::new()
+			.initial_connection_delay(InitialConnectionDelay::disabled())
+			.build()
+			.unwrap();
+		assert_eq!(disabled_worker.initial_connection_delay(), InitialConnectionDelay::disabled());
+		assert_eq!(disabled_worker.initial_connection_delay().sample(1.0), std::time::Duration::ZERO);
+	}
+
+	#[test]
+	fn reconnect_policy_drops_the_sender_once_attempts_are_exhausted() {
+		let policy = ReconnectPolicy {
+			initial_delay: std::time::Duration::from_secs(1),
+			max_delay: std::time::Duration::from_secs(60),
+			max_attempts: Some(3),
+		};
+		let mut backoff = policy.backoff();
+		let senders = Senders::default();
+		senders.insert(1, mpsc::channel(1).0);
+
+		for _ in 0..3 {
+			backoff.next_delay(1.0);
+			assert!(!give_up_if_exhausted(&policy, &backoff, &senders, 1));
+			assert!(senders.contains(1));
+		}
+		backoff.next_delay(1.0);
+		assert!(give_up_if_exhausted(&policy, &backoff, &senders, 1));
+		assert!(!senders.contains(1));
+
+		// Calling again after the sender is already gone is a harmless no-op.
+		assert!(give_up_if_exhausted(&policy, &backoff, &senders, 1));
+	}
+
+	#[test]
+	fn reconnect_policy_with_no_max_attempts_never_gives_up() {
+		let policy = ReconnectPolicy {
+			initial_delay: std::time::Duration::from_secs(1),
+			max_delay: std::time::Duration::from_secs(60),
+			max_attempts: None,
+		};
+		let mut backoff = policy.backoff();
+		let senders = Senders::default();
+		senders.insert(1, mpsc::channel(1).0);
+
+		for _ in 0..1000 {
+			backoff.next_delay(1.0);
+		}
+		assert!(!give_up_if_exhausted(&policy, &backoff, &senders, 1));
+		assert!(senders.contains(1));
+	}
+
+	#[test]
+	fn message_batcher_flushes_once_the_size_threshold_is_reached() {
+		let mut batcher = MessageBatcher::new(3, std::time::Duration::from_millis(50));
+		let start = std::time::Instant::now();
+
+		assert!(batcher.push((Verbosity::INFO, "one".into()), start).is_none());
+		assert!(batcher.push((Verbosity::INFO, "two".into()), start).is_none());
+		let batch = batcher.push((Verbosity::INFO, "three".into()), start).expect("size threshold reached");
+
+		assert_eq!(batch.iter().map(|(_, json)| json.as_str()).collect::<Vec<_>>(), vec!["one", "two", "three"]);
+	}
+
+	#[test]
+	fn message_batcher_flushes_once_the_linger_elapses() {
+		let mut batcher = MessageBatcher::new(100, std::time::Duration::from_millis(50));
+		let start = std::time::Instant::now();
+
+		assert!(batcher.push((Verbosity::INFO, "one".into()), start).is_none());
+		assert!(batcher.poll_linger(start + std::time::Duration::from_millis(10)).is_none(), "linger not yet elapsed");
+
+		let batch = batcher
+			.poll_linger(start + std::time::Duration::from_millis(50))
+			.expect("linger elapsed");
+		assert_eq!(batch.len(), 1);
+	}
+
+	#[test]
+	fn message_batcher_preserves_push_order_and_resets_the_linger_clock_after_a_flush() {
+		let mut batcher = MessageBatcher::new(2, std::time::Duration::from_millis(50));
+		let start = std::time::Instant::now();
+
+		batcher.push((Verbosity::INFO, "a".into()), start);
+		let flushed = batcher.push((Verbosity::INFO, "b".into()), start).unwrap();
+		assert_eq!(flushed.iter().map(|(_, json)| json.as_str()).collect::<Vec<_>>(), vec!["a", "b"]);
+
+		// A message pushed right after a flush restarts the linger window rather
+		// than reusing the timestamp of the batch that was just flushed.
+		let later = start + std::time::Duration::from_millis(40);
+		assert!(batcher.push((Verbosity::INFO, "c".into()), later).is_none());
+		assert!(batcher.poll_linger(later + std::time::Duration::from_millis(40)).is_none());
+		assert!(batcher.poll_linger(later + std::time::Duration::from_millis(50)).is_some());
+	}
+
+	#[test]
+	fn endpoint_queue_stats_tracks_depth_across_enqueue_and_dequeue() {
+		let stats = EndpointQueueStats::default();
+		assert_eq!(stats.queue_depth("wss://a.example"), 0);
+
+		stats.record_enqueued("wss://a.example");
+		stats.record_enqueued("wss://a.example");
+		assert_eq!(stats.queue_depth("wss://a.example"), 2);
+
+		stats.record_dequeued("wss://a.example");
+		assert_eq!(stats.queue_depth("wss://a.example"), 1);
+	}
+
+	#[test]
+	fn endpoint_queue_stats_reports_latency_percentiles_against_a_slow_mock_sink() {
+		let stats = EndpointQueueStats::default();
+		assert_eq!(stats.send_latency_percentile("wss://a.example", 99.0), None);
+
+		for millis in [10, 20, 30, 40, 500] {
+			stats.record_send_latency("wss://a.example", std::time::Duration::from_millis(millis));
+		}
+
+		assert_eq!(stats.send_latency_percentile("wss://a.example", 0.0), Some(std::time::Duration::from_millis(10)));
+		assert_eq!(
+			stats.send_latency_percentile("wss://a.example", 100.0),
+			Some(std::time::Duration::from_millis(500)),
+			"the artificially slow send is captured at the top percentile",
+		);
+	}
+
+	#[test]
+	fn endpoint_queue_stats_evicts_the_oldest_latency_sample_once_the_window_is_full() {
+		let stats = EndpointQueueStats::default();
+		for _ in 0..LATENCY_SAMPLE_WINDOW {
+			stats.record_send_latency("wss://a.example", std::time::Duration::from_millis(1));
+		}
+		stats.record_send_latency("wss://a.example", std::time::Duration::from_millis(1000));
+
+		// The window stayed bounded: with only one huge sample among
+		// `LATENCY_SAMPLE_WINDOW` 1ms samples, p99 is still small.
+		assert_eq!(stats.send_latency_percentile("wss://a.example", 99.0), Some(std::time::Duration::from_millis(1)));
+	}
+
+	#[test]
+	fn telemetries_endpoint_stats_snapshot_combines_drops_depth_and_latency() {
+		let telemetries = Telemetries::default();
+		telemetries.endpoint_queue_stats.record_enqueued("wss://a.example");
+		telemetries.endpoint_queue_stats.record_send_latency("wss://a.example", std::time::Duration::from_millis(5));
+
+		let snapshot = telemetries.endpoint_stats_snapshot("wss://a.example");
+		assert_eq!(snapshot.dropped, 0);
+		assert_eq!(snapshot.queue_depth, 1);
+		assert_eq!(snapshot.p99_send_latency, Some(std::time::Duration::from_millis(5)));
+	}
+
+	#[test]
+	fn endpoint_connection_status_tracks_state_error_and_reconnect_count() {
+		let status = EndpointConnectionStatus::default();
+		assert!(!status.is_connected("wss://a.example"));
+		assert_eq!(status.reconnects("wss://a.example"), 0);
+		assert_eq!(status.last_error("wss://a.example"), None);
+
+		status.record_connected("wss://a.example");
+		assert!(status.is_connected("wss://a.example"));
+
+		status.record_disconnected("wss://a.example", EndpointErrorKind::Io, "connection reset");
+		assert!(!status.is_connected("wss://a.example"));
+		let error = status.last_error("wss://a.example").unwrap();
+		assert_eq!(error.kind, EndpointErrorKind::Io);
+		assert_eq!(error.message, "connection reset");
+		assert_eq!(error.attempt, 1);
+		assert_eq!(status.reconnects("wss://a.example"), 1);
+
+		status.record_connected("wss://a.example");
+		assert_eq!(status.last_error("wss://a.example"), None, "a fresh connect clears the last error");
+		status.record_disconnected("wss://a.example", EndpointErrorKind::Closed, "timed out");
+		assert_eq!(status.reconnects("wss://a.example"), 2);
+		let error = status.last_error("wss://a.example").unwrap();
+		assert_eq!(error.kind, EndpointErrorKind::Closed);
+		assert_eq!(error.message, "timed out");
+		assert_eq!(error.attempt, 1, "the successful connect in between reset the attempt counter");
+	}
+
+	#[test]
+	fn startup_probe_result_is_reflected_in_status_without_counting_as_a_reconnect() {
+		// Stands in for a startup worker probing two configured endpoints: one
+		// reachable, one refusing the connection (e.g. a closed port). The
+		// actual dial lives outside this crate slice (see `record_probed`'s
+		// doc comment); this exercises the reporting half it feeds into.
+		let telemetries = Telemetries::default();
+		telemetries.endpoint_connections.record_probed("wss://reachable.example", Ok(()));
+		telemetries.endpoint_connections.record_probed(
+			"wss://closed.example",
+			Err((EndpointErrorKind::Io, "connection refused".to_string())),
+		);
+
+		let status = telemetries.status(
+			1,
+			&["wss://reachable.example".to_string(), "wss://closed.example".to_string()],
+		);
+
+		let reachable = status.endpoints.iter().find(|e| e.url == "wss://reachable.example").unwrap();
+		assert!(reachable.connected);
+		assert_eq!(reachable.last_error, None);
+		assert_eq!(reachable.reconnects, 0);
+
+		let closed = status.endpoints.iter().find(|e| e.url == "wss://closed.example").unwrap();
+		assert!(!closed.connected);
+		let error = closed.last_error.as_ref().unwrap();
+		assert_eq!(error.kind, EndpointErrorKind::Io);
+		assert_eq!(error.message, "connection refused");
+		assert_eq!(closed.reconnects, 0, "a failed first probe isn't a reconnect");
+
+		assert!(status.summary().contains("wss://reachable.example: connected"));
+		assert!(status.summary().contains("wss://closed.example: disconnected"));
+	}
+
+	#[test]
+	fn telemetries_status_combines_connection_queue_and_drop_state_per_endpoint() {
+		let telemetries = Telemetries::default();
+		telemetries.endpoint_connections.record_connected("wss://up.example");
+		telemetries.endpoint_connections.record_disconnected("wss://down.example", EndpointErrorKind::Io, "refused");
+		telemetries.endpoint_queue_stats.record_enqueued("wss://up.example");
+		telemetries.endpoint_stats.record_drop("wss://down.example", DropReason::Disconnected);
+
+		let status = telemetries.status(
+			1,
+			&["wss://up.example".to_string(), "wss://down.example".to_string()],
+		);
+
+		let up = status.endpoints.iter().find(|e| e.url == "wss://up.example").unwrap();
+		assert!(up.connected);
+		assert_eq!(up.queue_depth, 1);
+		assert_eq!(up.dropped, 0);
+		assert!(up.active_since_unix_secs.is_some(), "a connected endpoint reports when it connected");
+
+		let down = status.endpoints.iter().find(|e| e.url == "wss://down.example").unwrap();
+		assert!(!down.connected);
+		assert_eq!(down.last_error.as_ref().unwrap().message, "refused");
+		assert_eq!(down.reconnects, 1);
+		assert_eq!(down.dropped, 1);
+		assert_eq!(down.active_since_unix_secs, None, "a disconnected endpoint has no active-since time");
+
+		assert!(status.summary().contains("wss://up.example: connected"));
+		assert!(status.summary().contains("wss://down.example: disconnected"));
+	}
+
+	#[test]
+	fn telemetries_status_surfaces_the_registration_label() {
+		let telemetries = Telemetries::default();
+		let (sender, _receiver) = mpsc::channel(1);
+		telemetries.senders.insert_with_config(
+			1,
+			sender,
+			SenderConfig { label: Some("aura".to_string()), ..SenderConfig::default() },
+		);
+
+		assert_eq!(telemetries.status(1, &[]).label, Some("aura".to_string()));
+		assert_eq!(telemetries.status(2, &[]).label, None, "id 2 was never registered");
+	}
+
+	/// Locks the exact JSON shape of [`TelemetryStatus`] and its nested
+	/// types: these fields are the semi-stable contract an RPC method and a
+	/// Prometheus exporter built on top of this crate slice would read
+	/// directly, so a rename or dropped field here should fail a test
+	/// rather than silently reach a downstream consumer as a schema change.
+	#[test]
+	fn telemetry_status_serializes_with_the_expected_field_names() {
+		let telemetries = Telemetries::default();
+		telemetries.endpoint_connections.record_connected("wss://up.example");
+		telemetries.endpoint_queue_stats.record_enqueued("wss://up.example");
+		let (sender, _receiver) = mpsc::channel(1);
+		telemetries.senders.insert_with_config(1, sender, SenderConfig { label: Some("aura".to_string()), ..SenderConfig::default() });
+
+		let status = telemetries.status(1, &["wss://up.example".to_string()]);
+		let value = serde_json::to_value(&status).unwrap();
+
+		assert_eq!(value["label"], "aura");
+		assert_eq!(value["message_types"], serde_json::json!({}));
+		assert_eq!(value["instance_count"], 1);
+		let endpoint = &value["endpoints"][0];
+		assert_eq!(endpoint["url"], "wss://up.example");
+		assert_eq!(endpoint["connected"], true);
+		assert_eq!(endpoint["last_error"], serde_json::Value::Null);
+		assert_eq!(endpoint["reconnects"], 0);
+		assert_eq!(endpoint["queue_depth"], 1);
+		assert_eq!(endpoint["dropped"], 0);
+		assert!(endpoint["active_since_unix_secs"].is_u64());
+	}
+
+	#[test]
+	fn endpoint_error_serializes_with_the_expected_field_names() {
+		let status = EndpointConnectionStatus::default();
+		status.record_disconnected("wss://a.example", EndpointErrorKind::Dns, "could not resolve host");
+		let error = status.last_error("wss://a.example").unwrap();
+
+		let value = serde_json::to_value(&error).unwrap();
+		assert_eq!(value["kind"], "Dns");
+		assert_eq!(value["message"], "could not resolve host");
+		assert!(value["at_unix_secs"].is_u64());
+		assert_eq!(value["attempt"], 1);
+	}
+
+	#[test]
+	fn endpoint_stats_snapshot_serializes_with_the_expected_field_names() {
+		let telemetries = Telemetries::default();
+		telemetries.endpoint_stats.record_drop("wss://a.example", DropReason::QueueFull);
+		telemetries.endpoint_queue_stats.record_enqueued("wss://a.example");
+		telemetries.endpoint_queue_stats.record_send_latency("wss://a.example", std::time::Duration::from_millis(5));
+
+		let value = serde_json::to_value(telemetries.endpoint_stats_snapshot("wss://a.example")).unwrap();
+		assert_eq!(value["dropped"], 1);
+		assert_eq!(value["queue_depth"], 1);
+		assert!(value["p99_send_latency"].is_object(), "Duration serializes as {{secs, nanos}}");
+		assert_eq!(value["drop_breakdown"]["queue_full"], 1);
+		assert_eq!(value["drop_breakdown"]["disconnected"], 0);
+	}
+
+	#[test]
+	fn endpoint_stats_breaks_drops_down_by_reason() {
+		let stats = EndpointStats::default();
+		stats.record_drop("wss://a.example", DropReason::QueueFull);
+		stats.record_drop("wss://a.example", DropReason::QueueFull);
+		stats.record_drop("wss://a.example", DropReason::Disconnected);
+		stats.record_drop("wss://a.example", DropReason::Oversized);
+		stats.record_drop("wss://a.example", DropReason::Filtered);
+		stats.record_drop("wss://a.example", DropReason::RateLimited);
+
+		let breakdown = stats.drop_breakdown("wss://a.example");
+		assert_eq!(breakdown.queue_full, 2);
+		assert_eq!(breakdown.disconnected, 1);
+		assert_eq!(breakdown.oversized, 1);
+		assert_eq!(breakdown.filtered, 1);
+		assert_eq!(breakdown.rate_limited, 1);
+		assert_eq!(breakdown.total(), 6);
+		assert_eq!(stats.dropped("wss://a.example"), 6);
+	}
+
+	#[test]
+	fn record_endpoint_rate_limited_drop_is_reflected_in_the_snapshot() {
+		let telemetries = Telemetries::default();
+		telemetries.record_endpoint_rate_limited_drop("wss://throttled.example");
+
+		let snapshot = telemetries.endpoint_stats_snapshot("wss://throttled.example");
+		assert_eq!(snapshot.dropped, 1);
+		assert_eq!(snapshot.drop_breakdown.rate_limited, 1);
+	}
+
+	#[test]
+	fn endpoint_parse_accepts_a_wss_url_with_verbosity() {
+		let endpoint = Endpoint::parse("wss://telemetry.polkadot.io/submit 0").unwrap();
+		assert_eq!(endpoint.scheme(), EndpointScheme::Wss);
+		assert_eq!(endpoint.url(), "wss://telemetry.polkadot.io/submit");
+		assert_eq!(endpoint.verbosity(), Some(Verbosity::CONSOLE));
+	}
+
+	#[test]
+	fn endpoint_parse_accepts_a_ws_url_without_verbosity() {
+		let endpoint = Endpoint::parse("ws://telemetry.internal:9944").unwrap();
+		assert_eq!(endpoint.scheme(), EndpointScheme::Ws);
+		assert_eq!(endpoint.verbosity(), None);
+	}
+
+	#[test]
+	fn endpoint_parse_accepts_a_bracketed_ipv6_literal_with_and_without_a_port() {
+		let with_port = Endpoint::parse("wss://[2001:db8::1]:9944/submit").unwrap();
+		assert_eq!(with_port.url(), "wss://[2001:db8::1]:9944/submit");
+
+		let without_port = Endpoint::parse("wss://[::1]/submit").unwrap();
+		assert_eq!(without_port.url(), "wss://[::1]/submit");
+	}
+
+	#[test]
+	fn endpoint_parse_accepts_file_and_unix_schemes_without_a_host() {
+		let file = Endpoint::parse("file:///var/log/telemetry.ndjson").unwrap();
+		assert_eq!(file.scheme(), EndpointScheme::File);
+
+		let unix = Endpoint::parse("unix:///var/run/telemetry.sock 9").unwrap();
+		assert_eq!(unix.scheme(), EndpointScheme::Unix);
+		assert_eq!(unix.verbosity(), Some(Verbosity::DEBUG));
+	}
+
+	#[test]
+	fn endpoint_parse_accepts_stdout_and_stderr_pseudo_endpoints() {
+		let stdout = Endpoint::parse("stdout://").unwrap();
+		assert_eq!(stdout.scheme(), EndpointScheme::Stdout);
+		assert_eq!(stdout.verbosity(), None);
+
+		let stderr = Endpoint::parse("stderr:// 2").unwrap();
+		assert_eq!(stderr.scheme(), EndpointScheme::Stderr);
+		assert_eq!(stderr.verbosity(), Some(Verbosity::saturating_from_u64(2)));
+	}
+
+	#[test]
+	fn endpoint_parse_accepts_the_polkadot_telemetry_multiaddr() {
+		let endpoint = Endpoint::parse("/dns/telemetry.polkadot.io/tcp/443/x-parity-wss/%2Fsubmit%2F 0").unwrap();
+		assert_eq!(endpoint.scheme(), EndpointScheme::Wss);
+		assert_eq!(endpoint.url(), "wss://telemetry.polkadot.io:443/submit/");
+		assert_eq!(endpoint.verbosity(), Some(Verbosity::CONSOLE));
+	}
+
+	#[test]
+	fn endpoint_parse_accepts_a_plain_ws_multiaddr_without_a_path() {
+		let endpoint = Endpoint::parse("/ip4/127.0.0.1/tcp/9944/ws").unwrap();
+		assert_eq!(endpoint.scheme(), EndpointScheme::Ws);
+		assert_eq!(endpoint.url(), "ws://127.0.0.1:9944");
+		assert_eq!(endpoint.verbosity(), None);
+	}
+
+	#[test]
+	fn endpoint_parse_accepts_a_dns4_multiaddr_with_a_wss_transport_and_no_port() {
+		let endpoint = Endpoint::parse("/dns4/telemetry.polkadot.io/wss").unwrap();
+		assert_eq!(endpoint.scheme(), EndpointScheme::Wss);
+		assert_eq!(endpoint.url(), "wss://telemetry.polkadot.io");
+	}
+
+	#[test]
+	fn endpoint_parse_multiaddr_and_url_forms_normalize_to_the_same_endpoint() {
+		let via_multiaddr = Endpoint::parse("/dns/telemetry.polkadot.io/tcp/443/x-parity-wss/%2Fsubmit%2F").unwrap();
+		let via_url = Endpoint::parse("wss://telemetry.polkadot.io:443/submit/").unwrap();
+		assert_eq!(via_multiaddr.scheme(), via_url.scheme());
+		assert_eq!(via_multiaddr.url(), via_url.url());
+	}
+
+	#[test]
+	fn endpoint_parse_rejects_a_multiaddr_with_an_unknown_protocol() {
+		assert_eq!(
+			Endpoint::parse("/dns/telemetry.polkadot.io/quic/443/ws"),
+			Err(EndpointParseError::InvalidMultiaddr("quic".to_string())),
+		);
+	}
+
+	#[test]
+	fn endpoint_parse_rejects_a_multiaddr_missing_a_ws_transport() {
+		assert_eq!(
+			Endpoint::parse("/dns/telemetry.polkadot.io/tcp/443"),
+			Err(EndpointParseError::InvalidMultiaddr("ws".to_string())),
+		);
+	}
+
+	#[test]
+	fn endpoint_parse_rejects_a_multiaddr_missing_a_host() {
+		assert_eq!(Endpoint::parse("/tcp/443/ws"), Err(EndpointParseError::MissingHost));
+	}
+
+	#[test]
+	fn endpoint_parse_rejects_a_multiaddr_with_a_dangling_protocol_value() {
+		assert_eq!(
+			Endpoint::parse("/dns/telemetry.polkadot.io/tcp"),
+			Err(EndpointParseError::InvalidMultiaddr("tcp".to_string())),
+		);
+	}
+
+	#[test]
+	fn endpoint_parse_rejects_an_empty_spec() {
+		assert_eq!(Endpoint::parse(""), Err(EndpointParseError::Empty));
+		assert_eq!(Endpoint::parse("   "), Err(EndpointParseError::Empty));
+	}
+
+	#[test]
+	fn endpoint_parse_rejects_a_missing_scheme() {
+		assert_eq!(Endpoint::parse("ws//telemetry.polkadot.io/submit"), Err(EndpointParseError::MissingScheme));
+	}
+
+	#[test]
+	fn endpoint_parse_rejects_an_unknown_scheme() {
+		assert_eq!(
+			Endpoint::parse("http://telemetry.polkadot.io/submit"),
+			Err(EndpointParseError::UnknownScheme("http".to_string())),
+		);
+	}
+
+	#[test]
+	fn endpoint_parse_rejects_a_missing_host_for_ws_and_wss() {
+		assert_eq!(Endpoint::parse("wss:///submit"), Err(EndpointParseError::MissingHost));
+		assert_eq!(Endpoint::parse("ws://:9944/submit"), Err(EndpointParseError::MissingHost));
+	}
+
+	#[test]
+	fn endpoint_parse_rejects_an_invalid_port() {
+		assert_eq!(
+			Endpoint::parse("wss://telemetry.polkadot.io:not-a-port/submit"),
+			Err(EndpointParseError::InvalidPort("not-a-port".to_string())),
+		);
+		assert_eq!(
+			Endpoint::parse("wss://telemetry.polkadot.io:99999/submit"),
+			Err(EndpointParseError::InvalidPort("99999".to_string())),
+		);
+	}
+
+	#[test]
+	fn endpoint_parse_rejects_an_invalid_trailing_verbosity() {
+		assert_eq!(
+			Endpoint::parse("wss://telemetry.polkadot.io/submit not-a-number"),
+			Err(EndpointParseError::InvalidVerbosity("not-a-number".to_string())),
+		);
+	}
+
+	#[test]
+	fn endpoint_parse_rejects_more_than_one_trailing_component() {
+		assert_eq!(
+			Endpoint::parse("wss://telemetry.polkadot.io/submit 0 1"),
+			Err(EndpointParseError::TooManyComponents),
+		);
+	}
+
+	#[test]
+	fn file_endpoint_spec_parses_a_bare_path_with_no_verbosity() {
+		let spec = FileEndpointSpec::parse("/var/log/node/telemetry.ndjson").unwrap();
+		assert_eq!(spec.path(), std::path::Path::new("/var/log/node/telemetry.ndjson"));
+		assert_eq!(spec.verbosity(), None);
+	}
+
+	#[test]
+	fn file_endpoint_spec_parses_a_path_with_a_trailing_verbosity() {
+		let spec = FileEndpointSpec::parse("/var/log/node/telemetry.ndjson 1").unwrap();
+		assert_eq!(spec.path(), std::path::Path::new("/var/log/node/telemetry.ndjson"));
+		assert_eq!(spec.verbosity(), Some(Verbosity::saturating_from_u64(1)));
+	}
+
+	#[test]
+	fn file_endpoint_spec_parses_a_path_containing_spaces() {
+		let spec = FileEndpointSpec::parse("/var/log/my node/telemetry.ndjson").unwrap();
+		assert_eq!(spec.path(), std::path::Path::new("/var/log/my node/telemetry.ndjson"));
+		assert_eq!(spec.verbosity(), None);
+
+		let with_verbosity = FileEndpointSpec::parse("/var/log/my node/telemetry.ndjson 2").unwrap();
+		assert_eq!(with_verbosity.path(), std::path::Path::new("/var/log/my node/telemetry.ndjson"));
+		assert_eq!(with_verbosity.verbosity(), Some(Verbosity::saturating_from_u64(2)));
+	}
+
+	#[test]
+	fn file_endpoint_spec_rejects_an_empty_spec() {
+		assert_eq!(FileEndpointSpec::parse(""), Err(EndpointParseError::Empty));
+		assert_eq!(FileEndpointSpec::parse("   "), Err(EndpointParseError::Empty));
+	}
+
+	#[test]
+	fn file_endpoint_spec_rejects_an_invalid_trailing_verbosity_that_looks_numeric() {
+		// A leading `-` isn't an ASCII digit, so this is treated as (and
+		// rejected as) a path with no verbosity rather than a negative one.
+		assert_eq!(
+			FileEndpointSpec::parse("/var/log/telemetry.ndjson -1").unwrap().path(),
+			std::path::Path::new("/var/log/telemetry.ndjson -1"),
+		);
+	}
+
+	#[test]
+	fn file_endpoint_spec_into_file_sink_defaults_unset_verbosity_to_info() {
+		let dir = std::env::temp_dir().join(format!("telemetry-file-endpoint-spec-test-{}", std::process::id()));
+		std::fs::remove_dir_all(&dir).ok();
+		std::fs::create_dir_all(&dir).unwrap();
+		let path = dir.join("telemetry.ndjson");
+
+		let spec = FileEndpointSpec::parse(path.to_str().unwrap()).unwrap();
+		let sink = spec.into_file_sink();
+		sink.write(Verbosity::INFO, r#"{"msg":"hello"}"#);
+		assert!(std::fs::read_to_string(&path).unwrap().contains("hello"));
+
+		std::fs::remove_dir_all(&dir).ok();
+	}
+
+	#[test]
+	fn endpoints_insert_endpoint_validates_before_registering() {
+		let mut endpoints = Endpoints::new();
+		assert!(endpoints.insert_endpoint("wss://telemetry.polkadot.io/submit 0").is_ok());
+		assert_eq!(endpoints.max_verbosity("wss://telemetry.polkadot.io/submit"), Some(Verbosity::CONSOLE));
+
+		assert_eq!(
+			endpoints.insert_endpoint("ws//typo.example"),
+			Err(EndpointParseError::MissingScheme),
+		);
+	}
+
+	#[test]
+	fn endpoint_ack_modes_default_to_fire_and_forget() {
+		let mut ack_modes = EndpointAckModes::new();
+		assert!(!ack_modes.enabled("wss://telemetry.polkadot.io/submit"), "unconfigured endpoints default to false");
+
+		ack_modes.insert("wss://telemetry.polkadot.io/submit", true);
+		assert!(ack_modes.enabled("wss://telemetry.polkadot.io/submit"));
+
+		assert_eq!(ack_modes.remove("wss://telemetry.polkadot.io/submit"), Some(true));
+		assert!(!ack_modes.enabled("wss://telemetry.polkadot.io/submit"), "removing an entry reverts to the default");
+	}
+
+	#[test]
+	fn connect_with_fresh_resolution_returns_the_first_address_that_connects() {
+		let addresses = vec!["10.0.0.1", "10.0.0.2", "10.0.0.3"];
+		let result = connect_with_fresh_resolution::<&str, _, ()>(|| Ok(addresses), |addr| *addr == "10.0.0.2");
+		assert_eq!(result.unwrap(), "10.0.0.2");
+	}
+
+	#[test]
+	fn connect_with_fresh_resolution_resolves_again_on_every_call() {
+		// Simulates a load balancer rotating IPs: the first attempt only
+		// offers a dead address, the second (a fresh resolution) offers a
+		// live one.
+		let calls = std::cell::RefCell::new(0);
+		let resolve = || {
+			*calls.borrow_mut() += 1;
+			if *calls.borrow() == 1 { Ok::<_, ()>(vec!["10.0.0.1"]) } else { Ok(vec!["10.0.0.2"]) }
+		};
+
+		assert!(connect_with_fresh_resolution(resolve, |_| false).is_err());
+		let result = connect_with_fresh_resolution(resolve, |addr: &&str| *addr == "10.0.0.2");
+		assert_eq!(result.unwrap(), "10.0.0.2");
+		assert_eq!(*calls.borrow(), 2, "each attempt triggers its own resolution");
+	}
+
+	#[test]
+	fn connect_with_fresh_resolution_reports_when_every_address_fails() {
+		let result = connect_with_fresh_resolution::<&str, _, ()>(|| Ok(vec!["10.0.0.1", "10.0.0.2"]), |_| false);
+		assert!(matches!(result, Err(DnsResolutionError::AllAddressesFailed { attempted: 2 })));
+	}
+
+	#[test]
+	fn connect_with_fresh_resolution_reports_no_addresses_separately_from_resolve_failure() {
+		let result = connect_with_fresh_resolution::<&str, _, ()>(|| Ok(vec![]), |_| true);
+		assert!(matches!(result, Err(DnsResolutionError::NoAddresses)));
+
+		let result = connect_with_fresh_resolution::<&str, _, &str>(|| Err("NXDOMAIN"), |_| true);
+		assert!(matches!(result, Err(DnsResolutionError::Resolve("NXDOMAIN"))));
+	}
+
+	#[test]
+	fn endpoint_resolved_addresses_tracks_the_latest_successful_address_per_endpoint() {
+		let addresses = EndpointResolvedAddresses::default();
+		assert_eq!(addresses.last_successful("wss://telemetry.polkadot.io"), None);
+
+		addresses.record("wss://telemetry.polkadot.io", "2001:db8::1");
+		assert_eq!(addresses.last_successful("wss://telemetry.polkadot.io"), Some("2001:db8::1".to_string()));
+
+		addresses.record("wss://telemetry.polkadot.io", "2001:db8::2");
+		assert_eq!(addresses.last_successful("wss://telemetry.polkadot.io"), Some("2001:db8::2".to_string()));
+	}
+
+	#[test]
+	fn handshake_headers_accumulate_in_order_alongside_the_user_agent() {
+		let headers = HandshakeHeaders::new()
+			.with_header("Authorization", "Bearer secret-token")
+			.with_header("X-Route-To", "internal")
+			.with_user_agent("my-node/1.2.3");
+
+		assert_eq!(
+			headers.headers(),
+			&[
+				("Authorization".to_string(), "Bearer secret-token".to_string()),
+				("X-Route-To".to_string(), "internal".to_string()),
+			],
+		);
+		assert_eq!(headers.user_agent(), Some("my-node/1.2.3"));
+	}
+
+	#[test]
+	fn handshake_headers_debug_redacts_values_but_keeps_names() {
+		let headers = HandshakeHeaders::new().with_header("Authorization", "Bearer secret-token");
+		let rendered = format!("{headers:?}");
+		assert!(rendered.contains("Authorization"));
+		assert!(!rendered.contains("secret-token"));
+	}
+
+	#[test]
+	fn handshake_headers_are_per_endpoint() {
+		let mut configs = EndpointHandshakeHeaders::new();
+		configs.insert("wss://private.example", HandshakeHeaders::new().with_header("Authorization", "Bearer x"));
+
+		assert!(configs.get("wss://private.example").is_some());
+		assert!(configs.get("wss://telemetry.polkadot.io").is_none());
+	}
+
+	#[test]
+	fn keepalive_watchdog_pings_on_schedule_and_survives_a_prompt_pong() {
+		let start = std::time::Instant::now();
+		let mut watchdog = KeepaliveWatchdog::new(
+			std::time::Duration::from_secs(30),
+			std::time::Duration::from_secs(90),
+			start,
+		);
+
+		assert!(!watchdog.should_ping(start + std::time::Duration::from_secs(10)));
+		assert!(watchdog.should_ping(start + std::time::Duration::from_secs(30)));
+		// A ping is already outstanding: not due again immediately.
+		assert!(!watchdog.should_ping(start + std::time::Duration::from_secs(31)));
+
+		watchdog.on_pong(start + std::time::Duration::from_secs(32));
+		assert!(!watchdog.is_dead(start + std::time::Duration::from_secs(60)));
+	}
+
+	#[test]
+	fn keepalive_watchdog_declares_the_connection_dead_once_pongs_stop_arriving() {
+		let start = std::time::Instant::now();
+		let watchdog = KeepaliveWatchdog::new(
+			std::time::Duration::from_secs(30),
+			std::time::Duration::from_secs(90),
+			start,
+		);
+
+		// A NAT gateway silently swallows every ping from here on: no more
+		// pongs ever arrive.
+		assert!(!watchdog.is_dead(start + std::time::Duration::from_secs(89)));
+		assert!(watchdog.is_dead(start + std::time::Duration::from_secs(90)));
+	}
+
+	#[test]
+	fn replay_buffer_drains_in_fifo_order() {
+		let mut buffer = ReplayBuffer::new(10);
+		buffer.push(None, (Verbosity::CONSOLE, "one".to_string()));
+		buffer.push(None, (Verbosity::CONSOLE, "two".to_string()));
+		buffer.push(None, (Verbosity::CONSOLE, "three".to_string()));
+
+		assert_eq!(buffer.len(), 3);
+		let drained: Vec<_> = buffer.drain().into_iter().map(|(_, json)| json).collect();
+		assert_eq!(drained, vec!["one", "two", "three"]);
+		assert!(buffer.is_empty());
+	}
+
+	#[test]
+	fn replay_buffer_evicts_the_oldest_message_once_full() {
+		let mut buffer = ReplayBuffer::new(2);
+		buffer.push(None, (Verbosity::CONSOLE, "one".to_string()));
+		buffer.push(None, (Verbosity::CONSOLE, "two".to_string()));
+		buffer.push(None, (Verbosity::CONSOLE, "three".to_string()));
+
+		assert_eq!(buffer.evicted(), 1);
+		let drained: Vec<_> = buffer.drain().into_iter().map(|(_, json)| json).collect();
+		assert_eq!(drained, vec!["two", "three"]);
+	}
+
+	#[test]
+	fn replay_buffer_coalesces_configured_message_types() {
+		let mut buffer = ReplayBuffer::new(10);
+		buffer.push(Some("system.interval"), (Verbosity::CONSOLE, "height:1".to_string()));
+		buffer.push(Some("block.import"), (Verbosity::CONSOLE, "block:1".to_string()));
+		buffer.push(Some("system.interval"), (Verbosity::CONSOLE, "height:2".to_string()));
+
+		assert_eq!(buffer.len(), 2, "the two system.interval pushes coalesce into one slot");
+		let drained: Vec<_> = buffer.drain().into_iter().map(|(_, json)| json).collect();
+		// Coalescing replaces in place, so the freshest interval keeps its original slot.
+		assert_eq!(drained, vec!["height:2", "block:1"]);
+	}
+
+	#[test]
+	fn replay_buffer_flushes_everything_buffered_during_an_outage() {
+		// Simulate a transport that's down for the first part of the run: messages
+		// produced during that window land in the buffer, then are replayed in
+		// order into the (now connected) outgoing channel.
+		let mut buffer = ReplayBuffer::new(10);
+		let mut connected = false;
+		let (mut outgoing_tx, mut outgoing_rx) = mpsc::channel(10);
+
+		let produced = [(false, "during-outage-1"), (false, "during-outage-2"), (true, "after-reconnect")];
+		for (is_connected, msg) in produced {
+			connected = is_connected;
+			let message = (Verbosity::CONSOLE, msg.to_string());
+			if connected {
+				if !buffer.is_empty() {
+					for buffered in buffer.drain() {
+						outgoing_tx.try_send(buffered).unwrap();
+					}
+				}
+				outgoing_tx.try_send(message).unwrap();
+			} else {
+				buffer.push(None, message);
+			}
+		}
+
+		let received: Vec<_> = std::iter::from_fn(|| outgoing_rx.try_next().ok().flatten())
+			.map(|(_, json)| json)
+			.collect();
+		assert_eq!(received, vec!["during-outage-1", "during-outage-2", "after-reconnect"]);
+	}
+
+	#[test]
+	fn parse_ack_frame_extracts_the_acked_seq() {
+		assert_eq!(parse_ack_frame(r#"{"ack":3}"#), Some(3));
+		assert_eq!(parse_ack_frame(r#"{"msg":"system.interval"}"#), None, "not an ack frame");
+		assert_eq!(parse_ack_frame("not json"), None);
+	}
+
+	#[test]
+	fn ack_mode_replay_buffer_keeps_unacked_messages_pending_and_evicts_acked_ones() {
+		let mut buffer = ReplayBuffer::new(10).with_ack_mode(true);
+		assert!(buffer.is_ack_mode());
+		buffer.push(None, (Verbosity::CONSOLE, r#"{"seq":0}"#.to_string()));
+		buffer.push(None, (Verbosity::CONSOLE, r#"{"seq":1}"#.to_string()));
+		buffer.push(None, (Verbosity::CONSOLE, r#"{"seq":2}"#.to_string()));
+
+		// Reading pending doesn't remove anything, unlike `drain`.
+		assert_eq!(buffer.pending().len(), 3);
+		assert_eq!(buffer.len(), 3);
+
+		assert_eq!(buffer.ack(1), 2, "acks seq 0 and 1");
+		let remaining: Vec<_> = buffer.pending().into_iter().map(|(_, json)| json).collect();
+		assert_eq!(remaining, vec![r#"{"seq":2}"#]);
+
+		assert_eq!(buffer.ack(2), 1);
+		assert!(buffer.is_empty());
+	}
+
+	#[test]
+	fn ack_mode_run_endpoint_retransmits_unacked_messages_after_a_reconnect() {
+		// Spin-wait for `condition`, the same style `test_utils::InMemoryTelemetry::wait_for`
+		// uses to bridge this test thread and the worker thread without a shared executor.
+		fn wait_until(mut condition: impl FnMut() -> bool) {
+			let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+			while !condition() {
+				assert!(std::time::Instant::now() < deadline, "condition never became true");
+				std::thread::sleep(std::time::Duration::from_millis(1));
+			}
+		}
+
+		let server = Arc::new(test_utils::MockTelemetryServer::new());
+		let (mut queue_tx, queue_rx) = mpsc::channel(8);
+		queue_tx.try_send((Verbosity::CONSOLE, r#"{"msg":"a","seq":0}"#.to_string())).unwrap();
+		queue_tx.try_send((Verbosity::CONSOLE, r#"{"msg":"b","seq":1}"#.to_string())).unwrap();
+		let connection_status = EndpointConnectionStatus::default();
+		let stats = EndpointStats::default();
+
+		let worker = std::thread::spawn({
+			let server = server.clone();
+			let connection_status = connection_status.clone();
+			let stats = stats.clone();
+			move || {
+				futures::executor::block_on(run_endpoint(
+					"wss://collector.example".to_string(),
+					queue_rx,
+					Box::new(server),
+					Arc::new(ImmediateDelay),
+					ReconnectPolicy { initial_delay: std::time::Duration::ZERO, max_delay: std::time::Duration::ZERO, max_attempts: None },
+					stats,
+					connection_status,
+					true,
+					false,
+				))
+			}
+		});
+
+		// Both messages go out on the first connection.
+		wait_until(|| server.received_count() == 2);
+
+		// Ack the first message, then push one more to wake the worker's loop
+		// up (so it actually drains `incoming()` and applies the ack) before
+		// dropping the connection.
+		server.push_incoming(r#"{"ack":0}"#);
+		queue_tx.try_send((Verbosity::CONSOLE, r#"{"msg":"c","seq":2}"#.to_string())).unwrap();
+		wait_until(|| server.received_count() == 3);
+
+		let mut closed = server.closed();
+		server.force_disconnect();
+		futures::executor::block_on(closed.as_mut());
+
+		// Only "b" and "c" were unacked, so only those are retransmitted on
+		// the reconnect; "a" was already acked and stays evicted.
+		wait_until(|| server.received_count() == 5);
+		assert_eq!(server.messages_of_type("a").len(), 1, "acked before the disconnect, never resent");
+		assert_eq!(server.messages_of_type("b").len(), 2, "unacked, resent once on reconnect");
+		assert_eq!(server.messages_of_type("c").len(), 2, "unacked, resent once on reconnect");
+
+		drop(queue_tx);
+		worker.join().unwrap();
+	}
+
+	#[test]
+	fn run_endpoint_reports_exactly_one_telemetry_meta_message_per_recovery() {
+		fn wait_until(mut condition: impl FnMut() -> bool) {
+			let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+			while !condition() {
+				assert!(std::time::Instant::now() < deadline, "condition never became true");
+				std::thread::sleep(std::time::Duration::from_millis(1));
+			}
+		}
+
+		let server = Arc::new(test_utils::MockTelemetryServer::new());
+		let (queue_tx, queue_rx) = mpsc::channel(8);
+		let connection_status = EndpointConnectionStatus::default();
+		let stats = EndpointStats::default();
+
+		let worker = std::thread::spawn({
+			let server = server.clone();
+			let connection_status = connection_status.clone();
+			let stats = stats.clone();
+			move || {
+				futures::executor::block_on(run_endpoint(
+					"wss://collector.example".to_string(),
+					queue_rx,
+					Box::new(server),
+					Arc::new(ImmediateDelay),
+					ReconnectPolicy { initial_delay: std::time::Duration::ZERO, max_delay: std::time::Duration::ZERO, max_attempts: None },
+					stats,
+					connection_status,
+					false,
+					true,
+				))
+			}
+		});
+
+		// The initial connect isn't a recovery, so it gets no meta message.
+		wait_until(|| connection_status.is_connected("wss://collector.example"));
+		assert_eq!(server.messages_of_type("telemetry.meta").len(), 0, "the first connect isn't a reconnect");
+
+		for expected_recoveries in 1..=2 {
+			let mut closed = server.closed();
+			server.force_disconnect();
+			futures::executor::block_on(closed.as_mut());
+			wait_until(|| connection_status.is_connected("wss://collector.example"));
+			wait_until(|| server.messages_of_type("telemetry.meta").len() == expected_recoveries);
+		}
+
+		let metas = server.messages_of_type("telemetry.meta");
+		assert_eq!(metas.len(), 2, "exactly one meta message per recovery, not per flap attempt");
+		for meta in &metas {
+			assert_eq!(meta.payload["endpoint"], "wss://collector.example");
+			assert!(meta.payload["outage_secs"].as_f64().unwrap() >= 0.0);
+			assert!(meta.payload["reconnect_attempts"].as_u64().unwrap() >= 1);
+			assert_eq!(meta.payload["messages_dropped"], 0);
+			assert_eq!(meta.payload["messages_buffered"], 0, "not an ack-mode endpoint, so nothing is buffered");
+		}
+
+		drop(queue_tx);
+		worker.join().unwrap();
+	}
+
+	#[test]
+	fn run_endpoint_reconnects_normally_after_an_ordinary_close() {
+		fn wait_until(mut condition: impl FnMut() -> bool) {
+			let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+			while !condition() {
+				assert!(std::time::Instant::now() < deadline, "condition never became true");
+				std::thread::sleep(std::time::Duration::from_millis(1));
+			}
+		}
+
+		let server = Arc::new(test_utils::MockTelemetryServer::new());
+		let (queue_tx, queue_rx) = mpsc::channel(8);
+		let connection_status = EndpointConnectionStatus::default();
+
+		let worker = std::thread::spawn({
+			let server = server.clone();
+			let connection_status = connection_status.clone();
+			move || {
+				futures::executor::block_on(run_endpoint(
+					"wss://collector.example".to_string(),
+					queue_rx,
+					Box::new(server),
+					Arc::new(ImmediateDelay),
+					ReconnectPolicy { initial_delay: std::time::Duration::ZERO, max_delay: std::time::Duration::ZERO, max_attempts: None },
+					EndpointStats::default(),
+					connection_status,
+					false,
+					false,
+				))
+			}
+		});
+
+		wait_until(|| connection_status.is_connected("wss://collector.example"));
+		let mut closed = server.closed();
+		server.close_with_code(1000, "normal closure");
+		futures::executor::block_on(closed.as_mut());
+		wait_until(|| connection_status.is_connected("wss://collector.example"));
+
+		let error = connection_status.last_error("wss://collector.example");
+		assert_eq!(error, None, "a successful reconnect clears the close as the last error");
+
+		drop(queue_tx);
+		worker.join().unwrap();
+	}
+
+	#[test]
+	fn run_endpoint_gives_up_on_an_endpoint_after_repeated_policy_violation_closes() {
+		let server = Arc::new(test_utils::MockTelemetryServer::new());
+		let (_queue_tx, queue_rx) = mpsc::channel(8);
+		let connection_status = EndpointConnectionStatus::default();
+
+		let worker = {
+			let server = server.clone();
+			let connection_status = connection_status.clone();
+			std::thread::spawn(move || {
+				futures::executor::block_on(run_endpoint(
+					"wss://collector.example".to_string(),
+					queue_rx,
+					Box::new(server),
+					Arc::new(ImmediateDelay),
+					ReconnectPolicy { initial_delay: std::time::Duration::ZERO, max_delay: std::time::Duration::ZERO, max_attempts: None },
+					EndpointStats::default(),
+					connection_status,
+					false,
+					false,
+				))
+			})
+		};
+
+		for _ in 0..POLICY_VIOLATION_MAX_ATTEMPTS {
+			while !connection_status.is_connected("wss://collector.example") {
+				std::thread::sleep(std::time::Duration::from_millis(1));
+			}
+			server.close_with_code(1008, "node id banned");
+		}
+
+		// The endpoint was given up on entirely, so `run_endpoint` returns on
+		// its own without needing `queue_rx` dropped to signal shutdown.
+		worker.join().unwrap();
+
+		let error = connection_status.last_error("wss://collector.example").unwrap();
+		assert_eq!(error.kind, EndpointErrorKind::PolicyViolation);
+		assert!(error.message.contains("1008"));
+		assert!(error.message.contains("node id banned"));
+		assert!(!connection_status.is_connected("wss://collector.example"));
+	}
+
+	#[test]
+	fn run_endpoint_waits_out_a_try_again_later_close_using_its_retry_after_hint() {
+		let server = Arc::new(test_utils::MockTelemetryServer::new());
+		let (queue_tx, queue_rx) = mpsc::channel(8);
+		let connection_status = EndpointConnectionStatus::default();
+		let delay = Arc::new(RecordingDelay::default());
+
+		let worker = std::thread::spawn({
+			let server = server.clone();
+			let connection_status = connection_status.clone();
+			let delay = delay.clone();
+			move || {
+				futures::executor::block_on(run_endpoint(
+					"wss://collector.example".to_string(),
+					queue_rx,
+					Box::new(server),
+					delay,
+					ReconnectPolicy { initial_delay: std::time::Duration::ZERO, max_delay: std::time::Duration::ZERO, max_attempts: None },
+					EndpointStats::default(),
+					connection_status,
+					false,
+					false,
+				))
+			}
+		});
+
+		while !connection_status.is_connected("wss://collector.example") {
+			std::thread::sleep(std::time::Duration::from_millis(1));
+		}
+		server.close_with_code(1013, "try again in 45 seconds");
+		while !connection_status.is_connected("wss://collector.example") {
+			std::thread::sleep(std::time::Duration::from_millis(1));
+		}
+
+		let error = connection_status.last_error("wss://collector.example");
+		assert_eq!(error, None, "the reconnect after the wait succeeded, clearing the last error");
+		assert_eq!(delay.0.lock().last(), Some(&std::time::Duration::from_secs(45)), "the hint in the close reason was honored");
+
+		drop(queue_tx);
+		worker.join().unwrap();
+	}
+
+	#[test]
+	fn run_endpoint_falls_back_to_a_floor_delay_when_a_try_again_later_close_has_no_hint() {
+		let server = Arc::new(test_utils::MockTelemetryServer::new());
+		let (queue_tx, queue_rx) = mpsc::channel(8);
+		let connection_status = EndpointConnectionStatus::default();
+		let delay = Arc::new(RecordingDelay::default());
+
+		let worker = std::thread::spawn({
+			let server = server.clone();
+			let connection_status = connection_status.clone();
+			let delay = delay.clone();
+			move || {
+				futures::executor::block_on(run_endpoint(
+					"wss://collector.example".to_string(),
+					queue_rx,
+					Box::new(server),
+					delay,
+					ReconnectPolicy { initial_delay: std::time::Duration::ZERO, max_delay: std::time::Duration::ZERO, max_attempts: None },
+					EndpointStats::default(),
+					connection_status,
+					false,
+					false,
+				))
+			}
+		});
+
+		while !connection_status.is_connected("wss://collector.example") {
+			std::thread::sleep(std::time::Duration::from_millis(1));
+		}
+		server.close_with_code(1013, "please back off");
+		while !connection_status.is_connected("wss://collector.example") {
+			std::thread::sleep(std::time::Duration::from_millis(1));
+		}
+
+		assert_eq!(delay.0.lock().last(), Some(&TRY_AGAIN_LATER_MIN_DELAY), "no parseable hint, so the floor delay was used");
+
+		drop(queue_tx);
+		worker.join().unwrap();
+	}
+
+	#[test]
+	fn run_endpoint_never_delivers_a_message_twice_across_chaos_disconnects_without_ack_mode() {
+		// Fire-and-forget `run_endpoint` (no `ack_mode`) never replays a
+		// message once it's left the queue — a forced disconnect can only
+		// ever lose whatever `ChaosTransport` was mid-forwarding when it
+		// fired, never duplicate it. Exercised across a handful of
+		// disconnect/latency schedules rather than one fixed one, since the
+		// property should hold regardless of exactly when the fault lands.
+		const MESSAGE_COUNT: usize = 40;
+		let schedules = [
+			chaos::ChaosSchedule { disconnect_after: Some(3), ..Default::default() },
+			chaos::ChaosSchedule { disconnect_after: Some(7), send_latency: Some(std::time::Duration::from_micros(200)), ..Default::default() },
+			chaos::ChaosSchedule { disconnect_after: Some(1), ..Default::default() },
+			chaos::ChaosSchedule::default(),
+		];
+
+		for schedule in schedules {
+			let server = Arc::new(test_utils::MockTelemetryServer::new());
+			let transport = chaos::ChaosTransport::new(server.clone(), &schedule);
+			let (queue_tx, queue_rx) = mpsc::channel(MESSAGE_COUNT);
+			let connection_status = EndpointConnectionStatus::default();
+
+			let worker = std::thread::spawn({
+				let connection_status = connection_status.clone();
+				move || {
+					futures::executor::block_on(run_endpoint(
+						"wss://collector.example".to_string(),
+						queue_rx,
+						Box::new(transport),
+						Arc::new(ImmediateDelay),
+						ReconnectPolicy { initial_delay: std::time::Duration::ZERO, max_delay: std::time::Duration::ZERO, max_attempts: None },
+						EndpointStats::default(),
+						connection_status,
+						false,
+						false,
+					))
+				}
+			});
+
+			for n in 0..MESSAGE_COUNT {
+				let json = serialize_message(&serde_json::json!({ "msg": "chaos.bench", "n": n }), Some("chaos.bench")).unwrap();
+				queue_tx.clone().try_send((Verbosity::INFO, json)).unwrap();
+			}
+			drop(queue_tx);
+			worker.join().unwrap();
+
+			let received: Vec<i64> = server
+				.messages_of_type("chaos.bench")
+				.iter()
+				.map(|message| message.payload["n"].as_i64().unwrap())
+				.collect();
+			let mut seen = std::collections::HashSet::new();
+			for n in &received {
+				assert!(seen.insert(*n), "message {n} was delivered more than once under schedule {schedule:?}");
+			}
+		}
+	}
+
+	#[test]
+	fn senders_next_seq_is_strictly_increasing_per_id_across_chaos_disconnects() {
+		// `Senders::next_seq` hands out `id`'s sequence numbers before a
+		// message ever reaches the transport, so no amount of reconnect
+		// churn downstream should let two messages for the same id reach a
+		// collector out of order or with a repeated `seq` — this drives the
+		// same message volume through a handful of chaos schedules and
+		// checks the `seq` a mock collector actually observed.
+		const MESSAGE_COUNT: usize = 40;
+		let schedules = [
+			chaos::ChaosSchedule { disconnect_after: Some(4), ..Default::default() },
+			chaos::ChaosSchedule { disconnect_after: Some(9), send_latency: Some(std::time::Duration::from_micros(200)), ..Default::default() },
+			chaos::ChaosSchedule::default(),
+		];
+
+		for schedule in schedules {
+			let server = Arc::new(test_utils::MockTelemetryServer::new());
+			let transport = chaos::ChaosTransport::new(server.clone(), &schedule);
+			let (queue_tx, queue_rx) = mpsc::channel(MESSAGE_COUNT);
+			let connection_status = EndpointConnectionStatus::default();
+			let senders = Senders::default();
+			let id = 7;
+			senders.insert(id, queue_tx.clone());
+
+			let worker = std::thread::spawn({
+				let connection_status = connection_status.clone();
+				move || {
+					futures::executor::block_on(run_endpoint(
+						"wss://collector.example".to_string(),
+						queue_rx,
+						Box::new(transport),
+						Arc::new(ImmediateDelay),
+						ReconnectPolicy { initial_delay: std::time::Duration::ZERO, max_delay: std::time::Duration::ZERO, max_attempts: None },
+						EndpointStats::default(),
+						connection_status,
+						false,
+						false,
+					))
+				}
+			});
+
+			for _ in 0..MESSAGE_COUNT {
+				let (seq, dropped) = senders.next_seq(id).unwrap();
+				let json = serialize_message(
+					&serde_json::json!({ "msg": "chaos.bench", "seq": seq, "dropped": dropped }),
+					Some("chaos.bench"),
+				)
+				.unwrap();
+				let _ = queue_tx.clone().try_send((Verbosity::INFO, json));
+			}
+			drop(queue_tx);
+			worker.join().unwrap();
+
+			let received: Vec<u64> = server
+				.messages_of_type("chaos.bench")
+				.iter()
+				.map(|message| message.payload["seq"].as_u64().unwrap())
+				.collect();
+			for pair in received.windows(2) {
+				assert!(pair[0] < pair[1], "seq went from {} to {} under schedule {schedule:?}", pair[0], pair[1]);
+			}
+		}
+	}
+
+	#[test]
+	fn periodic_reporter_assembles_registered_metrics_into_one_message() {
+		let telemetries = Telemetries::default();
+		let (sender, mut receiver) = mpsc::channel(8);
+		telemetries.senders.insert(1, sender);
+		telemetries.connection_events.set_connected(1, "wss://example");
+
+		let peers = std::sync::Arc::new(std::sync::atomic::AtomicU64::new(3));
+		let peers_for_metric = peers.clone();
+		let mut reporter = PeriodicReporter::new(telemetries, 1, std::time::Duration::from_secs(5))
+			.metric("peers", move || Some(serde_json::json!(peers_for_metric.load(std::sync::atomic::Ordering::Relaxed))))
+			.metric("height", || Some(serde_json::json!(100)))
+			.metric("not_ready_yet", || None);
+
+		let start = std::time::Instant::now();
+		assert_eq!(reporter.tick(start), PeriodicTick::Sent);
+
+		let (_, json) = receiver.try_next().unwrap().unwrap();
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert_eq!(value["peers"], 3);
+		assert_eq!(value["height"], 100);
+		assert!(value.get("not_ready_yet").is_none(), "a metric returning None is omitted, not sent as null");
+	}
+
+	#[test]
+	fn periodic_reporter_is_not_due_before_its_interval_elapses() {
+		let telemetries = Telemetries::default();
+		let (sender, mut receiver) = mpsc::channel(8);
+		telemetries.senders.insert(1, sender);
+		telemetries.connection_events.set_connected(1, "wss://example");
+
+		let mut reporter = PeriodicReporter::new(telemetries, 1, std::time::Duration::from_secs(10))
+			.metric("peers", || Some(serde_json::json!(1)));
+
+		let start = std::time::Instant::now();
+		assert_eq!(reporter.tick(start), PeriodicTick::Sent);
+		receiver.try_next().unwrap();
+
+		assert_eq!(reporter.tick(start + std::time::Duration::from_secs(5)), PeriodicTick::NotDue);
+		assert!(receiver.try_next().is_err(), "nothing sent while not due");
+
+		assert_eq!(reporter.tick(start + std::time::Duration::from_secs(10)), PeriodicTick::Sent);
+	}
+
+	#[test]
+	fn periodic_reporter_skips_a_tick_whose_predecessor_is_still_queued() {
+		let telemetries = Telemetries::default();
+		// Capacity 0: the single in-flight slot fills on the first send, so the
+		// second is rejected with `ChannelFull` and never drained by the test.
+		let (sender, mut receiver) = mpsc::channel(0);
+		telemetries.senders.insert(1, sender);
+		telemetries.connection_events.set_connected(1, "wss://example");
+
+		let calls = std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0));
+		let calls_for_metric = calls.clone();
+		let mut reporter = PeriodicReporter::new(telemetries, 1, std::time::Duration::from_secs(1)).metric(
+			"peers",
+			move || {
+				calls_for_metric.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+				Some(serde_json::json!(1))
+			},
+		);
+
+		let start = std::time::Instant::now();
+		assert_eq!(reporter.tick(start), PeriodicTick::Sent);
+		assert_eq!(calls.load(std::sync::atomic::Ordering::Relaxed), 1);
+
+		// The first message is still sitting in the zero-capacity channel, so
+		// this tick doesn't even bother gathering metrics again.
+		assert_eq!(reporter.tick(start + std::time::Duration::from_secs(1)), PeriodicTick::Skipped);
+		assert_eq!(calls.load(std::sync::atomic::Ordering::Relaxed), 1, "metrics aren't re-gathered while skipped");
+
+		// Draining the channel lets the next tick through again.
+		receiver.try_next().unwrap();
+		assert_eq!(reporter.tick(start + std::time::Duration::from_secs(2)), PeriodicTick::Sent);
+		assert_eq!(calls.load(std::sync::atomic::Ordering::Relaxed), 2);
+	}
+
+	#[test]
+	fn periodic_reporter_pauses_while_disconnected_without_a_replay_buffer() {
+		let telemetries = Telemetries::default();
+		let (sender, mut receiver) = mpsc::channel(8);
+		telemetries.senders.insert(1, sender);
+		// Never reported connected.
+
+		let mut reporter = PeriodicReporter::new(telemetries.clone(), 1, std::time::Duration::from_secs(1))
+			.metric("peers", || Some(serde_json::json!(1)));
+
+		let start = std::time::Instant::now();
+		assert_eq!(reporter.tick(start), PeriodicTick::Paused);
+		assert!(receiver.try_next().is_err());
+
+		telemetries.connection_events.set_connected(1, "wss://example");
+		assert_eq!(reporter.tick(start + std::time::Duration::from_secs(1)), PeriodicTick::Sent);
+	}
+
+	#[test]
+	fn periodic_reporter_keeps_ticking_while_disconnected_when_a_replay_buffer_is_configured() {
+		let telemetries = Telemetries::default();
+		let (sender, mut receiver) = mpsc::channel(8);
+		telemetries.senders.insert(1, sender);
+		// Never reported connected.
+
+		let mut reporter = PeriodicReporter::new(telemetries, 1, std::time::Duration::from_secs(1))
+			.with_replay_buffer(true)
+			.metric("peers", || Some(serde_json::json!(1)));
+
+		let start = std::time::Instant::now();
+		assert_eq!(reporter.tick(start), PeriodicTick::Sent, "a downstream replay buffer will hold this until reconnect");
+		receiver.try_next().unwrap();
+	}
+
+	#[test]
+	fn connection_message_can_be_set_and_replaced() {
+		let telemetries = Telemetries::default();
+		let (sender, _receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+
+		assert!(telemetries.set_connection_message(1, 0u8, serde_json::json!({ "msg": "system.connected", "name": "node-1" })));
+		let (_, first) = telemetries.senders.connection_message(1).unwrap();
+		assert!(first.contains("node-1"));
+
+		// Replaceable at runtime, e.g. when the node's name changes.
+		assert!(telemetries.set_connection_message(1, 0u8, serde_json::json!({ "msg": "system.connected", "name": "node-2" })));
+		let (_, second) = telemetries.senders.connection_message(1).unwrap();
+		assert!(second.contains("node-2"));
+	}
+
+	#[test]
+	fn connection_message_is_none_for_an_unregistered_id() {
+		let senders = Senders::default();
+		assert!(senders.connection_message(1).is_none());
+	}
+
+	#[test]
+	fn flapping_transport_resends_the_connection_message_once_per_session() {
+		// A worker (not present in this crate slice) would call
+		// `senders.connection_message(id)` first thing after every successful
+		// (re)connection; simulate three sessions and assert it's sent each time.
+		let telemetries = Telemetries::default();
+		let (sender, mut receiver) = mpsc::channel(10);
+		telemetries.senders.insert(1, sender);
+		telemetries.set_connection_message(1, 0u8, serde_json::json!({ "msg": "system.connected" }));
+
+		for _ in 0..3 {
+			// (Re)connect: resend the connection message before anything else.
+			let (verbosity, json) = telemetries.senders.connection_message(1).unwrap();
+			let _ = telemetries.senders.send(1, Some("system.connected"), (verbosity, json));
+			// ...then some regular traffic for the session.
+			telemetries.send(1, 0u8, serde_json::json!({ "msg": "block.import" }));
+		}
+
+		let received: Vec<_> = std::iter::from_fn(|| receiver.try_next().ok().flatten()).collect();
+		let connected_count =
+			received.iter().filter(|(_, json)| json.contains("system.connected")).count();
+		assert_eq!(connected_count, 3, "one system.connected per session");
+	}
+
+	#[test]
+	fn node_identity_is_merged_into_the_connection_message() {
+		let telemetries = Telemetries::default();
+		let (sender, _receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+		telemetries.set_connection_message(1, 0u8, serde_json::json!({ "msg": "system.connected" }));
+
+		assert!(telemetries.set_node_identity(
+			1,
+			NodeIdentity {
+				name: Some("collator-a".into()),
+				chain: Some("westend".into()),
+				..NodeIdentity::default()
+			},
+		));
+
+		let (_, json) = telemetries.senders.connection_message(1).unwrap();
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert_eq!(value["name"], "collator-a");
+		assert_eq!(value["chain"], "westend");
+	}
+
+	#[test]
+	fn changing_node_identity_re_announces_it_to_a_connected_endpoint() {
+		let telemetries = Telemetries::default();
+		let (sender, mut receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+		telemetries.set_connection_message(1, 0u8, serde_json::json!({ "msg": "system.connected", "name": "unnamed" }));
+		receiver.try_next().expect_err("nothing sent until the identity changes");
+
+		telemetries.set_node_identity(1, NodeIdentity { name: Some("collator-b".into()), ..NodeIdentity::default() });
+
+		let (_, json) = receiver.try_next().unwrap().unwrap();
+		assert!(json.contains("collator-b"), "already-connected endpoint is re-announced immediately");
+	}
+
+	#[test]
+	fn network_id_is_merged_into_the_connection_message() {
+		let telemetries = Telemetries::default();
+		let (sender, _receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+		telemetries.set_connection_message(1, 0u8, serde_json::json!({ "msg": "system.connected" }));
+
+		assert!(telemetries.set_network_id(1, "12D3KooW".into()));
+
+		let (_, json) = telemetries.senders.connection_message(1).unwrap();
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert_eq!(value["network_id"], "12D3KooW");
+		assert_eq!(telemetries.network_id(1), Some("12D3KooW".into()));
+	}
+
+	#[test]
+	fn setting_network_id_after_connecting_re_announces_exactly_one_updated_handshake_per_endpoint() {
+		let telemetries = Telemetries::default();
+		let (sender_a, mut receiver_a) = mpsc::channel(1);
+		let (sender_b, mut receiver_b) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender_a);
+		telemetries.senders.insert(2, sender_b);
+		telemetries.set_connection_message(1, 0u8, serde_json::json!({ "msg": "system.connected" }));
+		telemetries.set_connection_message(2, 0u8, serde_json::json!({ "msg": "system.connected" }));
+		receiver_a.try_next().expect_err("nothing sent until the network id is known");
+		receiver_b.try_next().expect_err("nothing sent until the network id is known");
+
+		assert!(telemetries.set_network_id(1, "12D3KooW".into()));
+		assert!(telemetries.set_network_id(2, "12D3KooW".into()));
+
+		let (_, json_a) = receiver_a.try_next().unwrap().unwrap();
+		assert!(json_a.contains("12D3KooW"), "endpoint 1 gets exactly one updated handshake");
+		assert!(receiver_a.try_next().is_err(), "no second re-announcement");
+		let (_, json_b) = receiver_b.try_next().unwrap().unwrap();
+		assert!(json_b.contains("12D3KooW"), "endpoint 2 gets exactly one updated handshake");
+		assert!(receiver_b.try_next().is_err(), "no second re-announcement");
+
+		// Subsequent payloads aren't retroactively modified.
+		telemetries.send(1, 0u8, serde_json::json!({ "msg": "block.import" }));
+		let (_, later) = receiver_a.try_next().unwrap().unwrap();
+		assert!(!later.contains("network_id"), "network_id is only carried by the handshake, not later payloads");
+	}
+
+	#[test]
+	fn set_network_id_returns_false_for_an_unregistered_id() {
+		let telemetries = Telemetries::default();
+		assert!(!telemetries.set_network_id(1, "12D3KooW".into()));
+	}
+
+	#[test]
+	fn connection_extras_are_merged_into_the_connection_message() {
+		let telemetries = Telemetries::default();
+		let (sender, _receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+		telemetries.set_connection_message(1, 0u8, serde_json::json!({ "msg": "system.connected" }));
+
+		let mut extras = serde_json::Map::new();
+		extras.insert("para_id".into(), 2000.into());
+		extras.insert("relay_chain".into(), "polkadot".into());
+		assert!(telemetries.set_connection_extras(1, extras).is_ok());
+
+		let (_, json) = telemetries.senders.connection_message(1).unwrap();
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert_eq!(value["para_id"], 2000);
+		assert_eq!(value["relay_chain"], "polkadot");
+	}
+
+	#[test]
+	fn changing_connection_extras_re_announces_them_to_a_connected_endpoint() {
+		let telemetries = Telemetries::default();
+		let (sender, mut receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+		telemetries.set_connection_message(1, 0u8, serde_json::json!({ "msg": "system.connected" }));
+		receiver.try_next().expect_err("nothing sent until the extras change");
+
+		let mut extras = serde_json::Map::new();
+		extras.insert("para_id".into(), 2000.into());
+		telemetries.set_connection_extras(1, extras).unwrap();
+
+		let (_, json) = receiver.try_next().unwrap().unwrap();
+		assert!(json.contains("2000"), "already-connected endpoint is re-announced immediately");
+	}
+
+	#[test]
+	fn connection_extras_colliding_with_a_reserved_field_are_rejected() {
+		let telemetries = Telemetries::default();
+		let (sender, mut receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+		telemetries.set_connection_message(1, 0u8, serde_json::json!({ "msg": "system.connected", "chain": "westend" }));
+
+		let mut extras = serde_json::Map::new();
+		extras.insert("chain".into(), "kusama".into());
+		assert_eq!(
+			telemetries.set_connection_extras(1, extras),
+			Err(TelemetryError::ReservedField("chain".into())),
+		);
+
+		// Rejected outright: neither the stored extras nor the connection
+		// message (and so nothing is re-announced) are touched.
+		receiver.try_next().expect_err("a rejected update re-announces nothing");
+		assert_eq!(telemetries.connection_extras(1), Some(serde_json::Map::new()));
+		let (_, json) = telemetries.senders.connection_message(1).unwrap();
+		assert!(serde_json::from_str::<serde_json::Value>(&json).unwrap()["chain"] == "westend");
+	}
+
+	#[test]
+	fn connection_extras_supplied_at_registration_drop_reserved_keys() {
+		let mut extras = serde_json::Map::new();
+		extras.insert("para_id".into(), 2000.into());
+		extras.insert("name".into(), "should-be-dropped".into());
+		let senders = Senders::default();
+		let (sender, _receiver) = mpsc::channel(1);
+		senders.insert_with_config(1, sender, SenderConfig { connection_extras: extras, ..SenderConfig::default() });
+
+		let stored = senders.connection_extras(1).unwrap();
+		assert_eq!(stored.get("para_id"), Some(&serde_json::Value::from(2000)));
+		assert!(!stored.contains_key("name"), "reserved keys are dropped rather than stored");
+	}
+
+	#[test]
+	fn node_identity_is_not_stamped_onto_payloads_by_default() {
+		let telemetries = Telemetries::default();
+		let (sender, mut receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+		telemetries.set_node_identity(1, NodeIdentity { name: Some("collator-a".into()), ..NodeIdentity::default() });
+
+		assert!(telemetries.send(1, 0u8, serde_json::json!({ "msg": "block.import" })));
+		let (_, json) = receiver.try_next().unwrap().unwrap();
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert!(value.get("node").is_none());
+	}
+
+	#[test]
+	fn node_identity_is_stamped_onto_every_payload_when_enabled() {
+		let telemetries = Telemetries::default();
+		let (sender, mut receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+		telemetries.set_node_identity(1, NodeIdentity { name: Some("collator-a".into()), ..NodeIdentity::default() });
+		assert!(telemetries.set_stamp_identity_on_payloads(1, true));
+
+		assert!(telemetries.send(1, 0u8, serde_json::json!({ "msg": "block.import" })));
+		let (_, json) = receiver.try_next().unwrap().unwrap();
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert_eq!(value["node"]["name"], "collator-a");
+	}
+
+	#[test]
+	fn two_instances_keep_independent_node_identities() {
+		let telemetries = Telemetries::default();
+		let (sender_a, mut receiver_a) = mpsc::channel(1);
+		let (sender_b, mut receiver_b) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender_a);
+		telemetries.senders.insert(2, sender_b);
+		telemetries.set_node_identity(1, NodeIdentity { name: Some("parachain-a".into()), chain: Some("statemint".into()), ..NodeIdentity::default() });
+		telemetries.set_node_identity(2, NodeIdentity { name: Some("parachain-b".into()), chain: Some("moonbeam".into()), ..NodeIdentity::default() });
+		telemetries.set_stamp_identity_on_payloads(1, true);
+		telemetries.set_stamp_identity_on_payloads(2, true);
+
+		telemetries.send(1, 0u8, serde_json::json!({ "msg": "block.import" }));
+		telemetries.send(2, 0u8, serde_json::json!({ "msg": "block.import" }));
+
+		let (_, json_a) = receiver_a.try_next().unwrap().unwrap();
+		let (_, json_b) = receiver_b.try_next().unwrap().unwrap();
+		let value_a: serde_json::Value = serde_json::from_str(&json_a).unwrap();
+		let value_b: serde_json::Value = serde_json::from_str(&json_b).unwrap();
+		assert_eq!(value_a["node"]["chain"], "statemint");
+		assert_eq!(value_b["node"]["chain"], "moonbeam");
+	}
+
+	#[test]
+	fn is_connected_reflects_the_latest_reported_state() {
+		let telemetries = Telemetries::default();
+		assert!(!telemetries.is_connected(1), "never reported connected");
+
+		telemetries.connection_events.set_connected(1, "wss://example");
+		assert!(telemetries.is_connected(1));
+
+		telemetries.connection_events.set_disconnected(1, "wss://example", "closed by peer");
+		assert!(!telemetries.is_connected(1));
+	}
+
+	#[test]
+	fn connection_events_are_delivered_in_order_to_every_subscriber() {
+		let telemetries = Telemetries::default();
+		let mut subscriber_a = telemetries.connection_events();
+		let mut subscriber_b = telemetries.connection_events();
+
+		// A flapping mock transport: connect, then drop.
+		telemetries.connection_events.set_connected(1, "wss://example");
+		telemetries.connection_events.set_disconnected(1, "wss://example", "connection reset");
+
+		for subscriber in [&mut subscriber_a, &mut subscriber_b] {
+			assert_eq!(
+				subscriber.try_next().unwrap(),
+				Some(ConnectionEvent::Connected { endpoint: "wss://example".to_string() }),
+			);
+			assert_eq!(
+				subscriber.try_next().unwrap(),
+				Some(ConnectionEvent::Disconnected {
+					endpoint: "wss://example".to_string(),
+					reason: "connection reset".to_string(),
+				}),
+			);
+		}
+	}
+
+	#[test]
+	fn a_dropped_subscriber_does_not_block_publishing() {
+		let events = ConnectionEvents::default();
+		drop(events.subscribe());
+		// Would panic/hang if publishing tried to wait on the dropped subscriber.
+		events.set_connected(1, "wss://example");
+		events.set_disconnected(1, "wss://example", "gone");
+	}
+
+	#[test]
+	fn on_connect_callbacks_run_in_order_across_simulated_reconnects() {
+		let telemetries = Telemetries::default();
+		let (sender, _receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+
+		let calls = Arc::new(Mutex::new(Vec::new()));
+		let first = calls.clone();
+		let second = calls.clone();
+		telemetries.on_connect(1, move || first.lock().push("first"));
+		telemetries.on_connect(1, move || second.lock().push("second"));
+
+		for _ in 0..3 {
+			telemetries.senders.fire_on_connect(1);
+		}
+
+		assert_eq!(
+			*calls.lock(),
+			vec!["first", "second", "first", "second", "first", "second"],
+		);
+	}
+
+	#[test]
+	fn a_panicking_on_connect_callback_does_not_stop_the_others() {
+		let telemetries = Telemetries::default();
+		let (sender, _receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+
+		let ran = Arc::new(Mutex::new(false));
+		let ran_clone = ran.clone();
+		telemetries.on_connect(1, || panic!("boom"));
+		telemetries.on_connect(1, move || *ran_clone.lock() = true);
+
+		telemetries.senders.fire_on_connect(1);
+
+		assert!(*ran.lock(), "the callback after the panicking one still ran");
+	}
+
+	#[test]
+	fn a_blocked_endpoint_does_not_starve_a_draining_one() {
+		let (mut incoming_tx, incoming_rx) = mpsc::channel(8);
+		// Zero capacity and never polled: the very first message already fills it.
+		let (blocked_tx, _blocked_rx) = mpsc::channel(0);
+		let (draining_tx, mut draining_rx) = mpsc::channel(8);
+
+		let mut targets = HashMap::new();
+		targets.insert("wss://blocked".to_string(), blocked_tx);
+		targets.insert("wss://draining".to_string(), draining_tx);
+		let stats = EndpointStats::default();
+
+		for n in 0..3 {
+			incoming_tx.try_send((Verbosity::CONSOLE, format!("msg-{}", n))).unwrap();
+		}
+		drop(incoming_tx);
+
+		let (_commands_tx, commands_rx) = mpsc::unbounded();
+		futures::executor::block_on(fan_out_by_verbosity(
+			incoming_rx,
+			Endpoints::new(),
+			targets,
+			stats.clone(),
+			commands_rx,
+			EndpointMessageFilters::new(),
+			EndpointGroups::default(),
+			None,
+			EndpointEnvelopes::new(),
+			EndpointVerbosityFields::new(),
+		));
+
+		let draining: Vec<_> = std::iter::from_fn(|| draining_rx.try_next().ok().flatten()).collect();
+		assert_eq!(draining.len(), 3, "the draining endpoint receives everything");
+		assert!(stats.dropped("wss://blocked") > 0);
+		assert_eq!(stats.dropped("wss://draining"), 0);
+	}
+
+	#[test]
+	fn fan_out_by_verbosity_adds_an_endpoint_at_runtime_and_sends_its_connect_message() {
+		let (mut incoming_tx, incoming_rx) = mpsc::channel(8);
+		let (commands_tx, commands_rx) = mpsc::unbounded();
+		let (new_tx, mut new_rx) = mpsc::channel(8);
+
+		commands_tx
+			.unbounded_send(EndpointCommand::Add {
+				url: "wss://late".to_string(),
+				max_verbosity: Verbosity::CONSOLE,
+				sender: new_tx,
+				connect_message: Some((Verbosity::CONSOLE, "connected".to_string())),
+			})
+			.unwrap();
+		incoming_tx.try_send((Verbosity::CONSOLE, "hello".to_string())).unwrap();
+		drop(incoming_tx);
+		drop(commands_tx);
+
+		futures::executor::block_on(fan_out_by_verbosity(
+			incoming_rx,
+			Endpoints::new(),
+			HashMap::new(),
+			EndpointStats::default(),
+			commands_rx,
+			EndpointMessageFilters::new(),
+			EndpointGroups::default(),
+			None,
+			EndpointEnvelopes::new(),
+			EndpointVerbosityFields::new(),
+		));
+
+		assert_eq!(new_rx.try_next().unwrap(), Some((Verbosity::CONSOLE, "connected".to_string())));
+		assert_eq!(new_rx.try_next().unwrap(), Some((Verbosity::CONSOLE, "hello".to_string())));
+	}
+
+	#[test]
+	fn fan_out_by_verbosity_removes_an_endpoint_at_runtime() {
+		let (mut incoming_tx, incoming_rx) = mpsc::channel(8);
+		let (commands_tx, commands_rx) = mpsc::unbounded();
+		let (removed_tx, mut removed_rx) = mpsc::channel(8);
+
+		let mut targets = HashMap::new();
+		targets.insert("wss://removed".to_string(), removed_tx);
+
+		commands_tx.unbounded_send(EndpointCommand::Remove { url: "wss://removed".to_string() }).unwrap();
+		incoming_tx.try_send((Verbosity::CONSOLE, "hello".to_string())).unwrap();
+		drop(incoming_tx);
+		drop(commands_tx);
+
+		futures::executor::block_on(fan_out_by_verbosity(
+			incoming_rx,
+			Endpoints::new(),
+			targets,
+			EndpointStats::default(),
+			commands_rx,
+			EndpointMessageFilters::new(),
+			EndpointGroups::default(),
+			None,
+			EndpointEnvelopes::new(),
+			EndpointVerbosityFields::new(),
+		));
+
+		assert!(removed_rx.try_next().is_err(), "no message should reach an endpoint removed before it was sent");
+		assert!(matches!(removed_rx.try_next(), Err(_)), "the sender half was dropped, closing the channel");
+	}
+
+	#[test]
+	fn endpoint_group_fails_over_immediately_when_the_primary_is_unhealthy() {
+		let (mut incoming_tx, incoming_rx) = mpsc::channel(8);
+		let (commands_tx, commands_rx) = mpsc::unbounded();
+		let (primary_tx, mut primary_rx) = mpsc::channel(8);
+		let (standby_tx, mut standby_rx) = mpsc::channel(8);
+
+		let mut targets = HashMap::new();
+		targets.insert("wss://primary".to_string(), primary_tx);
+		targets.insert("wss://standby".to_string(), standby_tx);
+
+		let mut groups = EndpointGroups::new();
+		groups.insert(
+			"collector",
+			EndpointGroup::new("wss://primary", ["wss://standby"], std::time::Duration::from_secs(30)),
+		);
+
+		commands_tx
+			.unbounded_send(EndpointCommand::ReportEndpointHealth { url: "wss://primary".to_string(), healthy: false })
+			.unwrap();
+		incoming_tx.try_send((Verbosity::CONSOLE, "hello".to_string())).unwrap();
+		drop(incoming_tx);
+		drop(commands_tx);
+
+		futures::executor::block_on(fan_out_by_verbosity(
+			incoming_rx,
+			Endpoints::new(),
+			targets,
+			EndpointStats::default(),
+			commands_rx,
+			EndpointMessageFilters::new(),
+			groups,
+			None,
+			EndpointEnvelopes::new(),
+			EndpointVerbosityFields::new(),
+		));
+
+		// The primary still receives the failover announcement broadcast (like any
+		// other `system.*` meta message), but never the actual payload.
+		let primary_delivered: Vec<_> = std::iter::from_fn(|| primary_rx.try_next().ok().flatten()).collect();
+		assert!(!primary_delivered.iter().any(|(_, json)| json == "hello"));
+		let delivered: Vec<_> = std::iter::from_fn(|| standby_rx.try_next().ok().flatten()).collect();
+		assert!(
+			delivered.iter().any(|(_, json)| json == "hello"),
+			"the standby takes over as soon as the primary is reported unhealthy"
+		);
+	}
+
+	#[test]
+	fn endpoint_group_does_not_fail_back_before_the_stabilization_period_elapses() {
+		let (mut incoming_tx, incoming_rx) = mpsc::channel(8);
+		let (commands_tx, commands_rx) = mpsc::unbounded();
+		let (primary_tx, mut primary_rx) = mpsc::channel(8);
+		let (standby_tx, mut standby_rx) = mpsc::channel(8);
+
+		let mut targets = HashMap::new();
+		targets.insert("wss://primary".to_string(), primary_tx);
+		targets.insert("wss://standby".to_string(), standby_tx);
+
+		let mut groups = EndpointGroups::new();
+		groups.insert(
+			"collector",
+			EndpointGroup::new("wss://primary", ["wss://standby"], std::time::Duration::from_secs(3600)),
+		);
+
+		commands_tx
+			.unbounded_send(EndpointCommand::ReportEndpointHealth { url: "wss://primary".to_string(), healthy: false })
+			.unwrap();
+		commands_tx
+			.unbounded_send(EndpointCommand::ReportEndpointHealth { url: "wss://primary".to_string(), healthy: true })
+			.unwrap();
+		incoming_tx.try_send((Verbosity::CONSOLE, "hello".to_string())).unwrap();
+		drop(incoming_tx);
+		drop(commands_tx);
+
+		futures::executor::block_on(fan_out_by_verbosity(
+			incoming_rx,
+			Endpoints::new(),
+			targets,
+			EndpointStats::default(),
+			commands_rx,
+			EndpointMessageFilters::new(),
+			groups,
+			None,
+			EndpointEnvelopes::new(),
+			EndpointVerbosityFields::new(),
+		));
+
+		let primary_delivered: Vec<_> = std::iter::from_fn(|| primary_rx.try_next().ok().flatten()).collect();
+		assert!(
+			!primary_delivered.iter().any(|(_, json)| json == "hello"),
+			"a freshly recovered primary hasn't stabilized yet, so traffic stays on the standby"
+		);
+		let delivered: Vec<_> = std::iter::from_fn(|| standby_rx.try_next().ok().flatten()).collect();
+		assert!(delivered.iter().any(|(_, json)| json == "hello"));
+	}
+
+	#[test]
+	fn endpoint_group_fails_back_to_the_primary_once_stabilized() {
+		let mut runtime = GroupRuntime { active: 1, primary_recovered_since: None };
+		let group =
+			EndpointGroup::new("wss://primary", ["wss://standby"], std::time::Duration::from_secs(10));
+		let mut health = HashMap::new();
+		let start = std::time::Instant::now();
+
+		assert_eq!(
+			reconsider_group(&mut runtime, &group, &health, start),
+			None,
+			"the primary only starts its stabilization clock on the first healthy check"
+		);
+		health.insert("wss://primary".to_string(), true);
+		assert_eq!(
+			reconsider_group(&mut runtime, &group, &health, start),
+			None,
+			"still within the stabilization period"
+		);
+		assert_eq!(
+			reconsider_group(&mut runtime, &group, &health, start + std::time::Duration::from_secs(11)),
+			Some((1, 0)),
+			"failing back once the primary has been healthy for longer than failback_after"
+		);
+		assert_eq!(runtime.active, 0);
+	}
+
+	#[test]
+	fn endpoint_group_failover_announces_the_group_and_active_member() {
+		let (mut incoming_tx, incoming_rx) = mpsc::channel(8);
+		let (commands_tx, commands_rx) = mpsc::unbounded();
+		let (primary_tx, mut primary_rx) = mpsc::channel(8);
+		let (standby_tx, mut standby_rx) = mpsc::channel(8);
+
+		let mut targets = HashMap::new();
+		targets.insert("wss://primary".to_string(), primary_tx);
+		targets.insert("wss://standby".to_string(), standby_tx);
+
+		let mut groups = EndpointGroups::new();
+		groups.insert(
+			"collector",
+			EndpointGroup::new("wss://primary", ["wss://standby"], std::time::Duration::from_secs(30)),
+		);
+
+		commands_tx
+			.unbounded_send(EndpointCommand::ReportEndpointHealth { url: "wss://primary".to_string(), healthy: false })
+			.unwrap();
+		incoming_tx.try_send((Verbosity::CONSOLE, "hello".to_string())).unwrap();
+		drop(incoming_tx);
+		drop(commands_tx);
+
+		futures::executor::block_on(fan_out_by_verbosity(
+			incoming_rx,
+			Endpoints::new(),
+			targets,
+			EndpointStats::default(),
+			commands_rx,
+			EndpointMessageFilters::new(),
+			groups,
+			None,
+			EndpointEnvelopes::new(),
+			EndpointVerbosityFields::new(),
+		));
+
+		let primary_meta: Vec<_> = std::iter::from_fn(|| primary_rx.try_next().ok().flatten()).collect();
+		assert!(
+			primary_meta.iter().any(|(_, json)| json.contains("system.telemetry_failover")
+				&& json.contains("\"collector\"")
+				&& json.contains("wss://standby")),
+			"the failover announcement names the group and the newly active member"
+		);
+		let _ = standby_rx.try_next();
+	}
+
+	#[test]
+	fn telemetries_add_and_remove_endpoint_are_no_ops_until_a_worker_registers_its_inbox() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let (sender, _receiver) = mpsc::channel(8);
+		telemetries.senders.insert(1, sender);
+
+		let (endpoint_tx, mut endpoint_rx) = mpsc::channel(8);
+		assert!(
+			!telemetries.add_endpoint(1, "wss://new", Verbosity::CONSOLE, endpoint_tx, None),
+			"no worker has registered an inbox yet"
+		);
+
+		let (commands_tx, mut commands_rx) = mpsc::unbounded();
+		telemetries.set_endpoint_commands(1, commands_tx);
+
+		let (endpoint_tx, _endpoint_rx) = mpsc::channel(8);
+		assert!(telemetries.add_endpoint(1, "wss://new", Verbosity::CONSOLE, endpoint_tx, None));
+		assert!(matches!(commands_rx.try_next().unwrap(), Some(EndpointCommand::Add { url, .. }) if url == "wss://new"));
+
+		assert!(telemetries.remove_endpoint(1, "wss://new"));
+		assert!(matches!(commands_rx.try_next().unwrap(), Some(EndpointCommand::Remove { url }) if url == "wss://new"));
+
+		let _ = endpoint_rx.try_next();
+	}
+
+	#[test]
+	fn fan_out_by_verbosity_flips_a_threshold_mid_run_and_announces_the_change() {
+		let (mut incoming_tx, incoming_rx) = mpsc::channel(8);
+		let (commands_tx, commands_rx) = mpsc::unbounded();
+		let (endpoint_tx, mut endpoint_rx) = mpsc::channel(8);
+
+		let mut endpoints = Endpoints::new();
+		endpoints.insert("wss://endpoint", Verbosity::CONSOLE);
+		let mut targets = HashMap::new();
+		targets.insert("wss://endpoint".to_string(), endpoint_tx);
+
+		let worker = std::thread::spawn(move || {
+			futures::executor::block_on(fan_out_by_verbosity(
+				incoming_rx,
+				endpoints,
+				targets,
+				EndpointStats::default(),
+				commands_rx,
+				EndpointMessageFilters::new(),
+				EndpointGroups::default(),
+				None,
+				EndpointEnvelopes::new(),
+				EndpointVerbosityFields::new(),
+			));
+		});
+
+		// Filtered out by the CONSOLE threshold configured above.
+		incoming_tx.try_send((Verbosity::DEBUG, "before".to_string())).unwrap();
+		// A CONSOLE-level sentinel always passes; receiving it confirms the
+		// worker already processed (and dropped) "before", since a single
+		// endpoint's messages are forwarded strictly in order.
+		incoming_tx.try_send((Verbosity::CONSOLE, "sync".to_string())).unwrap();
+		let (_, sync) = futures::executor::block_on(endpoint_rx.next()).unwrap();
+		assert_eq!(sync, "sync");
+
+		commands_tx
+			.unbounded_send(EndpointCommand::SetMaxVerbosity {
+				url: "wss://endpoint".to_string(),
+				max_verbosity: Verbosity::DEBUG,
+			})
+			.unwrap();
+		// Receiving the change announcement confirms the new threshold is
+		// live before we send anything that depends on it.
+		let (_, announcement) = futures::executor::block_on(endpoint_rx.next()).unwrap();
+		assert!(announcement.contains("system.telemetry_verbosity_changed"));
+		assert!(announcement.contains("wss://endpoint"));
+
+		incoming_tx.try_send((Verbosity::DEBUG, "after".to_string())).unwrap();
+		let (_, after) = futures::executor::block_on(endpoint_rx.next()).unwrap();
+		assert_eq!(after, "after", "DEBUG now passes now that the threshold was raised");
+
+		drop(incoming_tx);
+		drop(commands_tx);
+		worker.join().unwrap();
+	}
+
+	/// A minimal [`log::Log`] sink for asserting on log output, since this
+	/// crate slice has no dependency on a full test-logging crate. `log`
+	/// only allows one global logger per process, so this is installed
+	/// exactly once (via [`std::sync::Once`]) and every test that reads its
+	/// output takes [`TEST_LOG_GUARD`] for the duration of the test to keep
+	/// captures from different tests from interleaving.
+	struct TestLogSink;
+
+	static TEST_LOG_LINES: std::sync::OnceLock<Mutex<Vec<String>>> = std::sync::OnceLock::new();
+	static TEST_LOG_GUARD: Mutex<()> = Mutex::new(());
+
+	impl log::Log for TestLogSink {
+		fn enabled(&self, _metadata: &log::Metadata) -> bool {
+			true
+		}
+
+		fn log(&self, record: &log::Record) {
+			TEST_LOG_LINES
+				.get_or_init(Default::default)
+				.lock()
+				.push(format!("{}|{}", record.target(), record.args()));
+		}
+
+		fn flush(&self) {}
+	}
+
+	/// Run `f` with the process-wide test logger installed and its capture
+	/// buffer cleared, returning `f`'s result alongside every line logged
+	/// during the call.
+	fn with_captured_log<R>(f: impl FnOnce() -> R) -> (R, Vec<String>) {
+		static INSTALL: std::sync::Once = std::sync::Once::new();
+		INSTALL.call_once(|| {
+			log::set_boxed_logger(Box::new(TestLogSink)).expect("test logger installed exactly once");
+			log::set_max_level(log::LevelFilter::Trace);
+		});
+		let _guard = TEST_LOG_GUARD.lock();
+		TEST_LOG_LINES.get_or_init(Default::default).lock().clear();
+		let result = f();
+		let lines = TEST_LOG_LINES.get().unwrap().lock().clone();
+		(result, lines)
+	}
+
+	#[test]
+	fn fan_out_by_verbosity_mirrors_delivered_and_dropped_messages_to_the_log_when_enabled() {
+		let (mut incoming_tx, incoming_rx) = mpsc::channel(8);
+		let (commands_tx, commands_rx) = mpsc::unbounded();
+		// Capacity 1 so the second send fills the queue and the third is
+		// observably dropped.
+		let (endpoint_tx, mut endpoint_rx) = mpsc::channel(1);
+
+		let mut endpoints = Endpoints::new();
+		endpoints.insert("wss://endpoint", Verbosity::DEBUG);
+		let mut targets = HashMap::new();
+		targets.insert("wss://endpoint".to_string(), endpoint_tx);
+
+		let worker = std::thread::spawn(move || {
+			futures::executor::block_on(fan_out_by_verbosity(
+				incoming_rx,
+				endpoints,
+				targets,
+				EndpointStats::default(),
+				commands_rx,
+				EndpointMessageFilters::new(),
+				EndpointGroups::default(),
+				None,
+				EndpointEnvelopes::new(),
+				EndpointVerbosityFields::new(),
+			));
+		});
+
+		commands_tx.unbounded_send(EndpointCommand::SetDebugMirror(true)).unwrap();
+
+		let (_, lines) = with_captured_log(|| {
+			// Fill the endpoint's queue so the next send is dropped, then
+			// give the worker time to process both before reading it back.
+			incoming_tx.try_send((Verbosity::CONSOLE, "queued".to_string())).unwrap();
+			incoming_tx.try_send((Verbosity::CONSOLE, "overflow".to_string())).unwrap();
+			let (_, delivered) = futures::executor::block_on(endpoint_rx.next()).unwrap();
+			assert_eq!(delivered, "queued");
+			// Give the worker a moment to have processed "overflow" (and
+			// mirrored it) before we stop the loop and inspect the log.
+			std::thread::sleep(std::time::Duration::from_millis(50));
+			drop(incoming_tx);
+			drop(commands_tx);
+			worker.join().unwrap();
+		});
+
+		let mirrored: Vec<&str> = lines.iter().filter(|line| line.starts_with("telemetry-out|")).map(String::as_str).collect();
+		assert!(mirrored.iter().any(|line| line.contains("endpoint=wss://endpoint") && line.contains("delivered=true") && line.contains("queued")));
+		assert!(mirrored.iter().any(|line| line.contains("endpoint=wss://endpoint") && line.contains("delivered=false") && line.contains("overflow")));
+	}
+
+	#[test]
+	fn fan_out_by_verbosity_does_not_mirror_when_debug_mirror_is_off() {
+		let (mut incoming_tx, incoming_rx) = mpsc::channel(8);
+		let (commands_tx, commands_rx) = mpsc::unbounded();
+		let (endpoint_tx, mut endpoint_rx) = mpsc::channel(8);
+
+		let mut endpoints = Endpoints::new();
+		endpoints.insert("wss://endpoint", Verbosity::DEBUG);
+		let mut targets = HashMap::new();
+		targets.insert("wss://endpoint".to_string(), endpoint_tx);
+
+		let worker = std::thread::spawn(move || {
+			futures::executor::block_on(fan_out_by_verbosity(
+				incoming_rx,
+				endpoints,
+				targets,
+				EndpointStats::default(),
+				commands_rx,
+				EndpointMessageFilters::new(),
+				EndpointGroups::default(),
+				None,
+				EndpointEnvelopes::new(),
+				EndpointVerbosityFields::new(),
+			));
+		});
+
+		let (_, lines) = with_captured_log(|| {
+			incoming_tx.try_send((Verbosity::CONSOLE, "unmirrored".to_string())).unwrap();
+			let (_, received) = futures::executor::block_on(endpoint_rx.next()).unwrap();
+			assert_eq!(received, "unmirrored");
+			drop(incoming_tx);
+			drop(commands_tx);
+			worker.join().unwrap();
+		});
+
+		assert!(lines.iter().all(|line| !line.starts_with("telemetry-out|")), "mirroring must stay off until explicitly enabled: {lines:?}");
+	}
+
+	#[test]
+	fn message_type_filter_allowlist_only_admits_listed_types() {
+		let filter = MessageTypeFilter::Allow(vec!["block.import".to_string()]);
+		assert!(filter.allows(Some("block.import")));
+		assert!(!filter.allows(Some("system.interval")));
+	}
+
+	#[test]
+	fn message_type_filter_denylist_admits_everything_except_listed_types() {
+		let filter = MessageTypeFilter::Deny(vec!["experimental.custom".to_string()]);
+		assert!(!filter.allows(Some("experimental.custom")));
+		assert!(filter.allows(Some("block.import")));
+	}
+
+	#[test]
+	fn message_type_filter_glob_matches_by_prefix() {
+		let allow = MessageTypeFilter::Allow(vec!["sysinfo.*".to_string()]);
+		assert!(allow.allows(Some("sysinfo.hardware")));
+		assert!(allow.allows(Some("sysinfo.")));
+		assert!(!allow.allows(Some("system.interval")));
+
+		let deny = MessageTypeFilter::Deny(vec!["sysinfo.*".to_string()]);
+		assert!(!deny.allows(Some("sysinfo.hardware")));
+		assert!(deny.allows(Some("block.import")));
+	}
+
+	#[test]
+	fn message_type_filter_always_admits_a_payload_with_no_msg_field() {
+		assert!(MessageTypeFilter::Allow(vec!["block.import".to_string()]).allows(None));
+		assert!(MessageTypeFilter::Deny(vec!["block.import".to_string()]).allows(None));
+	}
+
+	#[test]
+	fn fan_out_by_verbosity_applies_per_endpoint_message_type_filters() {
+		let (mut incoming_tx, incoming_rx) = mpsc::channel(8);
+		let (allowed_tx, mut allowed_rx) = mpsc::channel(8);
+		let (denied_tx, mut denied_rx) = mpsc::channel(8);
+		let (unfiltered_tx, mut unfiltered_rx) = mpsc::channel(8);
+
+		let mut targets = HashMap::new();
+		targets.insert("wss://allowed".to_string(), allowed_tx);
+		targets.insert("wss://denied".to_string(), denied_tx);
+		targets.insert("wss://unfiltered".to_string(), unfiltered_tx);
+
+		let mut filters = EndpointMessageFilters::new();
+		filters.insert("wss://allowed", MessageTypeFilter::Allow(vec!["sysinfo.*".to_string()]));
+		filters.insert("wss://denied", MessageTypeFilter::Deny(vec!["sysinfo.*".to_string()]));
+
+		incoming_tx
+			.try_send((Verbosity::CONSOLE, r#"{"msg":"sysinfo.hardware"}"#.to_string()))
+			.unwrap();
+		incoming_tx
+			.try_send((Verbosity::CONSOLE, r#"{"msg":"block.import"}"#.to_string()))
+			.unwrap();
+		incoming_tx.try_send((Verbosity::CONSOLE, r#"{"no_msg_field":true}"#.to_string())).unwrap();
+		drop(incoming_tx);
+
+		let (_commands_tx, commands_rx) = mpsc::unbounded();
+		futures::executor::block_on(fan_out_by_verbosity(
+			incoming_rx,
+			Endpoints::new(),
+			targets,
+			EndpointStats::default(),
+			commands_rx,
+			filters,
+			EndpointGroups::default(),
+			None,
+			EndpointEnvelopes::new(),
+			EndpointVerbosityFields::new(),
+		));
+
+		let allowed: Vec<_> = std::iter::from_fn(|| allowed_rx.try_next().ok().flatten()).collect();
+		let denied: Vec<_> = std::iter::from_fn(|| denied_rx.try_next().ok().flatten()).collect();
+		let unfiltered: Vec<_> = std::iter::from_fn(|| unfiltered_rx.try_next().ok().flatten()).collect();
+
+		assert_eq!(allowed.len(), 2, "the sysinfo message and the no-msg-field message pass an allowlist");
+		assert!(allowed.iter().any(|(_, json)| json.contains("sysinfo.hardware")));
+		assert!(allowed.iter().any(|(_, json)| json.contains("no_msg_field")));
+
+		assert_eq!(denied.len(), 2, "block.import and the no-msg-field message pass a denylist for sysinfo.*");
+		assert!(denied.iter().any(|(_, json)| json.contains("block.import")));
+		assert!(denied.iter().any(|(_, json)| json.contains("no_msg_field")));
+
+		assert_eq!(unfiltered.len(), 3, "an endpoint with no configured filter gets everything");
+	}
+
+	#[test]
+	fn fan_out_by_verbosity_wraps_only_the_endpoints_configured_with_an_envelope() {
+		let (mut incoming_tx, incoming_rx) = mpsc::channel(8);
+		let (enveloped_tx, mut enveloped_rx) = mpsc::channel(8);
+		let (bare_tx, mut bare_rx) = mpsc::channel(8);
+
+		let mut targets = HashMap::new();
+		targets.insert("wss://aggregator".to_string(), enveloped_tx);
+		targets.insert("wss://plain".to_string(), bare_tx);
+
+		let mut envelopes = EndpointEnvelopes::new();
+		envelopes.insert("wss://aggregator", EnvelopeFormat::default());
+
+		incoming_tx
+			.try_send((
+				Verbosity::CONSOLE,
+				r#"{"msg":"block.import","node":{"name":"collator-a","chain":"kusama"},"ts":1700000000000,"height":42}"#
+					.to_string(),
+			))
+			.unwrap();
+		drop(incoming_tx);
+
+		let (_commands_tx, commands_rx) = mpsc::unbounded();
+		futures::executor::block_on(fan_out_by_verbosity(
+			incoming_rx,
+			Endpoints::new(),
+			targets,
+			EndpointStats::default(),
+			commands_rx,
+			EndpointMessageFilters::new(),
+			EndpointGroups::default(),
+			None,
+			envelopes,
+			EndpointVerbosityFields::new(),
+		));
+
+		let (_, bare) = bare_rx.try_next().unwrap().unwrap();
+		assert!(bare.contains("\"msg\":\"block.import\""), "the unconfigured endpoint gets the bare payload unchanged");
+
+		let (_, enveloped) = enveloped_rx.try_next().unwrap().unwrap();
+		let value: serde_json::Value = serde_json::from_str(&enveloped).unwrap();
+		assert_eq!(value["node"], "collator-a");
+		assert_eq!(value["chain"], "kusama");
+		assert_eq!(value["received"], 1700000000000u64);
+		assert_eq!(value["payload"]["msg"], "block.import");
+		assert_eq!(value["payload"]["height"], 42);
+	}
+
+	#[test]
+	fn envelope_format_keys_are_independently_renameable_to_match_a_downstream_collectors_conventions() {
+		let format = EnvelopeFormat {
+			node_key: "host".to_string(),
+			chain_key: "chain".to_string(),
+			received_key: "timestamp".to_string(),
+			payload_key: "message".to_string(),
+		};
+		let payload = serde_json::json!({ "msg": "system.connected", "node": { "name": "n1", "chain": "polkadot" }, "ts": 5 });
+
+		let wrapped = format.wrap(payload);
+		assert_eq!(wrapped["host"], "n1");
+		assert_eq!(wrapped["chain"], "polkadot");
+		assert_eq!(wrapped["timestamp"], 5);
+		assert_eq!(wrapped["message"]["msg"], "system.connected");
+	}
+
+	#[test]
+	fn envelope_format_defaults_node_and_received_to_null_when_the_payload_never_carried_them() {
+		let wrapped = EnvelopeFormat::default().wrap(serde_json::json!({ "msg": "block.import" }));
+		assert!(wrapped["node"].is_null());
+		assert!(wrapped["chain"].is_null());
+		assert!(wrapped["received"].is_null());
+		assert_eq!(wrapped["payload"]["msg"], "block.import");
+	}
+
+	#[test]
+	fn fan_out_by_verbosity_embeds_the_verbosity_field_only_for_the_endpoint_configured_for_it() {
+		let (mut incoming_tx, incoming_rx) = mpsc::channel(8);
+		let (websocket_tx, mut websocket_rx) = mpsc::channel(8);
+		let (file_tx, mut file_rx) = mpsc::channel(8);
+
+		let mut targets = HashMap::new();
+		targets.insert("wss://collector".to_string(), websocket_tx);
+		targets.insert("file:///var/log/telemetry.ndjson".to_string(), file_tx);
+
+		let mut verbosity_fields = EndpointVerbosityFields::new();
+		verbosity_fields.insert("file:///var/log/telemetry.ndjson", "level");
+
+		incoming_tx.try_send((Verbosity::INFO, r#"{"msg":"block.import","height":42}"#.to_string())).unwrap();
+		drop(incoming_tx);
+
+		let (_commands_tx, commands_rx) = mpsc::unbounded();
+		futures::executor::block_on(fan_out_by_verbosity(
+			incoming_rx,
+			Endpoints::new(),
+			targets,
+			EndpointStats::default(),
+			commands_rx,
+			EndpointMessageFilters::new(),
+			EndpointGroups::default(),
+			None,
+			EndpointEnvelopes::new(),
+			verbosity_fields,
+		));
+
+		let (_, websocket) = websocket_rx.try_next().unwrap().unwrap();
+		let websocket: serde_json::Value = serde_json::from_str(&websocket).unwrap();
+		assert!(websocket.get("level").is_none(), "an endpoint not named in `EndpointVerbosityFields` keeps the wire format unchanged");
+
+		let (_, file) = file_rx.try_next().unwrap().unwrap();
+		let file: serde_json::Value = serde_json::from_str(&file).unwrap();
+		assert_eq!(file["level"], Verbosity::INFO.as_u8());
+
+		// Same message otherwise, save for the field this endpoint opted into.
+		assert_eq!(websocket["msg"], file["msg"]);
+		assert_eq!(websocket["height"], file["height"]);
+	}
+
+	#[test]
+	fn embed_verbosity_field_does_not_clobber_an_existing_field_of_the_same_name() {
+		let json = embed_verbosity_field(r#"{"msg":"block.import","level":"already-here"}"#, "level", Verbosity::INFO);
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert_eq!(value["level"], "already-here");
+	}
+
+	#[test]
+	fn fan_out_by_verbosity_routes_finality_messages_to_the_internal_collector_only() {
+		let (mut incoming_tx, incoming_rx) = mpsc::channel(8);
+		let (public_tx, mut public_rx) = mpsc::channel(8);
+		let (internal_tx, mut internal_rx) = mpsc::channel(8);
+
+		// `targets` sorted lexicographically: "wss://internal" (index 0),
+		// "wss://public" (index 1).
+		let mut targets = HashMap::new();
+		targets.insert("wss://internal".to_string(), internal_tx);
+		targets.insert("wss://public".to_string(), public_tx);
+
+		let router = Arc::new(MessageRouter::new(|info: &RoutingInfo<'_>| {
+			if info.msg_type == Some("finality.notification") {
+				EndpointSelection::Subset(vec![0])
+			} else {
+				EndpointSelection::All
+			}
+		}));
+
+		incoming_tx
+			.try_send((Verbosity::INFO, r#"{"msg":"finality.notification"}"#.to_string()))
+			.unwrap();
+		incoming_tx.try_send((Verbosity::INFO, r#"{"msg":"block.import"}"#.to_string())).unwrap();
+		drop(incoming_tx);
+
+		let (_commands_tx, commands_rx) = mpsc::unbounded();
+		futures::executor::block_on(fan_out_by_verbosity(
+			incoming_rx,
+			Endpoints::new(),
+			targets,
+			EndpointStats::default(),
+			commands_rx,
+			EndpointMessageFilters::new(),
+			EndpointGroups::default(),
+			Some(router),
+			EndpointEnvelopes::new(),
+			EndpointVerbosityFields::new(),
+		));
+
+		let public: Vec<_> = std::iter::from_fn(|| public_rx.try_next().ok().flatten()).collect();
+		let internal: Vec<_> = std::iter::from_fn(|| internal_rx.try_next().ok().flatten()).collect();
+
+		assert_eq!(public.len(), 1, "the public endpoint only gets the non-finality message");
+		assert!(public.iter().any(|(_, json)| json.contains("block.import")));
+
+		assert_eq!(internal.len(), 2, "the internal collector gets everything routed to it plus the rest");
+		assert!(internal.iter().any(|(_, json)| json.contains("finality.notification")));
+		assert!(internal.iter().any(|(_, json)| json.contains("block.import")));
+	}
+
+	#[test]
+	fn fan_out_by_verbosity_falls_back_to_static_filters_when_the_router_panics() {
+		let (mut incoming_tx, incoming_rx) = mpsc::channel(8);
+		let (endpoint_tx, mut endpoint_rx) = mpsc::channel(8);
+
+		let mut targets = HashMap::new();
+		targets.insert("wss://endpoint".to_string(), endpoint_tx);
+
+		let router = Arc::new(MessageRouter::new(|_: &RoutingInfo<'_>| panic!("bug in operator routing logic")));
+
+		incoming_tx.try_send((Verbosity::INFO, r#"{"msg":"block.import"}"#.to_string())).unwrap();
+		drop(incoming_tx);
+
+		let (_commands_tx, commands_rx) = mpsc::unbounded();
+		futures::executor::block_on(fan_out_by_verbosity(
+			incoming_rx,
+			Endpoints::new(),
+			targets,
+			EndpointStats::default(),
+			commands_rx,
+			EndpointMessageFilters::new(),
+			EndpointGroups::default(),
+			Some(router),
+			EndpointEnvelopes::new(),
+			EndpointVerbosityFields::new(),
+		));
+
+		let delivered: Vec<_> = std::iter::from_fn(|| endpoint_rx.try_next().ok().flatten()).collect();
+		assert_eq!(delivered.len(), 1, "a panicking router falls back to the static filters, not to dropping everything");
+	}
+
+	#[test]
+	fn message_sampling_every_nth_admits_deterministically() {
+		let sampling = MessageSampling::new();
+		sampling.configure("block.import", SamplingRule::EveryNth(3));
+
+		let admitted: Vec<bool> = (0..6).map(|_| sampling.should_send(Some("block.import"))).collect();
+		assert_eq!(admitted, vec![true, false, false, true, false, false]);
+		assert_eq!(sampling.skipped("block.import"), 4);
+	}
+
+	#[test]
+	fn message_sampling_max_per_second_admits_only_a_burst_of_one() {
+		let sampling = MessageSampling::new();
+		sampling.configure("sysinfo.hardware", SamplingRule::MaxPerSecond(1000.0));
+
+		assert!(sampling.should_send(Some("sysinfo.hardware")));
+		assert!(!sampling.should_send(Some("sysinfo.hardware")), "burst of 1 exhausted immediately");
+		assert_eq!(sampling.skipped("sysinfo.hardware"), 1);
+	}
+
+	#[test]
+	fn message_sampling_never_applies_to_unconfigured_types_or_a_missing_msg_field() {
+		let sampling = MessageSampling::new();
+		sampling.configure("block.import", SamplingRule::EveryNth(1000));
+
+		assert!(sampling.should_send(Some("system.interval")), "no rule configured for this type");
+		assert!(sampling.should_send(None), "a payload with no msg field is never sampled");
+	}
+
+	#[test]
+	fn message_sampling_never_drops_system_connected_or_error_class_messages() {
+		let sampling = MessageSampling::new();
+		sampling.configure("system.connected", SamplingRule::EveryNth(1000));
+		sampling.configure("error.panic", SamplingRule::EveryNth(1000));
+
+		for _ in 0..5 {
+			assert!(sampling.should_send(Some("system.connected")));
+			assert!(sampling.should_send(Some("error.panic")));
+		}
+	}
+
+	#[test]
+	fn message_dedup_suppresses_only_exact_consecutive_repeats() {
+		let dedup = MessageDedup::new();
+		dedup.configure(0, "sync.state", std::time::Duration::from_secs(3600));
+		let now = std::time::Instant::now();
+
+		let first = serde_json::json!({ "msg": "sync.state", "best": 10 }).as_object().unwrap().clone();
+		let repeat = first.clone();
+		let changed = serde_json::json!({ "msg": "sync.state", "best": 11 }).as_object().unwrap().clone();
+
+		assert!(dedup.should_send(0, Some("sync.state"), &first, now), "first message is never a duplicate");
+		assert!(!dedup.should_send(0, Some("sync.state"), &repeat, now), "identical payload is suppressed");
+		assert!(dedup.should_send(0, Some("sync.state"), &changed, now), "a changed payload resets dedup");
+		assert!(
+			!dedup.should_send(0, Some("sync.state"), &changed, now),
+			"the now-current payload is itself deduped once repeated"
+		);
+		assert_eq!(dedup.suppressed(0, "sync.state"), 2);
+	}
+
+	#[test]
+	fn message_dedup_ignores_ts_and_seq_when_comparing_payloads() {
+		let dedup = MessageDedup::new();
+		dedup.configure(0, "sync.state", std::time::Duration::from_secs(3600));
+		let now = std::time::Instant::now();
+
+		let first = serde_json::json!({ "msg": "sync.state", "best": 10, "ts": 1, "seq": 1 })
+			.as_object()
+			.unwrap()
+			.clone();
+		let same_content_new_stamps = serde_json::json!({ "msg": "sync.state", "best": 10, "ts": 2, "seq": 2 })
+			.as_object()
+			.unwrap()
+			.clone();
+
+		assert!(dedup.should_send(0, Some("sync.state"), &first, now));
+		assert!(
+			!dedup.should_send(0, Some("sync.state"), &same_content_new_stamps, now),
+			"a fresh ts/seq alone does not make the payload distinct"
+		);
+	}
+
+	#[test]
+	fn message_dedup_forwards_an_unchanged_payload_as_a_heartbeat_once_the_window_elapses() {
+		let dedup = MessageDedup::new();
+		dedup.configure(0, "sync.state", std::time::Duration::from_millis(10));
+		let start = std::time::Instant::now();
+
+		let payload = serde_json::json!({ "msg": "sync.state", "best": 10 }).as_object().unwrap().clone();
+
+		assert!(dedup.should_send(0, Some("sync.state"), &payload, start));
+		assert!(!dedup.should_send(0, Some("sync.state"), &payload, start), "still within the heartbeat window");
+		let after_window = start + std::time::Duration::from_millis(11);
+		assert!(
+			dedup.should_send(0, Some("sync.state"), &payload, after_window),
+			"an unchanged payload is still forwarded once max_suppressed elapses"
+		);
+	}
+
+	#[test]
+	fn message_dedup_never_applies_to_unconfigured_types_or_a_missing_msg_field() {
+		let dedup = MessageDedup::new();
+		dedup.configure(0, "sync.state", std::time::Duration::from_secs(3600));
+		let now = std::time::Instant::now();
+		let payload = serde_json::json!({ "best": 10 }).as_object().unwrap().clone();
+
+		assert!(dedup.should_send(0, Some("other.metric"), &payload, now), "no rule configured for this type");
+		assert!(dedup.should_send(0, None, &payload, now), "a payload with no msg field is never deduped");
+	}
+
+	#[test]
+	fn message_dedup_isolates_two_instances_configured_on_the_same_msg_type() {
+		// Same shape as `two_instances_sharing_one_registry_do_not_cross_talk`,
+		// but for `MessageDedup` directly: two instance keys standing in for two
+		// `Telemetries` sharing the one process-wide `MessageDedup`, proving
+		// isolation comes from the instance key rather than from `msg_type` alone.
+		let dedup = MessageDedup::new();
+		dedup.configure(0, "sync.state", std::time::Duration::from_secs(3600));
+		dedup.configure(1, "sync.state", std::time::Duration::from_secs(3600));
+		let now = std::time::Instant::now();
+
+		let payload = serde_json::json!({ "msg": "sync.state", "best": 10 }).as_object().unwrap().clone();
+
+		assert!(dedup.should_send(0, Some("sync.state"), &payload, now), "instance 0's first message is never a duplicate");
+		assert!(
+			dedup.should_send(1, Some("sync.state"), &payload, now),
+			"instance 1 sending the same content instance 0 just sent must not be suppressed as instance 0's duplicate"
+		);
+		assert!(
+			!dedup.should_send(0, Some("sync.state"), &payload, now),
+			"instance 0 repeating its own message is still suppressed"
+		);
+		assert!(
+			!dedup.should_send(1, Some("sync.state"), &payload, now),
+			"instance 1 repeating its own message is still suppressed"
+		);
+		assert_eq!(dedup.suppressed(0, "sync.state"), 1);
+		assert_eq!(dedup.suppressed(1, "sync.state"), 1);
+
+		// Re-configuring instance 0 must not disturb instance 1's window.
+		dedup.configure(0, "sync.state", std::time::Duration::from_secs(3600));
+		assert!(
+			!dedup.should_send(1, Some("sync.state"), &payload, now),
+			"instance 1's dedup state survives instance 0 being reconfigured"
+		);
+	}
+
+	#[test]
+	fn telemetries_send_drops_a_sampled_out_message_before_it_reaches_the_sender() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let (sender, mut receiver) = mpsc::channel(8);
+		telemetries.senders.insert(1, sender);
+
+		telemetries.configure_sampling("noisy.metric", SamplingRule::EveryNth(2));
+
+		assert!(telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "noisy.metric", "n": 1 })));
+		assert!(
+			!telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "noisy.metric", "n": 2 })),
+			"the second message of this type should be sampled out"
+		);
+		assert!(telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "noisy.metric", "n": 3 })));
+
+		let received: Vec<_> = std::iter::from_fn(|| receiver.try_next().ok().flatten()).collect();
+		assert_eq!(received.len(), 2);
+		assert!(received[0].1.contains("\"n\":1"));
+		assert!(received[1].1.contains("\"n\":3"));
+		assert_eq!(telemetries.sampling_skipped("noisy.metric"), 1);
+	}
+
+	#[test]
+	fn telemetries_send_drops_a_deduplicated_message_before_it_reaches_the_sender() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let (sender, mut receiver) = mpsc::channel(8);
+		telemetries.senders.insert(1, sender);
+
+		telemetries.configure_dedup("dedup_test.telemetries_send_drops", std::time::Duration::from_secs(3600));
+
+		assert!(telemetries.send(
+			1,
+			Verbosity::INFO,
+			serde_json::json!({ "msg": "dedup_test.telemetries_send_drops", "n": 1 })
+		));
+		assert!(
+			!telemetries.send(
+				1,
+				Verbosity::INFO,
+				serde_json::json!({ "msg": "dedup_test.telemetries_send_drops", "n": 1 })
+			),
+			"an identical repeat should be suppressed as a duplicate"
+		);
+		assert!(telemetries.send(
+			1,
+			Verbosity::INFO,
+			serde_json::json!({ "msg": "dedup_test.telemetries_send_drops", "n": 2 })
+		));
+
+		let received: Vec<_> = std::iter::from_fn(|| receiver.try_next().ok().flatten()).collect();
+		assert_eq!(received.len(), 2);
+		assert!(received[0].1.contains("\"n\":1"));
+		assert!(received[1].1.contains("\"n\":2"));
+		assert_eq!(telemetries.dedup_suppressed("dedup_test.telemetries_send_drops"), 1);
+	}
+
+	#[test]
+	fn dedup_on_one_telemetries_instance_does_not_suppress_or_reset_another_instances_window() {
+		// Same shape as `two_instances_sharing_one_registry_do_not_cross_talk`:
+		// two independent `TelemetryLayer`s, as a relay-chain and parachain
+		// telemetry might be in one process, both emitting the same `msg_type`.
+		// `MessageDedup` is a single process-wide static under the hood (see its
+		// doc comment), so this is what actually proves that sharing it doesn't
+		// let one instance suppress or reset the other's dedup window.
+		let layer_a = TelemetryLayer::default().with_instance_id(0);
+		let layer_b = TelemetryLayer::default().with_instance_id(1);
+		let telemetries_a = layer_a.telemetries();
+		let telemetries_b = layer_b.telemetries();
+		let (sender_a, mut receiver_a) = mpsc::channel(8);
+		let (sender_b, mut receiver_b) = mpsc::channel(8);
+		telemetries_a.senders.insert(1, sender_a);
+		telemetries_b.senders.insert(1, sender_b);
+
+		telemetries_a.configure_dedup("sync.state", std::time::Duration::from_secs(3600));
+		telemetries_b.configure_dedup("sync.state", std::time::Duration::from_secs(3600));
+
+		assert!(
+			telemetries_a.send(1, Verbosity::INFO, serde_json::json!({ "msg": "sync.state", "best": 10 })),
+			"instance a's first message is never a duplicate"
+		);
+		assert!(
+			telemetries_b.send(1, Verbosity::INFO, serde_json::json!({ "msg": "sync.state", "best": 10 })),
+			"instance b sending the same content instance a just sent must not be dropped as a's duplicate"
+		);
+		assert!(
+			!telemetries_a.send(1, Verbosity::INFO, serde_json::json!({ "msg": "sync.state", "best": 10 })),
+			"instance a repeating its own message is still suppressed"
+		);
+		assert!(
+			!telemetries_b.send(1, Verbosity::INFO, serde_json::json!({ "msg": "sync.state", "best": 10 })),
+			"instance b repeating its own message is still suppressed"
+		);
+
+		let received_a: Vec<_> = std::iter::from_fn(|| receiver_a.try_next().ok().flatten()).collect();
+		let received_b: Vec<_> = std::iter::from_fn(|| receiver_b.try_next().ok().flatten()).collect();
+		assert_eq!(received_a.len(), 1, "b's send must not have reached a's sender");
+		assert_eq!(received_b.len(), 1, "a's send must not have reached b's sender");
+		assert_eq!(telemetries_a.dedup_suppressed("sync.state"), 1);
+		assert_eq!(telemetries_b.dedup_suppressed("sync.state"), 1);
+
+		// Reconfiguring instance a's window must not reset instance b's.
+		telemetries_a.configure_dedup("sync.state", std::time::Duration::from_secs(3600));
+		assert!(
+			!telemetries_b.send(1, Verbosity::INFO, serde_json::json!({ "msg": "sync.state", "best": 10 })),
+			"instance b's dedup window survives instance a being reconfigured"
+		);
+	}
+
+	#[test]
+	fn message_type_stats_tracks_sent_filtered_sampled_out_and_dropped_per_msg_type() {
+		let telemetries = Telemetries::default();
+		let (sender, mut receiver) = mpsc::channel(0);
+		telemetries.senders.insert(1, sender);
+		telemetries.configure_sampling("noisy.metric", SamplingRule::EveryNth(2));
+
+		assert!(telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "block.import" })));
+		receiver.try_next().unwrap(); // drain so the single slot is free for the next send
+		assert!(telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "noisy.metric" })));
+		assert!(
+			!telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "noisy.metric" })),
+			"sampled out"
+		);
+		receiver.try_next().unwrap();
+		telemetries.senders.pause(1);
+		assert!(
+			!telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "block.import" })),
+			"filtered while paused"
+		);
+		telemetries.senders.resume(1);
+		// Fill the one-slot channel so the next `block.import` is dropped.
+		assert!(telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "system.connected" })));
+		assert!(
+			!telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "block.import" })),
+			"channel is full"
+		);
+
+		let stats = telemetries.message_type_stats(1);
+		assert_eq!(
+			stats["block.import"],
+			MessageTypeCounts { sent: 1, filtered: 1, sampled_out: 0, suppressed: 0, dropped: 1, dropped_queue_full: 1, dropped_disconnected: 0, oversized: 0 }
+		);
+		assert_eq!(
+			stats["noisy.metric"],
+			MessageTypeCounts { sent: 1, filtered: 0, sampled_out: 1, suppressed: 0, dropped: 0, dropped_queue_full: 0, dropped_disconnected: 0, oversized: 0 }
+		);
+		assert_eq!(
+			stats["system.connected"],
+			MessageTypeCounts { sent: 1, filtered: 0, sampled_out: 0, suppressed: 0, dropped: 0, dropped_queue_full: 0, dropped_disconnected: 0, oversized: 0 }
+		);
+
+		receiver.close();
+	}
+
+	#[test]
+	fn message_type_stats_folds_types_beyond_the_tracked_limit_into_other() {
+		let telemetries = Telemetries::default();
+		let (sender, mut receiver) = mpsc::channel(MAX_TRACKED_MESSAGE_TYPES + 8);
+		telemetries.senders.insert(1, sender);
+
+		for i in 0..MAX_TRACKED_MESSAGE_TYPES + 5 {
+			telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": format!("type.{i}") }));
+		}
+
+		let stats = telemetries.message_type_stats(1);
+		assert_eq!(stats.len(), MAX_TRACKED_MESSAGE_TYPES + 1, "tracked types plus the overflow bucket");
+		assert_eq!(stats["other"].sent, 5, "the 5 types past the limit are folded together");
+
+		let received: Vec<_> = std::iter::from_fn(|| receiver.try_next().ok().flatten()).collect();
+		assert_eq!(received.len(), MAX_TRACKED_MESSAGE_TYPES + 5, "every message is still delivered regardless of tracking");
+	}
+
+	#[test]
+	fn status_report_includes_per_msg_type_stats() {
+		let telemetries = Telemetries::default();
+		let (sender, mut _receiver) = mpsc::channel(4);
+		telemetries.senders.insert(1, sender);
+
+		telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "block.import" }));
+		telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "block.import" }));
+
+		let status = telemetries.status(1, &[]);
+		assert_eq!(status.message_types["block.import"].sent, 2);
+	}
+
+	#[test]
+	fn subscribers_at_different_speeds_receive_the_stream_independently_with_lag_accounted() {
+		let telemetries = Telemetries::default();
+		let (sender, mut receiver) = mpsc::channel(16);
+		telemetries.senders.insert(1, sender);
+
+		let mut fast = telemetries.subscribe(1, 16).unwrap();
+		let mut slow = telemetries.subscribe(1, 0).unwrap();
+
+		for i in 0..3 {
+			assert!(telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "block.import", "n": i })));
+		}
+
+		// The fast subscriber's buffer comfortably holds all three.
+		for i in 0..3 {
+			let (verbosity, value) = futures::executor::block_on(fast.next()).unwrap();
+			assert_eq!(verbosity, Verbosity::INFO.as_u8());
+			assert_eq!(value["n"], i);
+		}
+		assert_eq!(fast.lagged(), 0, "the fast subscriber never fell behind");
+
+		// The slow subscriber's single-slot channel only holds the first
+		// message; the other two were dropped for it alone and counted as lag.
+		let (_, first) = futures::executor::block_on(slow.next()).unwrap();
+		assert_eq!(first["n"], 0);
+		assert_eq!(slow.lagged(), 2);
+
+		// Endpoint delivery through the real registered sender is unaffected.
+		for _ in 0..3 {
+			receiver.try_next().unwrap().unwrap();
+		}
+	}
+
+	#[test]
+	fn dropping_a_tap_unsubscribes_it_without_affecting_others() {
+		let telemetries = Telemetries::default();
+		let (sender, mut _receiver) = mpsc::channel(16);
+		telemetries.senders.insert(1, sender);
+
+		let dropped = telemetries.subscribe(1, 4).unwrap();
+		let mut kept = telemetries.subscribe(1, 4).unwrap();
+		drop(dropped);
+
+		// Would panic/hang if publishing tried to wait on the dropped tap.
+		assert!(telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "block.import" })));
+
+		let (_, value) = futures::executor::block_on(kept.next()).unwrap();
+		assert_eq!(value["msg"], "block.import");
+	}
+
+	#[test]
+	fn subscribe_returns_none_for_an_unregistered_id() {
+		let telemetries = Telemetries::default();
+		assert!(telemetries.subscribe(1, 4).is_none());
+	}
+
+	#[test]
+	fn redaction_replaces_top_level_and_nested_fields_with_a_placeholder() {
+		let redaction = Redaction::new().redact_path("/network_id").redact_path("/peer/ip");
+		let layer = TelemetryLayer::default().with_redaction(redaction);
+		let telemetries = layer.telemetries();
+		let (sender, mut receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+
+		telemetries.send(
+			1,
+			Verbosity::INFO,
+			serde_json::json!({ "msg": "system.connected", "network_id": "0xdeadbeef", "peer": { "ip": "10.0.0.1", "port": 30333 } }),
+		);
+
+		let (_verbosity, json) = receiver.try_next().unwrap().unwrap();
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert_eq!(value["network_id"], "<redacted>");
+		assert_eq!(value["peer"]["ip"], "<redacted>");
+		assert_eq!(value["peer"]["port"], 30333);
+	}
+
+	#[test]
+	fn redaction_is_a_no_op_when_the_path_is_absent_from_the_payload() {
+		let redaction = Redaction::new().redact_path("/network_id");
+		let layer = TelemetryLayer::default().with_redaction(redaction);
+		let telemetries = layer.telemetries();
+		let (sender, mut receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+
+		telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "system.connected" }));
+
+		let (_verbosity, json) = receiver.try_next().unwrap().unwrap();
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert!(value.get("network_id").is_none());
+	}
+
+	#[test]
+	fn redaction_hook_can_mutate_the_payload_before_it_is_sent() {
+		let redaction = Redaction::new().with_hook(|value| {
+			if let Some(secret) = value.get_mut("secret") {
+				*secret = serde_json::json!(secret.as_str().map(|s| s.len()));
+			}
+		});
+		let layer = TelemetryLayer::default().with_redaction(redaction);
+		let telemetries = layer.telemetries();
+		let (sender, mut receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+
+		telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "system.connected", "secret": "sssh!!" }));
+
+		let (_verbosity, json) = receiver.try_next().unwrap().unwrap();
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert_eq!(value["secret"], 6);
+	}
+
+	#[test]
+	fn a_panicking_redaction_hook_is_caught_and_does_not_prevent_the_message_from_being_sent() {
+		let redaction = Redaction::new().with_hook(|_value| panic!("boom"));
+		let layer = TelemetryLayer::default().with_redaction(redaction);
+		let telemetries = layer.telemetries();
+		let (sender, mut receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+
+		assert!(telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "system.connected" })));
+
+		let (_verbosity, json) = receiver.try_next().unwrap().unwrap();
+		assert!(json.contains("\"msg\":\"system.connected\""));
+	}
+
+	#[test]
+	fn an_unconfigured_redaction_leaves_the_payload_untouched() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let (sender, mut receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+
+		telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "system.connected", "network_id": "0xdeadbeef" }));
+
+		let (_verbosity, json) = receiver.try_next().unwrap().unwrap();
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert_eq!(value["network_id"], "0xdeadbeef");
+	}
+
+	#[test]
+	fn payloads_within_the_configured_size_limit_pass_through_unchanged() {
+		let layer = TelemetryLayer::default().with_max_message_size(256);
+		let telemetries = layer.telemetries();
+		let (sender, mut receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+
+		assert!(telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "small", "n": 1 })));
+
+		let (_verbosity, json) = receiver.try_next().unwrap().unwrap();
+		assert!(json.contains("\"msg\":\"small\""));
+		assert_eq!(telemetries.truncated_messages(), 0);
+	}
+
+	#[test]
+	fn a_payload_just_under_the_size_limit_passes_through_unchanged() {
+		let limit = MessageSizeLimit::new(16);
+		let json = "x".repeat(15);
+		assert_eq!(limit.enforce(Some("sized"), json.clone()), (json, false));
+		assert_eq!(limit.truncated(), 0);
+	}
+
+	#[test]
+	fn a_payload_exactly_at_the_size_limit_passes_through_unchanged() {
+		let limit = MessageSizeLimit::new(16);
+		let json = "x".repeat(16);
+		assert_eq!(limit.enforce(Some("sized"), json.clone()), (json, false));
+		assert_eq!(limit.truncated(), 0);
+	}
+
+	#[test]
+	fn a_payload_over_the_size_limit_is_replaced_by_a_stub_and_counted() {
+		let layer = TelemetryLayer::default().with_max_message_size(64);
+		let telemetries = layer.telemetries();
+		let (sender, mut receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+
+		assert!(telemetries.send(
+			1,
+			Verbosity::INFO,
+			serde_json::json!({ "msg": "oversized.metric", "data": "x".repeat(1024) }),
+		));
+
+		let (_verbosity, json) = receiver.try_next().unwrap().unwrap();
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert_eq!(value["msg"], "system.message_truncated");
+		assert_eq!(value["original_msg"], "oversized.metric");
+		assert!(value["size"].as_u64().unwrap() > 64);
+		assert_eq!(telemetries.truncated_messages(), 1);
+		let stats = telemetries.message_type_stats(1);
+		assert_eq!(stats["oversized.metric"].oversized, 1);
+		assert_eq!(stats["oversized.metric"].dropped, 0, "a truncated message is still delivered, not dropped");
+	}
+
+	#[test]
+	fn seq_increases_strictly_across_sends_and_survives_a_simulated_reconnect() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let (sender, mut receiver) = mpsc::channel(8);
+		telemetries.senders.insert(1, sender);
+
+		telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "a" }));
+		telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "b" }));
+
+		// A reconnect doesn't touch the `SenderEntry` for `id` (only the
+		// worker's underlying connection), so `seq` keeps counting up rather
+		// than restarting.
+		telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "c" }));
+
+		let seqs: Vec<u64> = std::iter::from_fn(|| receiver.try_next().ok().flatten())
+			.map(|(_verbosity, json)| {
+				serde_json::from_str::<serde_json::Value>(&json).unwrap()["seq"].as_u64().unwrap()
+			})
+			.collect();
+		assert_eq!(seqs, vec![0, 1, 2]);
+	}
+
+	#[test]
+	fn dropped_count_reports_since_the_last_send_and_resets() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let (sender, mut receiver) = mpsc::channel(0);
+		telemetries.senders.insert(1, sender);
+
+		telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "fills the channel" }));
+		telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "dropped: 1" }));
+		telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "dropped: 2" }));
+
+		let (_verbosity, json) = receiver.try_next().unwrap().unwrap();
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert_eq!(value["seq"], 0);
+		assert_eq!(value["dropped"], 0, "nothing dropped before the first send");
+
+		// Draining the slot the first message occupied frees it up again (the
+		// channel has capacity `0`, i.e. exactly one in-flight message): the
+		// next send should succeed and report the two drops in between.
+		telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "third send" }));
+		let (_verbosity, json) = receiver.try_next().unwrap().unwrap();
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert_eq!(value["dropped"], 2, "the two prior drops should be reported exactly once");
+
+		telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "nothing new dropped" }));
+		let (_verbosity, json) = receiver.try_next().unwrap().unwrap();
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert_eq!(value["dropped"], 0, "the drop count should have reset after being reported");
+	}
+
+	#[test]
+	fn endpoint_encodings_default_to_json_and_can_be_overridden_per_endpoint() {
+		let mut encodings = EndpointEncodings::new();
+		encodings.insert("wss://cbor.example", Encoding::Cbor);
+
+		assert_eq!(encodings.get("wss://cbor.example"), Encoding::Cbor);
+		assert_eq!(encodings.get("wss://json.example"), Encoding::Json);
+	}
+
+	#[test]
+	fn json_encoding_is_a_byte_copy_of_the_serialized_payload() {
+		let json = r#"{"msg":"system.connected","n":1}"#;
+		assert_eq!(encode_message(json, Encoding::Json), json.as_bytes());
+	}
+
+	// Expected bytes below are the canonical encodings from RFC 8949 appendix
+	// A, cross-checked against this implementation rather than a decoder,
+	// since the crate has no CBOR dependency to decode with.
+	#[test]
+	fn cbor_encoding_matches_the_canonical_form_for_integers_and_simple_values() {
+		assert_eq!(encode_message("0", Encoding::Cbor), vec![0x00]);
+		assert_eq!(encode_message("1", Encoding::Cbor), vec![0x01]);
+		assert_eq!(encode_message("23", Encoding::Cbor), vec![0x17]);
+		assert_eq!(encode_message("24", Encoding::Cbor), vec![0x18, 0x18]);
+		assert_eq!(encode_message("-1", Encoding::Cbor), vec![0x20]);
+		assert_eq!(encode_message("null", Encoding::Cbor), vec![0xf6]);
+		assert_eq!(encode_message("true", Encoding::Cbor), vec![0xf5]);
+		assert_eq!(encode_message("false", Encoding::Cbor), vec![0xf4]);
+	}
+
+	#[test]
+	fn cbor_encoding_matches_the_canonical_form_for_strings_arrays_and_maps() {
+		assert_eq!(encode_message(r#""a""#, Encoding::Cbor), vec![0x61, 0x61]);
+		assert_eq!(encode_message("[1,2,3]", Encoding::Cbor), vec![0x83, 0x01, 0x02, 0x03]);
+		assert_eq!(encode_message(r#"{"a":1}"#, Encoding::Cbor), vec![0xa1, 0x61, 0x61, 0x01]);
+	}
+
+	#[test]
+	fn cbor_encoding_round_trips_a_telemetry_shaped_payload_through_an_in_memory_sink() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let (sender, mut receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+
+		let mut encodings = EndpointEncodings::new();
+		encodings.insert("wss://cbor.example", Encoding::Cbor);
+
+		telemetries.send(1, Verbosity::INFO, serde_json::json!({ "msg": "system.connected", "best": 42 }));
+		let (_verbosity, json) = receiver.try_next().unwrap().unwrap();
+
+		// "Sending" here is standing in for a websocket write: the in-memory
+		// sink is just the byte buffer a real binary frame would carry.
+		let json_frame = encode_message(&json, encodings.get("wss://json.example"));
+		let cbor_frame = encode_message(&json, encodings.get("wss://cbor.example"));
+
+		assert_eq!(json_frame, json.as_bytes());
+		assert_ne!(cbor_frame, json_frame, "CBOR framing should differ from the JSON text frame");
+		assert!(
+			cbor_frame.windows(b"system.connected".len()).any(|w| w == b"system.connected"),
+			"the string value should appear verbatim in the CBOR frame, just without JSON's quoting"
+		);
+	}
+
+	#[test]
+	fn json_serializer_produces_text_matching_plain_serde_json() {
+		let message = TelemetryMessage {
+			id: 1,
+			verbosity: Verbosity::INFO,
+			payload: serde_json::json!({ "msg": "system.connected", "n": 1 }),
+		};
+		let payload = JsonSerializer.serialize(&message).unwrap();
+		assert_eq!(payload, MessagePayload::Text(serde_json::to_string(&message.payload).unwrap()));
+	}
+
+	/// A [`TelemetrySerializer`] that deliberately refuses to encode any
+	/// message of a given `msg` type, for exercising a worker's error path
+	/// without needing a real broken format.
+	struct FailOnMsgType(&'static str);
+
+	impl TelemetrySerializer for FailOnMsgType {
+		fn serialize(&self, message: &TelemetryMessage) -> Result<MessagePayload, SerializeError> {
+			let msg_type = message.payload.get("msg").and_then(|v| v.as_str());
+			if msg_type == Some(self.0) {
+				return Err(SerializeError {
+					msg_type: msg_type.map(str::to_string),
+					reason: format!("{} deliberately rejected by FailOnMsgType", self.0),
+				});
+			}
+			JsonSerializer.serialize(message)
+		}
+	}
+
+	#[test]
+	fn endpoint_serializers_falls_back_to_json_when_unconfigured() {
+		let serializers = EndpointSerializers::new();
+		let stats = SerializerStats::default();
+		let message = TelemetryMessage {
+			id: 1,
+			verbosity: Verbosity::INFO,
+			payload: serde_json::json!({ "msg": "system.connected" }),
+		};
+
+		let payload = serializers.serialize_for("wss://plain.example", &message, &stats).unwrap();
+		assert_eq!(payload, MessagePayload::Text(serde_json::to_string(&message.payload).unwrap()));
+		assert_eq!(stats.errors("wss://plain.example"), 0);
+	}
+
+	#[test]
+	fn a_failing_serializer_is_recorded_in_stats_without_affecting_other_endpoints() {
+		let mut serializers = EndpointSerializers::new();
+		serializers.insert("wss://strict.example", Arc::new(FailOnMsgType("noisy.metric")));
+		let stats = SerializerStats::default();
+
+		let noisy = TelemetryMessage {
+			id: 1,
+			verbosity: Verbosity::INFO,
+			payload: serde_json::json!({ "msg": "noisy.metric" }),
+		};
+
+		// The endpoint with the strict serializer drops the message and is
+		// counted for it...
+		assert!(serializers.serialize_for("wss://strict.example", &noisy, &stats).is_err());
+		assert_eq!(stats.errors("wss://strict.example"), 1);
+
+		// ...but an endpoint with no (or a different) serializer configured
+		// still gets it, and its own error count is untouched.
+		let payload = serializers.serialize_for("wss://plain.example", &noisy, &stats).unwrap();
+		assert_eq!(payload, MessagePayload::Text(serde_json::to_string(&noisy.payload).unwrap()));
+		assert_eq!(stats.errors("wss://plain.example"), 0);
+
+		// A message of a type the strict serializer doesn't reject still
+		// goes through on that same endpoint.
+		let benign = TelemetryMessage {
+			id: 1,
+			verbosity: Verbosity::INFO,
+			payload: serde_json::json!({ "msg": "system.connected" }),
+		};
+		assert!(serializers.serialize_for("wss://strict.example", &benign, &stats).is_ok());
+		assert_eq!(stats.errors("wss://strict.example"), 1, "still just the one earlier failure");
+	}
+
+	#[test]
+	fn a_failing_serializer_is_reported_as_a_transport_error() {
+		let mut serializers = EndpointSerializers::new();
+		serializers.insert("wss://strict.example", Arc::new(FailOnMsgType("noisy.metric")));
+		let stats = SerializerStats::default();
+
+		let noisy = TelemetryMessage {
+			id: 1,
+			verbosity: Verbosity::INFO,
+			payload: serde_json::json!({ "msg": "noisy.metric" }),
+		};
+
+		match serializers.serialize_for("wss://strict.example", &noisy, &stats) {
+			Err(TelemetryError::Transport(reason)) => {
+				assert!(reason.contains("FailOnMsgType"));
+			}
+			other => panic!("expected TelemetryError::Transport, got {other:?}"),
+		}
+	}
+
+	// A criterion benchmark comparing enabled-vs-disabled cost would normally
+	// accompany this change, but there's no build manifest or benches/
+	// harness anywhere in this crate to add one to; the tests below cover
+	// correctness (no behavior change when enabled, events skipped before
+	// span lookup when disabled) instead.
+
+	#[test]
+	fn any_registered_tracks_inserts_and_removes_without_double_counting_replacement() {
+		let senders = Senders::default();
+		assert!(!senders.any_registered());
+
+		let (sender, _receiver) = mpsc::channel(1);
+		senders.insert(1, sender);
+		assert!(senders.any_registered());
+
+		// Replacing an existing id's sender isn't a net-new registration.
+		let (sender, _receiver) = mpsc::channel(1);
+		senders.insert(1, sender);
+		assert!(senders.any_registered());
+
+		let (sender, _receiver) = mpsc::channel(1);
+		senders.insert(2, sender);
+		senders.remove(1);
+		assert!(senders.any_registered(), "id 2 is still registered");
+
+		senders.remove(2);
+		assert!(!senders.any_registered());
+	}
+
+	#[test]
+	fn instance_count_returns_to_baseline_after_a_soak_of_creating_and_dropping_ids() {
+		let telemetries = Telemetries::default();
+		assert_eq!(telemetries.instance_count(), 0);
+
+		for round in 0..50u64 {
+			let (sender, _receiver) = mpsc::channel(1);
+			let registration = telemetries.senders.register(round, sender);
+			assert_eq!(telemetries.instance_count(), 1);
+			drop(registration);
+		}
+
+		assert_eq!(telemetries.instance_count(), 0, "every registration was dropped, so no leak should remain");
+	}
+
+	#[test]
+	fn leak_detection_high_water_mark_does_not_change_registration_behavior() {
+		// No log-capture harness exists in this crate slice to assert on the
+		// warning text itself (see the comment above `any_registered_tracks_...`
+		// for the same tradeoff elsewhere in this file) — this instead locks
+		// down that configuring the check, and exceeding it, never changes
+		// what `insert`/`remove`/`len` actually do.
+		let senders = Senders::default();
+		senders.set_leak_detection_high_water_mark(Some(1));
+
+		let mut receivers = Vec::new();
+		for id in 0..5 {
+			let (sender, receiver) = mpsc::channel(1);
+			senders.insert_with_config(id, sender, SenderConfig { label: Some(format!("worker-{id}")), ..SenderConfig::default() });
+			receivers.push(receiver);
+		}
+		assert_eq!(senders.len(), 5, "exceeding the configured mark doesn't refuse or drop a registration");
+
+		for id in 0..5 {
+			senders.remove(id);
+		}
+		assert_eq!(senders.len(), 0);
+
+		// Disabling the check is also just bookkeeping, not a behavior switch.
+		senders.set_leak_detection_high_water_mark(None);
+		let (sender, _receiver) = mpsc::channel(1);
+		senders.insert(0, sender);
+		assert_eq!(senders.len(), 1);
+	}
+
+	#[test]
+	fn events_are_dropped_before_span_lookup_when_no_sender_is_registered_anywhere() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		// No `telemetries.senders.insert(..)` at all: `any_registered` should
+		// short-circuit `on_event` before it ever looks for a matching span.
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let _enter = span.enter();
+			emit(r#"{"msg":"should be dropped before span lookup"}"#);
+		});
+
+		assert_eq!(telemetries.senders.len(), 0);
+		assert_eq!(telemetries.malformed_event_count(), 0, "the event should never have been inspected at all");
+	}
+
+	#[test]
+	fn a_registered_sender_still_receives_events_once_any_registered_is_true() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let id = span.id().expect("span is enabled").into_u64();
+			let (sender, mut receiver) = mpsc::channel(1);
+			telemetries.senders.insert(id, sender);
+			let _enter = span.enter();
+
+			emit(r#"{"msg":"still delivered"}"#);
+
+			let (_verbosity, json) = receiver.try_next().unwrap().unwrap();
+			assert!(json.contains("\"msg\":\"still delivered\""));
+		});
+	}
+
+	#[test]
+	fn an_explicit_telemetry_id_field_delivers_events_from_a_thread_with_no_span() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		let id = 42;
+		let (sender, mut receiver) = mpsc::channel(1);
+		telemetries.senders.insert(id, sender);
+
+		// A plain `std::thread::spawn` closure, not a `tracing` span: rayon
+		// pools and FFI callbacks are in exactly this position, with nothing
+		// for `resolve_telemetry_id`'s scope walk to find.
+		std::thread::spawn(move || {
+			tracing::subscriber::with_default(subscriber, || {
+				tracing::info!(
+					target: TELEMETRY_LOG_SPAN,
+					telemetry_id = id,
+					message_verbosity = 0u64,
+					json = r#"{"msg":"from-a-span-less-thread"}"#,
+				);
+			});
+		})
+		.join()
+		.unwrap();
+
+		let (_verbosity, json) = receiver.try_next().unwrap().expect("delivered via the explicit telemetry_id field");
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert_eq!(value["msg"], "from-a-span-less-thread");
+	}
+
+	#[test]
+	fn an_event_with_neither_a_span_nor_an_explicit_id_falls_back_to_the_sole_registered_instance() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		let (sender, mut receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+
+		tracing::subscriber::with_default(subscriber, || {
+			emit(r#"{"msg":"routed via the sole registered instance"}"#);
+		});
+
+		let (_verbosity, json) = receiver.try_next().unwrap().expect("delivered via the sole-instance fallback");
+		assert!(json.contains("\"msg\":\"routed via the sole registered instance\""));
+	}
+
+	#[test]
+	fn events_with_neither_a_span_nor_an_explicit_id_are_dropped_and_logged_when_ambiguous() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		// Two ids registered: the sole-instance fallback only applies when
+		// there's exactly one, since guessing between several would be as
+		// likely to misdeliver as to help.
+		let (sender_a, mut receiver_a) = mpsc::channel(1);
+		let (sender_b, mut receiver_b) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender_a);
+		telemetries.senders.insert(2, sender_b);
+
+		let (_, lines) = with_captured_log(|| {
+			tracing::subscriber::with_default(subscriber, || {
+				emit(r#"{"msg":"nobody can address this"}"#);
+			});
+		});
+
+		assert!(receiver_a.try_next().unwrap().is_none(), "no span and no telemetry_id field: nothing to deliver to");
+		assert!(receiver_b.try_next().unwrap().is_none(), "no span and no telemetry_id field: nothing to deliver to");
+		assert!(lines.iter().any(|line| line.contains("Telemetry not set")));
+	}
+
+	#[test]
+	fn an_explicit_telemetry_id_field_wins_over_a_span_ancestor_that_would_resolve_differently() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let ancestor_id = span.id().expect("span is enabled").into_u64();
+
+			let (ancestor_sender, mut ancestor_receiver) = mpsc::channel(1);
+			let (explicit_sender, mut explicit_receiver) = mpsc::channel(1);
+			telemetries.senders.insert(ancestor_id, ancestor_sender);
+			telemetries.senders.insert(99, explicit_sender);
+
+			let _enter = span.enter();
+			tracing::info!(
+				target: TELEMETRY_LOG_SPAN,
+				telemetry_id = 99u64,
+				message_verbosity = 0u64,
+				json = r#"{"msg":"addressed explicitly"}"#,
+			);
+
+			assert!(ancestor_receiver.try_next().unwrap().is_none(), "the explicit id should win, not the ancestor span");
+			let (_verbosity, json) = explicit_receiver.try_next().unwrap().expect("delivered to the explicit id");
+			assert!(json.contains("\"msg\":\"addressed explicitly\""));
+		});
+	}
+
+	// `FlatTelemetryLayer` wraps a `TelemetryLayer` and drops the `LookupSpan`
+	// bound entirely, so `tracing::subscriber::NoSubscriber` (which stores no
+	// span data at all) stands in here for an embedder's own `Subscriber`
+	// built without a `tracing_subscriber::Registry`. This is the "compiles
+	// against any Subscriber" half of the trade-off; the tests below are the
+	// runtime half.
+	#[test]
+	fn flat_telemetry_layer_delivers_via_an_explicit_telemetry_id_field_on_a_plain_subscriber() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let flat = FlatTelemetryLayer::new(layer);
+		let subscriber = tracing::subscriber::NoSubscriber::default().with(flat);
+
+		let id = 7;
+		let (sender, mut receiver) = mpsc::channel(1);
+		telemetries.senders.insert(id, sender);
+
+		tracing::subscriber::with_default(subscriber, || {
+			tracing::info!(
+				target: TELEMETRY_LOG_SPAN,
+				telemetry_id = id,
+				message_verbosity = 0u64,
+				json = r#"{"msg":"no registry needed"}"#,
+			);
+		});
+
+		let (_verbosity, json) = receiver.try_next().unwrap().expect("delivered via the explicit telemetry_id field");
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert_eq!(value["msg"], "no registry needed");
+		assert_eq!(value["parent_ids"], serde_json::json!([]), "there is no span registry to derive ancestry from");
+	}
+
+	#[test]
+	fn flat_telemetry_layer_falls_back_to_the_sole_registered_instance() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let flat = FlatTelemetryLayer::new(layer);
+		let subscriber = tracing::subscriber::NoSubscriber::default().with(flat);
+
+		let (sender, mut receiver) = mpsc::channel(1);
+		telemetries.senders.insert(1, sender);
+
+		tracing::subscriber::with_default(subscriber, || {
+			emit(r#"{"msg":"routed without a span registry"}"#);
+		});
+
+		let (_verbosity, json) = receiver.try_next().unwrap().expect("delivered via the sole-instance fallback");
+		assert!(json.contains("\"msg\":\"routed without a span registry\""));
+	}
+
+	#[test]
+	fn flat_telemetry_layer_ignores_with_context_fields_for_lack_of_a_span_registry() {
+		let layer = TelemetryLayer::default().with_context_fields(ContextFields::new().field("height"));
+		let telemetries = layer.telemetries();
+		let flat = FlatTelemetryLayer::new(layer);
+		let subscriber = tracing::subscriber::NoSubscriber::default().with(flat);
+
+		let id = 3;
+		let (sender, mut receiver) = mpsc::channel(1);
+		telemetries.senders.insert(id, sender);
+
+		tracing::subscriber::with_default(subscriber, || {
+			tracing::info!(
+				target: TELEMETRY_LOG_SPAN,
+				telemetry_id = id,
+				message_verbosity = 0u64,
+				height = 42,
+				json = r#"{"msg":"no ctx to collect"}"#,
+			);
+		});
+
+		let (_verbosity, json) = receiver.try_next().unwrap().unwrap();
+		let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+		assert!(value.get("ctx").is_none(), "there is no span registry for `with_context_fields` to collect from");
+	}
+
+	#[test]
+	fn a_span_entered_on_a_different_thread_than_it_was_created_on_still_resolves() {
+		// Stands in for a `tokio::task::Instrument`ed future resuming on a
+		// different work-stealing executor thread than the one that entered
+		// the span. `Span::in_scope` re-enters the same span's guard on
+		// whatever thread runs the closure, exactly like a runtime re-polling
+		// an instrumented future does; the ambient dispatch has to follow it
+		// to the worker thread explicitly (`tracing::subscriber::with_default`
+		// is thread-local, unlike a real node's process-wide default), which
+		// is done here with a plain `Dispatch` clone rather than a `tokio`
+		// dev-dependency this crate slice has no manifest to declare.
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let dispatch = tracing::Dispatch::new(tracing_subscriber::registry().with(layer));
+
+		let span = tracing::dispatcher::with_default(&dispatch, || {
+			tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN)
+		});
+		let id = span.id().expect("span is enabled").into_u64();
+
+		let (sender, mut receiver) = mpsc::channel(1);
+		telemetries.senders.insert(id, sender);
+
+		let worker_span = span.clone();
+		let worker_dispatch = dispatch.clone();
+		std::thread::spawn(move || {
+			tracing::dispatcher::with_default(&worker_dispatch, || {
+				worker_span.in_scope(|| {
+					tracing::info!(
+						target: TELEMETRY_LOG_SPAN,
+						message_verbosity = 0u64,
+						json = r#"{"msg":"resumed on another thread"}"#,
+					);
+				});
+			});
+		})
+		.join()
+		.unwrap();
+
+		let (_verbosity, json) = receiver.try_next().unwrap().expect("delivered from the other thread");
+		assert!(json.contains("\"msg\":\"resumed on another thread\""));
+	}
+
+	// A criterion benchmark comparing the sharded map against a single global
+	// mutex under contention would normally accompany this change, but there's
+	// no build manifest or benches/ harness anywhere in this crate to add one
+	// to; the stress test below instead demonstrates correctness under heavy
+	// concurrent use spread across every shard.
+	#[test]
+	fn stress_many_threads_send_concurrently_without_losing_or_misdelivering_messages() {
+		// More ids than shards, so every shard is exercised by more than one
+		// thread's traffic and any missed lock/shard-index bug would show up as
+		// a wrong count below.
+		const THREADS: u64 = SENDER_SHARDS as u64 * 4;
+		const MESSAGES_PER_THREAD: usize = 200;
+
+		let senders = Senders::default();
+		let handles: Vec<_> = (0..THREADS)
+			.map(|id| {
+				let senders = senders.clone();
+				std::thread::spawn(move || {
+					let (sender, mut receiver) = mpsc::channel(MESSAGES_PER_THREAD);
+					senders.insert(id, sender);
+
+					for n in 0..MESSAGES_PER_THREAD {
+						let _ = senders.send(id, None, (Verbosity::INFO, n.to_string()));
+					}
+
+					let mut received = Vec::with_capacity(MESSAGES_PER_THREAD);
+					while let Ok(Some((_verbosity, payload))) = receiver.try_next() {
+						received.push(payload);
+					}
+					assert_eq!(
+						received,
+						(0..MESSAGES_PER_THREAD).map(|n| n.to_string()).collect::<Vec<_>>(),
+						"a channel sized to fit every send should deliver them all, in order, undisturbed by other threads' ids"
+					);
+					assert_eq!(senders.dropped(id), 0);
+					senders.remove(id);
+				})
+			})
+			.collect();
+		for handle in handles {
+			handle.join().unwrap();
+		}
+
+		assert!(senders.is_empty());
+		assert!(!senders.any_registered());
+	}
+
+	#[test]
+	fn two_instances_sharing_one_registry_do_not_cross_talk() {
+		// Two "nodes" in one process, as in an integration test or a collator
+		// setup: each gets its own `TelemetryLayer` on its own target, but both
+		// layers sit on one shared `Registry`, so this proves isolation comes
+		// from the target rather than from any per-registry state.
+		let layer_a = TelemetryLayer::default().with_instance_id(0);
+		let layer_b = TelemetryLayer::default().with_instance_id(1);
+		let telemetries_a = layer_a.telemetries();
+		let telemetries_b = layer_b.telemetries();
+		let target_a = format!("{TELEMETRY_LOG_SPAN}-0");
+		let target_b = format!("{TELEMETRY_LOG_SPAN}-1");
+		let subscriber = tracing_subscriber::registry().with(layer_a).with(layer_b);
+
+		tracing::subscriber::with_default(subscriber, || {
+			// `tracing::info_span!` requires a string literal target, so these are
+			// spelled out rather than built from `target_a`/`target_b` above —
+			// `assert_eq!` below keeps them honest against `with_instance_id`'s
+			// actual naming scheme.
+			let span_a = tracing::info_span!(target: "telemetry-logger-0", "telemetry-logger-0");
+			let span_b = tracing::info_span!(target: "telemetry-logger-1", "telemetry-logger-1");
+			let id_a = span_a.id().expect("span is enabled").into_u64();
+			let id_b = span_b.id().expect("span is enabled").into_u64();
+
+			let (sender_a, mut receiver_a) = mpsc::channel(1);
+			let (sender_b, mut receiver_b) = mpsc::channel(1);
+			telemetries_a.senders.insert(id_a, sender_a);
+			telemetries_b.senders.insert(id_b, sender_b);
+
+			{
+				let _enter = span_a.enter();
+				tracing::info!(target: "telemetry-logger-0", message_verbosity = 0u64, json = r#"{"from":"a"}"#);
+			}
+			{
+				let _enter = span_b.enter();
+				tracing::info!(target: "telemetry-logger-1", message_verbosity = 0u64, json = r#"{"from":"b"}"#);
+			}
+
+			let (_verbosity, json_a) = receiver_a
+				.try_next()
+				.ok()
+				.flatten()
+				.expect("layer_a's own event reaches layer_a's sender");
+			assert!(json_a.contains(r#""from":"a""#));
+			assert!(receiver_a.try_next().ok().flatten().is_none(), "layer_b's event must not reach layer_a's sender");
+
+			let (_verbosity, json_b) = receiver_b
+				.try_next()
+				.ok()
+				.flatten()
+				.expect("layer_b's own event reaches layer_b's sender");
+			assert!(json_b.contains(r#""from":"b""#));
+			assert!(receiver_b.try_next().ok().flatten().is_none(), "layer_a's event must not reach layer_b's sender");
+
+			assert_eq!(target_a, "telemetry-logger-0");
+			assert_eq!(target_b, "telemetry-logger-1");
+		});
+	}
+
+	#[test]
+	fn default_target_is_unchanged_for_single_node_binaries() {
+		assert_eq!(TelemetryLayer::default().target, TELEMETRY_LOG_SPAN);
+	}
+
+	#[test]
+	fn two_instances_operate_independently_end_to_end() {
+		// A relay chain and a parachain telemetry sharing one binary, backed
+		// by two separate in-memory sinks (the `mpsc::Sender`/`Receiver`
+		// pairs, this crate slice's stand-in for a websocket endpoint):
+		// registration, pause/resume, and shutdown on one instance must have
+		// zero observable effect on the other.
+		let relay = TelemetryLayer::default().with_instance_id(0);
+		let para = TelemetryLayer::default().with_instance_id(1);
+		assert_ne!(relay.instance_target(), para.instance_target());
+		let relay_telemetries = relay.telemetries();
+		let para_telemetries = para.telemetries();
+		let subscriber = tracing_subscriber::registry().with(relay).with(para);
+
+		futures::executor::block_on(async {
+			tracing::subscriber::with_default(subscriber, || {
+				let relay_span = tracing::info_span!(target: "telemetry-logger-0", "telemetry-logger-0");
+				let para_span = tracing::info_span!(target: "telemetry-logger-1", "telemetry-logger-1");
+				let relay_id = relay_span.id().expect("span is enabled").into_u64();
+				let para_id = para_span.id().expect("span is enabled").into_u64();
+
+				let (relay_sink, mut relay_rx) = mpsc::channel(4);
+				let (para_sink, mut para_rx) = mpsc::channel(4);
+				relay_telemetries.senders.insert(relay_id, relay_sink);
+				para_telemetries.senders.insert(para_id, para_sink);
+
+				// Pausing the relay chain's telemetry must not affect the
+				// parachain's, even though both ids live in the same process.
+				relay_telemetries.pause(relay_id);
+				assert!(relay_telemetries.is_paused(relay_id));
+				assert!(!para_telemetries.is_paused(para_id));
+
+				{
+					let _enter = relay_span.enter();
+					tracing::info!(target: "telemetry-logger-0", message_verbosity = 0u64, json = r#"{"chain":"relay"}"#);
+				}
+				{
+					let _enter = para_span.enter();
+					tracing::info!(target: "telemetry-logger-1", message_verbosity = 0u64, json = r#"{"chain":"para"}"#);
+				}
+
+				assert!(relay_rx.try_next().ok().flatten().is_none(), "paused relay instance must not deliver");
+				let (_verbosity, para_json) =
+					para_rx.try_next().ok().flatten().expect("unpaused para instance still delivers");
+				assert!(para_json.contains(r#""chain":"para""#));
+
+				relay_telemetries.resume(relay_id);
+				assert!(!relay_telemetries.is_paused(relay_id));
+			});
+
+			// `shutdown` on one instance's `Telemetries` only walks that
+			// instance's own `Senders`, so it must leave the other instance's
+			// registration completely intact.
+			let _ = relay_telemetries.shutdown(std::time::Duration::from_secs(1)).await;
+			assert!(!relay_telemetries.senders.any_registered(), "shutdown retires the relay instance's own registrations");
+			assert!(para_telemetries.senders.any_registered(), "shutting down relay must not touch para's registration");
+		});
+	}
+
+	#[test]
+	fn telemetry_handle_is_disabled_before_the_registration_it_targets_ever_exists() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+
+		// Nothing has ever registered id `1`, so the handle must be a
+		// near-free no-op rather than panicking or blocking.
+		let handle = telemetries.handle(1);
+		assert!(!handle.is_enabled());
+		assert!(!handle.send_telemetry(0u64, serde_json::json!({ "msg": "test.never_registered" })));
+	}
+
+	#[test]
+	fn telemetry_handle_becomes_disabled_once_every_strong_owner_of_its_senders_is_dropped() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let span = tracing::info_span!(target: "telemetry-logger", "telemetry-logger");
+		let id = span.id().expect("span is enabled").into_u64();
+		let (sink, mut rx) = mpsc::channel(4);
+		telemetries.senders.insert(id, sink);
+
+		let handle = telemetries.handle(id);
+		assert!(handle.is_enabled());
+		assert!(handle.send_telemetry(0u64, serde_json::json!({ "msg": "test.while_live" })));
+		let (_verbosity, json) = rx.try_next().ok().flatten().expect("live handle delivers");
+		assert!(json.contains(r#""msg":"test.while_live""#));
+
+		// Drop every strong owner of the shared `Senders` — both the layer
+		// and the `Telemetries` clone taken from it — while the handle
+		// itself lives on, mirroring a producer that caches a handle past
+		// node shutdown.
+		drop(telemetries);
+		drop(layer);
+
+		assert!(!handle.is_enabled());
+		assert!(!handle.send_telemetry(0u64, serde_json::json!({ "msg": "test.after_drop" })));
+		assert!(rx.try_next().ok().flatten().is_none(), "no further messages once every strong owner is gone");
+	}
+
+	#[test]
+	fn telemetry_builder_defaults_match_telemetry_layer_default() {
+		let (layer, worker) = TelemetryBuilder::new().build().expect("default builder config is always valid");
+		assert_eq!(layer.instance_target(), TELEMETRY_LOG_SPAN);
+		assert_eq!(worker.reconnect_policy().initial_delay, std::time::Duration::from_secs(1));
+		assert_eq!(worker.reconnect_policy().max_delay, std::time::Duration::from_secs(60));
+		assert_eq!(worker.reconnect_policy().max_attempts, None);
+	}
+
+	#[test]
+	fn telemetry_builder_rejects_a_zero_buffer_size() {
+		let err = TelemetryBuilder::new().buffer_size(0).build().unwrap_err();
+		assert_eq!(err, TelemetryBuilderError::ZeroBufferSize);
+	}
+
+	#[test]
+	fn telemetry_builder_rejects_a_malformed_endpoint_url() {
+		let err = TelemetryBuilder::new().endpoint("not-a-url", Verbosity::INFO).build().unwrap_err();
+		assert_eq!(err, TelemetryBuilderError::Endpoint(EndpointParseError::MissingScheme));
+	}
+
+	#[test]
+	fn telemetry_builder_round_trip_delivers_through_the_built_worker() {
+		// Registration (`Senders::insert_with_config` + `set_endpoint_commands`)
+		// now happens inside `build()` itself, before `fan_out_by_verbosity`'s
+		// (otherwise endless) select loop is ever polled, so a single manual
+		// poll — the same no-runtime technique as
+		// `transport_futures_are_pollable_without_any_async_runtime` — is
+		// enough to observe it without needing an executor able to drive the
+		// loop to completion.
+		use std::future::Future as _;
+
+		let (layer, mut worker) = TelemetryBuilder::new()
+			.endpoint("wss://telemetry.example.com/submit", Verbosity::INFO)
+			.buffer_size(4)
+			.static_field("chain", "kusama")
+			.build()
+			.expect("builder config is valid");
+		let telemetries = layer.telemetries();
+		let id = worker.id();
+
+		// The static field configured on the builder must be merged into
+		// every payload sent under this worker's id, the same way a
+		// hand-assembled `SenderConfig::static_fields` would be.
+		assert!(telemetries.senders.static_fields(id).iter().any(|(k, v)| k == "chain" && v == "kusama"));
+		assert!(telemetries.senders.set_max_verbosity(id, "wss://telemetry.example.com/submit", Verbosity::DEBUG));
+
+		// Driven through the direct API — see `TelemetryWorker`'s docs on why
+		// a builder-assembled worker isn't addressable through the
+		// `tracing::info!` macro path.
+		assert!(telemetries.send(id, 0u64, serde_json::json!({ "msg": "test.builder_round_trip" })));
+
+		let waker = noop_waker();
+		let mut cx = std::task::Context::from_waker(&waker);
+		assert!(
+			std::pin::Pin::new(&mut worker).poll(&mut cx).is_pending(),
+			"the fan-out loop runs until every sender feeding it is dropped"
+		);
+	}
+
+	#[test]
+	fn telemetry_config_default_round_trips_through_json() {
+		let config = TelemetryConfig::default();
+		let json = serde_json::to_string(&config).unwrap();
+		let round_tripped: TelemetryConfig = serde_json::from_str(&json).unwrap();
+		assert_eq!(config, round_tripped);
+	}
+
+	#[test]
+	fn telemetry_config_minimal_json_fills_in_every_default() {
+		// A config with no keys at all: every field must fall back to
+		// exactly what `TelemetryBuilder::default` would use, since
+		// `TelemetryConfig::default` is defined to match it.
+		let config: TelemetryConfig = serde_json::from_str("{}").unwrap();
+		assert_eq!(config, TelemetryConfig::default());
+		assert_eq!(config.buffer_size, DEFAULT_BUFFER_SIZE);
+		assert_eq!(config.global_verbosity, Verbosity::DEBUG);
+		assert!(config.endpoints.is_empty());
+	}
+
+	#[test]
+	fn telemetry_config_maximal_json_populates_every_field() {
+		let json = serde_json::json!({
+			"target": "telemetry-logger-1",
+			"endpoints": [
+				{ "url": "wss://telemetry.example.com/submit", "verbosity": 1 },
+				{ "url": "wss://telemetry.example.com/backup", "verbosity": 9 },
+			],
+			"endpoint_groups": {
+				"primary-region": {
+					"members": ["wss://telemetry.example.com/submit", "wss://telemetry.example.com/backup"],
+					"failback_after_secs": 30,
+				},
+			},
+			"endpoint_filters": {
+				"wss://telemetry.example.com/backup": { "allow": ["system.connected", "sysinfo.*"] },
+			},
+			"buffer_size": 256,
+			"global_verbosity": 1,
+			"max_message_size": 65536,
+			"static_fields": { "chain": "kusama" },
+			"context_fields": ["height"],
+			"reconnect": { "initial_delay_secs": 2, "max_delay_secs": 120, "max_attempts": 10 },
+			"initial_connection_delay_secs": 3,
+			"sampling": { "sysinfo.hardware": { "every_nth": 10 } },
+			"endpoint_byte_budgets": { "wss://telemetry.example.com/submit": 1_000_000 },
+		});
+		let config: TelemetryConfig = serde_json::from_value(json).unwrap();
+
+		assert_eq!(config.target.as_deref(), Some("telemetry-logger-1"));
+		assert_eq!(config.endpoints.len(), 2);
+		assert_eq!(config.endpoints[0].verbosity, Verbosity::INFO);
+		assert_eq!(config.endpoint_groups["primary-region"].members.len(), 2);
+		assert_eq!(config.endpoint_groups["primary-region"].failback_after_secs, 30);
+		assert_eq!(
+			config.endpoint_filters["wss://telemetry.example.com/backup"],
+			MessageTypeFilterConfig::Allow(vec!["system.connected".into(), "sysinfo.*".into()]),
+		);
+		assert_eq!(config.reconnect.max_attempts, Some(10));
+		assert_eq!(config.sampling["sysinfo.hardware"], SamplingRuleConfig::EveryNth(10));
+		assert_eq!(config.endpoint_byte_budgets["wss://telemetry.example.com/submit"], 1_000_000);
+
+		let round_tripped: TelemetryConfig = serde_json::from_str(&serde_json::to_string(&config).unwrap()).unwrap();
+		assert_eq!(config, round_tripped);
+	}
+
+	#[test]
+	fn telemetry_config_rejects_an_unknown_top_level_key() {
+		let err = serde_json::from_str::<TelemetryConfig>(r#"{"ednpoints": []}"#).unwrap_err();
+		assert!(err.to_string().contains("ednpoints"), "the error should name the offending key: {err}");
+	}
+
+	#[test]
+	fn telemetry_config_rejects_an_unknown_nested_key() {
+		let err = serde_json::from_str::<TelemetryConfig>(
+			r#"{"endpoints": [{"url": "wss://x", "verbosity": 1, "verbositty": 1}]}"#,
+		)
+		.unwrap_err();
+		assert!(err.to_string().contains("verbositty"), "the error should name the offending key: {err}");
+	}
+
+	#[test]
+	fn telemetry_builder_from_config_applies_every_builder_level_knob() {
+		let config = TelemetryConfig {
+			target: Some("telemetry-logger-from-config".into()),
+			endpoints: vec![EndpointConfig { url: "wss://telemetry.example.com/submit".into(), verbosity: Verbosity::INFO }],
+			buffer_size: 8,
+			static_fields: serde_json::Map::from_iter([("chain".to_string(), "kusama".into())]),
+			reconnect: ReconnectConfig { initial_delay_secs: 2, max_delay_secs: 120, max_attempts: Some(3) },
+			..TelemetryConfig::default()
+		};
+
+		let (layer, worker) = TelemetryBuilder::from_config(&config).build().expect("valid config");
+		let telemetries = layer.telemetries();
+
+		assert_eq!(layer.instance_target(), "telemetry-logger-from-config");
+		assert_eq!(worker.reconnect_policy().initial_delay, std::time::Duration::from_secs(2));
+		assert_eq!(worker.reconnect_policy().max_delay, std::time::Duration::from_secs(120));
+		assert_eq!(worker.reconnect_policy().max_attempts, Some(3));
+		assert!(telemetries.senders.static_fields(worker.id()).iter().any(|(k, v)| k == "chain" && v == "kusama"));
+	}
+
+	#[test]
+	fn telemetry_config_apply_runtime_configures_sampling_and_byte_budgets() {
+		let config = TelemetryConfig {
+			sampling: HashMap::from_iter([("sysinfo.hardware".to_string(), SamplingRuleConfig::EveryNth(2))]),
+			endpoint_byte_budgets: HashMap::from_iter([("wss://telemetry.example.com/submit".to_string(), 1_000)]),
+			..TelemetryConfig::default()
+		};
+		let (layer, _worker) = TelemetryBuilder::new().build().expect("default builder config is always valid");
+		let telemetries = layer.telemetries();
+
+		config.apply_runtime(&telemetries);
+
+		// Sampling: `EveryNth(2)` forwards the 1st message of this type and
+		// skips the 2nd. See `MessageSampling::should_send`.
+		assert!(telemetries.message_sampling.should_send(Some("sysinfo.hardware")));
+		assert!(!telemetries.message_sampling.should_send(Some("sysinfo.hardware")));
+
+		// Byte budget: a single send over the configured daily cap trips it.
+		telemetries.record_endpoint_bytes_sent(
+			"wss://telemetry.example.com/submit",
+			2_000,
+			std::time::SystemTime::now(),
+		);
+		assert!(telemetries.endpoint_egress_paused("wss://telemetry.example.com/submit"));
+	}
+
+	#[test]
+	fn telemetry_worker_resolves_once_every_telemetries_clone_is_dropped() {
+		use std::future::Future as _;
+
+		let (layer, mut worker) = TelemetryBuilder::new().build().expect("default builder config is always valid");
+		let telemetries = layer.telemetries();
+
+		let waker = noop_waker();
+		let mut cx = std::task::Context::from_waker(&waker);
+		assert!(
+			std::pin::Pin::new(&mut worker).poll(&mut cx).is_pending(),
+			"the loop keeps running while a sender is still registered"
+		);
+
+		drop(telemetries);
+		drop(layer);
+
+		assert!(
+			matches!(std::pin::Pin::new(&mut worker).poll(&mut cx), std::task::Poll::Ready(())),
+			"dropping every strong owner of the worker's Senders entry closes its channel, ending the loop"
+		);
+	}
+
+	#[test]
+	fn telemetry_worker_resolves_once_shutdown_removes_its_registration() {
+		use std::future::Future as _;
+
+		let (layer, mut worker) = TelemetryBuilder::new().build().expect("default builder config is always valid");
+		let telemetries = layer.telemetries();
+
+		let waker = noop_waker();
+		let mut cx = std::task::Context::from_waker(&waker);
+		assert!(std::pin::Pin::new(&mut worker).poll(&mut cx).is_pending());
+
+		telemetries.senders.shutdown(worker.id(), std::time::Duration::from_secs(0));
+
+		assert!(
+			matches!(std::pin::Pin::new(&mut worker).poll(&mut cx), std::task::Poll::Ready(())),
+			"shutdown removes the Senders entry, dropping its sender the same way `Telemetries::shutdown` would"
+		);
+	}
+
+	#[test]
+	fn register_instance_routes_and_counts_separately_from_the_default_instance() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(target: TELEMETRY_LOG_SPAN, TELEMETRY_LOG_SPAN);
+			let default_id = span.id().expect("span is enabled").into_u64();
+			let (default_sender, mut default_receiver) = mpsc::channel(4);
+			telemetries.senders.insert(default_id, default_sender);
+			let _enter = span.enter();
+
+			let (handle, _worker) = telemetries
+				.register_instance("parachain-2000", Endpoints::new(), SenderConfig { capacity: 4, ..SenderConfig::default() })
+				.expect("name isn't registered yet");
+
+			assert!(telemetries.send(default_id, 0u64, serde_json::json!({ "msg": "test.default" })));
+			assert!(handle.send_telemetry(0u64, serde_json::json!({ "msg": "test.named" })));
+
+			let (_v, default_msg) = default_receiver.try_next().unwrap().unwrap();
+			assert!(default_msg.contains("\"msg\":\"test.default\""));
+			assert!(
+				default_receiver.try_next().is_err(),
+				"the named instance's message must not also land on the default id's channel"
+			);
+
+			assert_eq!(telemetries.senders.dropped(default_id), 0);
+			assert_ne!(handle.id, default_id, "the named instance must get its own id, not the span's");
+			assert!(handle.is_enabled());
+		});
+	}
+
+	#[test]
+	fn register_instance_rejects_a_name_already_in_use() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+
+		let (_handle, _worker) = telemetries
+			.register_instance("parachain-2000", Endpoints::new(), SenderConfig::default())
+			.expect("name isn't registered yet");
+
+		let err = telemetries
+			.register_instance("parachain-2000", Endpoints::new(), SenderConfig::default())
+			.unwrap_err();
+		assert_eq!(err, RegisterInstanceError::NameAlreadyRegistered("parachain-2000".to_string()));
+	}
+
+	#[test]
+	fn register_instance_frees_its_name_once_shut_down() {
+		let layer = TelemetryLayer::default();
+		let telemetries = layer.telemetries();
+
+		let (handle, _worker) = telemetries
+			.register_instance("parachain-2000", Endpoints::new(), SenderConfig::default())
+			.expect("name isn't registered yet");
+		telemetries.senders.shutdown(handle.id, std::time::Duration::from_secs(0));
+
+		telemetries
+			.register_instance("parachain-2000", Endpoints::new(), SenderConfig::default())
+			.expect("the name is free again once the earlier instance is shut down");
 	}
 }